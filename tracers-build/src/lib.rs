@@ -1,4 +1,5 @@
 //! Simply re-exports the build-related functions in `tracers-codegen`
 
 pub use tracers_codegen::{build, tracers_build};
+pub use tracers_codegen::{BuildFailure, BuildReport, Builder, NativeLib, TargetKind};
 pub use tracers_codegen::{TracersError, TracersResult};
@@ -13,9 +13,18 @@
 //! update the `from_syn_type` function accordingly
 use crate::serde_helpers;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::ffi::{CStr, CString};
 #[cfg(unix)]
 use std::ffi::{OsStr, OsString};
+use std::num::{
+    NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU16, NonZeroU32,
+    NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use syn::parse_quote;
 use tracers_core::argtypes::*;
 use tracers_core::{ProbeArgType, ProbeArgWrapper};
@@ -79,17 +88,120 @@ pub(crate) fn from_syn_type(ty: &syn::Type) -> Option<ArgTypeInfo> {
     //type hierarchy directly is just agony
     maybe_types!(@primitive ty, i8, u8, i16, u16, i32, u32, i64, u64, usize, isize);
     maybe_types!(@string ty, &str, &String);
+    maybe_types!(@string ty, &Cow<str>, &Box<str>, &Arc<str>);
 
     #[cfg(unix)] // Only the unix impl of OsStr/OsString exposes the string as bytes
     maybe_types!(@string ty, &OsStr, &OsString);
+    #[cfg(unix)] // Paths are `OsStr`s underneath, so the same restriction applies
+    maybe_types!(@string ty, &Path, &PathBuf);
     maybe_types!(@string ty, &CStr, &CString);
 
     maybe_type!(@primitive ty, bool);
+    maybe_type!(@primitive ty, char);
+    maybe_types!(@primitive ty, NonZeroI8, NonZeroU8, NonZeroI16, NonZeroU16, NonZeroI32, NonZeroU32);
+    maybe_types!(@primitive ty, NonZeroI64, NonZeroU64, NonZeroUsize, NonZeroIsize);
+    maybe_type!(@primitive ty, Duration);
 
     //Else, this isn't a type we recognize
     None
 }
 
+/// 128-bit integers have no C type, so they don't go through `from_syn_type`.  Instead each one is
+/// lowered into two `u64` native args, holding the high and low 64 bits (see the `native_arg`
+/// module).
+///
+/// If the type expression is `u128` or `i128`, or a reference or `Option` of one of those in the
+/// same forms supported for other primitive types, returns the type info of each of the two native
+/// args.  Otherwise returns `None`.
+pub(crate) fn from_syn_wide_integer_type(ty: &syn::Type) -> Option<ArgTypeInfo> {
+    macro_rules! maybe_wide_type {
+        ($rust_t:ty) => {
+            for wide_t in [
+                parse_quote! { $rust_t },
+                parse_quote! { &$rust_t },
+                parse_quote! { &Option<$rust_t> },
+                parse_quote! { &Option<&$rust_t> },
+            ]
+            .iter()
+            {
+                let wide_t: &syn::Type = wide_t;
+                if ty == wide_t {
+                    return Some(ArgTypeInfo::new::<u64>());
+                }
+            }
+        };
+    }
+
+    maybe_wide_type!(u128);
+    maybe_wide_type!(i128);
+
+    None
+}
+
+/// `Result` has no C type either.  A `&Result<T, E>` is lowered into an `is_ok` flag followed by
+/// the `T` and `E` payloads (see the `native_arg` module), each of which is lowered in turn.
+///
+/// If the type expression is `&Result<T, E>` or `&io::Result<T>`, returns the types `T` and `E`.
+/// Otherwise returns `None`.  This doesn't check if the payload types are themselves supported.
+pub(crate) fn result_payload_types(ty: &syn::Type) -> Option<(syn::Type, syn::Type)> {
+    if let syn::Type::Reference(syn::TypeReference { elem, .. }) = ty {
+        if let syn::Type::Path(syn::TypePath { qself: None, path }) = elem.as_ref() {
+            let segments: Vec<_> = path.segments.iter().collect();
+            let (last, preceding) = segments.split_last()?;
+
+            if last.ident != "Result" {
+                return None;
+            }
+
+            let args: Vec<_> = match last.arguments {
+                syn::PathArguments::AngleBracketed(ref args) => args
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        syn::GenericArgument::Type(typ) => Some(typ.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => return None,
+            };
+
+            let is_io_result = preceding.last().map(|segment| segment.ident == "io") == Some(true);
+            match (args.len(), is_io_result) {
+                (2, false) => return Some((args[0].clone(), args[1].clone())),
+                (1, true) => return Some((args[0].clone(), parse_quote! { ::std::io::Error })),
+                _ => return None,
+            }
+        }
+    }
+
+    None
+}
+
+/// `std::io::Error` is lowered into three native args, its raw OS error code, its kind and its
+/// message (see the `native_arg` module).
+///
+/// Returns `true` if the type expression is `io::Error` or `std::io::Error`.  Note that this is the
+/// error type itself, not a reference to it.
+pub(crate) fn is_io_error_type(ty: &syn::Type) -> bool {
+    if let syn::Type::Path(syn::TypePath { qself: None, path }) = ty {
+        let segments: Vec<_> = path
+            .segments
+            .iter()
+            .map(|segment| {
+                if segment.arguments.is_empty() {
+                    segment.ident.to_string()
+                } else {
+                    String::new()
+                }
+            })
+            .collect();
+
+        segments == ["io", "Error"] || segments == ["std", "io", "Error"]
+    } else {
+        false
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub(crate) struct ArgTypeInfo {
     #[serde(with = "serde_helpers::string")]
@@ -210,19 +322,115 @@ mod test {
         test_type!(@primitive usize, CType::SizeT, "libc::size_t");
         test_type!(@primitive isize, CType::SSizeT, "libc::ssize_t");
         test_type!(@primitive bool, CType::Int, "std::os::raw::c_int");
+        test_type!(@primitive char, CType::UInt, "std::os::raw::c_uint");
+        test_type!(@primitive NonZeroI8, CType::Char, "std::os::raw::c_char");
+        test_type!(@primitive NonZeroU8, CType::UChar, "std::os::raw::c_uchar");
+        test_type!(@primitive NonZeroI16, CType::Short, "std::os::raw::c_short");
+        test_type!(@primitive NonZeroU16, CType::UShort, "std::os::raw::c_ushort");
+        test_type!(@primitive NonZeroI32, CType::Int, "std::os::raw::c_int");
+        test_type!(@primitive NonZeroU32, CType::UInt, "std::os::raw::c_uint");
+        test_type!(@primitive NonZeroI64, CType::LongLong, "std::os::raw::c_longlong");
+        test_type!(@primitive NonZeroU64, CType::ULongLong, "std::os::raw::c_ulonglong");
+        test_type!(@primitive NonZeroUsize, CType::SizeT, "libc::size_t");
+        test_type!(@primitive NonZeroIsize, CType::SSizeT, "libc::ssize_t");
+        test_type!(@primitive Duration, CType::ULongLong, "std::os::raw::c_ulonglong");
 
         test_type!(@string &str, CType::CharPtr, "*const std::os::raw::c_char");
         test_type!(@string &String, CType::CharPtr, "*const std::os::raw::c_char");
+        test_type!(@string &Cow<str>, CType::CharPtr, "*const std::os::raw::c_char");
+        test_type!(@string &Box<str>, CType::CharPtr, "*const std::os::raw::c_char");
+        test_type!(@string &Arc<str>, CType::CharPtr, "*const std::os::raw::c_char");
 
         #[cfg(unix)] // Only the unix impl of OsStr/OsString exposes the string as bytes
         test_type!(@string &OsStr, CType::CharPtr, "*const std::os::raw::c_char");
         #[cfg(unix)] // Only the unix impl of OsStr/OsString exposes the string as bytes
         test_type!(@string &OsString, CType::CharPtr, "*const std::os::raw::c_char");
+        #[cfg(unix)] // Paths are `OsStr`s underneath, so the same restriction applies
+        test_type!(@string &Path, CType::CharPtr, "*const std::os::raw::c_char");
+        #[cfg(unix)] // Paths are `OsStr`s underneath, so the same restriction applies
+        test_type!(@string &PathBuf, CType::CharPtr, "*const std::os::raw::c_char");
 
         test_type!(@string &CStr, CType::CharPtr, "*const std::os::raw::c_char");
         test_type!(@string &CString, CType::CharPtr, "*const std::os::raw::c_char");
     }
 
+    #[test]
+    fn test_wide_integer_type_support() {
+        for typ in ["u128", "&i128", "&Option<u128>", "&Option<&i128>"].iter() {
+            let syn_typ: syn::Type = syn::parse_str(typ).unwrap();
+
+            assert_eq!(None, from_syn_type(&syn_typ));
+            assert_eq!(
+                Some(CType::ULongLong),
+                from_syn_wide_integer_type(&syn_typ).map(|info| info.get_c_type_enum()),
+                "Got unexpected wide integer type info for type expression '{}'",
+                typ
+            );
+        }
+
+        //Pointers to wide integers are just pointers
+        let syn_typ: syn::Type = parse_quote! { *const u128 };
+        assert_eq!(None, from_syn_wide_integer_type(&syn_typ));
+    }
+
+    #[test]
+    fn test_result_type_support() {
+        let cases: Vec<(syn::Type, Option<(syn::Type, syn::Type)>)> = vec![
+            (
+                parse_quote! { &Result<u32, &str> },
+                Some((parse_quote! { u32 }, parse_quote! { &str })),
+            ),
+            (
+                parse_quote! { &std::result::Result<(), String> },
+                Some((parse_quote! { () }, parse_quote! { String })),
+            ),
+            (
+                parse_quote! { &io::Result<usize> },
+                Some((parse_quote! { usize }, parse_quote! { ::std::io::Error })),
+            ),
+            (parse_quote! { Result<u32, &str> }, None),
+            (parse_quote! { &Result<u32> }, None),
+            (parse_quote! { &Option<u32> }, None),
+        ];
+
+        for (typ, expected) in cases.into_iter() {
+            assert_eq!(expected, result_payload_types(&typ));
+        }
+
+        for typ in ["io::Error", "std::io::Error", "::std::io::Error"].iter() {
+            let syn_typ: syn::Type = syn::parse_str(typ).unwrap();
+            assert!(is_io_error_type(&syn_typ), "'{}' is an io::Error", typ);
+        }
+
+        for typ in ["&io::Error", "Error", "fmt::Error"].iter() {
+            let syn_typ: syn::Type = syn::parse_str(typ).unwrap();
+            assert!(!is_io_error_type(&syn_typ), "'{}' isn't an io::Error", typ);
+        }
+    }
+
+    /// Types which are lowered into more than one native arg don't have an `ArgTypeInfo` of their
+    /// own, so for those the test data use the C type of the first native arg
+    fn first_native_arg_type_info(typ: &syn::Type) -> Option<ArgTypeInfo> {
+        if let Some(arg_type_info) = from_syn_type(typ) {
+            return Some(arg_type_info);
+        }
+
+        if let Some(half_type_info) = from_syn_wide_integer_type(typ) {
+            return Some(half_type_info);
+        }
+
+        if result_payload_types(typ).is_some() {
+            return Some(ArgTypeInfo::new::<bool>());
+        }
+
+        match typ {
+            syn::Type::Reference(syn::TypeReference { elem, .. }) if is_io_error_type(elem) => {
+                Some(ArgTypeInfo::new::<i32>())
+            }
+            _ => None,
+        }
+    }
+
     #[test]
     fn test_support_for_all_test_traits() {
         //Anything in our corpus of valid provider traits should correspond to a known type
@@ -239,7 +447,7 @@ mod test {
             //representation
             for probe in test_trait.probes.unwrap().into_iter() {
                 for (name, rust_syn_type, c_type) in probe.args.into_iter() {
-                    let arg_type_info = from_syn_type(&rust_syn_type);
+                    let arg_type_info = first_native_arg_type_info(&rust_syn_type);
 
                     assert_ne!(None, arg_type_info,
                                "test trait '{}' probe '{}' arg '{}' has a type which `from_syn_type` can't identify",
@@ -2,7 +2,7 @@
 //! the suitable tracing implementation at build time, and within a dependent crate's `build.rs`
 //! file to perform the build-time code generation to support the selected tracing implementation
 
-use crate::cargo;
+use crate::cargo::{self, TargetKind};
 use crate::error::{TracersError, TracersResult};
 use crate::gen;
 use crate::gen::NativeLib;
@@ -10,6 +10,7 @@ use crate::TracingImplementation;
 use failure::ResultExt;
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::Write;
 use std::io::{BufReader, BufWriter};
@@ -26,6 +27,13 @@ struct FeatureFlags {
     force_static_stap: bool,
     force_static_lttng: bool,
     force_static_noop: bool,
+    force_static_chrome_trace: bool,
+    force_static_ctf: bool,
+    probe_stats: bool,
+    provider_definitions: bool,
+    stap_dtrace: bool,
+    strict: bool,
+    tee_callback: bool,
 }
 
 impl FeatureFlags {
@@ -33,7 +41,7 @@ impl FeatureFlags {
     ///
     /// Fails with an error if the combination of features is not valid
     pub fn from_env() -> TracersResult<FeatureFlags> {
-        Self::new(
+        let features = Self::new(
             Self::is_feature_enabled("dynamic-tracing"),
             Self::is_feature_enabled("static-tracing"),
             Self::is_feature_enabled("force-dyn-stap"),
@@ -41,7 +49,23 @@ impl FeatureFlags {
             Self::is_feature_enabled("force-static-stap"),
             Self::is_feature_enabled("force-static-lttng"),
             Self::is_feature_enabled("force-static-noop"),
-        )
+        )?;
+
+        let features = features.with_force_static_chrome_trace(Self::is_feature_enabled(
+            "force-static-chrome-trace",
+        ))?;
+        let features =
+            features.with_force_static_ctf(Self::is_feature_enabled("force-static-ctf"))?;
+
+        //Unlike the others, these features don't influence which implementation is selected
+        Ok(FeatureFlags {
+            probe_stats: Self::is_feature_enabled("probe-stats"),
+            provider_definitions: Self::is_feature_enabled("provider-definitions"),
+            stap_dtrace: Self::is_feature_enabled("stap-dtrace"),
+            strict: Self::is_feature_enabled("strict"),
+            tee_callback: Self::is_feature_enabled("tee-callback"),
+            ..features
+        })
     }
 
     /// Creates a feature flag structure from explicit arguments.  Mostly used for testing
@@ -78,6 +102,52 @@ impl FeatureFlags {
             force_static_stap,
             force_static_lttng,
             force_static_noop,
+            force_static_chrome_trace: false,
+            force_static_ctf: false,
+            probe_stats: false,
+            provider_definitions: false,
+            stap_dtrace: false,
+            strict: false,
+            tee_callback: false,
+        })
+    }
+
+    /// Sets whether the `force-static-chrome-trace` feature is enabled.  Like the other `force-static`
+    /// features, it's mutually exclusive with all of them
+    pub fn with_force_static_chrome_trace(
+        self,
+        force_static_chrome_trace: bool,
+    ) -> TracersResult<FeatureFlags> {
+        if force_static_chrome_trace
+            && (self.force_static_stap
+                || self.force_static_lttng
+                || self.force_static_noop
+                || self.force_static_ctf)
+        {
+            return Err(TracersError::code_generation_error("The feature `force-static-chrome-trace` is mutually exclusive with the other `force-static` features; please choose one"));
+        }
+
+        Ok(FeatureFlags {
+            force_static_chrome_trace,
+            ..self
+        })
+    }
+
+    /// Sets whether the `force-static-ctf` feature is enabled.  Like the other `force-static`
+    /// features, it's mutually exclusive with all of them
+    pub fn with_force_static_ctf(self, force_static_ctf: bool) -> TracersResult<FeatureFlags> {
+        if force_static_ctf
+            && (self.force_static_stap
+                || self.force_static_lttng
+                || self.force_static_noop
+                || self.force_static_chrome_trace)
+        {
+            return Err(TracersError::code_generation_error("The feature `force-static-ctf` is mutually exclusive with the other `force-static` features; please choose one"));
+        }
+
+        Ok(FeatureFlags {
+            force_static_ctf,
+            ..self
         })
     }
 
@@ -113,6 +183,68 @@ impl FeatureFlags {
         self.force_static_lttng
     }
 
+    pub fn force_static_chrome_trace(&self) -> bool {
+        //Should probes be written to a Chrome trace file rather than a platform tracing system?
+        self.force_static_chrome_trace
+    }
+
+    pub fn force_static_ctf(&self) -> bool {
+        //Should probes be written to a CTF trace by the process itself rather than by LTTng?
+        self.force_static_ctf
+    }
+
+    pub fn probe_stats(&self) -> bool {
+        //Should the generated code count the hits and fires of each probe?
+        self.probe_stats
+    }
+
+    pub fn provider_definitions(&self) -> bool {
+        //Should a `.d` file defining each provider be written to `OUT_DIR`?
+        self.provider_definitions
+    }
+
+    pub fn stap_dtrace(&self) -> bool {
+        //Should static stap providers be compiled with SystemTap's `dtrace` tool?
+        self.stap_dtrace
+    }
+
+    pub fn strict(&self) -> bool {
+        //Should a provider without generated native code be a compile error rather than a no-op?
+        self.strict
+    }
+
+    pub fn tee_callback(&self) -> bool {
+        //Should dynamic probes also be passed to an in-process callback?
+        self.tee_callback
+    }
+
+    /// The names of the `tracers` features which are enabled, as recorded in the `BuildInfo`
+    pub fn enabled_features(&self) -> Vec<String> {
+        let flags = [
+            self.enable_dynamic_tracing,
+            self.enable_static_tracing,
+            self.force_dyn_stap,
+            self.force_dyn_noop,
+            self.force_static_stap,
+            self.force_static_lttng,
+            self.force_static_noop,
+            self.force_static_chrome_trace,
+            self.force_static_ctf,
+            self.probe_stats,
+            self.provider_definitions,
+            self.stap_dtrace,
+            self.strict,
+            self.tee_callback,
+        ];
+
+        FEATURE_NAMES
+            .iter()
+            .zip(flags.iter())
+            .filter(|(_, enabled)| **enabled)
+            .map(|(name, _)| (*name).to_owned())
+            .collect()
+    }
+
     fn is_feature_enabled(name: &str) -> bool {
         env::var(&format!(
             "CARGO_FEATURE_{}",
@@ -122,31 +254,285 @@ impl FeatureFlags {
     }
 }
 
+/// The `tracers` features which influence code generation, in the order of the corresponding
+/// `FeatureFlags` fields
+const FEATURE_NAMES: &[&str] = &[
+    "dynamic-tracing",
+    "static-tracing",
+    "force-dyn-stap",
+    "force-dyn-noop",
+    "force-static-stap",
+    "force-static-lttng",
+    "force-static-noop",
+    "force-static-chrome-trace",
+    "force-static-ctf",
+    "probe-stats",
+    "provider-definitions",
+    "stap-dtrace",
+    "strict",
+    "tee-callback",
+];
+
+/// The version of the `BuildInfo` format.  Increment this whenever a change to `BuildInfo` means
+/// an older or newer `tracers-codegen` can't make sense of it.  Files written before the format was
+/// versioned deserialize as version 0.
+const BUILD_INFO_SCHEMA_VERSION: u32 = 1;
+
 /// Serializable struct which is populated in `build.rs` to indicate to the proc macros which
 /// tracing implementation they should use.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub(crate) struct BuildInfo {
+    /// The `BUILD_INFO_SCHEMA_VERSION` of the `tracers-codegen` which wrote this
+    #[serde(default)]
+    pub schema_version: u32,
+
+    /// The version of the `tracers-codegen` which wrote this, which is the version of
+    /// `tracers-build` used by `tracers`.  The `tracers-build` in the dependent crate's `build.rs`
+    /// and the `tracers-macros` which read this must be the same version.
+    #[serde(default)]
+    pub codegen_version: String,
+
+    /// The `tracers` features which were enabled when this was written
+    #[serde(default)]
+    pub features: Vec<String>,
+
+    /// The target triple `tracers` was built for, if known
+    #[serde(default)]
+    pub target: Option<String>,
+
     pub package_name: String,
     pub implementation: TracingImplementation,
+
+    /// If `true`, `tracers` was built with the `probe-stats` feature, so the generated code keeps
+    /// counters for each probe
+    #[serde(default)]
+    pub probe_stats: bool,
+
+    /// If `true`, `tracers` was built with the `provider-definitions` feature, so a DTrace-style
+    /// `.d` file defining each provider is written to the `OUT_DIR` of the crate which declares it
+    #[serde(default)]
+    pub provider_definitions: bool,
+
+    /// If `true`, `tracers` was built with the `stap-dtrace` feature, so static stap providers are
+    /// compiled from the header and object file which SystemTap's `dtrace` tool generates from the
+    /// provider's `.d` file, rather than with the copy of `sys/sdt.h` bundled with `tracers`
+    #[serde(default)]
+    pub stap_dtrace: bool,
+
+    /// If `true`, `tracers` was built with the `strict` feature, or with `TRACERS_STRICT=1`, so a
+    /// provider trait which should have native code but doesn't is a compile error instead of
+    /// silently falling back to no-op probes
+    #[serde(default)]
+    pub strict: bool,
+
+    /// If `true`, `tracers` was built with the `tee-callback` feature, so the dynamic
+    /// `SystemTracer` fires every probe with both the platform's tracer and `CallbackTracer`
+    #[serde(default)]
+    pub tee_callback: bool,
 }
 
 impl BuildInfo {
     pub fn new(package_name: String, implementation: TracingImplementation) -> BuildInfo {
         BuildInfo {
+            schema_version: BUILD_INFO_SCHEMA_VERSION,
+            codegen_version: env!("CARGO_PKG_VERSION").to_owned(),
+            features: Vec::new(),
+            target: None,
             package_name,
             implementation,
+            probe_stats: false,
+            provider_definitions: false,
+            stap_dtrace: false,
+            strict: false,
+            tee_callback: false,
         }
     }
 
-    pub fn load() -> TracersResult<BuildInfo> {
+    /// Tests if the generated code should keep counters for each probe.  There is no runtime to
+    /// keep them in when tracing is disabled entirely.
+    pub fn probe_stats_enabled(&self) -> bool {
+        self.probe_stats && self.implementation.is_enabled()
+    }
+
+    /// Tests if a provider trait which wasn't processed by the native code generator should be a
+    /// compile error.  Besides the `strict` feature, this can be turned on for a single build by
+    /// setting `TRACERS_STRICT=1`, which is handy in CI.  That's read when `tracers` is built, not
+    /// here, so that Cargo knows to rebuild when it changes.
+    pub fn strict_enabled(&self) -> bool {
+        self.strict
+    }
+
+    /// Loads the `BuildInfo` written when `tracers` was built, and checks that `reader` can use it
+    pub fn load(reader: BuildInfoReader) -> TracersResult<BuildInfo> {
         let path = Self::get_build_path()?;
 
         let file = File::open(&path)
             .map_err(|e| TracersError::build_info_read_error(path.clone(), e.into()))?;
-        let reader = BufReader::new(file);
 
-        serde_json::from_reader(reader)
-            .map_err(|e| TracersError::build_info_read_error(path.clone(), e.into()))
+        let build_info: BuildInfo = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| TracersError::build_info_read_error(path.clone(), e.into()))?;
+        build_info.validate(&path, reader)?;
+
+        Ok(build_info)
+    }
+
+    /// Checks that this `BuildInfo`, which was written when `tracers` was built, can be used by
+    /// `reader`.  If the `tracers` crates resolve to different versions, the file is left over
+    /// from some other build, or its features don't match its implementation, the mismatch is
+    /// reported here rather than as some baffling failure later on.
+    fn validate(&self, path: &Path, reader: BuildInfoReader) -> TracersResult<()> {
+        let version = env!("CARGO_PKG_VERSION");
+
+        //Only build scripts know which target they're building for; `rustc` doesn't tell the proc
+        //macros
+        let target = match reader {
+            BuildInfoReader::BuildScript => env::var("TARGET").ok(),
+            BuildInfoReader::ProcMacros => None,
+        };
+        let reader = reader.crate_name();
+
+        if self.schema_version != BUILD_INFO_SCHEMA_VERSION {
+            let writer = if self.codegen_version.is_empty() {
+                "an older version of tracers-build".to_owned()
+            } else {
+                format!("tracers-build {}", self.codegen_version)
+            };
+            return Err(TracersError::build_info_mismatch_error(
+                path.to_owned(),
+                format!(
+                    "it was written by {} in format version {}, but {} {} expects version {}",
+                    writer, self.schema_version, reader, version, BUILD_INFO_SCHEMA_VERSION
+                ),
+            ));
+        }
+
+        if self.codegen_version != version {
+            return Err(TracersError::build_info_mismatch_error(
+                path.to_owned(),
+                format!(
+                    "tracers-build {} vs {} {}",
+                    self.codegen_version, reader, version
+                ),
+            ));
+        }
+
+        if let Some(feature) = self
+            .features
+            .iter()
+            .find(|feature| !FEATURE_NAMES.contains(&feature.as_str()))
+        {
+            return Err(TracersError::build_info_mismatch_error(
+                path.to_owned(),
+                format!(
+                    "`tracers` was built with the feature `{}`, which {} {} doesn't know about",
+                    feature, reader, version
+                ),
+            ));
+        }
+
+        if let Some(feature) = self
+            .features
+            .iter()
+            .find(|feature| !self.is_implementation_consistent_with(feature))
+        {
+            return Err(TracersError::build_info_mismatch_error(
+                path.to_owned(),
+                format!(
+                    "`tracers` was built with the feature `{}`, but with the implementation {}",
+                    feature,
+                    self.implementation.as_ref()
+                ),
+            ));
+        }
+
+        if self.tee_callback && !self.implementation.is_dynamic() {
+            return Err(TracersError::build_info_mismatch_error(
+                path.to_owned(),
+                format!(
+                    "the `tee-callback` feature requires dynamic tracing, but the implementation is {}",
+                    self.implementation.as_ref()
+                ),
+            ));
+        }
+
+        if let Some((feature, flag)) = self.find_inconsistent_flag() {
+            let problem = if flag {
+                format!(
+                    "its `{}` flag is set, but it wasn't built with that feature",
+                    feature
+                )
+            } else {
+                format!(
+                    "it was built with the feature `{}`, but that feature's flag isn't set",
+                    feature
+                )
+            };
+            return Err(TracersError::build_info_mismatch_error(
+                path.to_owned(),
+                format!("`tracers` {}", problem),
+            ));
+        }
+
+        if let (Some(expected), Some(actual)) = (&self.target, &target) {
+            if expected != actual {
+                return Err(TracersError::build_info_mismatch_error(
+                    path.to_owned(),
+                    format!(
+                        "`tracers` was built for target {}, but this build is for {}",
+                        expected, actual
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tests if `feature` could have been enabled when `select_implementation` chose this
+    /// `BuildInfo`'s implementation.  Features which don't influence that choice always are.
+    fn is_implementation_consistent_with(&self, feature: &str) -> bool {
+        let implementation = &self.implementation;
+        match feature {
+            "dynamic-tracing" => implementation.is_dynamic(),
+            "static-tracing" => implementation.is_static(),
+            "force-dyn-stap" => *implementation == TracingImplementation::DynamicStap,
+            "force-dyn-noop" => *implementation == TracingImplementation::DynamicNoOp,
+            "force-static-stap" => *implementation == TracingImplementation::StaticStap,
+            "force-static-lttng" => *implementation == TracingImplementation::StaticLttng,
+            "force-static-noop" => *implementation == TracingImplementation::StaticNoOp,
+            "force-static-chrome-trace" => {
+                *implementation == TracingImplementation::StaticChromeTrace
+            }
+            "force-static-ctf" => *implementation == TracingImplementation::StaticCtf,
+            _ => true,
+        }
+    }
+
+    /// Finds a flag which disagrees with `features` about whether the feature it records was
+    /// enabled, returning the feature's name and the flag's value.  `strict` can also be set by
+    /// `TRACERS_STRICT`, and `tee_callback` is only set for dynamic implementations.
+    fn find_inconsistent_flag(&self) -> Option<(&'static str, bool)> {
+        let has = |feature: &str| self.features.iter().any(|f| f == feature);
+        let flags = [
+            ("probe-stats", self.probe_stats, has("probe-stats")),
+            (
+                "provider-definitions",
+                self.provider_definitions,
+                has("provider-definitions"),
+            ),
+            ("stap-dtrace", self.stap_dtrace, has("stap-dtrace")),
+            ("strict", self.strict, has("strict") || self.strict),
+            (
+                "tee-callback",
+                self.tee_callback,
+                has("tee-callback") && self.implementation.is_dynamic(),
+            ),
+        ];
+
+        flags
+            .iter()
+            .find(|(_, flag, expected)| flag != expected)
+            .map(|(feature, flag, _)| (*feature, *flag))
     }
 
     pub fn save(&self) -> TracersResult<PathBuf> {
@@ -223,6 +609,227 @@ impl BuildInfo {
     }
 }
 
+/// Which of the `tracers` crates is loading the `BuildInfo`, which decides what it's checked
+/// against and how the reader is named in errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum BuildInfoReader {
+    /// `tracers-build`, called from the `build.rs` of `tracers` or of a crate which uses it
+    BuildScript,
+
+    /// `tracers-macros`, while `rustc` compiles a crate which uses `tracers`
+    ProcMacros,
+}
+
+impl BuildInfoReader {
+    fn crate_name(self) -> &'static str {
+        match self {
+            BuildInfoReader::BuildScript => "tracers-build",
+            BuildInfoReader::ProcMacros => "tracers-macros",
+        }
+    }
+}
+
+/// The environment variable which tells the proc macros where the native code was generated, if
+/// that's not `OUT_DIR`
+pub(crate) const OUT_DIR_ENV_VAR: &str = "TRACERS_OUT_DIR";
+
+/// The environment variable which, when set to `1` at build time, has the same effect as the
+/// `strict` feature
+pub(crate) const STRICT_ENV_VAR: &str = "TRACERS_STRICT";
+
+/// Something which went wrong generating the native code for a crate's providers.  These are
+/// reported as Cargo warnings, but don't fail the build unless `Builder::with_warnings_as_errors`
+/// is set; the affected probes just aren't available.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BuildFailure {
+    /// A source file couldn't be processed, so any providers in it weren't found
+    File { path: PathBuf, message: String },
+
+    /// A provider was found, but code generation for it failed
+    Provider { provider: String, message: String },
+}
+
+impl fmt::Display for BuildFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildFailure::File { path, message } => write!(f, "{}: {}", path.display(), message),
+            BuildFailure::Provider { provider, message } => write!(f, "{}: {}", provider, message),
+        }
+    }
+}
+
+/// What `Builder::try_build` did.  Only static tracing generates native code, so with any other
+/// implementation no providers are looked for.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildReport {
+    providers: Vec<String>,
+    native_libs: Vec<NativeLib>,
+    failures: Vec<BuildFailure>,
+}
+
+impl BuildReport {
+    /// The names of the providers found in the scanned source files, whether or not code
+    /// generation for them succeeded
+    pub fn providers(&self) -> &[String] {
+        &self.providers
+    }
+
+    /// The native libraries generated for the providers, and the support libraries they need
+    pub fn native_libs(&self) -> &[NativeLib] {
+        &self.native_libs
+    }
+
+    pub fn failures(&self) -> &[BuildFailure] {
+        &self.failures
+    }
+
+    pub(crate) fn add_provider(&mut self, name: &str) {
+        //A source file can be part of more than one target, so it can be processed more than once
+        if !self.providers.iter().any(|provider| provider == name) {
+            self.providers.push(name.to_owned());
+        }
+    }
+
+    pub(crate) fn add_failure(&mut self, failure: BuildFailure) {
+        if !self.failures.contains(&failure) {
+            self.failures.push(failure);
+        }
+    }
+}
+
+/// Configures the build-time code generation for a crate which uses `tracers`, for `build.rs`
+/// scripts which need more control than `build()` gives them.  By default it does exactly what
+/// `build()` does, which is to scan all of the package's targets and generate code in `OUT_DIR`.
+///
+/// ```no_execute
+/// // build.rs
+/// use tracers_build::{Builder, TargetKind};
+///
+/// fn main() {
+///     let report = Builder::new()
+///         .with_target_kinds(vec![TargetKind::Lib, TargetKind::Bin])
+///         .with_warnings_as_errors(true)
+///         .try_build()
+///         .expect("tracers code generation failed");
+///
+///     //....
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Builder {
+    target_kinds: Option<Vec<TargetKind>>,
+    source_roots: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+    warnings_as_errors: bool,
+    manifest_path: Option<PathBuf>,
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder::default()
+    }
+
+    /// Scans only the package's targets of these kinds for providers, rather than all of them
+    pub fn with_target_kinds(self, target_kinds: impl IntoIterator<Item = TargetKind>) -> Builder {
+        Builder {
+            target_kinds: Some(target_kinds.into_iter().collect()),
+            ..self
+        }
+    }
+
+    /// Also scans the source file at `path`, and the modules it declares, for providers.  This is
+    /// for source files which aren't reachable from the package's targets, like generated ones.
+    /// A relative path is relative to the package's manifest directory.
+    pub fn with_source_root(mut self, path: impl Into<PathBuf>) -> Builder {
+        self.source_roots.push(path.into());
+        self
+    }
+
+    /// Generates code in `out_dir` rather than in `OUT_DIR`
+    pub fn with_out_dir(self, out_dir: impl Into<PathBuf>) -> Builder {
+        Builder {
+            out_dir: Some(out_dir.into()),
+            ..self
+        }
+    }
+
+    /// If `true`, any `BuildFailure` fails the build, rather than only being reported as a warning
+    pub fn with_warnings_as_errors(self, warnings_as_errors: bool) -> Builder {
+        Builder {
+            warnings_as_errors,
+            ..self
+        }
+    }
+
+    /// Writes the `BuildReport` to `path` as JSON, for tools which need to know which providers
+    /// and libraries the build produced.  It's written even if there were failures.
+    pub fn with_manifest_path(self, manifest_path: impl Into<PathBuf>) -> Builder {
+        Builder {
+            manifest_path: Some(manifest_path.into()),
+            ..self
+        }
+    }
+
+    /// Performs the code generation, printing the instructions for Cargo to stdout.  Unlike
+    /// `build()`, this never exits the process; the caller decides what to do with an error.
+    pub fn try_build(&self) -> TracersResult<BuildReport> {
+        self.build_internal(&mut std::io::stdout())
+    }
+
+    fn build_internal(&self, out: &mut dyn Write) -> TracersResult<BuildReport> {
+        //First things first; get the BuildInfo from the `tracers` build, and tell Cargo to make
+        //that available to the proc macros at compile time via an environment variable
+        let build_info_path = BuildInfo::get_build_path()?;
+        writeln!(
+            out,
+            "cargo:rustc-env=TRACERS_BUILD_INFO_PATH={}",
+            build_info_path.display()
+        )
+        .unwrap();
+
+        //The proc macros look for the generated code in `OUT_DIR` unless told otherwise
+        if let Some(out_dir) = &self.out_dir {
+            writeln!(
+                out,
+                "cargo:rustc-env={}={}",
+                OUT_DIR_ENV_VAR,
+                out_dir.display()
+            )
+            .unwrap();
+        }
+
+        let report = generate_native_code(out, self)?;
+
+        if let Some(manifest_path) = &self.manifest_path {
+            write_build_report(&report, manifest_path)?;
+        }
+
+        if self.warnings_as_errors && !report.failures.is_empty() {
+            let failures: Vec<_> = report.failures.iter().map(ToString::to_string).collect();
+            return Err(TracersError::code_generation_error(format!(
+                "{} failure(s) with warnings treated as errors: {}",
+                failures.len(),
+                failures.join("; ")
+            )));
+        }
+
+        Ok(report)
+    }
+}
+
+fn write_build_report(report: &BuildReport, path: &Path) -> TracersResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|_| format!("Creating directory {}", parent.display()))?;
+    }
+
+    let file = File::create(path).with_context(|_| format!("Creating {}", path.display()))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), report)
+        .with_context(|_| format!("Writing build report to {}", path.display()))?;
+
+    Ok(())
+}
+
 /// Called from the `build.rs` of all crates which have a direct dependency on `tracers` and
 /// `tracers_macros`.  This determines the compile-time configuration of the `tracers` crate, and
 /// performs any build-time code generation necessary to support the code generated by the
@@ -240,11 +847,14 @@ impl BuildInfo {
 ///     //....
 /// }
 /// ```
+///
+/// This is `Builder::new().try_build()`, except that it exits the process if that fails.  Use
+/// `Builder` to configure the build, or to handle failure some other way.
 pub fn build() {
     let mut stdout = std::io::stdout();
     let mut stderr = std::io::stderr();
 
-    match build_internal(&mut stdout) {
+    match Builder::new().try_build() {
         Ok(_) => writeln!(stdout, "probes build succeeded").unwrap(),
         Err(e) => {
             //An error that propagates all the way up to here is serious enough that it means we
@@ -256,20 +866,6 @@ pub fn build() {
     };
 }
 
-fn build_internal<OUT: Write>(out: &mut OUT) -> TracersResult<()> {
-    //First things first; get the BuildInfo from the `tracers` build, and tell Cargo to make that
-    //available to the proc macros at compile time via an environment variable
-    let build_info_path = BuildInfo::get_build_path()?;
-    writeln!(
-        out,
-        "cargo:rustc-env=TRACERS_BUILD_INFO_PATH={}",
-        build_info_path.display()
-    )
-    .unwrap();
-
-    generate_native_code(out)
-}
-
 /// This function is the counterpart to `build`, which is intended to be invoked in the `tracers`
 /// `build.rs` script.  It reads the feature flags enabled on `tracers`, and from those flags and
 /// other information about the target sytem and the local build environment selects an
@@ -295,6 +891,14 @@ pub fn tracers_build() {
 fn tracers_build_internal<OUT: Write>(out: &mut OUT, features: FeatureFlags) -> TracersResult<()> {
     writeln!(out, "Detected features: \n{:?}", features).unwrap();
 
+    //`TRACERS_STRICT` is recorded in the build info, so `tracers` must be rebuilt when it changes.
+    //Asking for that replaces Cargo's default of rerunning this whenever a file in the package
+    //changes, which the code generation for the tests and examples below still needs
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").context("CARGO_MANIFEST_DIR")?;
+    writeln!(out, "cargo:rerun-if-env-changed={}", STRICT_ENV_VAR).unwrap();
+    writeln!(out, "cargo:rerun-if-changed={}", manifest_dir).unwrap();
+    let strict_env_var = env::var(STRICT_ENV_VAR).map(|v| v == "1").unwrap_or(false);
+
     select_implementation(&features).map(|implementation| {
             // Some implementation was selected, but it's possible that the selected
             // "implementation" is to completely disable tracing.  If that's not the case, set the
@@ -310,6 +914,11 @@ fn tracers_build_internal<OUT: Write>(out: &mut OUT, features: FeatureFlags) ->
                     }
                 ).unwrap(); //this category of tracing is enabled
                 writeln!(out, "cargo:rustc-cfg={}_enabled", implementation.as_ref()).unwrap(); //this specific impl is enabled
+
+                //The in-process callback is teed with the dynamic implementation
+                if implementation.is_dynamic() && features.tee_callback() {
+                    writeln!(out, "cargo:rustc-cfg=dyn_tee_callback_enabled").unwrap();
+                }
             }
 
             //All downstream creates from `tracers` will just call `tracers_build::build`, but this
@@ -318,7 +927,14 @@ fn tracers_build_internal<OUT: Write>(out: &mut OUT, features: FeatureFlags) ->
             //This decision needs to be saved to the OUT_DIR somewhere, so that all of our tests,
             //examples, binaries, and benchmarks which use the proc macros will be able to generate
             //the correct runtime tracing code to match the implementation we've chosen here
-            let build_info = BuildInfo::new(env::var("CARGO_PKG_NAME").expect("CARGO_PKG_NAME"), implementation);
+            let mut build_info = BuildInfo::new(env::var("CARGO_PKG_NAME").expect("CARGO_PKG_NAME"), implementation);
+            build_info.probe_stats = features.probe_stats();
+            build_info.provider_definitions = features.provider_definitions();
+            build_info.stap_dtrace = features.stap_dtrace();
+            build_info.strict = features.strict() || strict_env_var;
+            build_info.tee_callback = features.tee_callback() && build_info.implementation.is_dynamic();
+            build_info.features = features.enabled_features();
+            build_info.target = env::var("TARGET").ok();
             match build_info.save() {
                 Ok(build_info_path) => {
                     //The above statements set compile-time features to the compiler knows which modules to
@@ -339,7 +955,7 @@ fn tracers_build_internal<OUT: Write>(out: &mut OUT, features: FeatureFlags) ->
     //library code contains any `#[tracer]` traits, but the tests and examples do, so if we
     //want them to work propertly we need to run codegen for them just like on any other
     //crate
-    generate_native_code(out)
+    generate_native_code(out, &Builder::new()).map(|_| ())
 }
 
 /// Selects a `tracers` implementation given a set of feature flags specified by the user
@@ -381,24 +997,49 @@ fn select_implementation(features: &FeatureFlags) -> TracersResult<TracingImplem
             Ok(TracingImplementation::StaticStap)
         } else if features.force_static_lttng() {
             Ok(TracingImplementation::StaticLttng)
+        } else if features.force_static_chrome_trace() {
+            Ok(TracingImplementation::StaticChromeTrace)
+        } else if features.force_static_ctf() {
+            Ok(TracingImplementation::StaticCtf)
         } else {
             Ok(TracingImplementation::StaticNoOp)
         }
     }
 }
 
-fn generate_native_code(out: &mut dyn Write) -> TracersResult<()> {
-    let manifest_dir = env::var("CARGO_MANIFEST_DIR").context(
+fn generate_native_code(out: &mut dyn Write, builder: &Builder) -> TracersResult<BuildReport> {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").context(
         "CARGO_MANIFEST_DIR is not set; are you sure you're calling this from within build.rs?",
-    )?;
+    )?);
 
-    let manifest_path = PathBuf::from(manifest_dir).join("Cargo.toml");
+    let manifest_path = manifest_dir.join("Cargo.toml");
     let package_name = env::var("CARGO_PKG_NAME").unwrap();
-    let targets = cargo::get_targets(&manifest_path, &package_name).context("get_targets")?;
-    let out_path = &PathBuf::from(env::var("OUT_DIR").context("OUT_DIR")?);
+    let mut targets: Vec<PathBuf> = cargo::get_targets(&manifest_path)
+        .context("get_targets")?
+        .into_iter()
+        .filter(|target| {
+            builder
+                .target_kinds
+                .as_ref()
+                .map_or(true, |kinds| kinds.contains(&target.kind))
+        })
+        .map(|target| target.src_path)
+        .collect();
+    targets.extend(
+        builder
+            .source_roots
+            .iter()
+            .map(|source_root| manifest_dir.join(source_root)),
+    );
+    let out_path = &match &builder.out_dir {
+        Some(out_dir) => out_dir.clone(),
+        None => PathBuf::from(env::var("OUT_DIR").context("OUT_DIR")?),
+    };
 
-    let mut native_libs = gen::code_generator()?.generate_native_code(
+    let mut report = BuildReport::default();
+    let mut native_libs = gen::code_generator(BuildInfoReader::BuildScript)?.generate_native_code(
         out,
+        &mut report,
         &Path::new(&manifest_path),
         &out_path,
         &package_name,
@@ -411,7 +1052,7 @@ fn generate_native_code(out: &mut dyn Write) -> TracersResult<()> {
     native_libs.dedup();
 
     //Scan through all of the native libs output and send the info to cargo as applicable
-    for native_lib in native_libs.into_iter() {
+    for native_lib in native_libs.iter() {
         match native_lib {
             NativeLib::StaticWrapperLib(_) => {
                 //This is the name of a generated native wrapper.  Ignore it here; the `tracers`
@@ -433,10 +1074,46 @@ fn generate_native_code(out: &mut dyn Write) -> TracersResult<()> {
                 //for the crate, to support any number of generated native wrappers
                 println!("cargo:rustc-link-lib=static={}", lib);
             }
+            NativeLib::Tapset {
+                provider_name,
+                path,
+            } => {
+                //This is a SystemTap tapset, which is only of use outside of the build, so it's
+                //installed if the user asked for that.  Failing to install it doesn't affect the
+                //probes themselves, so that's only a warning
+                if let Ok(tapset_dir) = env::var("TRACERS_TAPSET_DIR") {
+                    let binary =
+                        env::var("TRACERS_TAPSET_BINARY").unwrap_or_else(|_| package_name.clone());
+                    match gen::r#static::install_tapset(
+                        provider_name,
+                        path,
+                        Path::new(&tapset_dir),
+                        &binary,
+                    ) {
+                        Ok(installed_path) => {
+                            writeln!(out, "Installed tapset {}", installed_path.display()).unwrap()
+                        }
+                        Err(e) => {
+                            writeln!(
+                                out,
+                                "cargo:warning=Error installing tapset for '{}': {}",
+                                provider_name, e
+                            )
+                            .unwrap();
+                            report.add_failure(BuildFailure::Provider {
+                                provider: provider_name.clone(),
+                                message: format!("Error installing tapset: {}", e),
+                            });
+                        }
+                    }
+                }
+            }
         };
     }
 
-    Ok(())
+    report.native_libs = native_libs;
+
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -501,6 +1178,20 @@ mod tests {
                 FeatureFlags::new(false, true, false, false, false, true, false).unwrap(),
                 TracingImplementation::StaticLttng,
             ),
+            (
+                // Tracing enabled, dynamic disabled, static enabled with force-static-chrome-trace
+                FeatureFlags::new(false, true, false, false, false, false, false)
+                    .and_then(|features| features.with_force_static_chrome_trace(true))
+                    .unwrap(),
+                TracingImplementation::StaticChromeTrace,
+            ),
+            (
+                // Tracing enabled, dynamic disabled, static enabled with force-static-ctf
+                FeatureFlags::new(false, true, false, false, false, false, false)
+                    .and_then(|features| features.with_force_static_ctf(true))
+                    .unwrap(),
+                TracingImplementation::StaticCtf,
+            ),
         ];
 
         let temp_dir = tempfile::tempdir().unwrap();
@@ -532,12 +1223,13 @@ mod tests {
             //That worked.  The resulting build info should have been written out
             let build_info_path = BuildInfo::get_build_path().unwrap();
 
-            let step1_build_info = BuildInfo::load().unwrap_or_else(|_| {
-                panic!(format!(
-                    "Failed to load build info for features: {:?}",
-                    features
-                ))
-            });
+            let step1_build_info =
+                BuildInfo::load(BuildInfoReader::BuildScript).unwrap_or_else(|_| {
+                    panic!(format!(
+                        "Failed to load build info for features: {:?}",
+                        features
+                    ))
+                });
 
             assert_eq!(
                 expected_impl, step1_build_info.implementation,
@@ -548,6 +1240,11 @@ mod tests {
             //And the path to this should have been written to stdout such that cargo will treat it
             //as a variable that is passed to dependent crates' `build.rs`:
             let output = String::from_utf8(stdout).unwrap();
+            assert!(
+                output.contains("cargo:rerun-if-env-changed=TRACERS_STRICT"),
+                "{}",
+                context
+            );
             assert!(
                 output.contains(&format!(
                     "cargo:build-info-path={}",
@@ -583,12 +1280,13 @@ mod tests {
                 ("OUT_DIR", out_dir.to_str().unwrap()),
             ]);
 
-            let step2_build_info = BuildInfo::load().unwrap_or_else(|_| {
-                panic!(format!(
-                    "Failed to load build info for features: {:?}",
-                    features
-                ))
-            });
+            let step2_build_info =
+                BuildInfo::load(BuildInfoReader::BuildScript).unwrap_or_else(|_| {
+                    panic!(format!(
+                        "Failed to load build info for features: {:?}",
+                        features
+                    ))
+                });
 
             assert_eq!(step1_build_info, step2_build_info, "context: {}", context);
             drop(guard);
@@ -622,7 +1320,7 @@ mod tests {
                     ("OPT_LEVEL", "1"),
                 ]);
 
-                build_internal(&mut stdout).expect(&context);
+                Builder::new().build_internal(&mut stdout).expect(&context);
 
                 //After the build, it should output something on stdout to tell Cargo to set a
                 //compiler-visible env var telling the proc macros where the `BuildInfo` file is
@@ -642,16 +1340,269 @@ mod tests {
                 build_info_path.to_str().unwrap(),
             )]);
 
-            let step3_build_info = BuildInfo::load().unwrap_or_else(|_| {
-                panic!(format!(
-                    "Failed to load build info for features: {:?}",
-                    features
-                ))
-            });
+            let step3_build_info =
+                BuildInfo::load(BuildInfoReader::ProcMacros).unwrap_or_else(|_| {
+                    panic!(format!(
+                        "Failed to load build info for features: {:?}",
+                        features
+                    ))
+                });
 
             assert_eq!(step1_build_info, step3_build_info, "context: {}", context);
 
             drop(guard);
         }
     }
+
+    #[test]
+    fn records_strict_env_var_in_build_info() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let out_dir = temp_dir.path().join("out");
+
+        for (strict, expected) in [("1", true), ("0", false)].iter() {
+            let guard = testdata::with_env_vars(vec![
+                ("CARGO_PKG_NAME", "tracers"),
+                ("CARGO_PKG_VERSION", "1.2.3"),
+                ("CARGO_MANIFEST_DIR", env!("CARGO_MANIFEST_DIR")),
+                ("OUT_DIR", out_dir.to_str().unwrap()),
+                ("TRACERS_STRICT", strict),
+            ]);
+
+            let features =
+                FeatureFlags::new(false, true, false, false, false, false, true).unwrap();
+            tracers_build_internal(&mut Vec::new(), features).unwrap();
+
+            //The proc macros only go by the build info, whatever the env var is when they run
+            let build_info = BuildInfo::load(BuildInfoReader::ProcMacros).unwrap();
+            assert_eq!(
+                *expected,
+                build_info.strict_enabled(),
+                "TRACERS_STRICT={}",
+                strict
+            );
+
+            drop(guard);
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_build_info() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let build_info_path = temp_dir.path().join("buildinfo.json");
+        let version = env!("CARGO_PKG_VERSION");
+
+        //Loads the build info as the proc macros would, or as a dependent crate's `build.rs` would
+        //if `target` is given
+        let load = |json: &str, target: Option<&str>| {
+            std::fs::write(&build_info_path, json).unwrap();
+            let mut vars = vec![("TRACERS_BUILD_INFO_PATH", build_info_path.to_str().unwrap())];
+            let reader = match target {
+                Some(target) => {
+                    vars.push(("TARGET", target));
+                    BuildInfoReader::BuildScript
+                }
+                None => BuildInfoReader::ProcMacros,
+            };
+            let _guard = testdata::with_env_vars(vars);
+
+            BuildInfo::load(reader).map_err(|e| e.to_string())
+        };
+
+        let mut build_info =
+            BuildInfo::new("tracers".to_owned(), TracingImplementation::StaticNoOp);
+        build_info.features = vec!["static-tracing".to_owned(), "strict".to_owned()];
+        build_info.strict = true;
+        build_info.target = Some("x86_64-unknown-linux-gnu".to_owned());
+        let json = |build_info: &BuildInfo| serde_json::to_string(build_info).unwrap();
+
+        assert_eq!(Ok(build_info.clone()), load(&json(&build_info), None));
+        assert_eq!(
+            Ok(build_info.clone()),
+            load(&json(&build_info), Some("x86_64-unknown-linux-gnu"))
+        );
+
+        //Written before the format was versioned
+        let error = load(
+            r#"{"package_name":"tracers","implementation":"StaticNoOp"}"#,
+            None,
+        )
+        .unwrap_err();
+        assert!(
+            error.contains("an older version of tracers-build in format version 0"),
+            "{}",
+            error
+        );
+
+        let mut other_version = build_info.clone();
+        other_version.codegen_version = "0.0.1".to_owned();
+        let error = load(&json(&other_version), None).unwrap_err();
+        assert!(
+            error.contains(&format!(
+                "tracers-build 0.0.1 vs tracers-macros {}",
+                version
+            )),
+            "{}",
+            error
+        );
+        let error = load(&json(&other_version), Some("x86_64-unknown-linux-gnu")).unwrap_err();
+        assert!(
+            error.contains(&format!("tracers-build 0.0.1 vs tracers-build {}", version)),
+            "{}",
+            error
+        );
+
+        let mut unknown_feature = build_info.clone();
+        unknown_feature.features.push("force-static-etw".to_owned());
+        let error = load(&json(&unknown_feature), None).unwrap_err();
+        assert!(error.contains("`force-static-etw`"), "{}", error);
+
+        let mut wrong_implementation = build_info.clone();
+        wrong_implementation.implementation = TracingImplementation::DynamicNoOp;
+        let error = load(&json(&wrong_implementation), None).unwrap_err();
+        assert!(
+            error.contains(
+                "built with the feature `static-tracing`, but with the implementation dyn_noop"
+            ),
+            "{}",
+            error
+        );
+
+        let mut forced_implementation = build_info.clone();
+        forced_implementation
+            .features
+            .push("force-static-lttng".to_owned());
+        let error = load(&json(&forced_implementation), None).unwrap_err();
+        assert!(
+            error.contains("`force-static-lttng`, but with the implementation static_noop"),
+            "{}",
+            error
+        );
+
+        let mut static_tee = build_info.clone();
+        static_tee.tee_callback = true;
+        let error = load(&json(&static_tee), None).unwrap_err();
+        assert!(
+            error.contains("`tee-callback` feature requires dynamic tracing"),
+            "{}",
+            error
+        );
+
+        let mut unlisted_flag = build_info.clone();
+        unlisted_flag.probe_stats = true;
+        let error = load(&json(&unlisted_flag), None).unwrap_err();
+        assert!(
+            error.contains("its `probe-stats` flag is set, but it wasn't built with that feature"),
+            "{}",
+            error
+        );
+
+        let mut unset_flag = build_info.clone();
+        unset_flag.strict = false;
+        let error = load(&json(&unset_flag), None).unwrap_err();
+        assert!(
+            error.contains("the feature `strict`, but that feature's flag isn't set"),
+            "{}",
+            error
+        );
+
+        //`TRACERS_STRICT` sets `strict` without the feature, and `tee_callback` is only set for
+        //dynamic implementations
+        let mut flags_without_features = build_info.clone();
+        flags_without_features.features =
+            vec!["static-tracing".to_owned(), "tee-callback".to_owned()];
+        flags_without_features.strict = true;
+        assert_eq!(
+            Ok(flags_without_features.clone()),
+            load(&json(&flags_without_features), None)
+        );
+
+        let error = load(&json(&build_info), Some("aarch64-apple-darwin")).unwrap_err();
+        assert!(
+            error.contains("built for target x86_64-unknown-linux-gnu, but this build is for aarch64-apple-darwin"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn builder_selects_targets_and_reports() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let out_dir = temp_dir.path().join("out");
+        let build_info_path = temp_dir.path().join("buildinfo.json");
+        let build_info = BuildInfo::new("tracers".to_owned(), TracingImplementation::StaticStap);
+        serde_json::to_writer(File::create(&build_info_path).unwrap(), &build_info).unwrap();
+
+        let try_build = |package_name: &str, builder: Builder| {
+            let test_case = testdata::TEST_CRATES
+                .iter()
+                .find(|test_case| test_case.package_name == package_name)
+                .unwrap();
+            let guard = testdata::with_env_vars(vec![
+                (
+                    "DEP_TRACERS_BUILD_INFO_PATH",
+                    build_info_path.to_str().unwrap(),
+                ),
+                ("OUT_DIR", out_dir.to_str().unwrap()),
+                ("CARGO_PKG_NAME", test_case.package_name),
+                (
+                    "CARGO_MANIFEST_DIR",
+                    test_case.root_directory.to_str().unwrap(),
+                ),
+                ("TARGET", "x86_64-linux-gnu"),
+                ("HOST", "x86_64-linux-gnu"),
+                ("OPT_LEVEL", "1"),
+            ]);
+            let mut stdout = Vec::new();
+            let result = builder.build_internal(&mut stdout);
+            drop(guard);
+
+            (result, String::from_utf8(stdout).unwrap())
+        };
+
+        //Only the library of `complexlib` declares a provider
+        let (report, _) = try_build("complexlib", Builder::new());
+        let report = report.unwrap();
+        assert_eq!(1, report.providers().len(), "{:?}", report);
+        assert_eq!(Vec::<BuildFailure>::new(), report.failures());
+        assert!(report
+            .native_libs()
+            .iter()
+            .any(|lib| matches!(lib, NativeLib::StaticWrapperLib(_))));
+
+        let (bins_only, output) = try_build(
+            "complexlib",
+            Builder::new()
+                .with_target_kinds(vec![TargetKind::Bin, TargetKind::Example])
+                .with_out_dir(&out_dir),
+        );
+        assert_eq!(Vec::<String>::new(), bins_only.unwrap().providers());
+        assert!(output.contains(&format!(
+            "cargo:rustc-env=TRACERS_OUT_DIR={}",
+            out_dir.display()
+        )));
+
+        let (with_root, _) = try_build(
+            "complexlib",
+            Builder::new()
+                .with_target_kinds(vec![TargetKind::Bin])
+                .with_source_root("src/lib.rs"),
+        );
+        assert_eq!(report.providers(), with_root.unwrap().providers());
+
+        //The `erroneous` crate has a module which doesn't exist and a file which doesn't parse.
+        //Those are only warnings, unless they're treated as errors
+        let manifest_path = temp_dir.path().join("manifests/erroneous.json");
+        let (report, _) = try_build(
+            "erroneous",
+            Builder::new().with_manifest_path(&manifest_path),
+        );
+        let report = report.unwrap();
+        assert_eq!(2, report.failures().len(), "{:?}", report);
+        let manifest: BuildReport =
+            serde_json::from_reader(File::open(&manifest_path).unwrap()).unwrap();
+        assert_eq!(report, manifest);
+
+        let (result, _) = try_build("erroneous", Builder::new().with_warnings_as_errors(true));
+        assert!(result.is_err());
+    }
 }
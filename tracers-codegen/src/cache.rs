@@ -104,6 +104,21 @@ pub(crate) fn get_cached_object_computation<T: Serialize + DeserializeOwned>(
     load_cached_results::<T>(&abs_path)
 }
 
+/// Saves `result` in the cache as the result of a computation on an object, replacing any previous
+/// result.  This is for results which aren't produced by `cache_object_computation`, like a record
+/// of why that computation failed.
+pub(crate) fn save_object_computation<T: Serialize + DeserializeOwned>(
+    cache_path: &Path,
+    object_name: &str,
+    hash: HashCode,
+    key: &str,
+    result: &T,
+) -> Fallible<()> {
+    let results_path = cached_results_path(Path::new(object_name), key, hash);
+
+    save_results::<T>(&cache_path.join(results_path), result)
+}
+
 /// Lower-level caching function.  Given some arbitrary file name (and optional path components)
 /// relative to the cache path, if the file exists, returns the fully qualified path to the file in
 /// the cache, if not, it passes that fully qualified path to the provided closure, and if that
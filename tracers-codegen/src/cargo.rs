@@ -1,29 +1,279 @@
-use cargo_metadata::MetadataCommand;
-use failure::{format_err, Fallible};
+//! Finds the targets of a package from its `Cargo.toml`.  This is done by reading the manifest
+//! directly and applying Cargo's target auto-discovery conventions, rather than by running `cargo
+//! metadata`, which is slow on large workspaces and can fail from inside a build script when
+//! building offline or with a custom `CARGO_HOME`.
+use failure::{format_err, Fallible, ResultExt};
+use serde::{Deserialize, Serialize};
+use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 
-/// Given the path to a Cargo manifest and the name of a package, invokes `cargo metadata` and
-/// parses the output to find all of the targets of that package.
+/// The kinds of target a package can have.  Used with `Builder::with_target_kinds` to choose
+/// which of a package's targets are scanned for providers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TargetKind {
+    /// The package's library, of any crate type
+    Lib,
+    Bin,
+    Test,
+    Bench,
+    Example,
+    /// The package's `build.rs`
+    BuildScript,
+}
+
+/// One of the targets of a package
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Target {
+    pub kind: TargetKind,
+
+    /// The entry point of the target, like `src/lib.rs`
+    pub src_path: PathBuf,
+}
+
+/// The parts of `Cargo.toml` which determine the package's targets
+#[derive(Deserialize)]
+struct Manifest {
+    package: Package,
+    lib: Option<TargetManifest>,
+    #[serde(default)]
+    bin: Vec<TargetManifest>,
+    #[serde(default)]
+    example: Vec<TargetManifest>,
+    #[serde(default)]
+    test: Vec<TargetManifest>,
+    #[serde(default)]
+    bench: Vec<TargetManifest>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+
+    /// Usually a string, but it can be inherited from the workspace, which is only allowed in
+    /// editions much later than 2015
+    edition: Option<toml::Value>,
+
+    build: Option<BuildScript>,
+    autobins: Option<bool>,
+    autoexamples: Option<bool>,
+    autotests: Option<bool>,
+    autobenches: Option<bool>,
+}
+
+/// The `build` key, which is either the path of the build script or whether there is one at all
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BuildScript {
+    Enabled(bool),
+    Path(PathBuf),
+}
+
+/// A `[lib]`, `[[bin]]`, `[[example]]`, `[[test]]` or `[[bench]]` section
+#[derive(Deserialize)]
+struct TargetManifest {
+    name: Option<String>,
+    path: Option<PathBuf>,
+}
+
+/// Reads the package manifest at `manifest_path` and finds all of the targets of that package,
+/// the same way Cargo does.
 ///
-/// If successful, returns a `Vec` of `Path`s, each the entry point of one of the package's
-/// targets.
-pub(crate) fn get_targets(manifest_path: &Path, package_name: &str) -> Fallible<Vec<PathBuf>> {
-    let mut cmd = MetadataCommand::new();
-    let metadata = cmd
-        .manifest_path(&manifest_path)
-        .no_deps()
-        .exec()
-        .map_err(|e| format_err!("cargo metadata invocation failed: {}", e))?;
-
-    //Find this specific package
-    let package = metadata
-        .packages
+/// If successful, returns the kind and entry point of each target.  The entry points are relative
+/// to the manifest's directory, joined to the directory `manifest_path` is in.
+pub(crate) fn get_targets(manifest_path: &Path) -> Fallible<Vec<Target>> {
+    let contents = fs::read_to_string(manifest_path)
+        .with_context(|_| format!("Reading {}", manifest_path.display()))?;
+    let manifest: Manifest = toml::from_str(&contents)
+        .map_err(|e| format_err!("Error parsing {}: {}", manifest_path.display(), e))?;
+    let package_dir = manifest_path
+        .parent()
+        .ok_or_else(|| format_err!("{} has no parent directory", manifest_path.display()))?;
+
+    //In the 2015 edition, declaring any target of a kind turns off auto-discovery of that kind,
+    //unless it's explicitly turned back on
+    let is_2015 = match &manifest.package.edition {
+        None => true,
+        Some(toml::Value::String(edition)) => edition == "2015",
+        Some(_) => false,
+    };
+    let autodiscover = |auto: Option<bool>, declared: &[TargetManifest]| {
+        auto.unwrap_or(!is_2015 || declared.is_empty())
+    };
+
+    let mut targets = Vec::new();
+
+    let default_lib = Path::new("src/lib.rs");
+    let lib_path = match &manifest.lib {
+        Some(TargetManifest {
+            path: Some(path), ..
+        }) => Some(path.as_path()),
+        _ if package_dir.join(default_lib).is_file() => Some(default_lib),
+        _ => None,
+    };
+    if let Some(lib_path) = lib_path {
+        targets.push(Target {
+            kind: TargetKind::Lib,
+            src_path: package_dir.join(lib_path),
+        });
+    }
+
+    let mut bins = Vec::new();
+    if autodiscover(manifest.package.autobins, &manifest.bin) {
+        if package_dir.join("src/main.rs").is_file() {
+            bins.push((manifest.package.name.clone(), PathBuf::from("src/main.rs")));
+        }
+        bins.extend(discover_targets(package_dir, "src/bin")?);
+    }
+    targets.extend(resolve_targets(
+        package_dir,
+        TargetKind::Bin,
+        bins,
+        &manifest.bin,
+        |name| {
+            let mut paths = default_target_paths("src/bin", name);
+            if *name == manifest.package.name {
+                paths.push(PathBuf::from("src/main.rs"));
+            }
+            paths
+        },
+    ));
+
+    for (kind, dir, auto, declared) in [
+        (
+            TargetKind::Example,
+            "examples",
+            manifest.package.autoexamples,
+            &manifest.example,
+        ),
+        (
+            TargetKind::Test,
+            "tests",
+            manifest.package.autotests,
+            &manifest.test,
+        ),
+        (
+            TargetKind::Bench,
+            "benches",
+            manifest.package.autobenches,
+            &manifest.bench,
+        ),
+    ]
+    .iter()
+    {
+        let discovered = if autodiscover(*auto, declared) {
+            discover_targets(package_dir, dir)?
+        } else {
+            Vec::new()
+        };
+        targets.extend(resolve_targets(
+            package_dir,
+            *kind,
+            discovered,
+            declared,
+            |name| default_target_paths(dir, name),
+        ));
+    }
+
+    let build_script = match &manifest.package.build {
+        Some(BuildScript::Path(path)) => Some(path.clone()),
+        Some(BuildScript::Enabled(false)) => None,
+        Some(BuildScript::Enabled(true)) => Some(PathBuf::from("build.rs")),
+        None if package_dir.join("build.rs").is_file() => Some(PathBuf::from("build.rs")),
+        None => None,
+    };
+    if let Some(build_script) = build_script {
+        targets.push(Target {
+            kind: TargetKind::BuildScript,
+            src_path: package_dir.join(build_script),
+        });
+    }
+
+    Ok(targets)
+}
+
+/// Finds the targets Cargo would discover in a directory like `examples`: each `.rs` file in it,
+/// and each subdirectory with a `main.rs`.  Returns the name and path of each, sorted by name.
+fn discover_targets(package_dir: &Path, dir: &str) -> Fallible<Vec<(String, PathBuf)>> {
+    let mut targets = Vec::new();
+    let entries = match fs::read_dir(package_dir.join(dir)) {
+        Ok(entries) => entries,
+        //Most packages don't have all of these directories
+        Err(_) => return Ok(targets),
+    };
+
+    for entry in entries {
+        let path = entry
+            .with_context(|_| format!("Reading directory {}", dir))?
+            .path();
+        let relative_path = Path::new(dir).join(path.file_name().unwrap());
+
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "rs") {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            targets.push((name, relative_path));
+        } else if path.join("main.rs").is_file() {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            targets.push((name, relative_path.join("main.rs")));
+        }
+    }
+
+    targets.sort();
+    Ok(targets)
+}
+
+/// The paths where a target declared without a `path` can be, in the order Cargo looks
+fn default_target_paths(dir: &str, name: &str) -> Vec<PathBuf> {
+    vec![
+        Path::new(dir).join(format!("{}.rs", name)),
+        Path::new(dir).join(name).join("main.rs"),
+    ]
+}
+
+/// Combines the auto-discovered targets of a kind with the declared ones.  A declared target
+/// replaces a discovered one with the same name or path.  A declared target without a path is at
+/// the first of its `default_paths` which exists.
+fn resolve_targets(
+    package_dir: &Path,
+    kind: TargetKind,
+    discovered: Vec<(String, PathBuf)>,
+    declared: &[TargetManifest],
+    default_paths: impl Fn(&str) -> Vec<PathBuf>,
+) -> Vec<Target> {
+    let declared: Vec<(Option<&str>, PathBuf)> = declared
         .iter()
-        .find(|p| p.name == package_name)
-        .ok_or_else(|| format_err!("Unable to find package {} in cargo metadata", package_name))?;
+        .filter_map(|target| {
+            let name = target.name.as_deref();
+            let path = match (&target.path, name) {
+                (Some(path), _) => Some(path.clone()),
+                (None, Some(name)) => default_paths(name)
+                    .into_iter()
+                    .find(|path| package_dir.join(path).is_file()),
+                //Cargo rejects a target with neither, so there's nothing to find
+                (None, None) => None,
+            };
+
+            path.map(|path| (name, path))
+        })
+        .collect();
 
-    Ok(package.targets.iter().map(|t| t.src_path.clone()).collect())
+    let discovered: Vec<PathBuf> = discovered
+        .into_iter()
+        .filter(|(name, path)| {
+            !declared.iter().any(|(declared_name, declared_path)| {
+                *declared_name == Some(name.as_str()) || declared_path == path
+            })
+        })
+        .map(|(_, path)| path)
+        .collect();
+
+    discovered
+        .into_iter()
+        .chain(declared.into_iter().map(|(_, path)| path))
+        .map(|path| Target {
+            kind,
+            src_path: package_dir.join(path),
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -39,11 +289,12 @@ mod test {
                 .iter()
                 .map(|t| case.root_directory.join(t.entrypoint))
                 .collect();
-            let mut targets = get_targets(
-                &case.root_directory.join(PathBuf::from("Cargo.toml")),
-                case.package_name,
-            )
-            .unwrap();
+            let mut targets: Vec<_> =
+                get_targets(&case.root_directory.join(PathBuf::from("Cargo.toml")))
+                    .unwrap()
+                    .into_iter()
+                    .map(|t| t.src_path)
+                    .collect();
 
             expected_targets.sort();
             targets.sort();
@@ -51,4 +302,104 @@ mod test {
             assert_eq!(expected_targets, targets);
         }
     }
+
+    #[test]
+    fn gets_target_kinds() {
+        let case = TEST_CRATES
+            .iter()
+            .find(|case| case.package_name == "complexlib")
+            .unwrap();
+        let targets = get_targets(&case.root_directory.join(PathBuf::from("Cargo.toml"))).unwrap();
+        let kind_of = |entrypoint: &str| {
+            targets
+                .iter()
+                .find(|t| t.src_path == case.root_directory.join(entrypoint))
+                .map(|t| t.kind)
+        };
+
+        assert_eq!(Some(TargetKind::Lib), kind_of("src/lib.rs"));
+        assert_eq!(Some(TargetKind::Bin), kind_of("src/bin/bin1.rs"));
+        assert_eq!(Some(TargetKind::Example), kind_of("examples/ex1.rs"));
+        assert_eq!(Some(TargetKind::Test), kind_of("tests/test1.rs"));
+        assert_eq!(Some(TargetKind::BuildScript), kind_of("build.rs"));
+    }
+
+    #[test]
+    fn applies_manifest_settings() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_dir = temp_dir.path();
+        for file in [
+            "lib/mylib.rs",
+            "src/main.rs",
+            "src/bin/auto.rs",
+            "tools/tool.rs",
+            "examples/ex1.rs",
+            "examples/ex2/main.rs",
+            "tests/auto.rs",
+            "tests/declared.rs",
+            "tests/helpers/mod.rs",
+            "benches/bench1.rs",
+            "build.rs",
+        ]
+        .iter()
+        {
+            let path = package_dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "").unwrap();
+        }
+        fs::write(
+            package_dir.join("Cargo.toml"),
+            r#"
+[package]
+name = "settings"
+version = "0.1.0"
+build = false
+autobins = false
+autoexamples = true
+
+[lib]
+path = "lib/mylib.rs"
+
+[[bin]]
+name = "tool"
+path = "tools/tool.rs"
+
+[[example]]
+name = "ex2"
+crate-type = ["staticlib"]
+
+[[test]]
+name = "declared"
+"#,
+        )
+        .unwrap();
+
+        let mut targets: Vec<_> = get_targets(&package_dir.join("Cargo.toml"))
+            .unwrap()
+            .into_iter()
+            .map(|t| {
+                (
+                    t.kind,
+                    t.src_path.strip_prefix(package_dir).unwrap().to_owned(),
+                )
+            })
+            .collect();
+        targets.sort_by(|a, b| a.1.cmp(&b.1));
+
+        //No `src/main.rs` or `src/bin` because of `autobins`, no `tests/auto.rs` because 2015
+        //edition packages which declare tests don't discover any unless told to, as with
+        //`autoexamples`, and no `build.rs` because of `build`.  The declared `ex2` is the same
+        //target as the discovered one.
+        assert_eq!(
+            vec![
+                (TargetKind::Bench, PathBuf::from("benches/bench1.rs")),
+                (TargetKind::Example, PathBuf::from("examples/ex1.rs")),
+                (TargetKind::Example, PathBuf::from("examples/ex2/main.rs")),
+                (TargetKind::Lib, PathBuf::from("lib/mylib.rs")),
+                (TargetKind::Test, PathBuf::from("tests/declared.rs")),
+                (TargetKind::Bin, PathBuf::from("tools/tool.rs")),
+            ],
+            targets
+        );
+    }
 }
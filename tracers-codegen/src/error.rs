@@ -54,6 +54,11 @@ pub enum TracersError {
         error: Error,
     },
 
+    BuildInfoMismatchError {
+        message: String,
+        build_info_path: String,
+    },
+
     BuildInfoWriteError {
         message: String,
         build_info_path: String,
@@ -89,6 +94,7 @@ impl Display for TracersError {
             TracersError::OtherError { message, .. } => write!(f, "{}", message),
             TracersError::MissingCallInBuildRs => write!(f, "Build environment is incomplete; make sure you are calling `tracers_build::build()` in your `build.rs` build script"),
             TracersError::BuildInfoReadError { message, .. } => write!(f, "{}", message),
+            TracersError::BuildInfoMismatchError { message, .. } => write!(f, "{}", message),
             TracersError::BuildInfoWriteError { message, .. } => write!(f, "{}", message),
             TracersError::ProviderTraitNotProcessedError { message,.. } => write!(f, "{}", message),
             TracersError::CodeGenerationError { message } => write!(f, "Error generating probing code: {}", message),
@@ -192,6 +198,19 @@ impl TracersError {
         }
     }
 
+    pub fn build_info_mismatch_error<S: AsRef<str>>(
+        build_info_path: PathBuf,
+        details: S,
+    ) -> TracersError {
+        let message = format!("The build info in '{}' doesn't match this build: {}\nAll of the `tracers` crates in a build must be the same version; try `cargo update -p tracers`, or `cargo clean -p tracers` if the build info is stale",
+            build_info_path.display(),
+            details.as_ref());
+        TracersError::BuildInfoMismatchError {
+            message,
+            build_info_path: build_info_path.display().to_string(),
+        }
+    }
+
     pub fn build_info_write_error(build_info_path: PathBuf, e: Error) -> TracersError {
         let message = format!("Unable to write build info from '{}'.\nAre you sure you're calling `tracers_build::build()` in your `build.rs`?\nError cause: {}",
             build_info_path.display(),
@@ -1,13 +1,16 @@
 //! This module contains some code which is shared between the different code generation
 //! implementations.  It does not contain a working code generator implementation itself.
 use crate::build_rs::BuildInfo;
+use crate::spec::NativeArgSpecification;
 use crate::spec::ProbeArgSpecification;
 use crate::spec::ProbeSpecification;
+use crate::spec::ProbeThrottle;
 use crate::spec::ProviderInitSpecification;
 use crate::spec::ProviderSpecification;
+use crate::syn_helpers;
 use crate::TracersResult;
 use heck::SnakeCase;
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::ToTokens;
 use quote::{quote, quote_spanned};
 use syn::spanned::Spanned;
@@ -55,7 +58,7 @@ TODO: No other platforms supported yet
     ///
     /// ```no_execute
     /// #[allow(dead_code)]
-    /// #vis fn __try_init_provider() -> Result<&'static str, &'static str>
+    /// #vis fn __try_init_provider() -> ::tracers::ProviderInitResult
     /// ```
     ///
     /// The actual generator should insert this token stream in the generated trait/struct, followed by
@@ -81,9 +84,11 @@ TODO: No other platforms supported yet
             ///
             /// # Returns
             ///
-            /// The return value is a `Result<&'static str, &'static str>`.  The `Ok` value is a string
-            /// with some information about the provider.  The `Err` value is an error message
-            /// indicating the provider failed to initialize, and why.
+            /// The return value is a `tracers::ProviderInitResult`.  The `Ok` value is a
+            /// `ProviderInfo` describing the provider, its probes, and the tracing implementation
+            /// it was built with.  The `Err` value is a `ProviderInitError` indicating the provider
+            /// failed to initialize, and why.  Both implement `Display`, producing the string form
+            /// of the result which earlier versions returned.
             ///
             /// Note that whether or not the provider initialization failed, the `probe!` macros never
             /// return an error or panic.  They will detect the initialization failed and do nothing,
@@ -117,7 +122,29 @@ TODO: No other platforms supported yet
             /// probe!(MyProbes::probe0());
             /// ```
             #[allow(dead_code)]
-            #vis fn __try_init_provider() -> ::core::result::Result<&'static str, &'static str>
+            #vis fn __try_init_provider() -> ::tracers::ProviderInitResult
+        }
+    }
+
+    /// Generates an expression constructing the `tracers::ProviderInfo` which describes this
+    /// provider, for `__try_init_provider` to return when the provider initializes successfully.
+    /// `implementation` is the name of the tracing implementation the provider is built with.
+    fn generate_provider_info<P: ProbeGeneratorBase>(
+        &self,
+        probes: &[P],
+        implementation: &str,
+    ) -> TokenStream {
+        let provider_name = self.spec().name();
+        let version = env!("CARGO_PKG_VERSION");
+        let probe_names = probes.iter().map(|probe| &probe.spec().name);
+
+        quote! {
+            ::tracers::ProviderInfo {
+                name: #provider_name,
+                implementation: #implementation,
+                version: #version,
+                probes: &[#(#probe_names),*],
+            }
         }
     }
 
@@ -129,6 +156,88 @@ TODO: No other platforms supported yet
         syn::Ident::new(&snake_case_name, self.spec().item_trait().ident.span())
     }
 
+    /// Generates the static description of this provider and its probes, and the static
+    /// constructor which adds that description to the runtime registry (see
+    /// `tracers::runtime::registry`).  Each probe generator must also generate the function named
+    /// by `ProbeGeneratorBase::registered_enabled_func_ident`.  Must be placed in the impl mod.
+    fn generate_registration<P: ProbeGeneratorBase>(&self, probes: &[P]) -> TokenStream {
+        let provider_name = self.spec().name();
+        let trait_ident = &self.spec().item_trait().ident;
+        let probe_count = probes.len();
+        let registered_probes = probes.iter().map(|probe| {
+            let probe_name = &probe.spec().name;
+            let enabled_func = probe.registered_enabled_func_ident();
+            let args = probe.spec().args.iter().map(|arg| {
+                let arg_name = arg.ident().ident.to_string();
+                let rust_typ = syn_helpers::convert_to_compact_string(arg.syn_typ());
+                quote! {
+                    ::tracers::runtime::RegisteredProbeArg::new(#arg_name, #rust_typ)
+                }
+            });
+
+            let with_stats = if self.build_info().probe_stats_enabled() {
+                let stats_ident = get_probe_stats_ident(&probe.spec().method_name);
+                quote! { .with_stats(&#stats_ident) }
+            } else {
+                quote! {}
+            };
+
+            let with_level = match probe.spec().level {
+                Some(level) => {
+                    let level_ident = syn::Ident::new(&format!("{:?}", level), Span::call_site());
+                    quote! { .with_level(::tracers::runtime::ProbeLevel::#level_ident) }
+                }
+                None => quote! {},
+            };
+
+            quote! {
+                ::tracers::runtime::RegisteredProbe::new(#probe_name, &[#(#args),*], #enabled_func)
+                    #with_level
+                    #with_stats
+            }
+        });
+
+        let span = self.spec().item_trait().span();
+        quote_spanned! {span=>
+            static __TRACERS_REGISTERED_PROBES: [::tracers::runtime::RegisteredProbe; #probe_count] = [
+                #(#registered_probes),*
+            ];
+
+            static __TRACERS_REGISTERED_PROVIDER: ::tracers::runtime::RegisteredProvider =
+                ::tracers::runtime::RegisteredProvider::new(
+                    #provider_name,
+                    &__TRACERS_REGISTERED_PROBES,
+                    super::#trait_ident::__try_init_provider,
+                );
+
+            // A static constructor which the loader runs before `main`.  On platforms with no
+            // initializer section the provider simply isn't registered.
+            #[used]
+            #[cfg_attr(
+                any(
+                    target_os = "linux",
+                    target_os = "android",
+                    target_os = "freebsd",
+                    target_os = "netbsd",
+                    target_os = "openbsd",
+                    target_os = "dragonfly",
+                    target_os = "illumos",
+                    target_os = "solaris"
+                ),
+                link_section = ".init_array"
+            )]
+            #[cfg_attr(any(target_os = "macos", target_os = "ios"), link_section = "__DATA,__mod_init_func")]
+            #[cfg_attr(windows, link_section = ".CRT$XCU")]
+            static __TRACERS_REGISTER_PROVIDER: extern "C" fn() = {
+                extern "C" fn __tracers_register_provider() {
+                    ::tracers::runtime::register_provider(&__TRACERS_REGISTERED_PROVIDER);
+                }
+
+                __tracers_register_provider
+            };
+        }
+    }
+
     /// The name of the struct type within the impl module which represents the provider, eg `MyProbesProviderImpl`.
     /// Note that this is not the same as the struct which we generate which has the same name as the
     /// trait and implements its methods.
@@ -161,7 +270,29 @@ pub(super) trait ProbeGeneratorBase {
     /// Generates a doc comment to attach to the probe's method.  This includes additional information
     /// about how to work with this probe on various platforms.
     fn generate_probe_doc_comment(&self, provider: &ProviderSpecification) -> TokenStream {
-        let probe_comment = format!(r###"
+        let mut level_comment = String::new();
+        if let Some(level) = self.spec().level {
+            level_comment += &format!(
+                r###"
+## Level
+
+This probe's level is `{level}`.  In LTTng it's at the log level `{lttng_loglevel}`, so a session can
+enable it along with every other probe at that level or above, e.g.:
+```text
+lttng enable-event --userspace --loglevel={lttng_loglevel} '{provider}:*'
+```
+"###,
+                level = level,
+                lttng_loglevel = level.lttng_loglevel(),
+                provider = provider.name(),
+            );
+        }
+        if let Some(ref emf_uri) = self.spec().emf_uri {
+            level_comment += &format!("\nThe EMF model of this probe is `{}`.\n", emf_uri);
+        }
+
+        let probe_comment = format!(
+            r###"
 # Probing
 
 This method is translated at compile-time by `tracers` into a platform-specific tracing
@@ -180,7 +311,7 @@ probe!({trait_name}::{probe_name}(...));
 
 The exact details of how to interact with the probes depends on the underlying
 probing implementation.
-
+{level_comment}
 ## SystemTap/USDT (Linux x64)
 
 To trace the firing of this probe, use `bpftrace`, e.g.:
@@ -195,10 +326,11 @@ where `${{PID}}` should be the actual process ID of the process you are tracing.
 TODO: No other platforms supported yet
 
 "###,
-        trait_name = &provider.item_trait().ident,
-        probe_name = &self.spec().name,
-        provider = provider.name(),
-);
+            trait_name = &provider.item_trait().ident,
+            probe_name = &self.spec().name,
+            provider = provider.name(),
+            level_comment = level_comment,
+        );
 
         generate_multiline_comments(&probe_comment)
     }
@@ -222,40 +354,239 @@ TODO: No other platforms supported yet
             .collect::<Vec<syn::Lifetime>>()
     }
 
-    /// Build a tuple value expression, consisting of the names of the probe arguments in a tuple.
-    /// For example:
+    /// The name of the function generated in the impl mod which lowers and wraps this probe's
+    /// arguments; see `generate_wrapper_func`
+    fn wrapper_func_ident(&self) -> syn::Ident {
+        syn::Ident::new(
+            &format!("__{}_wrap", self.spec().name),
+            self.spec().original_method.span(),
+        )
+    }
+
+    /// The name of the function generated in the impl mod which fires this probe, given the
+    /// probe's arguments in their Rust form.  The `probe!` macro calls this function once it has
+    /// determined the probe is enabled.
+    fn fire_func_ident(&self) -> syn::Ident {
+        syn::Ident::new(
+            &format!("__{}_fire", self.spec().name),
+            self.spec().original_method.span(),
+        )
+    }
+
+    /// When probe stats are enabled, generates the static in the impl mod which holds this
+    /// probe's counters
+    fn generate_stats_static(&self, build_info: &BuildInfo) -> TokenStream {
+        if !build_info.probe_stats_enabled() {
+            return quote! {};
+        }
+
+        let stats_ident = get_probe_stats_ident(&self.spec().method_name);
+        let span = self.spec().original_method.span();
+        quote_spanned! {span=>
+            pub static #stats_ident: ::tracers::runtime::ProbeStats = ::tracers::runtime::ProbeStats::new();
+        }
+    }
+
+    /// When probe stats are enabled, generates the `(probe_name)_stats` method on the provider
+    /// struct which takes a snapshot of this probe's counters
+    fn generate_stats_method(&self, build_info: &BuildInfo, mod_name: &syn::Ident) -> TokenStream {
+        if !build_info.probe_stats_enabled() {
+            return quote! {};
+        }
+
+        let vis = &self.spec().vis;
+        let stats_ident = get_probe_stats_ident(&self.spec().method_name);
+        let method_ident = syn::Ident::new(
+            &format!("{}_stats", self.spec().name),
+            self.spec().original_method.span(),
+        );
+        let span = self.spec().original_method.span();
+        quote_spanned! {span=>
+            /// **NOTE**: This function was generated by the `tracers` macro because `tracers` was
+            /// built with the `probe-stats` feature
+            ///
+            /// Gets a snapshot of the counters of how many times this probe was hit and fired
+            #[allow(dead_code)]
+            #vis fn #method_ident() -> ::tracers::runtime::ProbeStatsSnapshot {
+                #mod_name::#stats_ident.snapshot()
+            }
+        }
+    }
+
+    /// Generates the `__(probe)_throttle` function in the impl mod, which the `probe!` macro calls
+    /// once it has found the probe is enabled to decide if it should actually fire.  Unthrottled
+    /// probes always fire, so the function is trivially `true` and is optimized away.
     ///
-    /// ```noexecute
-    /// fn probe(arg0: &str, arg1: usize); //results in tuple: (arg0, arg1,)
-    /// ```
-    fn args_as_tuple_value(&self) -> TokenStream {
-        let names = self.spec().args.iter().map(ProbeArgSpecification::ident);
+    /// A throttled probe gets a thread-local throttle, and also the `__(probe)_suppressed`
+    /// function which provides the value of the extra native arg with the number of suppressed
+    /// fires.
+    fn generate_throttle_funcs(&self) -> TokenStream {
+        let probe_ident = &self.spec().method_name;
+        let throttle_func = get_probe_throttle_func_ident(probe_ident);
+        let span = self.spec().original_method.span();
+
+        let (throttle_type, throttle_init) = match self.spec().throttle {
+            None => {
+                return quote_spanned! {span=>
+                    #[inline(always)]
+                    pub fn #throttle_func() -> bool {
+                        true
+                    }
+                };
+            }
+            Some(ProbeThrottle::Sample(every)) => (
+                quote! { ::tracers::runtime::SampleThrottle },
+                quote! { ::tracers::runtime::SampleThrottle::new(#every) },
+            ),
+            Some(ProbeThrottle::RateLimit {
+                count,
+                period_nanos,
+            }) => (
+                quote! { ::tracers::runtime::RateLimitThrottle },
+                quote! { ::tracers::runtime::RateLimitThrottle::new(#count, #period_nanos) },
+            ),
+        };
+
+        let throttle_ident = syn::Ident::new(
+            &format!("__{}_THROTTLE", probe_ident.to_string().to_uppercase()),
+            probe_ident.span(),
+        );
+        let suppressed_func =
+            syn::Ident::new(&format!("__{}_suppressed", probe_ident), probe_ident.span());
 
-        generate_tuple(names)
+        //While the thread is being torn down its throttle may already be gone, in which case the
+        //probe is suppressed
+        quote_spanned! {span=>
+            ::std::thread_local! {
+                static #throttle_ident: #throttle_type = const { #throttle_init };
+            }
+
+            #[inline(always)]
+            pub fn #throttle_func() -> bool {
+                #throttle_ident.try_with(|throttle| throttle.try_fire()).unwrap_or(false)
+            }
+
+            pub fn #suppressed_func() -> u64 {
+                #throttle_ident.try_with(|throttle| throttle.suppressed()).unwrap_or(0)
+            }
+        }
     }
 
-    /// Build a tuple type expression whose elements correspond to the arguments of this probe.
-    /// This includes only the type of each argument, and has no explicit lifetimes specified.  For
-    /// that there is `args_as_tuple_type_with_lifetimes`
-    fn args_as_tuple_type_without_lifetimes(&self) -> TokenStream {
-        //When the probe spec is constructed lifetime parameters are added, so to construct a tuple
-        //type without them they need to be stripped
-        let args = self.spec().args.iter().map(ProbeArgSpecification::syn_typ);
-        generate_tuple(args)
+    /// The name of the function generated in the impl mod which the runtime registry calls to
+    /// test if this probe is enabled.  Unlike the probe's other `_enabled` functions this must
+    /// never initialize the provider.
+    fn registered_enabled_func_ident(&self) -> syn::Ident {
+        syn::Ident::new(
+            &format!("__{}_registered_enabled", self.spec().name),
+            self.spec().original_method.span(),
+        )
     }
 
-    /// Like the method above constructs a tuple type corresponding to the types of the arguments of this probe.
-    ///  Unlike the above method, this tuple type is also annotated with explicit lifetime
-    ///  parameters for all reference types in the tuple.
-    fn args_as_tuple_type_with_lifetimes(&self) -> TokenStream {
-        // same as the above method, but use the version with lifetime annotations
-        let types = self
-            .spec()
+    /// Generates the arguments of a function which takes the same arguments as the probe, with
+    /// all lifetimes explicit.  Such a function must take `args_lifetime_parameters` as its
+    /// lifetime parameters.
+    fn generate_fn_args_with_lifetimes(&self) -> Vec<TokenStream> {
+        self.spec()
             .args
             .iter()
-            .map(ProbeArgSpecification::syn_typ_with_lifetimes);
+            .map(|arg| {
+                let arg_name = arg.ident();
+                let rust_typ = arg.syn_typ_with_lifetimes();
+                let span = arg.ident().span();
+                quote_spanned! {span=>
+                    #arg_name: #rust_typ
+                }
+            })
+            .collect()
+    }
+
+    /// Generates the type parameters, including the angle brackets, for a function which takes
+    /// the arguments generated by `generate_fn_args_with_lifetimes`.
+    fn generate_fn_lifetime_parameters(&self) -> TokenStream {
+        let lifetime_params = self.args_lifetime_parameters();
+        if lifetime_params.is_empty() {
+            quote! {}
+        } else {
+            quote! { <#(#lifetime_params),*> }
+        }
+    }
+
+    /// Each probe has a corresponding wrapper function called `__$PROBENAME_wrap` which takes as
+    /// input all of the probe's Rust arguments, lowers them to native args, and returns a tuple
+    /// containing a `ProbeArgWrapper` for each of the native args.
+    ///
+    /// This is required because at probe firing time we don't have enough information about the
+    /// actual data types of the parameters to avoid ambiguity when invoking the wrapper.
+    fn generate_wrapper_func(&self) -> TokenStream {
+        if self.spec().native_args().is_empty() {
+            //Don't generate a wrapper if there are no args to wrap
+            quote! {}
+        } else {
+            let func_name = self.wrapper_func_ident();
+
+            // If any of the probe arguments are reference types, we need to deal with the mess of
+            // difference reference lifetimes by taking one lifetime parameter for every reference
+            // type used by any of the probe args, and explicitly tie the return wrapper types to
+            // the corresponding lifetimes
+            let function_type_params = self.generate_fn_lifetime_parameters();
+            let args = self.generate_fn_args_with_lifetimes();
+            let native_args = self.spec().native_args();
+
+            let return_type = generate_tuple(
+                native_args
+                    .iter()
+                    .map(|native_arg| native_arg.wrapper_type()),
+            );
+
+            let result_expression = generate_tuple(
+                native_args
+                    .iter()
+                    .map(|native_arg| native_arg.wrap_expression()),
+            );
+
+            let span = self.spec().original_method.span();
+            quote_spanned! {span=>
+                #[allow(clippy::needless_lifetimes)] //if there's only one lifetime clippy triggers this lint
+                pub fn #func_name #function_type_params (#(#args),*) -> #return_type {
+                    #result_expression
+                }
+            }
+        }
+    }
 
-        generate_tuple(types)
+    /// Generates the statement which calls the wrapper function to lower and wrap all of the
+    /// probe's args, binding each native arg's wrapper to a variable with the same name as the
+    /// native arg.  Must be used in the impl mod where the probe args are in scope.
+    fn generate_wrap_statement(&self) -> TokenStream {
+        if self.spec().native_args().is_empty() {
+            quote! {}
+        } else {
+            let func_name = self.wrapper_func_ident();
+            let arg_names = self.spec().args.iter().map(ProbeArgSpecification::ident);
+            let wrapper_names = generate_tuple(
+                self.spec()
+                    .native_args()
+                    .into_iter()
+                    .map(NativeArgSpecification::ident),
+            );
+
+            quote! {
+                let #wrapper_names = #func_name(#(#arg_names),*);
+            }
+        }
+    }
+
+    /// Generates the expressions which convert each of the wrappers bound by
+    /// `generate_wrap_statement` into the C value which is passed to the native probe
+    fn generate_native_arg_values(&self) -> Vec<TokenStream> {
+        self.spec()
+            .native_args()
+            .into_iter()
+            .map(|native_arg| {
+                let name = native_arg.ident();
+                quote! { #name.as_c_type() }
+            })
+            .collect()
     }
 }
 
@@ -265,6 +596,155 @@ pub(super) fn get_provider_impl_mod_name(trait_ident: &syn::Ident) -> String {
     format!("__{}", format!("{}Provider", trait_ident).to_snake_case())
 }
 
+/// Given the path to a provider trait as it appears in a `probe!` call, returns the path to the
+/// provider's impl mod, which is always a sibling of the provider itself.
+pub(super) fn get_provider_impl_mod_path(provider: &syn::Path) -> syn::Path {
+    let mut mod_path = provider.clone();
+    let (provider, _) = mod_path
+        .segments
+        .pop()
+        .expect("provider path can't be empty")
+        .into_tuple();
+    let mod_name = syn::Ident::new(
+        &get_provider_impl_mod_name(&provider.ident),
+        provider.span(),
+    );
+    mod_path.segments.push(mod_name.into());
+
+    mod_path
+}
+
+/// Returns the name of the static in the impl mod which holds a probe's counters when probe stats
+/// are enabled, eg `__PROBE0_STATS`
+pub(super) fn get_probe_stats_ident(probe_ident: &syn::Ident) -> syn::Ident {
+    syn::Ident::new(
+        &format!("__{}_STATS", probe_ident.to_string().to_uppercase()),
+        probe_ident.span(),
+    )
+}
+
+/// Returns the path of the static in the impl mod which holds a probe's counters when probe stats
+/// are enabled, which is where a `probe!` call counts the probe unless it's fired on a provider
+/// instance with counters of its own
+pub(super) fn get_probe_stats_path(mod_path: &syn::Path, probe_ident: &syn::Ident) -> TokenStream {
+    let stats_ident = get_probe_stats_ident(probe_ident);
+    quote! { #mod_path::#stats_ident }
+}
+
+/// Returns the name of the function in the impl mod which decides if an enabled probe should fire,
+/// eg `__probe0_throttle`; see `ProbeGeneratorBase::generate_throttle_funcs`
+pub(super) fn get_probe_throttle_func_ident(probe_ident: &syn::Ident) -> syn::Ident {
+    syn::Ident::new(&format!("__{}_throttle", probe_ident), probe_ident.span())
+}
+
+/// When probe stats are enabled, generates the statement in a probe call which counts that the
+/// `probe!` call was reached, in the `ProbeStats` which `stats` evaluates to
+pub(super) fn generate_probe_stats_hit(build_info: &BuildInfo, stats: &TokenStream) -> TokenStream {
+    if !build_info.probe_stats_enabled() {
+        return quote! {};
+    }
+
+    quote! {
+        #stats.record_hit();
+    }
+}
+
+/// Wraps the statement in a probe call which fires an enabled probe so that, when probe stats are
+/// enabled, the fire is counted along with any args which couldn't be passed faithfully.  Those
+/// are counted on the current thread, so the count belongs to this probe alone.  `stats` evaluates
+/// to the probe's `ProbeStats`.
+pub(super) fn generate_counted_fire(
+    build_info: &BuildInfo,
+    stats: &TokenStream,
+    fire: TokenStream,
+) -> TokenStream {
+    if !build_info.probe_stats_enabled() {
+        return fire;
+    }
+
+    quote! {
+        #stats.record_enabled_fire();
+        let __tracers_failures_before = ::tracers::runtime::thread_lossy_string_conversions();
+        #fire
+        let __tracers_failures =
+            ::tracers::runtime::thread_lossy_string_conversions() - __tracers_failures_before;
+        if __tracers_failures != 0 {
+            #stats.record_arg_failures(__tracers_failures as u64);
+        }
+    }
+}
+
+/// Returns the names of the enter and exit probes of a span probe, eg `probe0_enter` and
+/// `probe0_exit` for `probe0`
+pub(super) fn get_span_probe_idents(probe_ident: &syn::Ident) -> (syn::Ident, syn::Ident) {
+    (
+        syn::Ident::new(&format!("{}_enter", probe_ident), probe_ident.span()),
+        syn::Ident::new(&format!("{}_exit", probe_ident), probe_ident.span()),
+    )
+}
+
+/// Generates the expression a `probe_span!` call evaluates to, once the implementation-specific
+/// code has made sure the span's probes exist.  `enter` and `exit` are each an expression which
+/// tests if the probe is enabled, any leading args its fire function takes before the probe's own
+/// args (the dynamic implementation passes the probe itself), and an expression which evaluates to
+/// its `ProbeStats`.
+///
+/// If either probe is enabled, the args are evaluated exactly once, the enter probe is fired if
+/// it's enabled, and the result is a `SpanGuard` which fires the exit probe with the same args
+/// plus the elapsed nanoseconds when it's dropped, if the exit probe is still enabled then.
+/// Otherwise the args are never evaluated and the guard does nothing.  Because the args are used
+/// for both probes they must be `Copy`, which all of the supported probe arg types are.
+pub(super) fn generate_span_guard(
+    build_info: &BuildInfo,
+    mod_path: &syn::Path,
+    probe_ident: &syn::Ident,
+    args: &[syn::Expr],
+    span: proc_macro2::Span,
+    enter: (TokenStream, TokenStream, TokenStream),
+    exit: (TokenStream, TokenStream, TokenStream),
+) -> TokenStream {
+    let (enter_ident, exit_ident) = get_span_probe_idents(probe_ident);
+    let (enter_enabled, enter_probe, enter_stats) = enter;
+    let (exit_enabled, exit_probe, exit_stats) = exit;
+
+    let arg_idents: Vec<_> = (0..args.len())
+        .map(|idx| syn::Ident::new(&format!("__tracers_arg{}", idx), span))
+        .collect();
+    let elapsed_ident = syn::Ident::new("__tracers_elapsed_ns", span);
+
+    let enter_fire_func = syn::Ident::new(&format!("__{}_fire", enter_ident), span);
+    let exit_fire_func = syn::Ident::new(&format!("__{}_fire", exit_ident), span);
+    let enter_fire = generate_counted_fire(
+        build_info,
+        &enter_stats,
+        quote_spanned! {span=> #mod_path::#enter_fire_func(#enter_probe #(#arg_idents),*); },
+    );
+    let exit_fire = generate_counted_fire(
+        build_info,
+        &exit_stats,
+        quote_spanned! {span=> #mod_path::#exit_fire_func(#exit_probe #(#arg_idents,)* #elapsed_ident); },
+    );
+
+    //The guard is created before the enter probe fires, so the time spent firing it counts
+    //towards the span
+    quote_spanned! {span=>
+        if #enter_enabled || #exit_enabled {
+            #(let #arg_idents = #args;)*
+            let __tracers_span = ::tracers::runtime::SpanGuard::new(move |#elapsed_ident: u64| {
+                if #exit_enabled {
+                    #exit_fire
+                }
+            });
+            if #enter_enabled {
+                #enter_fire
+            }
+            __tracers_span
+        } else {
+            ::tracers::runtime::SpanGuard::disabled()
+        }
+    }
+}
+
 /// Given an iterable sequence of `TokenStream`s, produces a new `TokenStream` which will be a
 /// tuple expression with each element of the tuple corresponding to a stream in the iterator.
 ///
@@ -9,7 +9,7 @@
 //! tracing.  However, this remains in case a use for it emerges, perhaps on another platform with
 //! more intrinsic support for dynamic style tracing.
 use super::NativeLib;
-use crate::build_rs::BuildInfo;
+use crate::build_rs::{BuildInfo, BuildReport};
 use crate::gen::common;
 use crate::spec::ProbeCallSpecification;
 use crate::spec::ProviderInitSpecification;
@@ -40,7 +40,11 @@ impl CodeGenerator for DynamicGenerator {
     }
 
     fn handle_probe_call(&self, call: ProbeCallSpecification) -> TracersResult<TokenStream> {
-        probe_call::generate_probe_call(call)
+        probe_call::generate_probe_call(&self.build_info, call)
+    }
+
+    fn handle_probe_span_call(&self, call: ProbeCallSpecification) -> TracersResult<TokenStream> {
+        probe_call::generate_probe_span_call(&self.build_info, call)
     }
 
     fn handle_init_provider(&self, init: ProviderInitSpecification) -> TracersResult<TokenStream> {
@@ -50,6 +54,7 @@ impl CodeGenerator for DynamicGenerator {
     fn generate_native_code(
         &self,
         stdout: &mut dyn Write,
+        _report: &mut BuildReport,
         _manifest_dir: &Path,
         _out_dir: &Path,
         _package_name: &str,
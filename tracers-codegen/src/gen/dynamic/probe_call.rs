@@ -2,10 +2,12 @@
 //! It's rather simple, because it assumes the Rust bindings on the `libstapsdt` API are already a
 //! dependency and exposed via the `SystemTracer` type alias.
 
-use crate::spec::ProbeCallSpecification;
-use crate::TracersResult;
+use crate::build_rs::BuildInfo;
+use crate::gen::common;
+use crate::spec::{ProbeCallDetails, ProbeCallSpecification};
+use crate::{TracersError, TracersResult};
 use proc_macro2::TokenStream;
-use quote::{quote, quote_spanned};
+use quote::quote_spanned;
 use syn::spanned::Spanned;
 
 /// Translates what looks to be an explicit call to the associated function corresponding to a
@@ -24,16 +26,24 @@ use syn::spanned::Spanned;
 /// ```noexecute
 /// {
 ///     if let Some(probe) = MyProvider::get_myprobe_probe() {
-///         if probe.is_enabled() {
-///             probe.fire((1, 5, "this is a string", compute_something(),)));
+///         if probe.is_enabled() && __my_provider_provider::__myprobe_throttle() {
+///             __my_provider_provider::__myprobe_fire(probe, 1, 5, "this is a string", compute_something());
 ///         }
 ///     }
 /// }
 /// ```
 ///
 /// In particular, note that the probe's parameters are not evaluated unless the provider
-/// initialized successfully and the probe is enabled.
-pub(super) fn generate_probe_call(call: ProbeCallSpecification) -> TracersResult<TokenStream> {
+/// initialized successfully, the probe is enabled, and (if the probe is throttled) the throttle
+/// allows it to fire.
+///
+/// If the call is made on an instance of the provider, as in `probe!(instance =>
+/// MyProvider::myprobe(...))`, the instance is evaluated once, and the probe is instead retrieved
+/// with `instance.get_myprobe_probe()`, and counted in the instance's own stats.
+pub(super) fn generate_probe_call(
+    build_info: &BuildInfo,
+    call: ProbeCallSpecification,
+) -> TracersResult<TokenStream> {
     match call {
         ProbeCallSpecification::FireOnly(details) => {
             //Easy one.  This call is already set up like a Rust method call on the probe method of
@@ -44,22 +54,41 @@ pub(super) fn generate_probe_call(call: ProbeCallSpecification) -> TracersResult
                 details.probe.ident.span(),
             );
             let span = details.call.span();
-            let provider = details.provider;
-
-            //the `fire` method on the probe object takes a single argument, which is a tuple of
-            //all of the probe args.  Build that here, though note there's a different syntax for
-            //an empty tuple
-            let args = if details.args.is_empty() {
-                quote! { () }
-            } else {
-                let args = details.args;
-                quote! { (#(#args),*,) }
-            };
+            let provider = &details.provider;
+
+            //The fire function in the provider's impl mod takes the probe args as-is, and takes
+            //care of lowering and wrapping them before firing the probe
+            let mod_path = common::get_provider_impl_mod_path(provider);
+            let fire_func = syn::Ident::new(
+                &format!("__{}_fire", details.probe.ident),
+                details.probe.ident.span(),
+            );
+            let args = &details.args;
+
+            let instance_binding = generate_instance_binding(&details);
+            let get_probe = generate_get_probe_call(&details, &probe_func_name);
+
+            //With the `probe-stats` feature, the probe is counted as hit even if the provider
+            //failed to initialize
+            let probe_ident = &details.probe.ident;
+            let stats = generate_probe_stats(&details, &mod_path, probe_ident);
+            let stats_hit = common::generate_probe_stats_hit(build_info, &stats);
+            let fire = common::generate_counted_fire(
+                build_info,
+                &stats,
+                quote_spanned! {span=> #mod_path::#fire_func(&__tracers_probe, #(#args),*); },
+            );
+
+            //A throttled probe may still decline to fire once it's found to be enabled
+            let throttle_func = common::get_probe_throttle_func_ident(probe_ident);
+
             Ok(quote_spanned! {span=>
                 {
-                    if let Some(__tracers_probe) = #provider::#probe_func_name() {
-                        if __tracers_probe.is_enabled() {
-                            __tracers_probe.fire(#args);
+                    #instance_binding
+                    #stats_hit
+                    if let Some(__tracers_probe) = #get_probe {
+                        if __tracers_probe.is_enabled() && #mod_path::#throttle_func() {
+                            #fire
                         }
                     }
                 }
@@ -68,3 +97,133 @@ pub(super) fn generate_probe_call(call: ProbeCallSpecification) -> TracersResult
         ProbeCallSpecification::FireWithCode { .. } => unimplemented!(),
     }
 }
+
+/// Generates the expansion of `probe_span!`, which fires the enter probe of a span probe and
+/// evaluates to a guard which fires the exit probe when dropped.  See
+/// `common::generate_span_guard` for the details.
+///
+/// If the provider failed to initialize, neither probe exists and the guard does nothing.
+pub(super) fn generate_probe_span_call(
+    build_info: &BuildInfo,
+    call: ProbeCallSpecification,
+) -> TracersResult<TokenStream> {
+    match call {
+        ProbeCallSpecification::FireOnly(details) => {
+            details.check_span_args()?;
+
+            let (enter_ident, exit_ident) = common::get_span_probe_idents(&details.probe.ident);
+            let get_probe_func = |probe_ident: &syn::Ident| {
+                syn::Ident::new(&format!("get_{}_probe", probe_ident), probe_ident.span())
+            };
+            let get_enter_func = get_probe_func(&enter_ident);
+            let get_exit_func = get_probe_func(&exit_ident);
+            let span = details.call.span();
+            let provider = &details.provider;
+            let mod_path = common::get_provider_impl_mod_path(provider);
+
+            let mut enter_stats = generate_probe_stats(&details, &mod_path, &enter_ident);
+            let mut exit_stats = generate_probe_stats(&details, &mod_path, &exit_ident);
+
+            //The guard outlives the block, so it can't borrow the reference to the instance bound
+            //in it, only what the instance itself lends out
+            let mut stats_bindings = quote_spanned! {span=> };
+            if details.instance.is_some() && build_info.probe_stats_enabled() {
+                stats_bindings = quote_spanned! {span=>
+                    let __tracers_enter_stats = #enter_stats;
+                    let __tracers_exit_stats = #exit_stats;
+                };
+                enter_stats = quote_spanned! {span=> __tracers_enter_stats };
+                exit_stats = quote_spanned! {span=> __tracers_exit_stats };
+            }
+
+            let enter_hit = common::generate_probe_stats_hit(build_info, &enter_stats);
+            let exit_hit = common::generate_probe_stats_hit(build_info, &exit_stats);
+            let guard = common::generate_span_guard(
+                build_info,
+                &mod_path,
+                &details.probe.ident,
+                &details.args,
+                span,
+                (
+                    quote_spanned! {span=> __tracers_enter.is_enabled() },
+                    quote_spanned! {span=> &__tracers_enter, },
+                    enter_stats,
+                ),
+                (
+                    quote_spanned! {span=> __tracers_exit.is_enabled() },
+                    quote_spanned! {span=> &__tracers_exit, },
+                    exit_stats,
+                ),
+            );
+
+            let instance_binding = generate_instance_binding(&details);
+            let get_enter_probe = generate_get_probe_call(&details, &get_enter_func);
+            let get_exit_probe = generate_get_probe_call(&details, &get_exit_func);
+
+            Ok(quote_spanned! {span=>
+                {
+                    #instance_binding
+                    #stats_bindings
+                    #enter_hit
+                    #exit_hit
+                    match (#get_enter_probe, #get_exit_probe) {
+                        (Some(__tracers_enter), Some(__tracers_exit)) => #guard,
+                        _ => ::tracers::runtime::SpanGuard::disabled(),
+                    }
+                }
+            })
+        }
+        ProbeCallSpecification::FireWithCode { statements, .. } => {
+            Err(TracersError::invalid_call_expression(
+                "`probe_span!` takes a single call to a span probe, not a block of code",
+                statements,
+            ))
+        }
+    }
+}
+
+/// If the call was made on an instance of the provider, generates the statement which evaluates the
+/// instance expression once, binding a reference to it which the rest of the generated code uses
+fn generate_instance_binding(details: &ProbeCallDetails) -> TokenStream {
+    let span = details.call.span();
+
+    match &details.instance {
+        None => quote_spanned! {span=> },
+        Some(instance) => quote_spanned! {span=> let __tracers_instance = &(#instance); },
+    }
+}
+
+/// Generates the call to the `get_(probe)_probe` function which retrieves a probe, either from the
+/// provider's default instance or from the instance the call was made on.  The former is a
+/// `&'static` reference to the probe, and the latter a reference which borrows the instance, so
+/// the generated code passes the probe on as `&__tracers_probe`, which works for both.
+fn generate_get_probe_call(
+    details: &ProbeCallDetails,
+    probe_func_name: &syn::Ident,
+) -> TokenStream {
+    let span = details.call.span();
+    let provider = &details.provider;
+
+    match &details.instance {
+        None => quote_spanned! {span=> #provider::#probe_func_name() },
+        Some(_) => quote_spanned! {span=> __tracers_instance.#probe_func_name() },
+    }
+}
+
+/// Generates the expression which evaluates to the `ProbeStats` a probe call is counted in, which
+/// are the instance's own if the call was made on an instance
+fn generate_probe_stats(
+    details: &ProbeCallDetails,
+    mod_path: &syn::Path,
+    probe_ident: &syn::Ident,
+) -> TokenStream {
+    let span = details.call.span();
+
+    match &details.instance {
+        None => common::get_probe_stats_path(mod_path, probe_ident),
+        Some(_) => {
+            let stats_func = syn::Ident::new(&format!("get_{}_stats", probe_ident), span);
+            quote_spanned! {span=> __tracers_instance.#stats_func() }
+        }
+    }
+}
@@ -1,12 +1,13 @@
 //!Code in this module processes the provider trait decorated with the `tracers` attribute, and
 //!replaces it with an implementation using libstapsdt.
 use crate::build_rs::BuildInfo;
-use crate::gen::common::{ProbeGeneratorBase, ProviderTraitGeneratorBase};
-use crate::spec::ProbeSpecification;
+use crate::gen::common::{self, ProbeGeneratorBase, ProviderTraitGeneratorBase};
+use crate::spec::ProbeArgSpecification;
 use crate::spec::ProviderSpecification;
+use crate::spec::{ArgOverflow, ProbeSpecification};
 use crate::syn_helpers;
 use crate::TracersResult;
-use heck::ShoutySnakeCase;
+use heck::{CamelCase, ShoutySnakeCase};
 use proc_macro2::TokenStream;
 use quote::{quote, quote_spanned};
 use syn::parse_quote;
@@ -75,6 +76,7 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         let struct_type_path: syn::Path = parse_quote! { #mod_name::#struct_type_name };
         for probe in self.probes.iter() {
             probe_methods.push(probe.generate_trait_methods(self, &struct_type_path)?);
+            probe_methods.push(probe.generate_stats_method(self.build_info, &mod_name));
         }
 
         // Re-generate the trait method that we took as input, with the modifications to support
@@ -95,16 +97,25 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         let trait_doc_comment = self.generate_trait_comment();
         let try_init_decl = self.generate_try_init_decl();
 
-        //the __try_init_provider returns a Result.  In this no-op implementation, we'll hard-code
-        //a successful result, with a string containing some metadata about the generated provider
-        let provider_name = self.spec.name();
+        //the __try_init_provider returns a Result.  On success, it contains some metadata about the
+        //generated provider
         let implementation = format!(
             "{}/{}",
             self.build_info.implementation.tracing_type().as_ref(),
             self.build_info.implementation.as_ref()
         );
+        let provider_info = self.generate_provider_info(&self.probes, &implementation);
 
-        let version = env!("CARGO_PKG_VERSION");
+        let instance_ident = self.get_provider_instance_type_name();
+        let instance_impl_type_name = self.get_instance_impl_type_name();
+        let instance_doc = format!(
+            " An instance of the `{0}` provider, defined with `{0}::define_instance`",
+            ident
+        );
+        let instance_probe_methods = self
+            .probes
+            .iter()
+            .map(|probe| probe.generate_instance_probe_method(self.build_info, &mod_name));
 
         let result = quote_spanned! {span=>
             #(#attrs)*
@@ -117,12 +128,53 @@ impl<'bi> ProviderTraitGenerator<'bi> {
                 #try_init_decl {
                     let result = #mod_name::#struct_type_name::get();
 
-                    // On success, translate from the probe struct to the informational message
-                    result.map(|_| {
-                        concat!(#provider_name, "::", #implementation, "::", #version)
+                    // On success, translate from the probe struct to the information about the
+                    // provider
+                    result.map(|_| #provider_info)
+                }
+
+                /// **NOTE**: This function was generated by the `tracers` macro
+                ///
+                /// Defines a new instance of this provider, separate from the one `probe!` fires
+                /// by default, which the platform's tracing tools will see as a provider named
+                /// `name`.  Fire its probes by passing the instance to `probe!`, for example
+                /// `probe!(instance => Provider::probe(...))`.  Clones of the instance share its
+                /// provider, and dropping the last of them removes its probes.
+                ///
+                /// Instances are only supported with dynamic tracing, and they aren't added to the
+                /// runtime registry.  Each instance counts its own probe stats.  Probes throttled
+                /// with `sample` or `rate_limit` can't be fired on an instance, since their
+                /// throttles belong to the default instance.
+                #[allow(dead_code)]
+                #vis fn define_instance(name: &str) -> ::tracers::runtime::failure::Fallible<#instance_ident> {
+                    let instance = #mod_name::#struct_type_name::define_instance(name)?;
+
+                    Ok(#instance_ident {
+                        instance: ::std::sync::Arc::new(instance),
+                        name: name.to_string(),
                     })
                 }
             }
+
+            /// **NOTE**: This struct was generated by the `tracers` macro
+            ///
+            #[doc = #instance_doc]
+            #[derive(Clone)]
+            #vis struct #instance_ident {
+                instance: ::std::sync::Arc<#mod_name::#instance_impl_type_name>,
+                name: String,
+            }
+
+            impl #instance_ident {
+                /// The name of this instance of the provider, as the platform's tracing tools will
+                /// see it
+                #[allow(dead_code)]
+                #vis fn name(&self) -> &str {
+                    &self.name
+                }
+
+                #(#instance_probe_methods)*
+            }
         };
 
         Ok(result)
@@ -144,8 +196,19 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         let struct_var_name = self.get_provider_impl_struct_var_name();
         let struct_type_params = self.generate_provider_struct_type_params();
         let instance_var_name = self.get_provider_instance_var_name();
-        let define_provider_call = self.generate_define_provider_call();
+        let provider_name = self.spec.name();
+        let define_provider_call = self.generate_define_provider_call(&quote! { #provider_name });
+        let define_instance_provider_call = self.generate_define_provider_call(&quote! { name });
         let provider_var_name = syn::Ident::new("p", self.spec.item_trait().span());
+        let instance_impl_type_name = self.get_instance_impl_type_name();
+        let instance_stats_fields: Vec<_> = if self.build_info.probe_stats_enabled() {
+            self.probes
+                .iter()
+                .map(ProbeGenerator::instance_stats_field_ident)
+                .collect()
+        } else {
+            Vec::new()
+        };
         let struct_members: Vec<_> = self
             .probes
             .iter()
@@ -155,13 +218,46 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         let struct_initializers: Vec<_> = self
             .probes
             .iter()
-            .map(|probe| probe.generate_struct_member_initialization(&provider_var_name))
+            .map(|probe| {
+                probe.generate_struct_member_initialization(self.spec.name(), &provider_var_name)
+            })
             .collect();
+        let native_args_types = self
+            .probes
+            .iter()
+            .map(ProbeGenerator::generate_native_args_type_alias);
+        let wrapper_funcs = self
+            .probes
+            .iter()
+            .map(ProbeGenerator::generate_wrapper_func);
+        let fire_funcs = self.probes.iter().map(ProbeGenerator::generate_fire_func);
+        let registered_enabled_funcs = self
+            .probes
+            .iter()
+            .map(ProbeGenerator::generate_registered_enabled_func);
+        let stats_statics = self
+            .probes
+            .iter()
+            .map(|probe| probe.generate_stats_static(self.build_info));
+        let throttle_funcs = self
+            .probes
+            .iter()
+            .map(ProbeGenerator::generate_throttle_funcs);
+        let registration = self.generate_registration(&self.probes);
 
+        let vis = &self.spec.item_trait().vis;
         let span = self.spec.item_trait().span();
         quote_spanned! {span=>
-            mod #mod_name {
+            #vis mod #mod_name {
+                // These imports aren't always used but it's easier to always import than to
+                // detect when probe arg types need `libc`, or types which the provider trait's
+                // module imported
+                #[allow(unused_imports)]
+                use super::*;
+                #[allow(unused_imports)]
+                use ::tracers::runtime::libc;
                 use ::tracers::runtime::failure::{format_err, Fallible};
+                use ::tracers::ProviderInitError;
                 use ::tracers::runtime::dynamic::once_cell::sync::OnceCell;
                 use ::tracers::runtime::dynamic::{SystemTracer,SystemProvider,ProviderBuilder,Tracer};
 
@@ -170,6 +266,20 @@ impl<'bi> ProviderTraitGenerator<'bi> {
                 use ::tracers::runtime::dynamic::Provider as _;
                 use ::core::result::Result;
 
+                #(#native_args_types)*
+
+                #(#wrapper_funcs)*
+
+                #(#throttle_funcs)*
+
+                #(#fire_funcs)*
+
+                #(#registered_enabled_funcs)*
+
+                #(#stats_statics)*
+
+                #registration
+
                 #[allow(dead_code)]
                 pub(super) struct #struct_type_name<#struct_type_params> {
                     #(pub #struct_members),*
@@ -178,54 +288,72 @@ impl<'bi> ProviderTraitGenerator<'bi> {
                 unsafe impl<#struct_type_params> Send for #struct_type_name<#struct_type_params> {}
                 unsafe impl<#struct_type_params> Sync for #struct_type_name <#struct_type_params>{}
 
-                static #instance_var_name: OnceCell<Fallible<SystemProvider>> = OnceCell::new();
-                static #struct_var_name: OnceCell<Result<#struct_type_name, String>> = OnceCell::new();
-                static IMPL_OPT: OnceCell<Result<&'static #struct_type_name, &'static str>> = OnceCell::new();
+                /// An instance of the provider defined by `define_instance`, with its probes and
+                /// their stats.  The probes borrow the provider, which is boxed to keep it at the
+                /// same address for as long as they do.
+                pub(super) struct #instance_impl_type_name {
+                    //NB: `probes` must be declared first so it's dropped before the provider
+                    pub probes: #struct_type_name<'static>,
+                    #(pub #instance_stats_fields: ::tracers::runtime::ProbeStats,)*
+                    _provider: Box<SystemProvider>,
+                }
+
+                static #instance_var_name: OnceCell<Result<SystemProvider, ProviderInitError>> = OnceCell::new();
+                static #struct_var_name: OnceCell<Result<#struct_type_name, ProviderInitError>> = OnceCell::new();
+                static IMPL_OPT: OnceCell<Result<&'static #struct_type_name, &'static ProviderInitError>> = OnceCell::new();
 
                 impl<#struct_type_params> #struct_type_name<#struct_type_params> {
                    #[allow(dead_code)]
-                   pub(super) fn get() -> Result<&'static #struct_type_name<#struct_type_params>, &'static str> {
-                       //let imp: &'static Result<&'static #struct_type_name, &'static str> = IMPL_OPT.get_or_init(|| {
-                       let imp: &'static Result<_,_> = IMPL_OPT.get_or_init(|| {
+                   pub(super) fn get() -> Result<&'static #struct_type_name<#struct_type_params>, &'static ProviderInitError> {
+                       //Copy this `&Result<&T, &E>` to a new `Result<&T, &E>`.  Since that should be
+                       //implemented as just a pointer, this should be effectively free
+                       *IMPL_OPT.get_or_init(|| {
                            // The reason for this seemingly-excessive nesting is that it's possible for
                            // both the creation of `SystemProvider` or the subsequent initialization of
                            // #struct_type_name to fail with different and also relevant errors.  By
                            // separting them this way we're able to preserve the details about any init
-                           // failures that happen, while at runtime when firing probes it's a simple
-                           // call of a method on an `Option<T>`.  I don't have any data to back this
-                           // up but I suspect that allows for better optimizations, since we know an
-                           // `Option<&T>` is implemented as a simple pointer where `None` is `NULL`.
-                           let imp: &Result<#struct_type_name<#struct_type_params>, String> = #struct_var_name.get_or_init(|| {
-                               // Initialzie the `SystemProvider`, capturing any initialization errors
-                               let #provider_var_name: &Fallible<SystemProvider> = #instance_var_name.get_or_init(|| {
-                                    #define_provider_call
-                               });
-
-                               // Transform this #provider_var_name into an owned `Fallible` containing
-                               // references to `T` or `E`, since there's not much useful you can do
-                               // with just a `&Result`.
-                               match #provider_var_name.as_ref() {
-                                   Err(e) => Err(format!("Provider initialization failed: {}", e)),
-                                   Ok(#provider_var_name) => {
-                                       // Proceed to create the struct containing each of the probes'
-                                       // `ProviderProbe` instances
-                                       Ok(
-                                           #struct_type_name{
-                                               #(#struct_initializers,)*
-                                           }
-                                       )
+                           // failures that happen, in a static so the caller can borrow them, while at
+                           // runtime when firing probes it's a simple call of a method on a `Result`
+                           // of two references.
+                           //
+                           // Initialzie the `SystemProvider`, capturing any initialization errors
+                           let #provider_var_name: &'static SystemProvider = #instance_var_name.get_or_init(|| {
+                                let provider: Fallible<SystemProvider> = { #define_provider_call };
+
+                                provider.map_err(|error| ProviderInitError::ProviderFailed { provider: #provider_name, error })
+                           }).as_ref()?;
+
+                           // Proceed to create the struct containing each of the probes'
+                           // `ProviderProbe` instances
+                           #struct_var_name.get_or_init(|| {
+                               Ok(
+                                   #struct_type_name{
+                                       #(#struct_initializers,)*
                                    }
-                               }
-                           });
-
-                           //Convert this &Fallible<..> into an Result<&T, &'static str>
-                           imp.as_ref().map_err(|e| e.as_ref())
-                       });
+                               )
+                           }).as_ref()
+                       })
+                   }
 
-                       //Copy this `&Result<&T, &String>` to a new `Result<&T, &str>`.  Since that should be
-                       //implemented as just a pointer, this should be effectively free
-                       //*imp
-                       imp.map_err(|e| e.as_ref())
+                   /// Defines a new instance of the provider named `name`, and gets each of its
+                   /// probes once, so that firing them needn't look them up, and a probe which is
+                   /// missing is reported now rather than silently never firing.
+                   #[allow(dead_code)]
+                   pub(super) fn define_instance(name: &str) -> Fallible<#instance_impl_type_name> {
+                       let provider: Box<SystemProvider> = Box::new({ #define_instance_provider_call }?);
+
+                       //The instance owns the provider, and drops it after the probes
+                       let #provider_var_name: &'static SystemProvider =
+                           unsafe { &*(&*provider as *const SystemProvider) };
+                       let probes = #struct_type_name{
+                           #(#struct_initializers,)*
+                       };
+
+                       Ok(#instance_impl_type_name {
+                           probes,
+                           #(#instance_stats_fields: ::tracers::runtime::ProbeStats::new(),)*
+                           _provider: provider,
+                       })
                    }
                 }
             }
@@ -235,15 +363,15 @@ impl<'bi> ProviderTraitGenerator<'bi> {
     /// A `Provider` is built by calling `define_provider` on a `Tracer` implementation.
     /// `define_provider` takes a closure and passes a `ProviderBuilder` parameter to that closure.
     /// This method generates the call to `SystemTracer::define_provider`, and includes code to add
-    /// each of the probes to the provider
-    fn generate_define_provider_call(&self) -> TokenStream {
+    /// each of the probes to the provider.  `provider_name` is an expression which evaluates to the
+    /// name of the provider, which is only known at runtime for provider instances.
+    fn generate_define_provider_call(&self, provider_name: &TokenStream) -> TokenStream {
         let builder = syn::Ident::new("builder", self.spec.item_trait().ident.span());
         let add_probe_calls: Vec<TokenStream> = self
             .probes
             .iter()
             .map(|probe| probe.generate_add_probe_call(&builder))
             .collect();
-        let provider_name = self.spec.name();
 
         let span = self.spec.item_trait().span();
         quote_spanned! {span=>
@@ -251,9 +379,9 @@ impl<'bi> ProviderTraitGenerator<'bi> {
             // and `bcc` tools have, shall we say, "evolving" support for USDT.  As of now, with the
             // latest git version of `bpftrace`, the provider name can't have dots or colons.  For now,
             // then, the provider name is just the name of the provider trait, converted into
-            // snake_case for consistency with USDT naming conventions.  If two modules in the same
-            // process have the same provider name, they will conflict and some unspecified `bad
-            // things` will happen.
+            // snake_case for consistency with USDT naming conventions, unless this is an instance
+            // of the provider, named by the caller.  If two modules in the same process have the
+            // same provider name, they will conflict and some unspecified `bad things` will happen.
             let provider_name = #provider_name;
 
             SystemTracer::define_provider(&provider_name, |mut #builder| {
@@ -264,25 +392,16 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         }
     }
 
-    /// The provider struct we declare to hold the probe objects needs to take a lot of type
-    /// parameters.  One type, 'a, which corresponds to the lifetime parameter of the underling
-    /// `ProviderProbe`s, and also one lifetime parameter for every reference argument of every probe
-    /// method.
+    /// The provider struct we declare to hold the probe objects needs to take a type parameter
+    /// 'a, which corresponds to the lifetime parameter of the underling `ProviderProbe`s.  The
+    /// probes themselves take only native args, none of which have lifetimes, so no other type
+    /// parameters are needed.
     ///
     /// The return value of this is a token stream consisting of all of the types, but not including
     /// the angle brackets.
     fn generate_provider_struct_type_params(&self) -> TokenStream {
-        // Make a list of all of the reference param lifetimes of all the probes
-        let probe_lifetimes: Vec<syn::Lifetime> = self
-            .probes
-            .iter()
-            .map(ProbeGenerator::args_lifetime_parameters)
-            .flatten()
-            .collect();
-
-        //The struct simply takes all of these lifetimes plus 'a
         quote! {
-            'a, #(#probe_lifetimes),*
+            'a
         }
     }
 
@@ -295,6 +414,18 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         )
     }
 
+    /// The name of the type of the instances of this provider, returned by `define_instance`, eg
+    /// `MyProbesInstance`
+    fn get_provider_instance_type_name(&self) -> syn::Ident {
+        syn_helpers::add_suffix_to_ident(&self.spec.item_trait().ident, "Instance")
+    }
+
+    /// The name of the type in the impl mod which holds an instance's provider, probes and stats,
+    /// eg `MyProbesInstanceImpl`
+    fn get_instance_impl_type_name(&self) -> syn::Ident {
+        syn_helpers::add_suffix_to_ident(&self.spec.item_trait().ident, "InstanceImpl")
+    }
+
     /// The name of the static variable which contains the singleton instance of the underlying tracing
     /// system's `Provider` instance, eg MYPROBESPROVIDER
     fn get_provider_instance_var_name(&self) -> syn::Ident {
@@ -371,30 +502,21 @@ impl ProbeGenerator {
         );
         probe_method.inputs = syn::punctuated::Punctuated::new();
         probe_method.output = syn::ReturnType::Default;
-        let probe_method_ret_type = self.generate_provider_probe_type();
+        let mod_name = provider.get_provider_impl_mod_name();
+        let probe_method_ret_type = self.generate_provider_probe_type(Some(&mod_name));
         let a_lifetime = syn::Lifetime::new("'a", self.spec.span);
+        probe_method.generics = syn::Generics::default();
         probe_method
             .generics
             .params
             .push(syn::GenericParam::Lifetime(syn::LifetimeDef::new(
                 a_lifetime,
             )));
-        for param in self.args_lifetime_parameters().iter() {
-            probe_method
-                .generics
-                .params
-                .push(syn::GenericParam::Lifetime(syn::LifetimeDef::new(
-                    param.clone(),
-                )))
-        }
-
-        //Generate an _impl method that actually fires the probe when called
-        let mut impl_method = original_method.clone();
-        impl_method.ident = syn_helpers::add_suffix_to_ident(&impl_method.ident, "_impl");
 
         //Generate the body of the original method, simply passing its arguments directly to the
-        //impl method
-        let probe_args_tuple = self.args_as_tuple_value();
+        //fire function in the impl mod
+        let fire_func = self.fire_func_ident();
+        let arg_names = self.spec.args.iter().map(ProbeArgSpecification::ident);
 
         //Keep the original probe method, but mark it deprecated with a helpful message so that if the
         //user calls the probe method directly they will at least be reminded that they should use the
@@ -422,7 +544,7 @@ impl ProbeGenerator {
             #vis #original_method {
                 if let Ok(probes) = #struct_type_path::get() {
                     if probes.#probe_ident.is_enabled() {
-                        probes.#probe_ident.fire(#probe_args_tuple)
+                        #mod_name::#fire_func(&probes.#probe_ident, #(#arg_names),*)
                     }
                 };
             }
@@ -444,32 +566,202 @@ impl ProbeGenerator {
         })
     }
 
+    /// Generates the `get_(probe)_probe` method of the provider's instance type, which the `probe!`
+    /// macro uses in place of the method of the same name on the provider struct when it's passed
+    /// an instance.  `define_instance` already got the probe, so this always returns it, but as an
+    /// `Option` so that the code `probe!` generates is the same either way.
+    ///
+    /// With probe stats, this also generates `get_(probe)_stats`, which `probe!` counts the probe
+    /// in, and `(probe)_stats`, which takes a snapshot of those counters.
+    ///
+    /// A throttled probe gets neither, so firing it on an instance fails to compile; its throttle
+    /// is thread-local state shared with the default instance.
+    pub(crate) fn generate_instance_probe_method(
+        &self,
+        build_info: &BuildInfo,
+        mod_name: &syn::Ident,
+    ) -> TokenStream {
+        if self.spec.throttle.is_some() {
+            return quote! {};
+        }
+
+        let vis = &self.spec.vis;
+        let probe_ident = &self.spec.method_name;
+        let probe_method = syn::Ident::new(
+            &format!("get_{}_probe", self.spec.method_name),
+            self.spec.method_name.span(),
+        );
+        let probe_type = self.generate_provider_probe_type(Some(mod_name));
+
+        let span = self.spec.span;
+        let stats_methods = if build_info.probe_stats_enabled() {
+            let stats_field = self.instance_stats_field_ident();
+            let get_stats_method = syn::Ident::new(
+                &format!("get_{}_stats", self.spec.method_name),
+                self.spec.method_name.span(),
+            );
+
+            quote_spanned! {span=>
+                #[doc(hidden)]
+                #[allow(dead_code)]
+                #vis fn #get_stats_method(&self) -> &::tracers::runtime::ProbeStats {
+                    &self.instance.#stats_field
+                }
+
+                /// **NOTE**: This function was generated by the `tracers` macro because `tracers`
+                /// was built with the `probe-stats` feature
+                ///
+                /// Gets a snapshot of the counters of how many times this probe was hit and fired
+                /// on this instance
+                #[allow(dead_code)]
+                #vis fn #stats_field(&self) -> ::tracers::runtime::ProbeStatsSnapshot {
+                    self.instance.#stats_field.snapshot()
+                }
+            }
+        } else {
+            quote! {}
+        };
+
+        quote_spanned! {span=>
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            #vis fn #probe_method<'a>(&'a self) -> Option<&'a #probe_type> {
+                Some(&self.instance.probes.#probe_ident)
+            }
+
+            #stats_methods
+        }
+    }
+
+    /// The name of the field of the provider's instance impl type which holds this probe's stats,
+    /// and of the instance type's method which takes a snapshot of them, eg `probe0_stats`
+    fn instance_stats_field_ident(&self) -> syn::Ident {
+        syn::Ident::new(
+            &format!("{}_stats", self.spec.method_name),
+            self.spec.method_name.span(),
+        )
+    }
+
+    /// Generates the function the runtime registry uses to test if this probe is enabled.  If the
+    /// provider hasn't been initialized yet then none of its probes are enabled, so rather than
+    /// initialize it this only looks at the provider if it's already there.
+    fn generate_registered_enabled_func(&self) -> TokenStream {
+        let func_ident = self.registered_enabled_func_ident();
+        let probe_var_name = self.probe_var_name();
+
+        let span = self.spec.original_method.span();
+        quote_spanned! {span=>
+            fn #func_ident() -> bool {
+                match IMPL_OPT.get() {
+                    Some(Ok(probes)) => probes.#probe_var_name.is_enabled(),
+                    _ => false,
+                }
+            }
+        }
+    }
+
     /// When building a provider, individual probes are added by calling `add_probe` on the
-    /// `ProviderBuilder` implementation.  This method generates that call for this probe.  In this
-    /// usage the lifetime parameters are not needed.
+    /// `ProviderBuilder` implementation.  This method generates that call for this probe.
     pub(crate) fn generate_add_probe_call(&self, builder: &syn::Ident) -> TokenStream {
         //The `add_probe` method takes one type parameter, which should be the tuple form of the
-        //arguments for this probe.
-        let args_type = self.args_as_tuple_type_with_lifetimes();
+        //native arguments for this probe.
+        let args_type = self.native_args_type_alias_ident();
         let probe_name = &self.spec.name;
 
+        //Tracers which can't pass this many native args are told what to do about it; the provider
+        //has always applied its default by now
+        let arg_overflow = match self.spec.arg_overflow.unwrap_or(ArgOverflow::Spill) {
+            ArgOverflow::Reject => quote! { Reject },
+            ArgOverflow::Spill => quote! { Spill },
+        };
+
         let span = self.spec.original_method.span();
         quote_spanned! {span=>
+            #builder.arg_overflow(::tracers::runtime::dynamic::ArgOverflow::#arg_overflow);
             #builder.add_probe::<#args_type>(#probe_name)
                 .map_err(|e| format_err!(concat!("Error adding probe '", #probe_name, "': {}"), e))?;
         }
     }
 
+    /// The name of the type alias declared in the impl mod for the tuple of this probe's native
+    /// arg types, eg `Probe0NativeArgs`
+    fn native_args_type_alias_ident(&self) -> syn::Ident {
+        syn::Ident::new(
+            &format!("{}NativeArgs", self.spec.name.to_camel_case()),
+            self.spec.span,
+        )
+    }
+
+    /// Generates a type alias in the impl mod for the tuple of this probe's native arg types.  The
+    /// `ProviderProbe` for this probe is typed in terms of the native args, which are what is
+    /// actually passed to the tracing system after the probe's Rust args have been lowered.  This
+    /// has the welcome side-effect of removing all lifetimes from the probe types.
+    ///
+    /// It's an alias because the native types are expressed in terms of `libc`, which is imported
+    /// into the impl mod but isn't necessarily available anywhere else.
+    fn generate_native_args_type_alias(&self) -> TokenStream {
+        let alias = self.native_args_type_alias_ident();
+        let native_types = self.spec.native_args().into_iter().map(|arg| {
+            let rust_type_str = arg.arg_type_info().get_rust_type_str();
+            syn::parse_str::<syn::Type>(rust_type_str).unwrap_or_else(|_| {
+                panic!("Failed to parse Rust type expression '{}'", rust_type_str)
+            })
+        });
+        let tuple = common::generate_tuple(native_types);
+
+        let span = self.spec.span;
+        quote_spanned! {span=>
+            pub type #alias = #tuple;
+        }
+    }
+
+    /// Generates the function in the impl mod which fires the probe.  It takes the probe's
+    /// `ProviderProbe`, and the probe's arguments as declared in the trait.  It lowers the args
+    /// into native args, wraps each of those in its `ProbeArgWrapper`, and fires the probe with
+    /// the resulting C values.
+    fn generate_fire_func(&self) -> TokenStream {
+        let func_name = self.fire_func_ident();
+        let args = self.generate_fn_args_with_lifetimes();
+        let wrap_statement = self.generate_wrap_statement();
+        let native_arg_values = common::generate_tuple(self.generate_native_arg_values());
+        let probe_type = self.generate_provider_probe_type(None);
+
+        //The `ProviderProbe` has the lifetime 'a, in addition to the lifetimes of the probe's args
+        let a_lifetime = syn::Lifetime::new("'a", self.spec.span);
+        let arg_lifetimes = self.args_lifetime_parameters();
+        let function_type_params = quote! { <#a_lifetime, #(#arg_lifetimes),*> };
+
+        let span = self.spec.original_method.span();
+        quote_spanned! {span=>
+            #[inline(always)]
+            #[allow(clippy::needless_lifetimes)] //if there's only one lifetime clippy triggers this lint
+            pub fn #func_name #function_type_params (__tracers_probe: &#probe_type, #(#args),*) {
+                // The compiler warns on this import as unused, even though without this trait
+                // imported the use of `as_c_type()` will fail
+                #[allow(unused_imports)]
+                use ::tracers::runtime::ProbeArgWrapper as _;
+
+                #wrap_statement
+                __tracers_probe.fire(#native_arg_values);
+            }
+        }
+    }
+
     /// Each probe has a corresponding field in the struct that we build for the provider.  That
     /// field is an instance of `ProviderProbe` which is a type-safe wrapper around the underlying
     /// untyped implementation.  Because it's type safe it must necessarily have type parameters
-    /// corresponding to the arguments to the probe.  Thus its declaration gets a bit complicated.
-    ///
-    /// Further complicating matters is that the lifetime elision that makes it so easy to declare
-    /// functions with reference args isn't available here, so every reference parameter the probe
-    /// takes needs to have a corresponding lifetime.  This gets messy, as you'll see.
-    pub(crate) fn generate_provider_probe_type(&self) -> TokenStream {
-        let arg_tuple = self.args_as_tuple_type_with_lifetimes();
+    /// corresponding to the native arguments of the probe, which are declared as a type alias in
+    /// the impl mod.  If this type is used outside of the impl mod, `mod_name` is the name of the
+    /// impl mod, to qualify the alias.
+    pub(crate) fn generate_provider_probe_type(
+        &self,
+        mod_name: Option<&syn::Ident>,
+    ) -> TokenStream {
+        let alias = self.native_args_type_alias_ident();
+        let arg_tuple = match mod_name {
+            Some(mod_name) => quote! { #mod_name::#alias },
+            None => quote! { #alias },
+        };
 
         //In addition to the lifetime params for any ref args, all `ProviderProbe`s have a lifetime
         //param 'a which corresponds to the lifetime of the underlying `UnsafeProviderProbeImpl`
@@ -483,12 +775,10 @@ impl ProbeGenerator {
     }
 
     /// Generates the declaration of the member field within the provider implementation struct
-    /// that holds the `ProviderProbe` instance for this probe.  It's a complex declaration because
-    /// it must include lifetime parameters for all of the reference types used by any of this
-    /// probe's arguments
+    /// that holds the `ProviderProbe` instance for this probe.
     pub(crate) fn generate_struct_member_declaration(&self) -> TokenStream {
         let name = self.probe_var_name();
-        let typ = self.generate_provider_probe_type();
+        let typ = self.generate_provider_probe_type(None);
 
         let span = self.spec.span;
         quote_spanned! {span=>
@@ -505,8 +795,8 @@ impl ProbeGenerator {
     ///
     /// ```noexecute
     /// FooProviderImpl{
-    ///     probe1: provider.probe::<(i32,)>("probe1")?,
-    ///     probe2: provider.probe::<(&str,&str,)>("probe2")?,
+    ///     probe1: provider.probe::<Probe1NativeArgs>("probe1")?,
+    ///     probe2: provider.probe::<Probe2NativeArgs>("probe2")?,
     ///     ...
     /// }
     /// ```
@@ -514,16 +804,17 @@ impl ProbeGenerator {
     /// This method generates just the line corresponding to this probe, without a trailing comma.
     pub(crate) fn generate_struct_member_initialization(
         &self,
+        provider_name: &str,
         provider: &syn::Ident,
     ) -> TokenStream {
         let name_literal = &self.spec.name;
         let name_ident = &self.spec.method_name;
-        let args_tuple = self.args_as_tuple_type_without_lifetimes();
+        let args_tuple = self.native_args_type_alias_ident();
 
         let span = self.spec.span;
         quote_spanned! {span=>
             #name_ident: #provider.get_probe::<#args_tuple>(#name_literal)
-                .map_err(|e| format!(concat!("Error getting probe '", #name_literal, "': {}"), e))?
+                .map_err(|error| ProviderInitError::ProbeFailed { provider: #provider_name, probe: #name_literal, error })?
         }
     }
 }
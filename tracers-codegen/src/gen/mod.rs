@@ -2,7 +2,7 @@
 //! `build.rs` and the generators used by the proc macros.  There are multiple implementations of
 //! these generators for the various tracing implementations, though only one can be active at
 //! compile time, via conditonal compilation
-use crate::build_rs::BuildInfo;
+use crate::build_rs::{BuildInfo, BuildInfoReader, BuildReport};
 use crate::error::TracersResult;
 use crate::spec::{ProbeCallSpecification, ProviderInitSpecification, ProviderSpecification};
 use crate::TracingType;
@@ -28,8 +28,8 @@ pub(crate) mod r#static;
 /// However the native tracing API sometimes requires supporting libraries, either static or
 /// dynamic.  These must be linked explicitly by printing specially formated links to stdout in the
 /// `build.rs` script so Cargo knows where to find the libs.
-#[derive(Serialize, Deserialize, Debug, PartialEq, PartialOrd)]
-pub(crate) enum NativeLib {
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd)]
+pub enum NativeLib {
     /// The name (minus `lib` prefix and `.a` or `.lib` suffix) of the generated static wrapper
     /// lib.  This should be linked by the `#[tracer]` macro so it will not be passed to cargo to
     /// link
@@ -50,6 +50,13 @@ pub(crate) enum NativeLib {
     /// A path where support libs can be found.  This will be passed to cargo as
     /// one of the native library search paths
     SupportLibPath(PathBuf),
+
+    /// A SystemTap tapset generated for a provider.  It's not linked at all, but if the
+    /// `TRACERS_TAPSET_DIR` environment variable is set it's installed to that directory
+    Tapset {
+        provider_name: String,
+        path: PathBuf,
+    },
 }
 
 /// Each probing implementation must implement this trait, which has components which are called at
@@ -64,6 +71,10 @@ pub(crate) trait CodeGenerator {
     /// Invoked by the `probe!` macro to (conditionally) fire a probe.
     fn handle_probe_call(&self, call: ProbeCallSpecification) -> TracersResult<TokenStream>;
 
+    /// Invoked by the `probe_span!` macro to (conditionally) fire the enter probe of a span probe,
+    /// and produce a guard which fires the exit probe when it's dropped.
+    fn handle_probe_span_call(&self, call: ProbeCallSpecification) -> TracersResult<TokenStream>;
+
     /// Invoked by the `init_provider!` macro to (optionally) initialize the provider, although one
     /// requirement of all implementations is that explicit initialization is not required and will
     /// be done lazily on first use.
@@ -75,10 +86,12 @@ pub(crate) trait CodeGenerator {
     ///
     /// It is designed not to panic; if there is a hard stop that should cause the dependent crate
     /// to fail, then it returns an error.  Most errors won't be hard stops, but merely warnings
-    /// that cause the probing system to switch to a no-nop implementation
+    /// that cause the probing system to switch to a no-nop implementation.  Those are recorded in
+    /// `report`, along with the providers found.
     fn generate_native_code(
         &self,
         stdout: &mut dyn Write,
+        report: &mut BuildReport,
         manifest_dir: &Path,
         out_dir: &Path,
         package_name: &str,
@@ -86,10 +99,10 @@ pub(crate) trait CodeGenerator {
     ) -> Vec<NativeLib>;
 }
 
-/// Loads the `BuildInfo` and based on its contents creates and returns the applicable
-/// `CodeGenerator` implementation
-pub(crate) fn code_generator() -> TracersResult<Box<dyn CodeGenerator>> {
-    let bi = BuildInfo::load()?;
+/// Loads the `BuildInfo` on behalf of `reader` and based on its contents creates and returns the
+/// applicable `CodeGenerator` implementation
+pub(crate) fn code_generator(reader: BuildInfoReader) -> TracersResult<Box<dyn CodeGenerator>> {
+    let bi = BuildInfo::load(reader)?;
 
     Ok(match bi.implementation.tracing_type() {
         //There are two implementations: one for static tracing (`disabled` is a special case of
@@ -18,7 +18,7 @@
 //! out how to make `static` work reliable.
 
 use super::NativeLib;
-use crate::build_rs::BuildInfo;
+use crate::build_rs::{BuildInfo, BuildReport};
 use crate::gen::common;
 use crate::spec::ProbeCallSpecification;
 use crate::spec::ProviderInitSpecification;
@@ -32,6 +32,8 @@ mod native_code;
 mod probe_call;
 mod provider_trait;
 
+pub(crate) use native_code::install_tapset;
+
 pub(crate) struct StaticGenerator {
     build_info: BuildInfo,
 }
@@ -44,7 +46,7 @@ impl StaticGenerator {
 
 impl CodeGenerator for StaticGenerator {
     fn handle_provider_trait(&self, provider: ProviderSpecification) -> TracersResult<TokenStream> {
-        let generator = provider_trait::ProviderTraitGenerator::new(&self.build_info, provider);
+        let generator = provider_trait::ProviderTraitGenerator::new(&self.build_info, provider)?;
 
         generator.generate()
     }
@@ -53,6 +55,10 @@ impl CodeGenerator for StaticGenerator {
         probe_call::generate_probe_call(&self.build_info, call)
     }
 
+    fn handle_probe_span_call(&self, call: ProbeCallSpecification) -> TracersResult<TokenStream> {
+        probe_call::generate_probe_span_call(&self.build_info, call)
+    }
+
     fn handle_init_provider(&self, init: ProviderInitSpecification) -> TracersResult<TokenStream> {
         common::generate_init_provider(init)
     }
@@ -60,6 +66,7 @@ impl CodeGenerator for StaticGenerator {
     fn generate_native_code(
         &self,
         stdout: &mut dyn Write,
+        report: &mut BuildReport,
         manifest_dir: &Path,
         out_dir: &Path,
         package_name: &str,
@@ -70,6 +77,7 @@ impl CodeGenerator for StaticGenerator {
             native_code::generate_native_code(
                 &self.build_info,
                 stdout,
+                report,
                 manifest_dir,
                 out_dir,
                 package_name,
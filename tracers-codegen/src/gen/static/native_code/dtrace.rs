@@ -0,0 +1,101 @@
+//! Generates DTrace-style `.d` files which define providers.  These are read by DTrace itself, and
+//! by the `dtrace` compatibility script which comes with SystemTap, which generates a `sys/sdt.h`
+//! based header (`dtrace -h`) and an object file with the probes' semaphores (`dtrace -G`) from
+//! them.  Thus Rust and C components of the same binary can share one provider definition.
+use crate::spec::{ProbeSpecification, ProviderSpecification};
+use crate::TracersError;
+use crate::TracersResult;
+use askama::Template;
+use std::fs;
+use std::path::Path;
+
+/// The definition of a provider and its probes, like:
+///
+/// ```text
+/// provider foo {
+///     probe bar(char*, int);
+/// };
+/// ```
+#[derive(Template)]
+#[template(path = "dtrace/provider.d", escape = "none")]
+struct ProviderDefinitionTemplate<'a> {
+    spec: &'a ProviderSpecification,
+}
+
+impl<'a> ProviderDefinitionTemplate<'a> {
+    /// The C types of the probe's native args, separated by commas
+    fn get_probe_arg_types(&self, probe: &ProbeSpecification) -> String {
+        let arg_types: Vec<_> = probe
+            .native_args()
+            .into_iter()
+            .map(|arg| arg.arg_type_info().get_c_type_str())
+            .collect();
+
+        arg_types.join(", ")
+    }
+}
+
+/// Renders the `.d` file which defines the provider
+pub(super) fn generate_provider_definition(
+    provider: &ProviderSpecification,
+) -> TracersResult<String> {
+    ProviderDefinitionTemplate { spec: provider }
+        .render()
+        .map_err(|e| {
+            TracersError::native_code_generation_error("Rendering provider definition template", e)
+        })
+}
+
+/// Writes the `.d` file which defines the provider to `path`
+pub(super) fn write_provider_definition(
+    provider: &ProviderSpecification,
+    path: &Path,
+) -> TracersResult<()> {
+    let definition = generate_provider_definition(provider)?;
+
+    fs::write(path, definition).map_err(|e| {
+        TracersError::native_code_generation_error(
+            format!("Writing provider definition {}", path.display()),
+            e,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::TracerAttributeArgs;
+    use crate::testdata;
+    use quote::quote;
+
+    #[test]
+    fn defines_provider_and_probes() {
+        let provider = ProviderSpecification::from_token_stream(
+            testdata::TEST_CRATE_NAME,
+            TracerAttributeArgs::default(),
+            quote! {
+                trait DefinedProbes {
+                    fn probe0(name: &str, count: i32);
+                    fn probe1();
+                    #[probe(sample = 10)]
+                    fn probe2(#[probe(encoding = "ptr_len")] path: &str);
+                }
+            },
+        )
+        .unwrap();
+
+        let definition = generate_provider_definition(&provider).unwrap();
+        assert!(
+            definition.ends_with(
+                "provider test_defined_probes {
+    probe probe0(char*, int);
+    probe probe1();
+    probe probe2(void*, size_t, unsigned long long);
+};
+"
+            ),
+            "{}",
+            definition
+        );
+    }
+}
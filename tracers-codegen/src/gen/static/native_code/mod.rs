@@ -2,7 +2,7 @@
 //! calls to the target platform's native tracing mechanism.  Most of the code generation logic is
 //! the same across all platforms, with platform specific bits factored out into one of the
 //! `platform` submodules
-use crate::build_rs::BuildInfo;
+use crate::build_rs::{BuildFailure, BuildInfo, BuildReport, OUT_DIR_ENV_VAR};
 use crate::cache;
 use crate::deps::{self, SourceDependency};
 use crate::gen::NativeLib;
@@ -14,8 +14,11 @@ use std::env;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+mod dtrace;
 mod target;
 
+pub(crate) use target::stap::install_tapset;
+
 /// The (possibly cached) data structure containing the results of processing a Rust source file
 #[derive(Serialize, Deserialize)]
 pub(crate) struct ProcessedFile {
@@ -47,6 +50,7 @@ trait NativeCodeGenerator {
 }
 
 const PROCESSED_PROVIDER_KEY: &str = "processed_provider";
+const FAILED_PROVIDER_KEY: &str = "failed_provider";
 
 /// Checks the cache to see if the provider described by `provider` has already been processed by
 /// the native code generator and produced a native lib and Rust bindings.  If so returns the
@@ -60,7 +64,7 @@ const PROCESSED_PROVIDER_KEY: &str = "processed_provider";
 pub(crate) fn get_processed_provider_info(
     provider: &ProviderSpecification,
 ) -> TracersResult<ProcessedProviderTrait> {
-    let out_dir = PathBuf::from(env::var("OUT_DIR").context("OUT_DIR")?);
+    let out_dir = get_out_dir()?;
     let cache_dir = cache::get_cache_path(&out_dir);
     cache::get_cached_object_computation(
         &cache_dir,
@@ -68,12 +72,40 @@ pub(crate) fn get_processed_provider_info(
         provider.hash(),
         PROCESSED_PROVIDER_KEY,
     )
-    .map_err(|e| TracersError::provider_trait_not_processed_error(provider.ident().to_string(), e))
+    .map_err(|e| {
+        //If the build tried to generate native code for this provider and failed, that's a more
+        //useful explanation than the missing cache entry
+        match cache::get_cached_object_computation::<String>(
+            &cache_dir,
+            provider.name(),
+            provider.hash(),
+            FAILED_PROVIDER_KEY,
+        ) {
+            Ok(message) => TracersError::provider_trait_not_processed_error(
+                provider.ident().to_string(),
+                failure::format_err!("Native code generation failed: {}", message),
+            ),
+            Err(_) => {
+                TracersError::provider_trait_not_processed_error(provider.ident().to_string(), e)
+            }
+        }
+    })
+}
+
+/// The directory `tracers_build::build()` generated code in, which is `OUT_DIR` unless it was
+/// overridden with `Builder::with_out_dir`, in which case the build tells Cargo to pass it to the
+/// proc macros as `TRACERS_OUT_DIR`
+fn get_out_dir() -> TracersResult<PathBuf> {
+    match env::var(OUT_DIR_ENV_VAR) {
+        Ok(out_dir) => Ok(PathBuf::from(out_dir)),
+        Err(_) => Ok(PathBuf::from(env::var("OUT_DIR").context("OUT_DIR")?)),
+    }
 }
 
 pub(super) fn generate_native_code(
     build_info: &BuildInfo,
     stdout: &mut dyn Write,
+    report: &mut BuildReport,
     manifest_dir: &Path,
     out_dir: &Path,
     _package_name: &str,
@@ -82,7 +114,10 @@ pub(super) fn generate_native_code(
     assert!(build_info.implementation.tracing_type() == TracingType::Static);
 
     match build_info.implementation.tracing_target() {
-        TracingTarget::Disabled | TracingTarget::NoOp => {
+        TracingTarget::Disabled
+        | TracingTarget::NoOp
+        | TracingTarget::ChromeTrace
+        | TracingTarget::Ctf => {
             writeln!(
                 stdout,
                 "No native code needed for {} tracing",
@@ -96,7 +131,13 @@ pub(super) fn generate_native_code(
             for target in targets.into_iter() {
                 let target_path = manifest_dir.join(&target);
                 writeln!(stdout, "Processing target {}", target_path.display()).unwrap();
-                libs.append(&mut process_file(build_info, stdout, out_dir, &target_path));
+                libs.append(&mut process_file(
+                    build_info,
+                    stdout,
+                    report,
+                    out_dir,
+                    &target_path,
+                ));
             }
 
             libs
@@ -107,6 +148,7 @@ pub(super) fn generate_native_code(
 fn process_file(
     build_info: &BuildInfo,
     stdout: &mut dyn Write,
+    report: &mut BuildReport,
     out_dir: &Path,
     file: &Path,
 ) -> Vec<NativeLib> {
@@ -149,19 +191,26 @@ fn process_file(
             for dependency in processed_file.dependencies.into_iter() {
                 match deps::resolve_dependency(file, &dependency) {
                     // Dependency resolved; recursively process this one also
-                    Ok(dep_file) => {
-                        libs.append(&mut process_file(build_info, stdout, out_dir, &dep_file))
-                    }
+                    Ok(dep_file) => libs.append(&mut process_file(
+                        build_info, stdout, report, out_dir, &dep_file,
+                    )),
 
                     // Failed to resolve dependency.  This code probably won't compile anyway, but log
                     // a warning through Cargo so the user understands the generation step wasn't
                     // successful either
-                    Err(_) => {
+                    Err(e) => {
                         writeln!(stdout,
                              "cargo:warning=Unable to resove dependency {:?} in {}; any tracing providers it may contain will not be processed",
                              dependency,
                              file.display()
                              ).unwrap();
+                        report.add_failure(BuildFailure::File {
+                            path: file.to_owned(),
+                            message: format!(
+                                "Unable to resolve dependency {:?}: {}",
+                                dependency, e
+                            ),
+                        });
                     }
                 }
             }
@@ -169,7 +218,9 @@ fn process_file(
             for provider in processed_file.providers.into_iter() {
                 //Call `process_provider` for each provider in the file.  If it fails, log the failure
                 //in a way that will cause Cargo to report a warning, and continue on
-                libs.append(&mut process_provider(build_info, stdout, out_dir, provider));
+                libs.append(&mut process_provider(
+                    build_info, stdout, report, out_dir, provider,
+                ));
             }
 
             libs
@@ -190,6 +241,10 @@ fn process_file(
                 file.display()
             )
             .unwrap();
+            report.add_failure(BuildFailure::File {
+                path: file.to_owned(),
+                message: e.to_string(),
+            });
 
             //On error there won't be any generated native libs obviously
             vec![]
@@ -200,20 +255,50 @@ fn process_file(
 fn process_provider(
     build_info: &BuildInfo,
     stdout: &mut dyn Write,
+    report: &mut BuildReport,
     out_dir: &Path,
     provider: ProviderSpecification,
 ) -> Vec<NativeLib> {
     let cache_dir = cache::get_cache_path(out_dir);
+    report.add_provider(provider.name());
+
+    // The provider definition isn't cached with the native code, since whether it's wanted depends
+    // on the features `tracers` is built with, not on the provider.  It's cheap to generate anyway.
+    if build_info.provider_definitions {
+        let definition_path = out_dir.join(format!("{}.d", provider.name()));
+        match dtrace::write_provider_definition(&provider, &definition_path) {
+            Ok(()) => writeln!(
+                stdout,
+                "Wrote provider definition {}",
+                definition_path.display()
+            )
+            .unwrap(),
+            Err(e) => {
+                writeln!(
+                    stdout,
+                    "cargo:warning=Error writing provider definition for '{}': {}",
+                    provider.ident(),
+                    e
+                )
+                .unwrap();
+                report.add_failure(BuildFailure::Provider {
+                    provider: provider.name().to_owned(),
+                    message: format!("Error writing provider definition: {}", e),
+                });
+            }
+        }
+    }
 
     // For this trait, generate native code for the probes.  If this trait was processed before
     // and hasn't changed, even if the source file it's in has changed, then we can skip that
     // generation and used the cached result
     let name = provider.name().to_owned();
     let ident = provider.ident().clone();
+    let hash = provider.hash();
     let result = cache::cache_object_computation(
         &cache_dir,
         &name,
-        provider.hash(),
+        hash,
         PROCESSED_PROVIDER_KEY,
         move || {
             let generator = create_native_code_generator(build_info, out_dir, provider);
@@ -237,12 +322,26 @@ fn process_provider(
                 ident, e
             )
             .unwrap();
+
+            //Record the failure so that the proc macros can explain why this provider isn't
+            //available.  If even that fails there's nothing more to be done about it.
+            let _ = cache::save_object_computation(
+                &cache_dir,
+                &name,
+                hash,
+                FAILED_PROVIDER_KEY,
+                &e.to_string(),
+            );
             writeln!(
                 stdout,
                 "cargo:warning=Tracing may not be available for {}",
                 ident
             )
             .unwrap();
+            report.add_failure(BuildFailure::Provider {
+                provider: name,
+                message: e.to_string(),
+            });
 
             //No native libs generated in the error case
             vec![]
@@ -256,12 +355,17 @@ fn create_native_code_generator(
     provider: ProviderSpecification,
 ) -> Box<dyn NativeCodeGenerator> {
     match build_info.implementation.tracing_target() {
-        TracingTarget::Disabled | TracingTarget::NoOp => panic!(
+        TracingTarget::Disabled
+        | TracingTarget::NoOp
+        | TracingTarget::ChromeTrace
+        | TracingTarget::Ctf => panic!(
             "{} should never be passed to this function",
             build_info.implementation.as_ref()
         ),
         TracingTarget::Stap => Box::new(target::stap::StapNativeCodeGenerator::new(
-            out_dir, provider,
+            out_dir,
+            provider,
+            build_info.stap_dtrace,
         )),
         TracingTarget::Lttng => Box::new(target::lttng::LttngNativeCodeGenerator::new(
             out_dir, provider,
@@ -311,6 +415,7 @@ mod tests {
                         process_file(
                             &build_info,
                             &mut stdout,
+                            &mut BuildReport::default(),
                             &out_dir,
                             &case.root_directory.join(target.entrypoint),
                         );
@@ -396,7 +501,13 @@ mod tests {
                 ]);
                 let mut stdout = Vec::new();
 
-                process_provider(&build_info, &mut stdout, &out_dir, provider.clone());
+                process_provider(
+                    &build_info,
+                    &mut stdout,
+                    &mut BuildReport::default(),
+                    &out_dir,
+                    provider.clone(),
+                );
 
                 let processed_provider = get_processed_provider_info(&provider)
                     .expect("There should be a processed provider");
@@ -3,7 +3,7 @@
 use crate::cache;
 use crate::gen::r#static::native_code::NativeCodeGenerator;
 use crate::gen::NativeLib;
-use crate::spec::ProbeArgSpecification;
+use crate::spec::NativeArgSpecification;
 use crate::spec::ProbeSpecification;
 use crate::spec::ProviderSpecification;
 use crate::TracersError;
@@ -15,9 +15,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::prelude::*;
-use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use tracers_core::argtypes::CType;
 
 /// Holds the important bits from `pkg-config` in a serializable form that we can cache to avoid
@@ -101,14 +99,15 @@ impl<'a> NativeProviderWrapperTemplate<'a> {
     fn get_probe_args<'args>(
         &self,
         probe: &'args ProbeSpecification,
-    ) -> Vec<&'args ProbeArgSpecification> {
+    ) -> Vec<&'args NativeArgSpecification> {
         get_probe_args(probe)
     }
 }
 
-/// LTTng has a tool, `lttng-gen-tp`, which can generate the header and C source file for a
-/// provider given only it's definitino in a template.  This is easier and more stable than
-/// manually generating both.
+/// The definition of each of the provider's tracepoints, in the same form as the template LTTng's
+/// `lttng-gen-tp` tool takes as input.  The header and C source file for the provider are generated
+/// from it just as `lttng-gen-tp` would, by `NativeProviderHeaderTemplate` and
+/// `NativeProviderSourceTemplate`.
 #[derive(Template)]
 #[template(path = "lttng/provider.tp", escape = "none")]
 struct NativeProviderTemplate<'a> {
@@ -131,10 +130,28 @@ impl<'a> NativeProviderTemplate<'a> {
     /// arguments: the pointer to the array and its length.  This could be one output field, using
     /// the `ctf_array` macro.
     ///
-    /// Our implementation is not so sophisticated, so every argument corresponds to exactly one
-    /// output field.  Perhaps in the future this will be extended to provide richer support for
-    /// things like arrays, enums, etc.
-    fn get_probe_arg_ctf_macro(arg: &ProbeArgSpecification) -> Option<String> {
+    /// Our implementation is not so sophisticated, so almost every argument corresponds to exactly
+    /// one output field.  The exception is a string passed as a pointer and a length (the
+    /// `ptr_len` encoding), which is output as a single sequence field whose length is taken from
+    /// the length argument; the length argument also gets its own output field.  Perhaps in the
+    /// future this will be extended to provide richer support for things like arrays, enums, etc.
+    fn get_probe_arg_ctf_macro(
+        arg: &NativeArgSpecification,
+        args: &[&NativeArgSpecification],
+    ) -> Option<String> {
+        //Only use the length arg if it's actually one of the args passed to LTTng
+        let length_arg = arg
+            .length_arg()
+            .filter(|length_arg| args.iter().any(|arg| arg.name() == *length_arg));
+
+        if let Some(length_arg) = length_arg {
+            return Some(format!(
+                "ctf_sequence_text(char, {0}, (const char *){0}, size_t, {1})",
+                arg.name(),
+                length_arg
+            ));
+        }
+
         match arg.arg_type_info().get_c_type_enum() {
             CType::NoArg => None,
             CType::VoidPtr | CType::UCharPtr => {
@@ -151,9 +168,20 @@ impl<'a> NativeProviderTemplate<'a> {
                 Some(format!("ctf_string({0}, {0})", arg.name()))
             }
             int_type => {
-                //Anything else is some kind of integer type.
+                //Anything else is some kind of integer type.  Some integers, like the two halves of
+                //a `u128`, are just bit patterns and are more readable in hex
                 let type_name: &'static str = int_type.into();
-                Some(format!("ctf_integer({0}, {1}, {1})", type_name, arg.name()))
+                let ctf_macro = if arg.is_hex() {
+                    "ctf_integer_hex"
+                } else {
+                    "ctf_integer"
+                };
+                Some(format!(
+                    "{0}({1}, {2}, {2})",
+                    ctf_macro,
+                    type_name,
+                    arg.name()
+                ))
             }
         }
     }
@@ -162,28 +190,176 @@ impl<'a> NativeProviderTemplate<'a> {
     ///
     /// As above operates only on the first 10
     fn get_probe_output_fields(&self, probe: &ProbeSpecification) -> String {
-        let fields: Vec<_> = self
-            .get_probe_args(probe)
+        let args = self.get_probe_args(probe);
+        let fields: Vec<_> = args
             .iter()
-            .map(|arg| Self::get_probe_arg_ctf_macro(arg))
+            .map(|arg| Self::get_probe_arg_ctf_macro(arg, &args))
             .flatten()
             .collect();
 
         fields.join("\n")
     }
 
+    /// Gets the `TRACEPOINT_LOGLEVEL` and `TRACEPOINT_MODEL_EMF_URI` declarations for the probe,
+    /// if it has a level or an EMF URI.  Without a level, LTTng puts the tracepoint at its default
+    /// level, `TRACE_DEBUG_LINE`.
+    fn get_probe_metadata(&self, probe: &ProbeSpecification) -> String {
+        let mut declarations = Vec::new();
+
+        if let Some(level) = probe.level {
+            declarations.push(format!(
+                "TRACEPOINT_LOGLEVEL({}, {}, {})",
+                self.spec.name(),
+                probe.name,
+                level.lttng_loglevel()
+            ));
+        }
+
+        if let Some(ref emf_uri) = probe.emf_uri {
+            declarations.push(format!(
+                "TRACEPOINT_MODEL_EMF_URI({}, {}, {})",
+                self.spec.name(),
+                probe.name,
+                c_string_literal(emf_uri)
+            ));
+        }
+
+        declarations.join("\n    ")
+    }
+
     fn get_probe_args<'args>(
         &self,
         probe: &'args ProbeSpecification,
-    ) -> Vec<&'args ProbeArgSpecification> {
+    ) -> Vec<&'args NativeArgSpecification> {
         get_probe_args(probe)
     }
 }
 
-/// Gets the probe's arguments (up to a maximum of 10).  LTTng supports no more than 10
+/// The header which defines the provider's tracepoints.  This is exactly the header `lttng-gen-tp`
+/// generates from the provider's template, so the tracepoint definitions are the same whether
+/// or not that tool is installed.
+#[derive(Template)]
+#[template(path = "lttng/provider.h", escape = "none")]
+struct NativeProviderHeaderTemplate<'a> {
+    provider_name: &'a str,
+    header_file_name: &'a str,
+    include_guard: String,
+    tracepoint_events: String,
+}
+
+impl<'a> NativeProviderHeaderTemplate<'a> {
+    /// `provider_template` is the rendered `NativeProviderTemplate`
+    fn from_provider_template(
+        provider: &'a ProviderSpecification,
+        header_file_name: &'a str,
+        provider_template: &str,
+    ) -> NativeProviderHeaderTemplate<'a> {
+        //`lttng-gen-tp` takes the name of the provider from the tracepoints, so if there aren't any
+        //the provider name is empty
+        let provider_name = if provider.probes().is_empty() {
+            ""
+        } else {
+            provider.name()
+        };
+
+        //Like `lttng-gen-tp`, the include guard is the header file name in upper case, with
+        //anything other than letters and digits replaced by `_`
+        let include_guard = header_file_name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        NativeProviderHeaderTemplate {
+            provider_name,
+            header_file_name,
+            include_guard,
+            tracepoint_events: remove_template_comments(provider_template),
+        }
+    }
+}
+
+/// The C source file which instantiates the provider's tracepoints, by including the header with
+/// `TRACEPOINT_CREATE_PROBES` and `TRACEPOINT_DEFINE` defined.  Like the header, this is exactly
+/// what `lttng-gen-tp` generates.
+#[derive(Template)]
+#[template(path = "lttng/provider.c", escape = "none")]
+struct NativeProviderSourceTemplate<'a> {
+    header_file_name: &'a str,
+}
+
+/// `lttng-gen-tp` removes "comments" starting with `#` from the template before copying it into the
+/// header, except for `#include` directives.  More precisely, it removes every match of the regex
+/// `#[^include].*$` (in multi-line mode), which is to say a `#` followed by any character that
+/// isn't one of the letters in "include", up to the end of the line.  The same is done here so the
+/// header is identical to the one `lttng-gen-tp` would generate.  The tracepoint definitions never
+/// contain a `#`, but the comment with the source code of the provider trait usually does.
+fn remove_template_comments(template: &str) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('#', Some(next)) if !"include".contains(*next) => {
+                //The character after the `#` is removed even if it's a newline, since the regex's
+                //character class matches newlines
+                chars.next();
+                while chars.peek().map(|c| *c != '\n').unwrap_or(false) {
+                    chars.next();
+                }
+            }
+            (c, _) => text.push(c),
+        }
+    }
+
+    text
+}
+
+/// Quotes a string as a C string literal.  Besides the usual escapes, any `#` is written as an octal
+/// escape, because `lttng-gen-tp` (and thus `remove_template_comments`) would otherwise treat the
+/// rest of the line as a comment.  EMF URIs often have a `#` in their fragment.
+fn c_string_literal(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '#' => literal.push_str("\\043"),
+            c if c.is_ascii_graphic() || c == ' ' => literal.push(c),
+            c => {
+                let mut buf = [0u8; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    literal.push_str(&format!("\\{:03o}", byte));
+                }
+            }
+        }
+    }
+
+    literal.push('"');
+    literal
+}
+
+/// Gets the probe's native arguments (up to a maximum of 10).  LTTng supports no more than 10
 /// arguments and will produce compile errors if any more are used
-fn get_probe_args<'args>(probe: &'args ProbeSpecification) -> Vec<&'args ProbeArgSpecification> {
-    probe.args.iter().take(10).collect()
+fn get_probe_args(probe: &ProbeSpecification) -> Vec<&NativeArgSpecification> {
+    probe.native_args().into_iter().take(10).collect()
+}
+
+fn write_code_file(path: &Path, code: &str) -> TracersResult<()> {
+    let mut file = File::create(path).map_err(|e| {
+        TracersError::native_code_generation_error(format!("Creating {}", path.display()), e)
+    })?;
+
+    file.write_all(code.as_bytes()).map_err(|e| {
+        TracersError::native_code_generation_error(format!("Writing to {}", path.display()), e)
+    })
 }
 
 pub(crate) struct LttngNativeCodeGenerator {
@@ -203,40 +379,48 @@ impl LttngNativeCodeGenerator {
 impl NativeCodeGenerator for LttngNativeCodeGenerator {
     fn generate_native_lib(&self) -> TracersResult<Vec<NativeLib>> {
         //LTTng is a bit more complex than STap because we generate the header and an
-        //implementation C file with a tool, then generated a C++ wrapper around it which we'll
-        //expose to the Rust code.
+        //implementation C file which define the provider, then generate a C++ wrapper around it
+        //which we'll expose to the Rust code.
         let cache_dir = cache::get_cache_path(&self.out_dir);
         let code_dir = self.build_dir();
         fs::create_dir_all(&code_dir).map_err(|e| {
             TracersError::native_code_generation_error("Creating build directory", e)
         })?;
 
-        //Create the <provider>.tp template file
+        //Generate the provider's tracepoint definitions, from which the header and C source
+        //file which define the provider are generated
         let provider_template = NativeProviderTemplate::from_provider_spec(&self.provider)
             .render()
             .map_err(|e| {
                 TracersError::native_code_generation_error("Rendering LTTNG template", e)
             })?;
-        let provider_template_path =
-            code_dir.join(format!("{}.tp", self.provider.name_with_hash()));
-        let mut file = File::create(&provider_template_path).map_err(|e| {
-            TracersError::native_code_generation_error(
-                format!(
-                    "Creating template file {}",
-                    provider_template_path.display()
-                ),
-                e,
-            )
-        })?;
 
         #[cfg(debug_assertions)]
         println!("Generated LTTng template code:\n{}", &provider_template);
 
-        file.write_all(provider_template.as_bytes()).map_err(|e| {
-            TracersError::native_code_generation_error("Writing to LTTng template file", e)
+        let provider_impl = code_dir.join(format!("{}_provider.c", self.provider.name_with_hash()));
+        let header_file_name = format!("{}_provider.h", self.provider.name_with_hash());
+        let provider_header = code_dir.join(&header_file_name);
+
+        let header_code = NativeProviderHeaderTemplate::from_provider_template(
+            &self.provider,
+            &header_file_name,
+            &provider_template,
+        )
+        .render()
+        .map_err(|e| {
+            TracersError::native_code_generation_error("Rendering LTTng provider header", e)
         })?;
+        write_code_file(&provider_header, &header_code)?;
 
-        drop(file);
+        let impl_code = NativeProviderSourceTemplate {
+            header_file_name: &header_file_name,
+        }
+        .render()
+        .map_err(|e| {
+            TracersError::native_code_generation_error("Rendering LTTng provider source", e)
+        })?;
+        write_code_file(&provider_impl, &impl_code)?;
 
         //Create the <provider>.cpp wrapper file
         let wrapper_code = NativeProviderWrapperTemplate::from_provider_spec(&self.provider)
@@ -246,74 +430,11 @@ impl NativeCodeGenerator for LttngNativeCodeGenerator {
             })?;
 
         let code_path = code_dir.join(format!("{}.cpp", self.provider.name_with_hash()));
-        let mut file = File::create(&code_path).map_err(|e| {
-            TracersError::native_code_generation_error(
-                format!("Creating wrapper file {}", code_path.display()),
-                e,
-            )
-        })?;
 
         #[cfg(debug_assertions)]
         println!("Generated wrapper code:\n{}", wrapper_code);
 
-        file.write_all(wrapper_code.as_bytes()).map_err(|e| {
-            TracersError::native_code_generation_error("Writing to wrapper file", e)
-        })?;
-
-        drop(file);
-
-        // Invoke the `lttng-gen-tp` command line to generate C code for the provider
-        // implementation functions
-        let provider_impl = code_dir.join(format!("{}_provider.c", self.provider.name_with_hash()));
-        let provider_header =
-            code_dir.join(format!("{}_provider.h", self.provider.name_with_hash()));
-        println!(
-            "Generating {} and {} with `lttng-gen-tp`...",
-            provider_impl.display(),
-            provider_header.display()
-        );
-        let output = match Command::new("lttng-gen-tp")
-            .current_dir(&code_dir)
-            .arg(provider_template_path)
-            .arg("-o").arg(&provider_impl.file_name().expect("Expected impl file name"))
-            .arg("-o").arg(&provider_header.file_name().expect("Expected header file name"))
-            .output()
-        {
-            Err(ref e) if e.kind() == ErrorKind::NotFound => Err(
-                TracersError::native_code_generation_error("Generating native tracing code",
-                                                                                                        format_err!(
-                "The `lttng-gen-tp` executable wasn't found; make sure it's installed and in the path"
-            ))),
-            Err(e) => Err(TracersError::native_code_generation_error(
-                "Error generating provider code with `lttng-gen-tp`",
-                e,
-            )),
-            Ok(output) => Ok(output),
-        }?;
-
-        //Echo the output to our stdout/stderr, with errors output as a warning
-        for line in String::from_utf8(output.stdout)
-            .expect("Expected valid UTF-8 output")
-            .lines()
-        {
-            println!("{}", line);
-        }
-        for line in String::from_utf8(output.stderr)
-            .expect("Expected valid UTF-8 output")
-            .lines()
-        {
-            eprintln!("cargo:warning={}", line);
-        }
-
-        if !output.status.success() {
-            return Err(TracersError::native_code_generation_error::<
-                &str,
-                failure::Error,
-            >(
-                "Error generating provider code with `lttng-gen-tp`",
-                format_err!("lttng-gen-tp failed with exit code {}", output.status),
-            ));
-        }
+        write_code_file(&code_path, &wrapper_code)?;
 
         let lib_dir = self.output_dir();
         fs::create_dir_all(&lib_dir).map_err(|e| {
@@ -361,3 +482,101 @@ impl NativeCodeGenerator for LttngNativeCodeGenerator {
         &self.out_dir
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, *};
+    use std::process::Command;
+
+    #[test]
+    fn removes_comments_like_lttng_gen_tp() {
+        assert_eq!(
+            "#include <foo.h>\n/* \n * trait Foo { fn bar (); }\n\n */\nend",
+            remove_template_comments(
+                "#include <foo.h>\n/* # [tracer]\n * trait Foo { fn bar (); }\n#\nnext line\n */\nend"
+            )
+        );
+    }
+
+    #[test]
+    fn quotes_c_strings() {
+        assert_eq!(
+            r#""model.emf\043//Foo \"bar\" \\ \303\251""#,
+            c_string_literal("model.emf#//Foo \"bar\" \\ é")
+        );
+    }
+
+    /// Renders the header and source of every test provider, and if `lttng-gen-tp` is installed
+    /// compares them with what it generates from the same template
+    #[test]
+    fn generates_same_code_as_lttng_gen_tp() {
+        for test_trait in
+            get_test_provider_traits(|t: &TestProviderTrait| t.expected_error.is_none())
+        {
+            let (attr, item_trait) = test_trait.get_attr_and_item_trait();
+            let provider =
+                ProviderSpecification::from_trait(testdata::TEST_CRATE_NAME, attr, item_trait)
+                    .unwrap();
+            let header_file_name = format!("{}_provider.h", provider.name_with_hash());
+            let source_file_name = format!("{}_provider.c", provider.name_with_hash());
+
+            let template = NativeProviderTemplate::from_provider_spec(&provider)
+                .render()
+                .unwrap();
+            let header = NativeProviderHeaderTemplate::from_provider_template(
+                &provider,
+                &header_file_name,
+                &template,
+            )
+            .render()
+            .unwrap();
+            let source = NativeProviderSourceTemplate {
+                header_file_name: &header_file_name,
+            }
+            .render()
+            .unwrap();
+
+            assert!(header.contains(&format!(
+                "#define TRACEPOINT_INCLUDE \"./{}\"",
+                header_file_name
+            )));
+            assert!(source.contains(&format!("#include \"{}\"", header_file_name)));
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let template_path = temp_dir.path().join("provider.tp");
+            fs::write(&template_path, &template).unwrap();
+
+            match Command::new("lttng-gen-tp")
+                .current_dir(temp_dir.path())
+                .arg(&template_path)
+                .arg("-o")
+                .arg(&source_file_name)
+                .arg("-o")
+                .arg(&header_file_name)
+                .status()
+            {
+                Ok(status) => {
+                    assert!(status.success(), "lttng-gen-tp failed: {}", status);
+
+                    let read = |name: &str| fs::read_to_string(temp_dir.path().join(name)).unwrap();
+                    assert_eq!(
+                        read(&header_file_name),
+                        header,
+                        "{}",
+                        test_trait.description
+                    );
+                    assert_eq!(
+                        read(&source_file_name),
+                        source,
+                        "{}",
+                        test_trait.description
+                    );
+                }
+                Err(_) => {
+                    println!("`lttng-gen-tp` isn't installed; not comparing with its output");
+                }
+            }
+        }
+    }
+}
@@ -1,56 +1,295 @@
 //! Contains the native C++ code generator and the Rust bindings generator to support Linux
 //! SystemTap user-mode tracing
-use crate::gen::r#static::native_code::NativeCodeGenerator;
+use crate::gen::r#static::native_code::{dtrace, NativeCodeGenerator};
 use crate::gen::NativeLib;
-use crate::spec::ProviderSpecification;
+use crate::spec::{ProbeSpecification, ProviderSpecification};
 use crate::TracersError;
 use crate::TracersResult;
 use askama::Template;
 use failure::format_err;
 use std::fs::{self, File};
 use std::io::prelude::*;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracers_core::argtypes::CType;
+
+/// The placeholder in the generated tapset for the path of the binary which contains the probes.
+/// It's replaced when the tapset is installed; see `install_tapset`.
+const TAPSET_BINARY_PLACEHOLDER: &str = "@BINARY@";
+
+/// Words which are reserved in the SystemTap language, and so can't be used as the names of the
+/// variables a tapset binds the probe args to.  Args with these names get a `_` suffix.
+const STAP_KEYWORDS: &[&str] = &[
+    "break", "catch", "continue", "delete", "else", "for", "foreach", "function", "global", "if",
+    "in", "limit", "long", "next", "private", "probe", "return", "string", "try", "while",
+];
 
 #[derive(Template)]
 #[template(path = "stap/provider_wrapper.cpp", escape = "none")]
 struct NativeProviderWrapperTemplate<'a> {
     spec: &'a ProviderSpecification,
+
+    /// The file name of the header generated by `dtrace -h`, if the provider is built with
+    /// SystemTap's `dtrace` tool rather than our own copy of `sys/sdt.h`
+    dtrace_header: Option<String>,
 }
 
 impl<'a> NativeProviderWrapperTemplate<'a> {
     fn from_provider_spec<'b: 'a>(
         provider: &'b ProviderSpecification,
+        dtrace_header: Option<String>,
     ) -> NativeProviderWrapperTemplate<'a> {
-        NativeProviderWrapperTemplate { spec: provider }
+        NativeProviderWrapperTemplate {
+            spec: provider,
+            dtrace_header,
+        }
+    }
+
+    fn get_dtrace_header(&self) -> &str {
+        self.dtrace_header.as_deref().unwrap_or_default()
+    }
+
+    /// Gets the name of the macro which `dtrace -h` generates to fire the probe.  The `dtrace`
+    /// script names it for the provider and the probe in upper case, with any `__` in the probe
+    /// name (which DTrace would translate to `-`) replaced by `_`.
+    fn get_dtrace_probe_macro(&self, probe: &ProbeSpecification) -> String {
+        format!(
+            "{}_{}",
+            self.spec.name(),
+            probe.name.to_string().replace("__", "_")
+        )
+        .to_uppercase()
+    }
+}
+
+/// A SystemTap tapset with an alias for each of the provider's probes, so that scripts can refer to
+/// the probe args by name rather than as `$arg1..$argN`.
+#[derive(Template)]
+#[template(path = "stap/provider.stp", escape = "none")]
+struct NativeProviderTapsetTemplate<'a> {
+    spec: &'a ProviderSpecification,
+}
+
+impl<'a> NativeProviderTapsetTemplate<'a> {
+    fn from_provider_spec<'b: 'a>(
+        provider: &'b ProviderSpecification,
+    ) -> NativeProviderTapsetTemplate<'a> {
+        NativeProviderTapsetTemplate { spec: provider }
+    }
+
+    /// Gets the assignment of each of the probe's native args to a variable of the same name.
+    /// Strings are copied from the traced process with `user_string`, or with `user_string_n` if
+    /// they're passed as a pointer and a length.  Strings are often NULL, as for `None`, and a fault
+    /// reading one would count towards the errors which abort the whole stap session, so they're
+    /// read with the forms which return `""` instead.  Everything else is assigned as is.
+    fn get_arg_bindings(&self, probe: &ProbeSpecification) -> Vec<String> {
+        let args = probe.native_args();
+
+        args.iter()
+            .enumerate()
+            .map(|(index, arg)| {
+                let length_index = arg
+                    .length_arg()
+                    .and_then(|length_arg| args.iter().position(|arg| arg.name() == length_arg));
+
+                let value = match (length_index, arg.arg_type_info().get_c_type_enum()) {
+                    (Some(length_index), _) => {
+                        format!(
+                            "user_string_n($arg{}, $arg{}, \"\")",
+                            index + 1,
+                            length_index + 1
+                        )
+                    }
+                    (None, CType::CharPtr) => format!("user_string($arg{}, \"\")", index + 1),
+                    _ => format!("$arg{}", index + 1),
+                };
+
+                let name = if STAP_KEYWORDS.contains(&arg.name()) {
+                    format!("{}_", arg.name())
+                } else {
+                    arg.name().to_owned()
+                };
+
+                format!("{} = {}", name, value)
+            })
+            .collect()
     }
 }
 
+/// Installs the tapset generated for the provider `provider_name` to `tapset_dir`, as
+/// `<provider_name>.stp`, so that it can be used with `stap -I <tapset_dir>`.  This replaces any
+/// tapset installed for an earlier version of the provider.  The placeholder for the binary containing the probes
+/// is replaced with `binary`, which can be either a path or the name of an executable in the
+/// `PATH`.  The placeholder is inside a string literal, so `binary` is escaped; a `binary` with
+/// control characters is an error.  Returns the path of the installed tapset.
+pub(crate) fn install_tapset(
+    provider_name: &str,
+    tapset_path: &Path,
+    tapset_dir: &Path,
+    binary: &str,
+) -> TracersResult<PathBuf> {
+    let binary = escape_stap_string(binary).ok_or_else(|| {
+        TracersError::native_code_generation_error(
+            format!("Installing tapset for binary {:?}", binary),
+            format_err!("the path of the binary can't contain control characters").compat(),
+        )
+    })?;
+
+    let tapset = fs::read_to_string(tapset_path).map_err(|e| {
+        TracersError::native_code_generation_error(
+            format!("Reading tapset {}", tapset_path.display()),
+            e,
+        )
+    })?;
+
+    fs::create_dir_all(tapset_dir).map_err(|e| {
+        TracersError::native_code_generation_error(
+            format!("Creating tapset directory {}", tapset_dir.display()),
+            e,
+        )
+    })?;
+    let installed_path = tapset_dir.join(format!("{}.stp", provider_name));
+    fs::write(
+        &installed_path,
+        tapset.replace(TAPSET_BINARY_PLACEHOLDER, &binary),
+    )
+    .map_err(|e| {
+        TracersError::native_code_generation_error(
+            format!("Writing tapset {}", installed_path.display()),
+            e,
+        )
+    })?;
+
+    Ok(installed_path)
+}
+
+/// Escapes `value` for use inside a SystemTap string literal, which like C uses `\` to escape `"`
+/// and `\` itself.  Returns `None` if `value` contains control characters, which have no place in
+/// the path of a binary.
+fn escape_stap_string(value: &str) -> Option<String> {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => return None,
+            c => escaped.push(c),
+        }
+    }
+
+    Some(escaped)
+}
+
+/// Runs SystemTap's `dtrace` tool with `args`, failing with a helpful error if it's not installed
+fn run_dtrace(args: &[&std::ffi::OsStr]) -> TracersResult<()> {
+    let output = Command::new("dtrace").args(args).output().map_err(|e| {
+        if e.kind() == ErrorKind::NotFound {
+            TracersError::native_code_generation_error(
+                "Running `dtrace`; the `stap-dtrace` feature requires SystemTap's `dtrace` tool, usually in the `systemtap-sdt-dev` or `systemtap-sdt-devel` package",
+                e,
+            )
+        } else {
+            TracersError::native_code_generation_error("Running `dtrace`", e)
+        }
+    })?;
+
+    if !output.status.success() {
+        return Err(TracersError::native_code_generation_error(
+            format!("Running `dtrace` with args {:?}", args),
+            format_err!(
+                "`dtrace` failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            )
+            .compat(),
+        ));
+    }
+
+    Ok(())
+}
+
 pub(crate) struct StapNativeCodeGenerator {
     out_dir: PathBuf,
     provider: ProviderSpecification,
+    use_dtrace: bool,
 }
 
 impl StapNativeCodeGenerator {
-    pub fn new(out_dir: &Path, provider: ProviderSpecification) -> StapNativeCodeGenerator {
+    pub fn new(
+        out_dir: &Path,
+        provider: ProviderSpecification,
+        use_dtrace: bool,
+    ) -> StapNativeCodeGenerator {
         StapNativeCodeGenerator {
             out_dir: out_dir.to_owned(),
             provider,
+            use_dtrace,
         }
     }
+
+    /// Writes the provider's `.d` file to the build directory, and runs `dtrace -h` and `dtrace
+    /// -G` on it to generate a header declaring the probes and an object file defining their
+    /// semaphores.  Returns the paths of the header and the object file.
+    fn generate_dtrace_files(&self, code_dir: &Path) -> TracersResult<(PathBuf, PathBuf)> {
+        let name = self.provider.name_with_hash();
+        let definition_path = code_dir.join(format!("{}.d", name));
+        let header_path = code_dir.join(format!("{}.h", name));
+        let object_path = code_dir.join(format!("{}.o", name));
+
+        dtrace::write_provider_definition(&self.provider, &definition_path)?;
+
+        run_dtrace(&[
+            "-h".as_ref(),
+            "-s".as_ref(),
+            definition_path.as_os_str(),
+            "-o".as_ref(),
+            header_path.as_os_str(),
+        ])?;
+        run_dtrace(&[
+            "-G".as_ref(),
+            "-s".as_ref(),
+            definition_path.as_os_str(),
+            "-o".as_ref(),
+            object_path.as_os_str(),
+        ])?;
+
+        Ok((header_path, object_path))
+    }
 }
 
 impl NativeCodeGenerator for StapNativeCodeGenerator {
     fn generate_native_lib(&self) -> TracersResult<Vec<NativeLib>> {
-        let wrapper_code = NativeProviderWrapperTemplate::from_provider_spec(&self.provider)
-            .render()
-            .map_err(|e| {
-                TracersError::native_code_generation_error("Rendering native wrapper template", e)
-            })?;
-
         let code_dir = self.build_dir();
         fs::create_dir_all(&code_dir).map_err(|e| {
             TracersError::native_code_generation_error("Creating build directory", e)
         })?;
+
+        let dtrace_files = if self.use_dtrace {
+            Some(self.generate_dtrace_files(&code_dir)?)
+        } else {
+            None
+        };
+        let dtrace_header = dtrace_files.as_ref().map(|(header_path, _)| {
+            header_path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        });
+
+        let wrapper_code =
+            NativeProviderWrapperTemplate::from_provider_spec(&self.provider, dtrace_header)
+                .render()
+                .map_err(|e| {
+                    TracersError::native_code_generation_error(
+                        "Rendering native wrapper template",
+                        e,
+                    )
+                })?;
+
         let code_path = code_dir.join(format!("{}.cpp", self.provider.name_with_hash()));
 
         let mut file = File::create(&code_path).map_err(|e| {
@@ -76,29 +315,50 @@ impl NativeCodeGenerator for StapNativeCodeGenerator {
         let lib_name = self.provider.name_with_hash();
         let lib_path = lib_dir.join(&lib_name);
 
-        cc::Build::new()
+        let mut build = cc::Build::new();
+        build
             .cpp(true)
             .cpp_link_stdlib(None) //The wrapper code doesn't use any of the C++ std lib
             .cargo_metadata(false) //Don't instruct cargo to link this lib
             .static_flag(true)
             .out_dir(&lib_dir)
-            .file(code_path)
-            .try_compile(&lib_name)
-            .map_err(|e| {
-                //Unfortunately, the type `cc::Error` does not implement `std::error::Error` for
-                //some reason, so we have to special-case it here
-                let error = format_err!("{:?}", e).compat();
-                TracersError::native_code_generation_error(
-                    "Compiling native wrapper library",
-                    error,
-                )
-            })?;
+            .file(code_path);
+
+        if let Some((_, object_path)) = dtrace_files {
+            //The object file generated by `dtrace -G` defines the probes' semaphores, so it goes in
+            //the same static lib as the wrappers
+            build.include(&code_dir).object(object_path);
+        }
+
+        build.try_compile(&lib_name).map_err(|e| {
+            //Unfortunately, the type `cc::Error` does not implement `std::error::Error` for
+            //some reason, so we have to special-case it here
+            let error = format_err!("{:?}", e).compat();
+            TracersError::native_code_generation_error("Compiling native wrapper library", error)
+        })?;
 
         println!("Compiled native wrapper library {}", lib_path.display());
 
+        let tapset = NativeProviderTapsetTemplate::from_provider_spec(&self.provider)
+            .render()
+            .map_err(|e| {
+                TracersError::native_code_generation_error("Rendering tapset template", e)
+            })?;
+        let tapset_path = lib_dir.join(format!("{}.stp", self.provider.name_with_hash()));
+        fs::write(&tapset_path, tapset).map_err(|e| {
+            TracersError::native_code_generation_error(
+                format!("Writing tapset {}", tapset_path.display()),
+                e,
+            )
+        })?;
+
         Ok(vec![
             NativeLib::StaticWrapperLib(lib_name),
             NativeLib::StaticWrapperLibPath(lib_dir),
+            NativeLib::Tapset {
+                provider_name: self.provider.name().to_owned(),
+                path: tapset_path,
+            },
         ])
     }
 
@@ -106,3 +366,132 @@ impl NativeCodeGenerator for StapNativeCodeGenerator {
         &self.out_dir
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::TracerAttributeArgs;
+    use crate::testdata;
+    use quote::quote;
+
+    fn provider() -> ProviderSpecification {
+        ProviderSpecification::from_token_stream(
+            testdata::TEST_CRATE_NAME,
+            TracerAttributeArgs::default(),
+            quote! {
+                trait TapsetProbes {
+                    fn probe0(count: u32, name: &str, #[probe(encoding = "ptr_len")] path: &str);
+                    fn probe1(next: u64);
+                }
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn binds_args_by_name() {
+        let provider = provider();
+        let template = NativeProviderTapsetTemplate::from_provider_spec(&provider);
+
+        let bindings: Vec<_> = provider
+            .probes()
+            .iter()
+            .map(|probe| template.get_arg_bindings(probe))
+            .collect();
+        assert_eq!(
+            vec![
+                vec![
+                    "count = $arg1",
+                    "name = user_string($arg2, \"\")",
+                    "path = user_string_n($arg3, $arg4, \"\")",
+                    "path_len = $arg4"
+                ],
+                vec!["next_ = $arg1"]
+            ],
+            bindings
+        );
+
+        let tapset = template.render().unwrap();
+        assert!(
+            tapset.contains("    name = user_string($arg2, \"\")\n"),
+            "{}",
+            tapset
+        );
+        assert!(tapset.contains("probe test_tapset_probes.probe1 = process(\"@BINARY@\").provider(\"test_tapset_probes\").mark(\"probe1\")\n{\n    next_ = $arg1\n}\n"), "{}", tapset);
+    }
+
+    #[test]
+    fn fires_probes_with_dtrace_macros() {
+        let provider = provider();
+        let wrapper = NativeProviderWrapperTemplate::from_provider_spec(
+            &provider,
+            Some("provider.h".to_owned()),
+        )
+        .render()
+        .unwrap();
+
+        assert!(wrapper.contains("#include \"provider.h\""), "{}", wrapper);
+        assert!(
+            wrapper.contains("TEST_TAPSET_PROBES_PROBE1(\n\t    next\n\t);"),
+            "{}",
+            wrapper
+        );
+        assert!(!wrapper.contains("_semaphore"), "{}", wrapper);
+        assert!(!wrapper.contains("STAP_PROBE"), "{}", wrapper);
+
+        let wrapper = NativeProviderWrapperTemplate::from_provider_spec(&provider, None)
+            .render()
+            .unwrap();
+        assert!(wrapper.contains("test_tapset_probes_probe1_semaphore"));
+        assert!(!wrapper.contains("TEST_TAPSET_PROBES_PROBE1"));
+    }
+
+    #[test]
+    fn installs_tapset_for_binary() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let tapset_path = temp_dir.path().join("generated.stp");
+        fs::write(
+            &tapset_path,
+            "probe p.probe0 = process(\"@BINARY@\").mark(\"probe0\") {}\n",
+        )
+        .unwrap();
+
+        let tapset_dir = temp_dir.path().join("tapsets");
+        let installed_path =
+            install_tapset("my_provider", &tapset_path, &tapset_dir, "/usr/bin/my_app").unwrap();
+
+        assert_eq!(tapset_dir.join("my_provider.stp"), installed_path);
+        assert_eq!(
+            "probe p.probe0 = process(\"/usr/bin/my_app\").mark(\"probe0\") {}\n",
+            fs::read_to_string(installed_path).unwrap()
+        );
+    }
+
+    #[test]
+    fn installs_tapset_for_binary_with_quotes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let tapset_path = temp_dir.path().join("generated.stp");
+        fs::write(
+            &tapset_path,
+            "probe p.probe0 = process(\"@BINARY@\").mark(\"probe0\") {}\n",
+        )
+        .unwrap();
+
+        let tapset_dir = temp_dir.path().join("tapsets");
+        let installed_path = install_tapset(
+            "my_provider",
+            &tapset_path,
+            &tapset_dir,
+            r#"/opt/my "app"\bin"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            r#"probe p.probe0 = process("/opt/my \"app\"\\bin").mark("probe0") {}"#.to_owned()
+                + "\n",
+            fs::read_to_string(installed_path).unwrap()
+        );
+
+        assert!(install_tapset("my_provider", &tapset_path, &tapset_dir, "my\napp").is_err());
+    }
+}
@@ -5,8 +5,8 @@
 
 use crate::build_rs::BuildInfo;
 use crate::gen::common;
-use crate::spec::ProbeCallSpecification;
-use crate::{TracersResult, TracingTarget};
+use crate::spec::{ProbeCallDetails, ProbeCallSpecification};
+use crate::{TracersError, TracersResult, TracingTarget};
 use proc_macro2::TokenStream;
 use quote::{quote, quote_spanned};
 use syn::spanned::Spanned;
@@ -20,6 +20,8 @@ pub(crate) fn generate_probe_call(
 
     match call {
         ProbeCallSpecification::FireOnly(details) => {
+            reject_instance(&details)?;
+
             match build_info.implementation.tracing_target() {
                 TracingTarget::Disabled => {
                     //When tracing is disabled there is no actual implementation, and each of the
@@ -37,111 +39,177 @@ pub(crate) fn generate_probe_call(
                 }
                 target @ TracingTarget::NoOp
                 | target @ TracingTarget::Stap
-                | target @ TracingTarget::Lttng => {
-                    //There is a low-level wrapper function with the same name as the probe, in the
-                    //impl module for the trait.
+                | target @ TracingTarget::Lttng
+                | target @ TracingTarget::ChromeTrace
+                | target @ TracingTarget::Ctf => {
+                    //There is a low-level fire function named after the probe, in the impl module
+                    //for the trait.
                     //Need to rewrite the path to the provider trait, replacing the trait with the
-                    //name of its corresponding impl mod.  Then create wrappers for each of the
-                    //arguments before passing them to the impl mod.
-                    let mut mod_path = details.provider.clone();
-                    let (provider, _) = mod_path
-                        .segments
-                        .pop()
-                        .expect("provider path can't be empty")
-                        .into_tuple();
-                    let mod_name = syn::Ident::new(
-                        &common::get_provider_impl_mod_name(&provider.ident),
-                        provider.span(),
+                    //name of its corresponding impl mod.
+                    let mod_path = common::get_provider_impl_mod_path(&details.provider);
+
+                    let conditional_expression =
+                        generate_enabled_expression(&target, &mod_path, &details.probe.ident);
+
+                    //The fire function generated by the `tracer` proc macro takes the probe args
+                    //as-is, and takes care of lowering and wrapping them before passing them to the
+                    //native probe.  It's only called when the probe is enabled, so the arguments,
+                    //which are arbitrary Rust expressions, are only evaluated then
+                    let fire_func = syn::Ident::new(
+                        &format!("__{}_fire", details.probe.ident),
+                        details.probe.span(),
                     );
-                    mod_path.segments.push(mod_name.into());
-
-                    let probe = &details.probe;
-
-                    let conditional_expression = match target {
-                        TracingTarget::NoOp => {
-                            //No-op always hard-codes the condition to `false`, which the compiler will
-                            //optimize away to nothing
-                            quote! { false }
-                        }
-                        TracingTarget::Stap | TracingTarget::Lttng => {
-                            //All of the "real" implementations have a `..._enabled` function
-                            let func_name = syn::Ident::new(
-                                &format!("{}_enabled", &details.probe.ident),
-                                details.probe.span(),
-                            );
-
-                            //TODO: if the `unlikely` intrinsic is ever stabilized, use that here so
-                            //the optimizer knows this will be false most of the time
-                            quote! { unsafe { #mod_path::#func_name() } }
-                        }
-                        _ => unreachable!(),
-                    };
-
-                    //For each argument, which is some arbitrary Rust expression, generate a
-                    //variable name which will be used to hold the wrapper for that argument
-                    let args_with_var_names: Vec<_> = details
-                        .args
-                        .iter()
-                        .enumerate()
-                        .map(|(index, arg)| {
-                            let span = arg.span();
-                            let arg_name = syn::Ident::new(&format!("__tracer_arg{}", index), span);
-
-                            (arg, arg_name)
-                        })
-                        .collect();
-
-                    //Generate the `let` statement assigning those variables to the wrapped
-                    //versions of each probe argument
-                    let wrapped_var_names = common::generate_tuple(
-                        args_with_var_names.iter().map(|(_, arg_name)| arg_name),
+                    let args = &details.args;
+                    let probe_ident = &details.probe.ident;
+                    let stats = common::get_probe_stats_path(&mod_path, probe_ident);
+                    let stats_hit = common::generate_probe_stats_hit(build_info, &stats);
+                    let fire = common::generate_counted_fire(
+                        build_info,
+                        &stats,
+                        quote! { #mod_path::#fire_func(#(#args),*); },
                     );
-                    let arg_names: Vec<_> =
-                        args_with_var_names.iter().map(|(arg, _)| arg).collect();
-
-                    let probe_parameters: Vec<_> = args_with_var_names
-                        .iter()
-                        .map(|(_, arg_name)| {
-                            quote! { #arg_name.as_c_type() }
-                        })
-                        .collect();
-
-                    //If there are any arguments, wrap them in the ProbeArgWrapper using the helper
-                    //function generated by the `tracer` proc macro
-                    let wrap_statement = if !details.args.is_empty() {
-                        let wrap_func = syn::Ident::new(
-                            &format!("__{}_wrap", details.probe.ident),
-                            details.probe.span(),
-                        );
-
-                        quote! { let (#wrapped_var_names) = #mod_path::#wrap_func(#(#arg_names),*); }
-                    } else {
-                        quote! {}
-                    };
-
-                    let unsafe_block = if target == TracingTarget::NoOp {
-                        //No unsafe block is needed and using one just triggers a warning
-                        quote! {}
-                    } else {
-                        //'real' impls call unsafe extern functions
-                        quote! { unsafe }
-                    };
+
+                    //A throttled probe may still decline to fire once it's found to be enabled,
+                    //which also spares evaluating the args
+                    let throttle_func = common::get_probe_throttle_func_ident(probe_ident);
 
                     let span = details.call.span();
                     Ok(quote_spanned! {span=>
                         {
-                            use ::tracers::runtime::ProbeArgWrapper as _;
+                            #stats_hit
+                            if #conditional_expression && #mod_path::#throttle_func() {
+                                #fire
+                            }
+                        }
+                    })
+                }
+            }
+        }
+        ProbeCallSpecification::FireWithCode { .. } => unimplemented!(),
+    }
+}
+
+/// With static tracing each provider is declared at compile time, so there are no provider
+/// instances to fire probes on
+fn reject_instance(details: &ProbeCallDetails) -> TracersResult<()> {
+    match &details.instance {
+        None => Ok(()),
+        Some(instance) => Err(TracersError::invalid_call_expression(
+            "Firing probes on a provider instance requires dynamic tracing",
+            instance,
+        )),
+    }
+}
+
+/// Generates the expansion of `probe_span!`, which fires the enter probe of a span probe and
+/// evaluates to a guard which fires the exit probe when dropped.  See
+/// `common::generate_span_guard` for the details.
+pub(crate) fn generate_probe_span_call(
+    build_info: &BuildInfo,
+    call: ProbeCallSpecification,
+) -> TracersResult<TokenStream> {
+    //It's a bug to use this function to generate code for a dynamic implementation
+    assert!(!build_info.implementation.is_dynamic());
 
-                            if #conditional_expression {
-                                #wrap_statement
+    match call {
+        ProbeCallSpecification::FireOnly(details) => {
+            reject_instance(&details)?;
+            details.check_span_args()?;
+
+            let (enter_ident, exit_ident) = common::get_span_probe_idents(&details.probe.ident);
+            let span = details.call.span();
 
-                                #unsafe_block { #mod_path::#probe(#(#probe_parameters),*); }
+            match build_info.implementation.tracing_target() {
+                TracingTarget::Disabled => {
+                    //As with `probe!` the probe methods are still called, so the compiler checks
+                    //the args and doesn't warn about unused methods.  There's no runtime to
+                    //provide a guard, so the guard is a local unit struct, which is nothing at all
+                    //at runtime.
+                    let provider = &details.provider;
+                    let args = &details.args;
+                    Ok(quote_spanned! {span=>
+                        {
+                            if false {
+                                #[allow(deprecated)]
+                                {
+                                    #provider::#enter_ident(#(#args),*);
+                                    #provider::#exit_ident(#(#args,)* 0);
+                                }
                             }
+
+                            struct DisabledSpanGuard;
+                            DisabledSpanGuard
+                        }
+                    })
+                }
+                target @ TracingTarget::NoOp
+                | target @ TracingTarget::Stap
+                | target @ TracingTarget::Lttng
+                | target @ TracingTarget::ChromeTrace
+                | target @ TracingTarget::Ctf => {
+                    let mod_path = common::get_provider_impl_mod_path(&details.provider);
+                    let enter_enabled =
+                        generate_enabled_expression(&target, &mod_path, &enter_ident);
+                    let exit_enabled = generate_enabled_expression(&target, &mod_path, &exit_ident);
+
+                    let enter_stats = common::get_probe_stats_path(&mod_path, &enter_ident);
+                    let exit_stats = common::get_probe_stats_path(&mod_path, &exit_ident);
+                    let enter_hit = common::generate_probe_stats_hit(build_info, &enter_stats);
+                    let exit_hit = common::generate_probe_stats_hit(build_info, &exit_stats);
+                    let guard = common::generate_span_guard(
+                        build_info,
+                        &mod_path,
+                        &details.probe.ident,
+                        &details.args,
+                        span,
+                        (enter_enabled, quote! {}, enter_stats),
+                        (exit_enabled, quote! {}, exit_stats),
+                    );
+
+                    Ok(quote_spanned! {span=>
+                        {
+                            #enter_hit
+                            #exit_hit
+                            #guard
                         }
                     })
                 }
             }
         }
-        ProbeCallSpecification::FireWithCode { .. } => unimplemented!(),
+        ProbeCallSpecification::FireWithCode { statements, .. } => {
+            Err(TracersError::invalid_call_expression(
+                "`probe_span!` takes a single call to a span probe, not a block of code",
+                statements,
+            ))
+        }
+    }
+}
+
+/// Generates the expression which tests if a probe is enabled.  For `NoOp` that's always `false`,
+/// and for the real implementations it calls the probe's `..._enabled` function
+fn generate_enabled_expression(
+    target: &TracingTarget,
+    mod_path: &syn::Path,
+    probe_ident: &syn::Ident,
+) -> TokenStream {
+    match target {
+        TracingTarget::NoOp => {
+            //No-op always hard-codes the condition to `false`, which the compiler will
+            //optimize away to nothing
+            quote! { false }
+        }
+        TracingTarget::Stap
+        | TracingTarget::Lttng
+        | TracingTarget::ChromeTrace
+        | TracingTarget::Ctf => {
+            //All of the "real" implementations have a `..._enabled` function
+            let func_name =
+                syn::Ident::new(&format!("{}_enabled", probe_ident), probe_ident.span());
+
+            //TODO: if the `unlikely` intrinsic is ever stabilized, use that here so
+            //the optimizer knows this will be false most of the time
+            quote! { unsafe { #mod_path::#func_name() } }
+        }
+        TracingTarget::Disabled => unreachable!(),
     }
 }
@@ -6,14 +6,17 @@
 //! module.  When there is target-specific logic, it is selected based on the `BuildInfo` in effect
 //! at the time of the code generation
 use crate::build_rs::BuildInfo;
-use crate::gen::common::{self, ProbeGeneratorBase, ProviderTraitGeneratorBase};
+use crate::gen::common::{ProbeGeneratorBase, ProviderTraitGeneratorBase};
 use crate::gen::r#static::native_code::{self, ProcessedProviderTrait};
 use crate::gen::NativeLib;
-use crate::spec::{ProbeArgSpecification, ProbeSpecification, ProviderSpecification};
-use crate::TracersResult;
+use crate::spec::{
+    NativeArgSpecification, ProbeArgSpecification, ProbeSpecification, ProviderSpecification,
+    SpanEvent,
+};
 use crate::TracingImplementation;
+use crate::{TracersError, TracersResult};
 use crate::{TracingTarget, TracingType};
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, quote_spanned};
 use std::borrow::Cow;
 use syn::spanned::Spanned;
@@ -39,7 +42,7 @@ impl<'bi> ProviderTraitGenerator<'bi> {
     pub fn new(
         build_info: &'bi BuildInfo,
         spec: ProviderSpecification,
-    ) -> ProviderTraitGenerator<'bi> {
+    ) -> TracersResult<ProviderTraitGenerator<'bi>> {
         //This implementation is specific to static tracing (of which `disabled` is merely a
         //special case)
         assert!(!build_info.implementation.is_dynamic());
@@ -50,9 +53,27 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         //information left behind from `build.rs` telling us where to find the generated C wrapper
         //and the generated Rust bindings for that wrapper.  This isn't generated for all targets,
         //and if generation fails it shouldn't cause a compile error but rather it should cause us
-        //to fall back to the NoOp generator for this provider
-        let processed_provider = if build_info.implementation.tracing_target().is_enabled() {
+        //to fall back to the NoOp generator for this provider, unless strict mode says otherwise
+        let processed_provider = if build_info.implementation.tracing_target().has_native_lib() {
             match native_code::get_processed_provider_info(&spec) {
+                Err(e) if build_info.strict_enabled() => {
+                    //The not-processed error's message says tracing will be disabled, which isn't
+                    //the case here, so report its cause instead
+                    let cause = match e {
+                        TracersError::ProviderTraitNotProcessedError { error, .. } => {
+                            error.to_string()
+                        }
+                        e => e.to_string(),
+                    };
+                    return Err(TracersError::invalid_provider(
+                        format!(
+                            "strict mode is enabled, but the native code for '{}' wasn't generated: {}\nEither `tracers_build::build()` wasn't called from `build.rs`, the module declaring this provider couldn't be found from the crate root, or its native code failed to compile",
+                            spec.ident(),
+                            cause
+                        ),
+                        &spec.item_trait().ident,
+                    ));
+                }
                 Err(e) => {
                     eprintln!("Warning: {}", e);
 
@@ -65,8 +86,8 @@ impl<'bi> ProviderTraitGenerator<'bi> {
                 Ok(processed_provider) => Some(processed_provider),
             }
         } else {
-            //Else the implementation isn't 'real' it's `Disabled` so no need to look for the
-            //processed provider info
+            //Else the implementation isn't 'real' it's `Disabled`, or it doesn't have a native lib,
+            //so no need to look for the processed provider info
             None
         };
 
@@ -74,12 +95,12 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         //wrap in our own ProbeGenerator
         let (spec, probes) = spec.separate_probes();
         let probes: Vec<_> = probes.into_iter().map(ProbeGenerator::new).collect();
-        ProviderTraitGenerator {
+        Ok(ProviderTraitGenerator {
             build_info,
             spec,
             processed_provider,
             probes,
-        }
+        })
     }
 
     pub fn generate(&self) -> TracersResult<TokenStream> {
@@ -106,8 +127,10 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         // From the probe specifications, generate the corresponding methods that will be on the probe
         // struct.
         let mut probe_methods: Vec<TokenStream> = Vec::new();
+        let mod_name = self.get_provider_impl_mod_name();
         for probe in self.probes.iter() {
             probe_methods.push(probe.generate_trait_methods(self)?);
+            probe_methods.push(probe.generate_stats_method(&self.build_info, &mod_name));
         }
 
         // Re-generate the trait method that we took as input, with the modifications to support
@@ -125,19 +148,21 @@ impl<'bi> ProviderTraitGenerator<'bi> {
         let try_init_decl = self.generate_try_init_decl();
 
         //the __try_init_provider returns a Result.  In this static implementation, we'll hard-code
-        //a successful result, with a string containing some metadata about the generated provider.
-        //Only dynamic implementations can actually fail to initialize, which doesn't apply here
-        let provider_name = self.spec.name();
-
+        //a successful result, with some metadata about the generated provider.  Only dynamic
+        //implementations can actually fail to initialize, which doesn't apply here
         let implementation = match self.build_info.implementation.tracing_target() {
             TracingTarget::Disabled => TracingType::Disabled.as_ref().to_string(),
-            TracingTarget::NoOp | TracingTarget::Stap | TracingTarget::Lttng => format!(
+            TracingTarget::NoOp
+            | TracingTarget::Stap
+            | TracingTarget::Lttng
+            | TracingTarget::ChromeTrace
+            | TracingTarget::Ctf => format!(
                 "{}/{}",
                 self.build_info.implementation.tracing_type().as_ref(),
                 self.build_info.implementation.as_ref()
             ),
         };
-        let version = env!("CARGO_PKG_VERSION");
+        let provider_info = self.generate_provider_info(&self.probes, &implementation);
 
         let result = quote_spanned! {span=>
             #(#attrs)*
@@ -148,7 +173,7 @@ impl<'bi> ProviderTraitGenerator<'bi> {
                 #(#probe_methods)*
 
                 #try_init_decl {
-                    Ok(concat!(#provider_name, "::", #implementation, "::", #version))
+                    Ok(#provider_info)
                 }
             }
         };
@@ -172,11 +197,26 @@ impl<'bi> ProviderTraitGenerator<'bi> {
             .probes
             .iter()
             .map(ProbeGenerator::generate_wrapper_func);
+        let fire_funcs = self.probes.iter().map(|p| p.generate_fire_func(&self));
+        let registered_enabled_funcs = self
+            .probes
+            .iter()
+            .map(|p| p.generate_registered_enabled_func(&self));
+        let stats_statics = self
+            .probes
+            .iter()
+            .map(|p| p.generate_stats_static(&self.build_info));
+        let throttle_funcs = self
+            .probes
+            .iter()
+            .map(ProbeGenerator::generate_throttle_funcs);
 
         // These imports aren't always used but it's easier to always import than to detect when
-        // probe arg types need `libc`
+        // probe arg types need `libc`, or types which the provider trait's module imported
         let mod_imports = if self.build_info.implementation.is_enabled() {
             quote! {
+                #[allow(unused_imports)]
+                use super::*;
                 #[allow(unused_imports)]
                 use ::tracers::runtime::libc;
             }
@@ -185,6 +225,10 @@ impl<'bi> ProviderTraitGenerator<'bi> {
             quote! {}
         };
 
+        //The registry is part of the `tracers::runtime`, so this is only used when tracing is
+        //enabled
+        let registration = self.generate_registration(&self.probes);
+
         match self.build_info.implementation.tracing_target() {
             TracingTarget::Disabled => {
                 //When tracing is disabled we can't assume the `tracers::runtime` is available to
@@ -207,7 +251,43 @@ impl<'bi> ProviderTraitGenerator<'bi> {
 
                         #(#wrapper_funcs)*
 
+                        #(#throttle_funcs)*
+
+                        #(#fire_funcs)*
+
+                        #(#native_declarations)*
+
+                        #(#registered_enabled_funcs)*
+
+                        #(#stats_statics)*
+
+                        #registration
+                    }
+                }
+            }
+            TracingTarget::ChromeTrace | TracingTarget::Ctf => {
+                //Like `NoOp`, the "native" functions are Rust functions, but these ones write the
+                //probe firings to the Chrome or CTF trace, and the probes are enabled whenever the
+                //trace is being written
+                quote_spanned! {span=>
+                    #vis mod #mod_name {
+                        #mod_imports
+
+                        #(#wrapper_funcs)*
+
+                        #(#throttle_funcs)*
+
+                        #(#fire_funcs)*
+
+                        #(#is_enabled_funcs)*
+
                         #(#native_declarations)*
+
+                        #(#registered_enabled_funcs)*
+
+                        #(#stats_statics)*
+
+                        #registration
                     }
                 }
             }
@@ -252,8 +332,18 @@ impl<'bi> ProviderTraitGenerator<'bi> {
 
                         #(#wrapper_funcs)*
 
+                        #(#throttle_funcs)*
+
+                        #(#fire_funcs)*
+
                         #(#is_enabled_funcs)*
 
+                        #(#registered_enabled_funcs)*
+
+                        #(#stats_statics)*
+
+                        #registration
+
                         #link_attr
                         extern "C" {
                             #(#native_declarations)*
@@ -357,49 +447,72 @@ impl ProbeGenerator {
                     #(#args)*
                 })
             }
-            target @ TracingTarget::NoOp
-            | target @ TracingTarget::Stap
-            | target @ TracingTarget::Lttng => {
+            TracingTarget::NoOp
+            | TracingTarget::Stap
+            | TracingTarget::Lttng
+            | TracingTarget::ChromeTrace
+            | TracingTarget::Ctf => {
                 //This is a `real` impl with a C wrapper underneath (or in the case of `noop` a
                 //Rust function with the same signature as a C wrapper).
                 //The implementation is in the impl mod, with each probe as a function named the
                 //same as the original probe method declaration, but taking as arguments the C
-                //version of each parameter (although obviously declared as the Rust equivalent).
+                //version of each native arg (although obviously declared as the Rust equivalent).
                 //
                 //Thus, there's no practical need for this method, other than to ensure if a user
                 //mis-uses the probing library and tries to call the probe method directly, it
                 //actually works (but they will still get a warning as this is not a very
-                //performant way to fire probes)
+                //performant way to fire probes).  It calls the same fire function in the impl mod
+                //that the `probe!` macro uses.
                 let mod_name = provider.get_provider_impl_mod_name();
-                let probe_name = &self.spec.method_name;
-                let wrap_args = self.spec.args.iter().map(|arg| {
-                    let arg_name = arg.ident();
-
-                    quote! { let #arg_name = ::tracers::runtime::wrap(#arg_name); }
-                });
+                let fire_func = self.fire_func_ident();
                 let arg_names = self.spec.args.iter().map(ProbeArgSpecification::ident);
 
-                let unsafe_block = if target == TracingTarget::NoOp {
-                    //No unsafe block is needed and using one just triggers a warning
-                    quote! {}
-                } else {
-                    //'real' impls call unsafe extern functions
-                    quote! { unsafe }
-                };
-
                 Ok(quote_spanned! {span=>
-                    // The compiler warns on this import as unused, even though without this trait
-                    // imported the use of `as_c_type()` will fail
-                    #[allow(unused_imports)]
-                    use ::tracers::runtime::ProbeArgWrapper as _;
-
-                    #(#wrap_args)*
-                    #unsafe_block { #mod_name::#probe_name(#(#arg_names.as_c_type()),*); }
+                    #mod_name::#fire_func(#(#arg_names),*);
                 })
             }
         }
     }
 
+    /// Generates the function in the impl mod which fires the probe.  It takes the probe's
+    /// arguments as declared in the trait, lowers them into native args, wraps each of those in
+    /// its `ProbeArgWrapper`, and passes the resulting C values to the "native" probe function.
+    ///
+    /// It's `#[inline(always)]` because it's only ever called after the probe has been found to be
+    /// enabled, and the `probe!` macro ensures that's the only time the args are evaluated.
+    fn generate_fire_func(&self, provider: &ProviderTraitGenerator) -> TokenStream {
+        let func_name = self.fire_func_ident();
+        let function_type_params = self.generate_fn_lifetime_parameters();
+        let args = self.generate_fn_args_with_lifetimes();
+        let wrap_statement = self.generate_wrap_statement();
+        let native_arg_values = self.generate_native_arg_values();
+        let probe_name = &self.spec.method_name;
+
+        let unsafe_block =
+            if provider.build_info.implementation.tracing_target() == TracingTarget::NoOp {
+                //No unsafe block is needed and using one just triggers a warning
+                quote! {}
+            } else {
+                //'real' impls call unsafe extern functions
+                quote! { unsafe }
+            };
+
+        let span = self.spec.original_method.span();
+        quote_spanned! {span=>
+            #[inline(always)]
+            #[allow(clippy::needless_lifetimes)] //if there's only one lifetime clippy triggers this lint
+            pub fn #func_name #function_type_params (#(#args),*) {
+                // The compiler warns on this import as unused, even though without this trait
+                // imported the use of `as_c_type()` will fail
+                #[allow(unused_imports)]
+                use ::tracers::runtime::ProbeArgWrapper as _;
+
+                #wrap_statement
+                #unsafe_block { self::#probe_name(#(#native_arg_values),*); }
+            }
+        }
+    }
+
     /// Generates the declaration for the "native" C++ functions which fire the probes using
     /// whatever the platform's tracing system is.  Depending upon the target, this generates one
     /// of two possible things:
@@ -440,7 +553,7 @@ impl ProbeGenerator {
             .build_info
             .implementation
             .tracing_target()
-            .is_enabled();
+            .has_native_lib();
         let provider_name_with_hash = provider.spec.name_with_hash();
 
         let native_func_name = format!("{}_{}", provider_name_with_hash, self.spec.name);
@@ -451,7 +564,8 @@ impl ProbeGenerator {
         };
         let func_ident = &self.spec.method_name;
 
-        let args = self.spec.args.iter().map(|arg| {
+        let native_args = self.spec.native_args();
+        let args = native_args.iter().map(|arg| {
             let arg_name = arg.ident();
             let rust_typ: syn::Type = syn::parse_str(arg.arg_type_info().get_rust_type_str())
                 .unwrap_or_else(|_| {
@@ -467,12 +581,17 @@ impl ProbeGenerator {
             }
         });
 
+        let target = provider.build_info.implementation.tracing_target();
         let func_body = if is_real {
             quote! { ; }
+        } else if target == TracingTarget::ChromeTrace {
+            self.generate_chrome_trace_write(provider)
+        } else if target == TracingTarget::Ctf {
+            self.generate_ctf_write(provider)
         } else {
             //The dummy no-op impl just pro-forma uses each argument to avoid a warning about
             //unused arguments
-            let args_use = self.spec.args.iter().map(|arg| {
+            let args_use = native_args.iter().map(|arg| {
                 let arg_name = arg.ident();
 
                 let span = arg.ident().span();
@@ -488,10 +607,175 @@ impl ProbeGenerator {
             }
         };
 
+        //The Chrome and CTF traces read strings from the pointers they're passed, just like a
+        //native probe
+        let unsafe_token = if target.is_runtime_trace() {
+            quote! { unsafe }
+        } else {
+            quote! {}
+        };
+
         let span = self.spec.original_method.span();
         quote_spanned! {span=>
             #func_attrs
-            pub fn  #func_ident( #(#args),* ) #func_body
+            #[allow(clippy::too_many_arguments)] //some probe args are lowered into several native args
+            pub #unsafe_token fn  #func_ident( #(#args),* ) #func_body
+        }
+    }
+
+    /// Generates the body of the function which writes the probe's firing to the Chrome trace.
+    /// Each probe arg is written with its own name.  Where an arg is lowered into more than one
+    /// native arg, like a `u128` passed as two halves, each of those is written with its native
+    /// name instead, except for the length of a string passed as a pointer and a length, which is
+    /// written as one string.
+    fn generate_chrome_trace_write(&self, provider: &ProviderTraitGenerator) -> TokenStream {
+        let provider_name = provider.spec.name();
+        let (name, phase) = match self.spec.span_event {
+            None => (self.spec.name.as_str(), quote! { Instant }),
+            Some(SpanEvent::Enter) => (self.spec.span_name().unwrap(), quote! { Begin }),
+            Some(SpanEvent::Exit) => (self.spec.span_name().unwrap(), quote! { End }),
+        };
+
+        let mut args: Vec<(&str, TokenStream)> = Vec::new();
+        for arg in self.spec.args.iter() {
+            let native_args = arg.native_args();
+            let values: Vec<_> = native_args
+                .iter()
+                .filter(|native_arg| {
+                    !native_args
+                        .iter()
+                        .any(|other| other.length_arg() == Some(native_arg.name()))
+                })
+                .map(|native_arg| {
+                    (
+                        native_arg.name(),
+                        Self::generate_arg_value(native_arg, native_args),
+                    )
+                })
+                .collect();
+
+            if values.len() == 1 {
+                args.push((arg.name(), values.into_iter().next().unwrap().1));
+            } else {
+                args.extend(values);
+            }
+        }
+        if let Some(suppressed_arg) = self.spec.suppressed_arg() {
+            args.push((
+                suppressed_arg.name(),
+                Self::generate_arg_value(suppressed_arg, &[]),
+            ));
+        }
+
+        let arg_names = args.iter().map(|(name, _)| name);
+        let arg_values = args.iter().map(|(_, value)| value);
+
+        quote! {
+            {
+                use ::tracers::runtime::chrome_trace::{self, Phase};
+                use ::tracers::runtime::ArgValue;
+
+                chrome_trace::write_event(
+                    #provider_name,
+                    #name,
+                    Phase::#phase,
+                    &[#((#arg_names, #arg_values)),*]
+                );
+            }
+        }
+    }
+
+    /// Generates the body of the function which writes the probe's firing to the CTF trace.  The
+    /// probe is declared as a static `Event` with a field for each native arg, named and typed
+    /// like the native arg, except that a pointer to a string passed with its length is a sequence
+    /// of bytes.  A sequence's length has to be declared before it, so that arg comes first.
+    fn generate_ctf_write(&self, provider: &ProviderTraitGenerator) -> TokenStream {
+        let provider_name = provider.spec.name();
+        let probe_name = &self.spec.name;
+        let level = match self.spec.level {
+            Some(level) => {
+                let level_ident = syn::Ident::new(&format!("{:?}", level), Span::call_site());
+                quote! { Some(::tracers::runtime::ProbeLevel::#level_ident) }
+            }
+            None => quote! { None },
+        };
+
+        //Each native arg paired with the native args of the same probe arg, which is where its
+        //length arg is if it has one
+        let native_args: Vec<(&NativeArgSpecification, &[NativeArgSpecification])> = self
+            .spec
+            .args
+            .iter()
+            .flat_map(|arg| {
+                let siblings = arg.native_args();
+                let (lengths, others): (Vec<_>, Vec<_>) = siblings.iter().partition(|native_arg| {
+                    siblings
+                        .iter()
+                        .any(|arg| arg.length_arg() == Some(native_arg.name()))
+                });
+                lengths
+                    .into_iter()
+                    .chain(others)
+                    .map(move |native_arg| (native_arg, siblings))
+            })
+            .chain(
+                self.spec
+                    .suppressed_arg()
+                    .map(|suppressed_arg| (suppressed_arg, &[][..])),
+            )
+            .collect();
+        let fields = native_args.iter().map(|(native_arg, siblings)| {
+            let name = native_arg.name();
+            let length_arg = native_arg
+                .length_arg()
+                .filter(|length_arg| siblings.iter().any(|arg| arg.name() == *length_arg));
+            if let Some(length_arg) = length_arg {
+                return quote! { Field::sequence(#name, #length_arg) };
+            }
+
+            let c_type = native_arg.arg_type_info().get_c_type_enum();
+            let c_type_ident = syn::Ident::new(&format!("{:?}", c_type), Span::call_site());
+
+            quote! { Field::new(#name, CType::#c_type_ident) }
+        });
+        let values = native_args
+            .iter()
+            .map(|(native_arg, siblings)| Self::generate_arg_value(native_arg, siblings));
+
+        quote! {
+            {
+                use ::tracers::runtime::ArgValue;
+                use ::tracers::runtime::ctf::{self, CType, Event, Field};
+
+                static EVENT: Event = Event::new(
+                    #provider_name,
+                    #probe_name,
+                    #level,
+                    &[#(#fields),*]
+                );
+
+                ctf::write_event(&EVENT, &[#(#values),*]);
+            }
+        }
+    }
+
+    /// Generates the `ArgValue` which a native arg is written to the Chrome or CTF trace as
+    fn generate_arg_value(
+        native_arg: &NativeArgSpecification,
+        siblings: &[NativeArgSpecification],
+    ) -> TokenStream {
+        let ident = native_arg.ident();
+        let length_arg = native_arg
+            .length_arg()
+            .and_then(|name| siblings.iter().find(|arg| arg.name() == name));
+
+        match length_arg {
+            Some(length_arg) => {
+                let length_ident = length_arg.ident();
+                quote! { ArgValue::bytes(#ident, #length_ident) }
+            }
+            None if native_arg.is_hex() => quote! { ArgValue::hex(#ident as u64) },
+            None => quote! { ArgValue::from(#ident) },
         }
     }
 
@@ -512,7 +796,7 @@ impl ProbeGenerator {
             .build_info
             .implementation
             .tracing_target()
-            .is_enabled();
+            .has_native_lib();
         let provider_name = provider.spec.name();
 
         let native_func_name = format!("{}_{}_enabled", provider_name, self.spec.name);
@@ -546,6 +830,10 @@ impl ProbeGenerator {
                     pub static #semaphore_ident: u16 #semaphore_initializer
                 }
             }
+            TracingTarget::ChromeTrace | TracingTarget::Ctf => {
+                //Whether the trace is being written is tested by `generate_rust_is_enabled`
+                quote! {}
+            }
             TracingTarget::Lttng => {
                 //LTTng does not provide a simple semaphore flag, because it uses some fancy RCU
                 //trickery that can't be expressed as an external variable declaration in Rust.
@@ -560,6 +848,34 @@ impl ProbeGenerator {
         }
     }
 
+    /// Generates the function the runtime registry uses to test if this probe is enabled.  It's
+    /// the same test the `probe!` macro makes.
+    fn generate_registered_enabled_func(&self, provider: &ProviderTraitGenerator) -> TokenStream {
+        let func_ident = self.registered_enabled_func_ident();
+        let is_enabled = match provider.build_info.implementation.tracing_target() {
+            TracingTarget::Disabled | TracingTarget::NoOp => quote! { false },
+            TracingTarget::Stap
+            | TracingTarget::Lttng
+            | TracingTarget::ChromeTrace
+            | TracingTarget::Ctf => {
+                let enabled_func = syn::Ident::new(
+                    &format!("{}_enabled", self.spec.name),
+                    self.spec.original_method.span(),
+                );
+
+                quote! { unsafe { self::#enabled_func() } }
+            }
+        };
+
+        let span = self.spec.original_method.span();
+        quote_spanned! {span=>
+            #[allow(unused_unsafe)] //only the LTTng `_enabled` function is an unsafe extern function
+            fn #func_ident() -> bool {
+                #is_enabled
+            }
+        }
+    }
+
     /// Some tracing implementations will use a C++ native function that tests if a probe is
     /// enabled or not.  Others use a static variable that the Rust code can query directly.  In
     /// that latter case, we need to generate a Rust function in the impl mod to query this
@@ -575,6 +891,26 @@ impl ProbeGenerator {
             .has_native_enabled_func()
         {
             quote! {}
+        } else if provider
+            .build_info
+            .implementation
+            .tracing_target()
+            .is_runtime_trace()
+        {
+            //Every probe is enabled while the trace is being written
+            let func_name = format!("{}_enabled", self.spec.name);
+            let func_ident = syn::Ident::new(&func_name, self.spec.original_method.span());
+            let trace_mod = syn::Ident::new(
+                provider.build_info.implementation.tracing_target().as_ref(),
+                Span::call_site(),
+            );
+
+            quote! {
+                #[inline(always)]
+                pub fn #func_ident() -> bool {
+                    ::tracers::runtime::#trace_mod::is_enabled()
+                }
+            }
         } else {
             let func_name = format!("{}_enabled", self.spec.name);
             let func_ident = syn::Ident::new(&func_name, self.spec.original_method.span());
@@ -591,72 +927,6 @@ impl ProbeGenerator {
             }
         }
     }
-
-    /// For the `Disabled` target, each probe will have a corresponding wrapper function called
-    /// `__$PROBENAME_wrap` which takes as input all of the probe's Rust arguments, and returns a
-    /// tuple containing a `ProbeArgWrapper` for each of the arguments.
-    ///
-    /// This is required because at probe firing time we don't have enough information about the
-    /// actual data types of the parameters to avoid ambiguity when invoking the wrapper.
-    fn generate_wrapper_func(&self) -> TokenStream {
-        if self.spec.args.is_empty() {
-            //Don't generate a wrapper if there are no args to wrap
-            quote! {}
-        } else {
-            let func_name = syn::Ident::new(
-                &format!("__{}_wrap", self.spec.name),
-                self.spec.original_method.span(),
-            );
-
-            // If any of the probe arguments are reference types, we need to deal with the mess of
-            // difference reference lifetimes by taking one lifetime parameter for every reference
-            // type used by any of the probe args, and explicitly tie the return wrapper types to
-            // the corresponding lifetimes
-            let lifetime_params = self.args_lifetime_parameters();
-            let function_type_params = if lifetime_params.is_empty() {
-                quote! {}
-            } else {
-                quote! { <#(#lifetime_params),*> }
-            };
-
-            let args = self.spec.args.iter().map(|arg| {
-                let arg_name = arg.ident();
-                let rust_typ = arg.syn_typ_with_lifetimes();
-                let span = arg.ident().span();
-                quote_spanned! {span=>
-                    #arg_name: #rust_typ
-                }
-            });
-
-            let return_types = self.spec.args.iter().map(|arg| {
-                let rust_typ = arg.syn_typ_with_lifetimes();
-                let span = arg.ident().span();
-                quote_spanned! {span=>
-                    <#rust_typ as ::tracers::runtime::ProbeArgType<#rust_typ>>::WrapperType
-                }
-            });
-
-            let return_type = common::generate_tuple(return_types);
-
-            let wrap_expressions = self.spec.args.iter().map(|arg| {
-                let arg_name = arg.ident();
-                let span = arg.ident().span();
-                quote_spanned! {span=>
-                    ::tracers::runtime::wrap(#arg_name)
-                }
-            });
-
-            let result_expression = common::generate_tuple(wrap_expressions);
-
-            let span = self.spec.original_method.span();
-            quote_spanned! {span=>
-                #[allow(clippy::needless_lifetimes)] //if there's only one lifetime clippy triggers this lint
-                pub fn #func_name #function_type_params (#(#args),*) -> #return_type {
-                    #result_expression
-                }
-            }
-        }
-    }
 }
 
 /// It's quite difficult to meaningfully test code generators that use the `quote` crate.  These
@@ -680,6 +950,8 @@ mod test {
             for implementation in vec![
                 TracingImplementation::Disabled,
                 TracingImplementation::StaticNoOp,
+                TracingImplementation::StaticChromeTrace,
+                TracingImplementation::StaticCtf,
             ]
             .into_iter()
             {
@@ -695,7 +967,7 @@ mod test {
 
                 let build_info =
                     BuildInfo::new(testdata::TEST_CRATE_NAME.to_owned(), implementation);
-                let generator = ProviderTraitGenerator::new(&build_info, spec);
+                let generator = ProviderTraitGenerator::new(&build_info, spec).unwrap();
                 generator.generate().unwrap_or_else(|_| {
                     panic!(format!(
                         "Failed to generate test trait '{}'",
@@ -706,6 +978,40 @@ mod test {
         }
     }
 
+    #[test]
+    fn generate_works_with_probe_stats() {
+        for test_case in testdata::get_test_provider_traits(|c: &testdata::TestProviderTrait| {
+            c.expected_error.is_none()
+        })
+        .into_iter()
+        {
+            let (attr, item_trait) = test_case.get_attr_and_item_trait();
+            let spec =
+                ProviderSpecification::from_trait(testdata::TEST_CRATE_NAME, attr, item_trait)
+                    .unwrap();
+
+            let mut build_info = BuildInfo::new(
+                testdata::TEST_CRATE_NAME.to_owned(),
+                TracingImplementation::StaticNoOp,
+            );
+            build_info.probe_stats = true;
+            let generator = ProviderTraitGenerator::new(&build_info, spec).unwrap();
+            let tokens = generator.generate().unwrap_or_else(|_| {
+                panic!("Failed to generate test trait '{}'", test_case.description)
+            });
+
+            if let Some(probe) = test_case.probes.as_ref().and_then(|p| p.first()) {
+                let stats_method = format!("{}_stats", probe.name);
+                assert!(
+                    tokens.to_string().contains(&stats_method),
+                    "test trait '{}' has no `{}` method",
+                    test_case.description,
+                    stats_method
+                );
+            }
+        }
+    }
+
     #[test]
     fn falls_back_to_disabled_on_error() {
         //If the native wrapper generation in `build.rs` failed, should fall back to `NoOp` no
@@ -731,7 +1037,7 @@ mod test {
 
                 let build_info =
                     BuildInfo::new(testdata::TEST_CRATE_NAME.to_owned(), implementation);
-                let generator = ProviderTraitGenerator::new(&build_info, spec);
+                let generator = ProviderTraitGenerator::new(&build_info, spec).unwrap();
                 assert_eq!(
                     TracingImplementation::StaticNoOp,
                     generator.build_info.implementation
@@ -745,4 +1051,38 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn fails_on_error_in_strict_mode() {
+        //Same as above, but in strict mode the missing ProcessedProviderTrait is an error
+        for test_case in testdata::get_test_provider_traits(|c: &testdata::TestProviderTrait| {
+            c.expected_error.is_none()
+        })
+        .into_iter()
+        {
+            let (attr, item_trait) = test_case.get_attr_and_item_trait();
+            let spec =
+                ProviderSpecification::from_trait(testdata::TEST_CRATE_NAME, attr, item_trait)
+                    .unwrap();
+            let trait_name = spec.ident().to_string();
+
+            let mut build_info = BuildInfo::new(
+                testdata::TEST_CRATE_NAME.to_owned(),
+                TracingImplementation::StaticStap,
+            );
+            build_info.strict = true;
+            match ProviderTraitGenerator::new(&build_info, spec) {
+                Ok(_) => panic!(
+                    "test trait '{}' should have failed in strict mode",
+                    test_case.description
+                ),
+                Err(e) => assert!(
+                    e.to_string().contains(&trait_name),
+                    "error for test trait '{}' doesn't name the trait: {}",
+                    test_case.description,
+                    e
+                ),
+            }
+        }
+    }
 }
@@ -22,8 +22,10 @@ mod syn_helpers;
 mod testdata;
 
 //Export some of the internal types from their (private) modules
-pub use build_rs::{build, tracers_build};
+pub use build_rs::{build, tracers_build, BuildFailure, BuildReport, Builder};
+pub use cargo::TargetKind;
 pub use error::*;
+pub use gen::NativeLib;
 
 /// The categories of tracing implementations.  Within `Static` and `Dynamic` there are various
 /// platform-specific implementations, however the behavior of all implementations within a
@@ -55,6 +57,10 @@ pub(crate) enum TracingTarget {
     Lttng,
     #[strum(serialize = "noop")]
     NoOp,
+    #[strum(serialize = "chrome_trace")]
+    ChromeTrace,
+    #[strum(serialize = "ctf")]
+    Ctf,
 }
 
 impl TracingTarget {
@@ -62,6 +68,17 @@ impl TracingTarget {
         *self != TracingTarget::Disabled && *self != TracingTarget::NoOp
     }
 
+    pub fn has_native_lib(&self) -> bool {
+        //The Chrome and CTF traces are written by the `tracers` runtime, so they're the only real
+        //targets which don't need a generated C wrapper library
+        self.is_enabled() && !self.is_runtime_trace()
+    }
+
+    /// True for the targets whose traces are written by the `tracers` runtime itself
+    pub fn is_runtime_trace(&self) -> bool {
+        *self == TracingTarget::ChromeTrace || *self == TracingTarget::Ctf
+    }
+
     pub fn has_native_enabled_func(&self) -> bool {
         //Thus far only LTTng provides a native version of a function to call to test for
         //enablement.  All others use a semaphore variable that can be queried directly
@@ -85,6 +102,15 @@ pub(crate) enum TracingImplementation {
     #[strum(serialize = "static_noop", props(type = "static", target = "noop"))]
     StaticNoOp,
 
+    #[strum(
+        serialize = "static_chrome_trace",
+        props(type = "static", target = "chrome_trace")
+    )]
+    StaticChromeTrace,
+
+    #[strum(serialize = "static_ctf", props(type = "static", target = "ctf"))]
+    StaticCtf,
+
     #[strum(serialize = "dyn_stap", props(type = "dynamic", target = "stap"))]
     DynamicStap,
 
@@ -3,6 +3,7 @@
 //! macros and nothing else.  That's an inconvenient restriction, especially since there's quite a
 //! lot of overlap between the macro code and the build-time probe code generation logic.  Hence,
 //! this bifurcation.
+use crate::build_rs::BuildInfoReader;
 use crate::gen;
 use crate::spec::ProbeCallSpecification;
 use crate::spec::ProviderInitSpecification;
@@ -48,20 +49,37 @@ pub fn report_error<T: quote::ToTokens, U: Display>(tokens: &T, message: U) -> T
 /// In particular, note that the probe's parameters are not evaluated unless the provider
 /// initialized successfully and the probe is enabled.
 pub fn probe_impl(tokens: TokenStream) -> TracersResult<TokenStream> {
-    gen::code_generator()?.handle_probe_call(ProbeCallSpecification::from_token_stream(tokens)?)
+    gen::code_generator(BuildInfoReader::ProcMacros)?
+        .handle_probe_call(ProbeCallSpecification::from_token_stream(tokens)?)
+}
+
+/// Translates a call to a span probe, like:
+///
+/// ```noexecute
+/// let _span = probe_span!(MyProvider::handle_request(id));
+/// ```
+///
+/// into code which, if either of the span's probes is enabled, evaluates the args once, fires the
+/// `handle_request_enter` probe, and returns a guard which fires the `handle_request_exit` probe
+/// with the same args and the elapsed nanoseconds when it's dropped.
+pub fn probe_span_impl(tokens: TokenStream) -> TracersResult<TokenStream> {
+    gen::code_generator(BuildInfoReader::ProcMacros)?
+        .handle_probe_span_call(ProbeCallSpecification::from_token_stream(tokens)?)
 }
 
 pub fn init_provider_impl(tokens: TokenStream) -> TracersResult<TokenStream> {
-    gen::code_generator()?
+    gen::code_generator(BuildInfoReader::ProcMacros)?
         .handle_init_provider(ProviderInitSpecification::from_token_stream(tokens)?)
 }
 
 /// Actual implementation of the macro logic, factored out of the proc macro itself so that it's
 /// more testable
 pub fn tracer_impl(attr_tokens: TokenStream, tokens: TokenStream) -> TracersResult<TokenStream> {
-    gen::code_generator()?.handle_provider_trait(ProviderSpecification::from_token_stream(
-        &std::env::var("CARGO_PKG_NAME").expect("CARGO_PKG_NAME"),
-        TracerAttributeArgs::from_token_stream(attr_tokens)?,
-        tokens,
-    )?)
+    gen::code_generator(BuildInfoReader::ProcMacros)?.handle_provider_trait(
+        ProviderSpecification::from_token_stream(
+            &std::env::var("CARGO_PKG_NAME").expect("CARGO_PKG_NAME"),
+            TracerAttributeArgs::from_token_stream(attr_tokens)?,
+            tokens,
+        )?,
+    )
 }
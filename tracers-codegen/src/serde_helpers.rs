@@ -105,3 +105,30 @@ pub(crate) mod span {
 }
 
 pub(crate) extern crate serde_str as string;
+
+/// Like `string`, but for optional values, which are serialized as an optional string
+pub(crate) mod opt_string {
+    use super::*;
+    use serde::de::*;
+    use serde::Serialize;
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub(crate) fn serialize<S: Serializer, T: ToString>(
+        x: &Option<T>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        x.as_ref().map(ToString::to_string).serialize(s)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>, T: FromStr>(
+        d: D,
+    ) -> Result<Option<T>, D::Error>
+    where
+        T::Err: Display,
+    {
+        Option::<String>::deserialize::<D>(d)?
+            .map(|as_str| as_str.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+}
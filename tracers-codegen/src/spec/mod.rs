@@ -13,17 +13,17 @@
 //! Thus this can be seen as one side of the code generator implementation.  The other side, which
 //! actually generates probing code, is in the `gen` module.
 mod init_provider;
+mod native_arg;
 mod probe;
 mod probe_arg;
 mod probe_call;
 mod provider;
 
 pub(crate) use init_provider::ProviderInitSpecification;
-pub(crate) use probe::ProbeSpecification;
+pub(crate) use native_arg::NativeArgSpecification;
+pub(crate) use probe::{ArgOverflow, ProbeSpecification, ProbeThrottle, SpanEvent};
 pub(crate) use probe_arg::ProbeArgSpecification;
-#[cfg(test)]
-pub(crate) use probe_call::ProbeCallDetails;
-pub(crate) use probe_call::ProbeCallSpecification;
+pub(crate) use probe_call::{ProbeCallDetails, ProbeCallSpecification};
 #[cfg(test)]
 pub(crate) use provider::TracerAttribute;
 pub(crate) use provider::TracerAttributeArgs;
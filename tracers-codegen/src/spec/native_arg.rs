@@ -0,0 +1,737 @@
+//! This module is concerned with the native arguments of a probe.  Most of the time a probe
+//! argument is passed to the native probe as exactly one C argument, but some probe arguments are
+//! lowered into more than one native argument; for example a string with the `ptr_len` encoding
+//! is passed as a pointer to its bytes and a length, a `u128` is passed as its high and low 64
+//! bits, and a `&Result<T, E>` is passed as an `is_ok` flag followed by each of its payloads.
+//!
+//! Each native argument consists of a Rust expression computed from the probe argument, and the
+//! (supported) Rust type of that expression.  The generated code wraps the result of that
+//! expression in its `ProbeArgWrapper` and passes the result of `as_c_type()` to the native probe,
+//! so everything downstream of the lowering deals only in native arguments.
+use crate::argtypes;
+use crate::argtypes::ArgTypeInfo;
+use crate::serde_helpers;
+use crate::spec::probe_arg::{ProbeArgAttributeArgs, StringEncoding};
+use crate::{TracersError, TracersResult};
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use syn::parse_quote;
+use tracers_core::argtypes::{CType, IoErrorMessage, NulEscaped, NulTruncated};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct NativeArgSpecification {
+    name: String,
+
+    #[serde(with = "serde_helpers::syn")]
+    ident: syn::Ident,
+
+    /// The type of the Rust value which will be wrapped in a `ProbeArgWrapper`, including lifetime
+    /// annotations
+    #[serde(with = "serde_helpers::syn")]
+    syn_typ: syn::Type,
+
+    /// The expression which computes the value to be wrapped, in terms of the probe arg
+    #[serde(with = "serde_helpers::token_stream")]
+    expression: TokenStream,
+
+    /// If `true`, `expression` evaluates to an `Option` of `syn_typ`, which is passed as the
+    /// default value of the C type when `None`
+    optional: bool,
+
+    arg_type_info: ArgTypeInfo,
+
+    /// If this is a pointer to a buffer, the name of the native arg which contains its length
+    length_arg: Option<String>,
+
+    /// If `true`, this integer is only meaningful as a bit pattern (like one half of a `u128`), so
+    /// tracing tools which have a choice should display it in hex
+    hex: bool,
+}
+
+impl fmt::Debug for NativeArgSpecification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "NativeArgSpecification(name={}, c_type={})",
+            self.name,
+            self.arg_type_info.get_c_type_str()
+        )
+    }
+}
+
+impl NativeArgSpecification {
+    fn new(
+        name: String,
+        span: proc_macro2::Span,
+        syn_typ: syn::Type,
+        expression: TokenStream,
+        optional: bool,
+        arg_type_info: ArgTypeInfo,
+    ) -> NativeArgSpecification {
+        NativeArgSpecification {
+            ident: syn::Ident::new(&name, span),
+            name,
+            syn_typ,
+            expression,
+            optional,
+            arg_type_info,
+            length_arg: None,
+            hex: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ident(&self) -> &syn::Ident {
+        &self.ident
+    }
+
+    pub fn arg_type_info(&self) -> &ArgTypeInfo {
+        &self.arg_type_info
+    }
+
+    /// If this native arg is a pointer to a buffer of bytes, gets the name of the native arg which
+    /// holds the length of that buffer
+    pub fn length_arg(&self) -> Option<&str> {
+        self.length_arg.as_ref().map(String::as_str)
+    }
+
+    /// If `true`, this native arg is an integer which is best displayed in hex
+    pub fn is_hex(&self) -> bool {
+        self.hex
+    }
+
+    /// The type of the `ProbeArgWrapper` which wraps this native arg's value.  Lifetimes are
+    /// explicit so this can be used in function return types.
+    pub fn wrapper_type(&self) -> TokenStream {
+        let typ = &self.syn_typ;
+        let wrapper = quote! { <#typ as ::tracers::runtime::ProbeArgType<#typ>>::WrapperType };
+
+        if self.optional {
+            quote! { Option<#wrapper> }
+        } else {
+            wrapper
+        }
+    }
+
+    /// An expression which computes this native arg's value from the probe arg, and wraps it in
+    /// its `ProbeArgWrapper`.  Only valid where the probe arg is in scope under its own name.
+    pub fn wrap_expression(&self) -> TokenStream {
+        let expression = &self.expression;
+        let span = self.ident.span();
+
+        if self.optional {
+            quote_spanned! {span=>
+                (#expression).map(::tracers::runtime::wrap)
+            }
+        } else {
+            quote_spanned! {span=>
+                ::tracers::runtime::wrap(#expression)
+            }
+        }
+    }
+}
+
+/// Lowers a probe argument into the native arguments which will actually be passed to the probe.
+///
+/// Returns `None` if the type of the probe arg isn't supported at all, and an error if the type is
+/// supported but the options in the arg's `#[probe]` attribute can't be applied to it.
+pub(super) fn lower_probe_arg(
+    ident: &syn::PatIdent,
+    syn_typ: &syn::Type,
+    syn_typ_with_lifetimes: &syn::Type,
+    attr_args: &ProbeArgAttributeArgs,
+) -> TracersResult<Option<Vec<NativeArgSpecification>>> {
+    let name = ident.ident.to_string();
+    let arg_ident = &ident.ident;
+
+    if !is_option_type(syn_typ) && (attr_args.option_flag() || attr_args.none_value().is_some()) {
+        return Err(TracersError::invalid_provider(
+            format!(
+                "The `option_flag` and `none_value` options can only be used on `Option` arguments, but '{}' is not an `Option`",
+                name
+            ),
+            syn_typ,
+        ));
+    }
+
+    let native_args = match attr_args.none_value() {
+        Some(none_value) => lower_option_with_none_value(
+            ident,
+            syn_typ,
+            syn_typ_with_lifetimes,
+            attr_args,
+            none_value,
+        )?,
+        None => lower_probe_arg_value(ident, syn_typ, syn_typ_with_lifetimes, attr_args)?,
+    };
+
+    let mut native_args = match native_args {
+        Some(native_args) => native_args,
+        None => return Ok(None),
+    };
+
+    //Either way, `None` is passed as some value which the arg could also have when it's `Some`, so
+    //optionally a flag is passed first to tell them apart
+    if attr_args.option_flag() {
+        native_args.insert(
+            0,
+            NativeArgSpecification::new(
+                format!("{}_is_some", name),
+                ident.ident.span(),
+                parse_quote! { bool },
+                quote! { #arg_ident.is_some() },
+                false,
+                ArgTypeInfo::new::<bool>(),
+            ),
+        );
+    }
+
+    Ok(Some(native_args))
+}
+
+/// The extra native arg passed last to a throttled probe, which is the number of fires the
+/// throttle suppressed on the current thread since the probe last fired.  Its value comes from the
+/// `__(probe)_suppressed` function which the generators emit in the impl mod alongside the
+/// probe's throttle.
+pub(super) fn suppressed_fires_arg(
+    probe_name: &str,
+    span: proc_macro2::Span,
+) -> NativeArgSpecification {
+    let suppressed_func = syn::Ident::new(&format!("__{}_suppressed", probe_name), span);
+
+    NativeArgSpecification::new(
+        "suppressed".to_string(),
+        span,
+        parse_quote! { u64 },
+        quote_spanned! {span=> self::#suppressed_func() },
+        false,
+        ArgTypeInfo::new::<u64>(),
+    )
+}
+
+/// Lowers the value of a probe argument, which for an `Option` means `None` is passed as the
+/// default value of the C type(s)
+fn lower_probe_arg_value(
+    ident: &syn::PatIdent,
+    syn_typ: &syn::Type,
+    syn_typ_with_lifetimes: &syn::Type,
+    attr_args: &ProbeArgAttributeArgs,
+) -> TracersResult<Option<Vec<NativeArgSpecification>>> {
+    let name = ident.ident.to_string();
+    let span = ident.ident.span();
+    let arg_ident = &ident.ident;
+
+    //Some types are always lowered into more than one native arg, none of which are strings, so
+    //they can't have an encoding either
+    if let Some(half_type_info) = argtypes::from_syn_wide_integer_type(syn_typ) {
+        check_no_encoding(attr_args, &name, syn_typ)?;
+
+        let optional = is_option_type(syn_typ);
+        let value = if optional {
+            quote! { #arg_ident.as_ref() }
+        } else {
+            quote! { #arg_ident }
+        };
+
+        return Ok(Some(lower_wide_integer_value(
+            &name,
+            span,
+            &value,
+            optional,
+            half_type_info,
+        )));
+    }
+
+    if let (Some(payload_types), Some(payload_types_with_lifetimes)) = (
+        argtypes::result_payload_types(syn_typ),
+        argtypes::result_payload_types(syn_typ_with_lifetimes),
+    ) {
+        check_no_encoding(attr_args, &name, syn_typ)?;
+
+        return Ok(lower_result_arg(
+            &name,
+            span,
+            arg_ident,
+            &first_lifetime(syn_typ_with_lifetimes),
+            payload_types,
+            payload_types_with_lifetimes,
+        ));
+    }
+
+    if let syn::Type::Reference(syn::TypeReference { elem, .. }) = syn_typ {
+        if argtypes::is_io_error_type(elem) {
+            check_no_encoding(attr_args, &name, syn_typ)?;
+
+            return Ok(Some(lower_io_error_value(
+                &name,
+                span,
+                &quote! { #arg_ident },
+                false,
+                &first_lifetime(syn_typ_with_lifetimes),
+            )));
+        }
+    }
+
+    let arg_type_info = match argtypes::from_syn_type(syn_typ) {
+        Some(arg_type_info) => arg_type_info,
+        None => return Ok(None),
+    };
+
+    let encoding = match attr_args.encoding() {
+        None => {
+            //The simple case: the arg is passed as-is
+            return Ok(Some(vec![NativeArgSpecification::new(
+                name,
+                span,
+                syn_typ_with_lifetimes.clone(),
+                quote! { #arg_ident },
+                false,
+                arg_type_info,
+            )]));
+        }
+        Some(encoding) => encoding,
+    };
+
+    if arg_type_info.get_c_type_enum() != CType::CharPtr {
+        return Err(encoding_not_supported(&name, syn_typ));
+    }
+
+    //If this is an `&Option` of some string the encoding applies to the string within, and each
+    //native arg is computed on the `Option` and passed as the C type's default when `None`
+    let optional = is_option_type(syn_typ);
+    let value = if optional {
+        quote! { #arg_ident.as_ref() }
+    } else {
+        quote! { #arg_ident }
+    };
+
+    //All string types are references so there is always at least one lifetime
+    let lifetime = first_lifetime(syn_typ_with_lifetimes);
+
+    let native_args = match encoding {
+        StringEncoding::Truncate => vec![NativeArgSpecification::new(
+            name,
+            span,
+            parse_quote! { ::tracers::runtime::NulTruncated<#lifetime> },
+            project(&value, optional, |v| {
+                quote! { ::tracers::runtime::NulTruncated::new(#v) }
+            }),
+            optional,
+            ArgTypeInfo::new::<NulTruncated<'static>>(),
+        )],
+        StringEncoding::Escape => vec![NativeArgSpecification::new(
+            name,
+            span,
+            parse_quote! { ::tracers::runtime::NulEscaped<#lifetime> },
+            project(&value, optional, |v| {
+                quote! { ::tracers::runtime::NulEscaped::new(#v) }
+            }),
+            optional,
+            ArgTypeInfo::new::<NulEscaped<'static>>(),
+        )],
+        StringEncoding::PtrLen => {
+            let len_name = format!("{}_len", name);
+            let mut ptr = NativeArgSpecification::new(
+                name,
+                span,
+                parse_quote! { *const u8 },
+                project(&value, optional, |v| {
+                    quote! { ::tracers::runtime::ProbeArgBytes::probe_arg_bytes(#v).as_ptr() }
+                }),
+                optional,
+                ArgTypeInfo::new::<*const u8>(),
+            );
+            ptr.length_arg = Some(len_name.clone());
+
+            let len = NativeArgSpecification::new(
+                len_name,
+                span,
+                parse_quote! { usize },
+                project(&value, optional, |v| {
+                    quote! { ::tracers::runtime::ProbeArgBytes::probe_arg_bytes(#v).len() }
+                }),
+                optional,
+                ArgTypeInfo::new::<usize>(),
+            );
+
+            vec![ptr, len]
+        }
+    };
+
+    Ok(Some(native_args))
+}
+
+/// Lowers an `&Option` probe argument for which the user has chosen the value to pass in place of
+/// `None` with `#[probe(none_value = "...")]`.  That only works if the value inside the `Option`
+/// is passed as a single native arg without any conversion, which means primitive types and
+/// references to them, and `&str`.
+fn lower_option_with_none_value(
+    ident: &syn::PatIdent,
+    syn_typ: &syn::Type,
+    syn_typ_with_lifetimes: &syn::Type,
+    attr_args: &ProbeArgAttributeArgs,
+    none_value: &str,
+) -> TracersResult<Option<Vec<NativeArgSpecification>>> {
+    let name = ident.ident.to_string();
+    let arg_ident = &ident.ident;
+
+    if attr_args.encoding().is_some() {
+        return Err(TracersError::invalid_provider(
+            format!(
+                "The `encoding` and `none_value` options can't both be used on '{}'",
+                name
+            ),
+            syn_typ,
+        ));
+    }
+
+    let none_value: syn::Expr = syn::parse_str(none_value).map_err(|_| {
+        TracersError::invalid_provider(
+            format!(
+                "The `none_value` of '{}' must be a Rust expression, but '{}' is not",
+                name, none_value
+            ),
+            syn_typ,
+        )
+    })?;
+
+    //Both of these are `Some` because the caller has already checked this is an `Option`
+    let inner_typ = option_inner_type(syn_typ).expect("option type");
+    let inner_typ_with_lifetimes = option_inner_type(syn_typ_with_lifetimes).expect("option type");
+
+    //The `Option` is always `Copy` for the types supported here, so it can be taken by value
+    let (value, value_typ, value_typ_with_lifetimes) = match inner_typ {
+        //`&Option<&str>`
+        syn::Type::Reference(syn::TypeReference { elem, .. }) if is_str_type(elem) => (
+            quote! { (*#arg_ident).unwrap_or(#none_value) },
+            inner_typ,
+            inner_typ_with_lifetimes,
+        ),
+        //`&Option<&T>` where `T` is hopefully a primitive type
+        syn::Type::Reference(syn::TypeReference { elem, .. }) => (
+            quote! { (*#arg_ident).map_or(#none_value, |__tracers_value| *__tracers_value) },
+            elem.as_ref(),
+            elem.as_ref(),
+        ),
+        //`&Option<T>` where `T` is hopefully a primitive type
+        _ => (
+            quote! { (*#arg_ident).unwrap_or(#none_value) },
+            inner_typ,
+            inner_typ,
+        ),
+    };
+
+    //Other string types, and anything else which isn't passed as-is, can't be used
+    let arg_type_info = match argtypes::from_syn_type(value_typ) {
+        Some(arg_type_info) if !is_reference_type(value_typ) || value_typ == inner_typ => {
+            arg_type_info
+        }
+        _ => return Err(none_value_not_supported(&name, syn_typ)),
+    };
+
+    Ok(Some(vec![NativeArgSpecification::new(
+        name,
+        ident.ident.span(),
+        value_typ_with_lifetimes.clone(),
+        value,
+        false,
+        arg_type_info,
+    )]))
+}
+
+fn none_value_not_supported(name: &str, syn_typ: &syn::Type) -> TracersError {
+    TracersError::invalid_provider(
+        format!(
+            "The `none_value` option can only be used on `Option`s of primitive types or `&str`, which '{}' is not",
+            name
+        ),
+        syn_typ,
+    )
+}
+
+/// Lowers a `u128` or `i128` value into two `u64` native args, `{name}_hi` and `{name}_lo`.
+/// `value` evaluates to the integer or a reference to it, or if `optional` is `true`, to an
+/// `Option` of one of those.  `None` is passed as zero in both.
+fn lower_wide_integer_value(
+    name: &str,
+    span: proc_macro2::Span,
+    value: &TokenStream,
+    optional: bool,
+    half_type_info: ArgTypeInfo,
+) -> Vec<NativeArgSpecification> {
+    let half = |suffix: &str, method: TokenStream| {
+        let mut native_arg = NativeArgSpecification::new(
+            format!("{}_{}", name, suffix),
+            span,
+            parse_quote! { u64 },
+            project(value, optional, |v| {
+                quote! { ::tracers::runtime::ProbeArgWideInteger::#method(&#v) }
+            }),
+            optional,
+            half_type_info.clone(),
+        );
+        native_arg.hex = true;
+        native_arg
+    };
+
+    vec![
+        half("hi", quote! { probe_arg_high_bits }),
+        half("lo", quote! { probe_arg_low_bits }),
+    ]
+}
+
+/// Lowers a `&Result<T, E>` probe arg into a `{name}_is_ok` flag, followed by the native args of
+/// the `T` payload (named for `{name}_ok`) and then the `E` payload (named for `{name}_err`).  Only
+/// one of the payloads is present at a time, and the other is passed as if it were `None`.  A `()`
+/// payload has no native args at all, so `io::Result<()>` is just the flag and the error.
+///
+/// Returns `None` if either payload type isn't supported.
+fn lower_result_arg(
+    name: &str,
+    span: proc_macro2::Span,
+    arg_ident: &syn::Ident,
+    lifetime: &syn::Lifetime,
+    (ok_typ, err_typ): (syn::Type, syn::Type),
+    (ok_typ_with_lifetimes, err_typ_with_lifetimes): (syn::Type, syn::Type),
+) -> Option<Vec<NativeArgSpecification>> {
+    let mut native_args = vec![NativeArgSpecification::new(
+        format!("{}_is_ok", name),
+        span,
+        parse_quote! { bool },
+        quote! { #arg_ident.is_ok() },
+        false,
+        ArgTypeInfo::new::<bool>(),
+    )];
+
+    let payloads = vec![
+        ("ok", quote! { ok }, ok_typ, ok_typ_with_lifetimes),
+        ("err", quote! { err }, err_typ, err_typ_with_lifetimes),
+    ];
+
+    for (suffix, method, typ, typ_with_lifetimes) in payloads.into_iter() {
+        if let syn::Type::Tuple(ref tuple) = typ {
+            if tuple.elems.is_empty() {
+                continue;
+            }
+        }
+
+        native_args.extend(lower_result_payload(
+            &format!("{}_{}", name, suffix),
+            span,
+            &quote! { #arg_ident.as_ref().#method() },
+            lifetime,
+            &typ,
+            &typ_with_lifetimes,
+        )?);
+    }
+
+    Some(native_args)
+}
+
+/// Lowers one of the payloads of a `Result`.  `value` evaluates to an `Option<&T>` where `T` is the
+/// payload type, and `lifetime` is the lifetime of the reference to the `Result` itself.
+fn lower_result_payload(
+    name: &str,
+    span: proc_macro2::Span,
+    value: &TokenStream,
+    lifetime: &syn::Lifetime,
+    typ: &syn::Type,
+    typ_with_lifetimes: &syn::Type,
+) -> Option<Vec<NativeArgSpecification>> {
+    if argtypes::is_io_error_type(typ) {
+        return Some(lower_io_error_value(name, span, value, true, lifetime));
+    }
+
+    //The payload is only available by reference, so first see if the reference type is supported.
+    //That covers most things, like `&u32`, `&String` and `&Option<&str>`.
+    let ref_typ: syn::Type = parse_quote! { &#typ };
+    if let Some(half_type_info) = argtypes::from_syn_wide_integer_type(&ref_typ) {
+        return Some(lower_wide_integer_value(
+            name,
+            span,
+            value,
+            true,
+            half_type_info,
+        ));
+    }
+
+    if let Some(arg_type_info) = argtypes::from_syn_type(&ref_typ) {
+        return Some(vec![NativeArgSpecification::new(
+            name.to_owned(),
+            span,
+            parse_quote! { &#lifetime #typ_with_lifetimes },
+            value.clone(),
+            true,
+            arg_type_info,
+        )]);
+    }
+
+    //If the payload is itself a reference, like `&str`, it can be dereferenced instead
+    if let syn::Type::Reference(_) = typ {
+        if let Some(arg_type_info) = argtypes::from_syn_type(typ) {
+            return Some(vec![NativeArgSpecification::new(
+                name.to_owned(),
+                span,
+                typ_with_lifetimes.clone(),
+                project(value, true, |v| quote! { *#v }),
+                true,
+                arg_type_info,
+            )]);
+        }
+    }
+
+    None
+}
+
+/// Lowers an `io::Error` into three native args: `{name}_raw_os_error`, which is `0` if the error
+/// didn't come from the OS, `{name}_kind` and `{name}_message`.  `value` evaluates to a reference
+/// to the error, or if `optional` is `true`, an `Option` of one.  `lifetime` is the lifetime of
+/// that reference.
+fn lower_io_error_value(
+    name: &str,
+    span: proc_macro2::Span,
+    value: &TokenStream,
+    optional: bool,
+    lifetime: &syn::Lifetime,
+) -> Vec<NativeArgSpecification> {
+    vec![
+        NativeArgSpecification::new(
+            format!("{}_raw_os_error", name),
+            span,
+            parse_quote! { i32 },
+            project(value, optional, |v| {
+                quote! { #v.raw_os_error().unwrap_or(0) }
+            }),
+            optional,
+            ArgTypeInfo::new::<i32>(),
+        ),
+        NativeArgSpecification::new(
+            format!("{}_kind", name),
+            span,
+            parse_quote! { &'static str },
+            project(value, optional, |v| {
+                quote! { ::tracers::runtime::io_error_kind_str(#v.kind()) }
+            }),
+            optional,
+            ArgTypeInfo::new::<&str>(),
+        ),
+        NativeArgSpecification::new(
+            format!("{}_message", name),
+            span,
+            parse_quote! { ::tracers::runtime::IoErrorMessage<#lifetime> },
+            project(value, optional, |v| {
+                quote! { ::tracers::runtime::IoErrorMessage::new(#v) }
+            }),
+            optional,
+            ArgTypeInfo::new::<IoErrorMessage<'static>>(),
+        ),
+    ]
+}
+
+fn check_no_encoding(
+    attr_args: &ProbeArgAttributeArgs,
+    name: &str,
+    syn_typ: &syn::Type,
+) -> TracersResult<()> {
+    if attr_args.encoding().is_some() {
+        Err(encoding_not_supported(name, syn_typ))
+    } else {
+        Ok(())
+    }
+}
+
+fn encoding_not_supported(name: &str, syn_typ: &syn::Type) -> TracersError {
+    TracersError::invalid_provider(
+        format!(
+            "The `encoding` option can only be used on string arguments, but '{}' is not a string",
+            name
+        ),
+        syn_typ,
+    )
+}
+
+/// Applies `f` to the expression `value`, or if `optional` is true, to the contents of the
+/// `Option` that `value` evaluates to
+fn project<F: FnOnce(&TokenStream) -> TokenStream>(
+    value: &TokenStream,
+    optional: bool,
+    f: F,
+) -> TokenStream {
+    if optional {
+        let inner_value = quote! { __tracers_value };
+        let projection = f(&inner_value);
+        quote! { #value.map(|#inner_value| #projection) }
+    } else {
+        f(value)
+    }
+}
+
+/// Returns `true` if the type is `&Option<T>`, which is the only way an `Option` can be passed to a
+/// probe
+pub(super) fn is_option_type(typ: &syn::Type) -> bool {
+    option_inner_type(typ).is_some()
+}
+
+fn is_reference_type(typ: &syn::Type) -> bool {
+    if let syn::Type::Reference(_) = typ {
+        true
+    } else {
+        false
+    }
+}
+
+fn is_str_type(typ: &syn::Type) -> bool {
+    let str_typ: syn::Type = parse_quote! { str };
+    *typ == str_typ
+}
+
+/// If the type is `&Option<T>`, returns `T`
+fn option_inner_type(typ: &syn::Type) -> Option<&syn::Type> {
+    if let syn::Type::Reference(syn::TypeReference { elem, .. }) = typ {
+        if let syn::Type::Path(syn::TypePath { qself: None, path }) = elem.as_ref() {
+            if let Some(segment) = path.segments.iter().last() {
+                if segment.ident == "Option" {
+                    if let syn::PathArguments::AngleBracketed(ref args) = segment.arguments {
+                        if let Some(syn::GenericArgument::Type(ref inner)) = args.args.first() {
+                            return Some(inner);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Gets the lifetime of the outermost reference in a type which has already been annotated with
+/// lifetimes, or `'static` if there are no references
+fn first_lifetime(typ: &syn::Type) -> syn::Lifetime {
+    match typ {
+        syn::Type::Reference(syn::TypeReference {
+            lifetime: Some(lifetime),
+            ..
+        }) => lifetime.clone(),
+        _ => parse_quote! { 'static },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detects_option_types() {
+        let typ: syn::Type = parse_quote! { &Option<&str> };
+        let inner: syn::Type = parse_quote! { &str };
+        assert_eq!(Some(&inner), option_inner_type(&typ));
+
+        let typ: syn::Type = parse_quote! { &str };
+        assert_eq!(None, option_inner_type(&typ));
+    }
+}
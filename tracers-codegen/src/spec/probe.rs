@@ -6,14 +6,176 @@
 //! the definition of a probe.
 
 use crate::serde_helpers;
-use crate::spec::ProbeArgSpecification;
+use crate::spec::native_arg;
+use crate::spec::probe_arg::{self, ProbeArgAttributeArgs};
+use crate::spec::{NativeArgSpecification, ProbeArgSpecification};
+use crate::syn_helpers;
 use crate::{TracersError, TracersResult};
+use darling::FromMeta;
 use proc_macro2::Span;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fmt;
+use std::str::FromStr;
+use syn::parse_quote;
 use syn::spanned::Spanned;
 use syn::Visibility;
 use syn::{FnArg, Ident, ItemTrait, ReturnType, TraitItemMethod};
+use tracers_core::ProbeLevel;
+
+/// The most native args that a probe can take.  This is the limit for the `STAP_PROBEn` macros, and
+/// for the dynamic tracing API's `fireN` methods.
+pub(crate) const MAX_NATIVE_ARGS: usize = 12;
+
+/// The name of the extra argument of the exit probe of a span probe, which is the duration of the
+/// span in nanoseconds
+const SPAN_ELAPSED_ARG: &str = "elapsed_ns";
+
+/// The contents of the optional `#[probe(...)]` attribute on a probe method
+#[derive(Debug, FromMeta, Default)]
+struct ProbeAttributeArgs {
+    /// Fire the probe only once in every `sample` times it's enabled
+    #[darling(default)]
+    sample: Option<u64>,
+
+    /// Fire the probe at most this often, like `"1000/s"`
+    #[darling(default)]
+    rate_limit: Option<String>,
+
+    /// Declares a span probe, which is fired with `probe_span!` instead of `probe!`
+    #[darling(default)]
+    span: bool,
+
+    /// The level of the probe, like `"warning"`, overriding the provider's default level
+    #[darling(default)]
+    level: Option<String>,
+
+    /// The URI of the EMF model of the probe, which LTTng records with the tracepoint
+    #[darling(default)]
+    emf_uri: Option<String>,
+
+    /// What dynamic tracers with a limit on native args, like SystemTap, do if the probe has too
+    /// many; `"spill"` or `"reject"`, overriding the provider's default
+    #[darling(default)]
+    arg_overflow: Option<String>,
+}
+
+/// What a dynamic tracer which can't pass all of a probe's native args does with the probe.  This
+/// is the codegen counterpart of `tracers_core::dynamic::ArgOverflow`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum ArgOverflow {
+    /// Fail to initialize the provider
+    Reject,
+
+    /// Pass the args which don't fit some other way.  This is the default for `#[tracer]`
+    /// providers, so a probe which grows extra native args doesn't break its whole provider.
+    Spill,
+}
+
+/// Parses the value of an `arg_overflow` option on a `#[probe]` or `#[tracer]` attribute
+pub(crate) fn parse_arg_overflow<T: quote::ToTokens>(
+    arg_overflow: &str,
+    tokens: T,
+) -> TracersResult<ArgOverflow> {
+    match arg_overflow {
+        "reject" => Ok(ArgOverflow::Reject),
+        "spill" => Ok(ArgOverflow::Spill),
+        _ => Err(TracersError::invalid_provider(
+            format!(
+                "The `arg_overflow` option must be one of reject, spill, but it is \"{}\"",
+                arg_overflow
+            ),
+            tokens,
+        )),
+    }
+}
+
+/// Parses the value of a `level` option on a `#[probe]` or `#[tracer]` attribute
+pub(crate) fn parse_probe_level<T: quote::ToTokens>(
+    level: &str,
+    tokens: T,
+) -> TracersResult<ProbeLevel> {
+    ProbeLevel::from_str(level).map_err(|_| {
+        let names: Vec<_> = ProbeLevel::ALL.iter().map(|level| level.name()).collect();
+        TracersError::invalid_provider(
+            format!(
+                "The `level` option must be one of {}, but it is \"{}\"",
+                names.join(", "),
+                level
+            ),
+            tokens,
+        )
+    })
+}
+
+/// Which of the two probes of a span probe a probe is
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum SpanEvent {
+    /// The probe fired when the span starts
+    Enter,
+
+    /// The probe fired when the span ends, with the elapsed time
+    Exit,
+}
+
+/// Limits how often an enabled probe actually fires.  Throttled probes are passed an extra native
+/// arg with the number of fires which were suppressed since the probe last fired.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum ProbeThrottle {
+    /// Fire once in every `n` times
+    Sample(u64),
+
+    /// Fire at most `count` times per `period_nanos` nanoseconds
+    RateLimit { count: u64, period_nanos: u64 },
+}
+
+impl ProbeThrottle {
+    fn from_attribute_args(
+        args: &ProbeAttributeArgs,
+        method: &TraitItemMethod,
+    ) -> TracersResult<Option<ProbeThrottle>> {
+        match (args.sample, args.rate_limit.as_ref()) {
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => Err(TracersError::invalid_provider(
+                "The `sample` and `rate_limit` options can't both be used on the same probe",
+                method,
+            )),
+            (Some(0), None) => Err(TracersError::invalid_provider(
+                "The `sample` option must be at least 1",
+                method,
+            )),
+            (Some(n), None) => Ok(Some(ProbeThrottle::Sample(n))),
+            (None, Some(rate)) => Self::parse_rate_limit(rate)
+                .map(Some)
+                .ok_or_else(|| {
+                    TracersError::invalid_provider(
+                        format!("The `rate_limit` option must be a number of fires greater than zero per `ms`, `s` or `min`, like \"1000/s\", but it is \"{}\"", rate),
+                        method,
+                    )
+                }),
+        }
+    }
+
+    fn parse_rate_limit(rate: &str) -> Option<ProbeThrottle> {
+        let mut parts = rate.splitn(2, '/');
+        let count: u64 = parts.next()?.trim().parse().ok()?;
+        let period_nanos = match parts.next()?.trim() {
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "min" => 60_000_000_000,
+            _ => return None,
+        };
+
+        if count == 0 {
+            None
+        } else {
+            Some(ProbeThrottle::RateLimit {
+                count,
+                period_nanos,
+            })
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct ProbeSpecification {
@@ -27,6 +189,25 @@ pub(crate) struct ProbeSpecification {
     #[serde(with = "serde_helpers::span")]
     pub span: Span,
     pub args: Vec<ProbeArgSpecification>,
+    pub throttle: Option<ProbeThrottle>,
+
+    /// The level from the probe's `#[probe(level = "...")]` attribute, or else the default level
+    /// of the provider
+    #[serde(with = "serde_helpers::opt_string")]
+    pub level: Option<ProbeLevel>,
+
+    /// The URI from the probe's `#[probe(emf_uri = "...")]` attribute
+    pub emf_uri: Option<String>,
+
+    /// The option from the probe's `#[probe(arg_overflow = "...")]` attribute, or else the
+    /// provider's default, which is itself `Spill` unless set with `#[tracer(arg_overflow = "...")]`
+    pub arg_overflow: Option<ArgOverflow>,
+
+    /// If this is one of the probes of a span probe, which one it is
+    pub span_event: Option<SpanEvent>,
+
+    /// For a throttled probe, the extra native arg which is passed after all of the others
+    suppressed_arg: Option<NativeArgSpecification>,
 }
 
 impl fmt::Debug for ProbeSpecification {
@@ -42,6 +223,90 @@ impl fmt::Debug for ProbeSpecification {
 }
 
 impl ProbeSpecification {
+    /// Computes the probes which correspond to a trait method.  Most methods are a single probe,
+    /// but a span probe declared with `#[probe(span)]`, like:
+    ///
+    /// ```noexecute
+    /// #[probe(span)]
+    /// fn handle_request(id: u64);
+    /// ```
+    ///
+    /// is two probes: `handle_request_enter(id: u64)`, which fires when the span starts, and
+    /// `handle_request_exit(id: u64, elapsed_ns: u64)`, which fires when it ends.  The
+    /// `probe_span!` macro fires both of them.
+    pub(crate) fn from_trait_method(
+        item: &ItemTrait,
+        method: &TraitItemMethod,
+        defaults: &ProbeArgAttributeArgs,
+    ) -> TracersResult<Vec<ProbeSpecification>> {
+        let attr_args: ProbeAttributeArgs = probe_arg::parse_probe_attributes(&method.attrs)?;
+        if !attr_args.span {
+            return Ok(vec![ProbeSpecification::from_method(
+                item, method, defaults,
+            )?]);
+        }
+
+        if attr_args.sample.is_some() || attr_args.rate_limit.is_some() {
+            return Err(TracersError::invalid_provider(
+                "Span probes can't be throttled, because their enter and exit probes must always fire in pairs",
+                method,
+            ));
+        }
+
+        for input in method.sig.inputs.iter() {
+            if let FnArg::Typed(pat_type) = input {
+                if syn_helpers::convert_to_string(&pat_type.pat) == SPAN_ELAPSED_ARG {
+                    return Err(TracersError::invalid_provider(
+                        format!("The exit probe of a span probe is passed the duration of the span as an extra argument named '{}', which collides with one of this probe's arguments; rename the argument", SPAN_ELAPSED_ARG),
+                        method,
+                    ));
+                }
+            }
+        }
+
+        //The attribute is stripped from the enter and exit probes, so they get its level, EMF URI
+        //and arg overflow explicitly
+        let level = attr_args
+            .level
+            .as_ref()
+            .map(|level| parse_probe_level(level, method))
+            .transpose()?;
+        let arg_overflow = attr_args
+            .arg_overflow
+            .as_ref()
+            .map(|arg_overflow| parse_arg_overflow(arg_overflow, method))
+            .transpose()?;
+
+        let mut enter_method = method.clone();
+        probe_arg::strip_probe_attributes(&mut enter_method.attrs);
+        let mut exit_method = enter_method.clone();
+
+        let name = &method.sig.ident;
+        enter_method.sig.ident = syn::Ident::new(&format!("{}_enter", name), name.span());
+        exit_method.sig.ident = syn::Ident::new(&format!("{}_exit", name), name.span());
+        let elapsed_ident = syn::Ident::new(SPAN_ELAPSED_ARG, name.span());
+        exit_method
+            .sig
+            .inputs
+            .push(parse_quote! { #elapsed_ident: u64 });
+
+        let mut probes = vec![
+            ProbeSpecification::from_method(item, &enter_method, defaults)?,
+            ProbeSpecification::from_method(item, &exit_method, defaults)?,
+        ];
+        for (probe, span_event) in probes
+            .iter_mut()
+            .zip([SpanEvent::Enter, SpanEvent::Exit].iter())
+        {
+            probe.level = level;
+            probe.emf_uri = attr_args.emf_uri.clone();
+            probe.arg_overflow = arg_overflow;
+            probe.span_event = Some(*span_event);
+        }
+
+        Ok(probes)
+    }
+
     /// Given a trait method, compute the probe that corresponds to that method.
     /// If the method isn't suitable for use as a probe method, returns an error
     ///
@@ -54,9 +319,14 @@ impl ProbeSpecification {
     /// * Not `unsafe`, `const`, `async`, or `extern "C"`
     /// * No type parameters; generics are not supported in probe types
     /// * Not variadic
+    ///
+    /// `defaults` are the provider-wide defaults for the options on the `#[probe]` attributes of
+    /// each arg.  A `#[probe]` attribute on the method itself can throttle the probe with
+    /// `sample = N` or `rate_limit = "N/s"`, and can set its `level` and `emf_uri`.
     pub(crate) fn from_method(
         item: &ItemTrait,
         method: &TraitItemMethod,
+        defaults: &ProbeArgAttributeArgs,
     ) -> TracersResult<ProbeSpecification> {
         if method.default != None {
             return Err(TracersError::invalid_provider(
@@ -97,22 +367,110 @@ impl ProbeSpecification {
             ));
         }
 
+        let attr_args: ProbeAttributeArgs = probe_arg::parse_probe_attributes(&method.attrs)?;
+        let throttle = ProbeThrottle::from_attribute_args(&attr_args, method)?;
+        let level = attr_args
+            .level
+            .as_ref()
+            .map(|level| parse_probe_level(level, method))
+            .transpose()?;
+        let arg_overflow = attr_args
+            .arg_overflow
+            .as_ref()
+            .map(|arg_overflow| parse_arg_overflow(arg_overflow, method))
+            .transpose()?;
+        let suppressed_arg = throttle.map(|_| {
+            native_arg::suppressed_fires_arg(&method.sig.ident.to_string(), method.span())
+        });
+
         let mut args: Vec<ProbeArgSpecification> = Vec::new();
         for (idx, arg) in method.sig.inputs.iter().enumerate() {
-            args.push(ProbeArgSpecification::from_fnarg(method, idx, arg)?);
+            args.push(ProbeArgSpecification::from_fnarg(
+                method, idx, arg, defaults,
+            )?);
+        }
+
+        //Some args are lowered to more than one native arg, with names derived from the arg name,
+        //so it's possible (if unlikely) for those names to collide
+        let mut native_arg_names = HashSet::new();
+        for native_arg in args.iter().flat_map(ProbeArgSpecification::native_args) {
+            if !native_arg_names.insert(native_arg.name()) {
+                return Err(TracersError::invalid_provider(
+                    format!("The probe argument '{}' has the same name as one of the native arguments generated for another probe argument; rename the argument", native_arg.name()),
+                    method,
+                ));
+            }
+        }
+
+        if let Some(ref suppressed_arg) = suppressed_arg {
+            if !native_arg_names.insert(suppressed_arg.name()) {
+                return Err(TracersError::invalid_provider(
+                    format!("Throttled probes are passed the number of suppressed fires as an extra native argument named '{}', which collides with one of this probe's arguments; rename the argument", suppressed_arg.name()),
+                    method,
+                ));
+            }
+        }
+
+        if native_arg_names.len() > MAX_NATIVE_ARGS {
+            return Err(TracersError::invalid_provider(
+                format!("Probes can have at most {} native arguments, but this probe's arguments are passed as {} native arguments", MAX_NATIVE_ARGS, native_arg_names.len()),
+                method,
+            ));
+        }
+
+        //The `#[probe]` attributes on the method and its args have served their purpose, and must
+        //not be re-emitted by the generators
+        let mut original_method = method.clone();
+        probe_arg::strip_probe_attributes(&mut original_method.attrs);
+        for input in original_method.sig.inputs.iter_mut() {
+            if let FnArg::Typed(ref mut pat_type) = input {
+                probe_arg::strip_probe_attributes(&mut pat_type.attrs);
+            }
         }
 
         let spec = ProbeSpecification {
             name: method.sig.ident.to_string(),
             method_name: method.sig.ident.clone(),
-            original_method: method.clone(),
+            original_method,
             vis: item.vis.clone(),
             span: method.span(),
             args,
+            throttle,
+            level,
+            emf_uri: attr_args.emf_uri,
+            arg_overflow,
+            span_event: None,
+            suppressed_arg,
         };
 
         Ok(spec)
     }
+
+    /// For a throttled probe, the native arg with the number of suppressed fires
+    pub(crate) fn suppressed_arg(&self) -> Option<&NativeArgSpecification> {
+        self.suppressed_arg.as_ref()
+    }
+
+    /// The name of the span probe which this probe is one of, if it is
+    pub(crate) fn span_name(&self) -> Option<&str> {
+        let suffix = match self.span_event? {
+            SpanEvent::Enter => "_enter",
+            SpanEvent::Exit => "_exit",
+        };
+
+        Some(&self.name[..self.name.len() - suffix.len()])
+    }
+
+    /// All of the native args of all of this probe's args, in order, followed by the number of
+    /// suppressed fires if the probe is throttled.  These are what is actually passed to the
+    /// native probe.
+    pub(crate) fn native_args(&self) -> Vec<&NativeArgSpecification> {
+        self.args
+            .iter()
+            .flat_map(ProbeArgSpecification::native_args)
+            .chain(self.suppressed_arg.iter())
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -132,6 +490,10 @@ mod test {
                 && self.original_method == other.original_method
                 && self.vis == other.vis
                 && self.args == other.args
+                && self.throttle == other.throttle
+                && self.level == other.level
+                && self.emf_uri == other.emf_uri
+                && self.span_event == other.span_event
         }
     }
 
@@ -195,12 +557,13 @@ mod test {
         for input in data::valid_test_cases().iter() {
             let input_string = quote! { #input }.to_string();
 
-            ProbeSpecification::from_method(&data::trait_item(), input).unwrap_or_else(|_| {
-                panic!(format!(
-                    "This should be treated as a valid method: {}",
-                    input_string
-                ))
-            });
+            ProbeSpecification::from_method(&data::trait_item(), input, &Default::default())
+                .unwrap_or_else(|_| {
+                    panic!(format!(
+                        "This should be treated as a valid method: {}",
+                        input_string
+                    ))
+                });
         }
     }
 
@@ -209,7 +572,7 @@ mod test {
         for input in data::invalid_test_cases().iter() {
             let input_string = quote! { #input }.to_string();
 
-            ProbeSpecification::from_method(&data::trait_item(), input)
+            ProbeSpecification::from_method(&data::trait_item(), input, &Default::default())
                 .err()
                 .unwrap_or_else(|| {
                     panic!(format!(
@@ -220,6 +583,225 @@ mod test {
         }
     }
 
+    #[test]
+    fn parses_throttle_attributes() {
+        let test_cases: Vec<(TraitItemMethod, Option<ProbeThrottle>)> = vec![
+            (parse_quote! { fn probe0(arg0: i32); }, None),
+            (
+                parse_quote! { #[probe(sample = 100)] fn probe0(arg0: i32); },
+                Some(ProbeThrottle::Sample(100)),
+            ),
+            (
+                parse_quote! { #[probe(rate_limit = "1000/s")] fn probe0(arg0: i32); },
+                Some(ProbeThrottle::RateLimit {
+                    count: 1000,
+                    period_nanos: 1_000_000_000,
+                }),
+            ),
+            (
+                parse_quote! { #[probe(rate_limit = "5/min")] fn probe0(); },
+                Some(ProbeThrottle::RateLimit {
+                    count: 5,
+                    period_nanos: 60_000_000_000,
+                }),
+            ),
+        ];
+
+        for (method, expected) in test_cases.into_iter() {
+            let probe =
+                ProbeSpecification::from_method(&data::trait_item(), &method, &Default::default())
+                    .unwrap();
+
+            assert_eq!(expected, probe.throttle);
+
+            //The attribute must not be re-emitted, and a throttled probe gets an extra native arg
+            assert!(probe.original_method.attrs.is_empty());
+            let native_arg_names: Vec<_> = probe
+                .native_args()
+                .into_iter()
+                .map(NativeArgSpecification::name)
+                .collect();
+            assert_eq!(
+                expected.is_some(),
+                native_arg_names.last() == Some(&"suppressed"),
+                "{:?}",
+                native_arg_names
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_throttle_attributes() {
+        let test_cases: Vec<(TraitItemMethod, &str)> = vec![
+            (
+                parse_quote! { #[probe(sample = 10, rate_limit = "10/s")] fn probe0(); },
+                "can't both be used",
+            ),
+            (
+                parse_quote! { #[probe(sample = 0)] fn probe0(); },
+                "must be at least 1",
+            ),
+            (
+                parse_quote! { #[probe(rate_limit = "0/s")] fn probe0(); },
+                "The `rate_limit` option must be",
+            ),
+            (
+                parse_quote! { #[probe(rate_limit = "10/hour")] fn probe0(); },
+                "The `rate_limit` option must be",
+            ),
+            (
+                parse_quote! { #[probe(rate_limit = "10")] fn probe0(); },
+                "The `rate_limit` option must be",
+            ),
+            (
+                parse_quote! { #[probe(rate_limit = "10/s")] fn probe0(suppressed: u64); },
+                "named 'suppressed'",
+            ),
+        ];
+
+        for (method, expected_error) in test_cases.into_iter() {
+            let error =
+                ProbeSpecification::from_method(&data::trait_item(), &method, &Default::default())
+                    .err()
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "This should be an invalid method: {}",
+                            syn_helpers::convert_to_string(&method)
+                        )
+                    });
+
+            assert!(
+                error.to_string().contains(expected_error),
+                "'{}' doesn't contain '{}'",
+                error,
+                expected_error
+            );
+        }
+    }
+
+    #[test]
+    fn parses_level_attributes() {
+        let method: TraitItemMethod = parse_quote! {
+            #[probe(level = "warning", emf_uri = "model.emf")]
+            fn probe0(arg0: i32);
+        };
+        let probe =
+            ProbeSpecification::from_method(&data::trait_item(), &method, &Default::default())
+                .unwrap();
+        assert_eq!(Some(ProbeLevel::Warning), probe.level);
+        assert_eq!(Some("model.emf"), probe.emf_uri.as_deref());
+        assert!(probe.original_method.attrs.is_empty());
+
+        //The enter and exit probes of a span probe both get its level
+        let method: TraitItemMethod = parse_quote! {
+            #[probe(span, level = "info")]
+            fn probe0(arg0: i32);
+        };
+        let probes = ProbeSpecification::from_trait_method(
+            &data::trait_item(),
+            &method,
+            &Default::default(),
+        )
+        .unwrap();
+        assert!(probes
+            .iter()
+            .all(|probe| probe.level == Some(ProbeLevel::Info) && probe.emf_uri.is_none()));
+
+        let method: TraitItemMethod = parse_quote! { #[probe(level = "verbose")] fn probe0(); };
+        let error =
+            ProbeSpecification::from_method(&data::trait_item(), &method, &Default::default())
+                .err()
+                .unwrap();
+        assert!(error.to_string().contains("emergency, alert, critical"));
+    }
+
+    #[test]
+    fn splits_span_probes() {
+        let method: TraitItemMethod = parse_quote! {
+            /// Handles a request
+            #[probe(span)]
+            fn handle_request(id: u64, path: &str);
+        };
+
+        let probes = ProbeSpecification::from_trait_method(
+            &data::trait_item(),
+            &method,
+            &Default::default(),
+        )
+        .unwrap();
+
+        let expected: Vec<TraitItemMethod> = vec![
+            parse_quote! {
+                /// Handles a request
+                fn handle_request_enter(id: u64, path: &str);
+            },
+            parse_quote! {
+                /// Handles a request
+                fn handle_request_exit(id: u64, path: &str, elapsed_ns: u64);
+            },
+        ];
+        assert_eq!(expected.len(), probes.len());
+        for (probe, expected) in probes.iter().zip(expected.iter()) {
+            assert_eq!(
+                syn_helpers::convert_to_string(expected),
+                syn_helpers::convert_to_string(&probe.original_method)
+            );
+            assert_eq!(Some("handle_request"), probe.span_name());
+        }
+        assert_eq!(
+            vec![Some(SpanEvent::Enter), Some(SpanEvent::Exit)],
+            probes.iter().map(|p| p.span_event).collect::<Vec<_>>()
+        );
+
+        //Methods without the attribute are just one probe
+        let method: TraitItemMethod = parse_quote! { fn probe0(arg0: i32); };
+        let probes = ProbeSpecification::from_trait_method(
+            &data::trait_item(),
+            &method,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(1, probes.len());
+        assert_eq!(None, probes[0].span_name());
+        assert_eq!("probe0", probes[0].name);
+    }
+
+    #[test]
+    fn rejects_invalid_span_probes() {
+        let test_cases: Vec<(TraitItemMethod, &str)> = vec![
+            (
+                parse_quote! { #[probe(span, rate_limit = "10/s")] fn probe0(); },
+                "can't be throttled",
+            ),
+            (
+                parse_quote! { #[probe(span)] fn probe0(elapsed_ns: u64); },
+                "named 'elapsed_ns'",
+            ),
+        ];
+
+        for (method, expected_error) in test_cases.into_iter() {
+            let error = ProbeSpecification::from_trait_method(
+                &data::trait_item(),
+                &method,
+                &Default::default(),
+            )
+            .err()
+            .unwrap_or_else(|| {
+                panic!(
+                    "This should be an invalid method: {}",
+                    syn_helpers::convert_to_string(&method)
+                )
+            });
+
+            assert!(
+                error.to_string().contains(expected_error),
+                "'{}' doesn't contain '{}'",
+                error,
+                expected_error
+            );
+        }
+    }
+
     #[test]
     fn decorates_args_with_lifetime_params() {
         // Verify that when a probe is created from a trait method, all reference types anywhere in
@@ -249,13 +831,14 @@ mod test {
         ];
 
         for (method, expected) in test_cases.iter() {
-            let probe = ProbeSpecification::from_method(&data::trait_item(), method)
-                .unwrap_or_else(|_| {
-                    panic!(format!(
-                        "This method should be valid: {}",
-                        syn_helpers::convert_to_string(method)
-                    ))
-                });
+            let probe =
+                ProbeSpecification::from_method(&data::trait_item(), method, &Default::default())
+                    .unwrap_or_else(|_| {
+                        panic!(format!(
+                            "This method should be valid: {}",
+                            syn_helpers::convert_to_string(method)
+                        ))
+                    });
 
             //Re-construct the probe method using the args as they've been computed in the
             //ProbeSpecification.  The lifetimes should be present
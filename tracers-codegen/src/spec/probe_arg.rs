@@ -1,14 +1,134 @@
 //! This module is concerned with parsing and interpreting the arguments to a probe
 
-use crate::argtypes;
-use crate::argtypes::ArgTypeInfo;
 use crate::serde_helpers;
+use crate::spec::native_arg::{self, NativeArgSpecification};
 use crate::syn_helpers;
 use crate::{TracersError, TracersResult};
+use darling::FromMeta;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use strum_macros::EnumString;
 use syn::spanned::Spanned;
 
+/// How a string probe argument which contains NUL bytes (and thus isn't a valid C string) is passed
+/// to the probe.  By default such strings are passed as NULL; an encoding can be selected with
+/// `#[probe(encoding = "...")]` on the argument.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString)]
+pub(crate) enum StringEncoding {
+    /// Pass the string up to its first NUL byte
+    #[strum(serialize = "truncate")]
+    Truncate,
+
+    /// Pass the whole string, escaping NUL bytes as `\0` and backslashes as `\\`
+    #[strum(serialize = "escape")]
+    Escape,
+
+    /// Pass the string as two native args, a pointer to its bytes and its length
+    #[strum(serialize = "ptr_len")]
+    PtrLen,
+}
+
+impl FromMeta for StringEncoding {
+    fn from_string(value: &str) -> darling::Result<Self> {
+        value
+            .parse()
+            .map_err(|_| darling::Error::unknown_value(value))
+    }
+}
+
+/// The contents of the optional `#[probe(...)]` attribute on a probe argument
+#[derive(Debug, FromMeta, Clone, Serialize, Deserialize, Default)]
+pub(crate) struct ProbeArgAttributeArgs {
+    #[darling(default)]
+    encoding: Option<StringEncoding>,
+
+    /// If set on an `Option` arg, an extra `bool` native arg is passed before the value indicating
+    /// if the value is `Some`.  Otherwise `None` is passed as the C type's default value, which
+    /// makes `None` and (for example) `Some(0)` indistinguishable.
+    #[darling(default)]
+    option_flag: Option<bool>,
+
+    /// A Rust expression for the value to pass in place of `None`, instead of the C type's default
+    /// value
+    #[darling(default)]
+    none_value: Option<String>,
+}
+
+impl ProbeArgAttributeArgs {
+    /// The provider-wide defaults for probe arg options, as set on the `#[tracer]` attribute
+    pub(crate) fn provider_defaults(option_flag: bool) -> ProbeArgAttributeArgs {
+        ProbeArgAttributeArgs {
+            option_flag: Some(option_flag),
+            ..ProbeArgAttributeArgs::default()
+        }
+    }
+
+    pub(crate) fn encoding(&self) -> Option<StringEncoding> {
+        self.encoding
+    }
+
+    pub(crate) fn option_flag(&self) -> bool {
+        self.option_flag.unwrap_or(false)
+    }
+
+    pub(crate) fn none_value(&self) -> Option<&str> {
+        self.none_value.as_ref().map(String::as_str)
+    }
+
+    /// Applies the provider-wide defaults in `defaults` to any options which weren't specified on
+    /// this arg.  This is only done for the args the defaults are meaningful for, so a provider can
+    /// turn on `option_flag` without every non-`Option` arg being an error.
+    fn inherit_defaults(&mut self, defaults: &ProbeArgAttributeArgs, typ: &syn::Type) {
+        if self.option_flag.is_none() && native_arg::is_option_type(typ) {
+            self.option_flag = defaults.option_flag;
+        }
+    }
+}
+
+/// Gets the arguments of any `#[probe(...)]` attributes in `attrs`, combined as if they were all
+/// specified in one attribute.
+///
+/// These attributes are only meaningful to `tracers`, so the generators must not re-emit them; see
+/// `strip_probe_attributes`.
+pub(crate) fn parse_probe_attributes<T: FromMeta + Default>(
+    attrs: &[syn::Attribute],
+) -> TracersResult<T> {
+    let mut nested: Vec<syn::NestedMeta> = Vec::new();
+    let mut found = false;
+
+    for attr in attrs.iter().filter(|attr| is_probe_attribute(attr)) {
+        found = true;
+        match attr
+            .parse_meta()
+            .map_err(|e| TracersError::syn_error("Error parsing attribute metadata", e))?
+        {
+            syn::Meta::List(list) => nested.extend(list.nested.into_iter()),
+            syn::Meta::Path(_) => {}
+            syn::Meta::NameValue(_) => {
+                return Err(TracersError::syn_like_error(
+                    "Expected name/value pairs in ()",
+                    attr,
+                ))
+            }
+        }
+    }
+
+    if found {
+        T::from_list(&nested).map_err(TracersError::darling_error)
+    } else {
+        Ok(T::default())
+    }
+}
+
+/// Removes any `#[probe(...)]` attributes from `attrs`
+pub(crate) fn strip_probe_attributes(attrs: &mut Vec<syn::Attribute>) {
+    attrs.retain(|attr| !is_probe_attribute(attr))
+}
+
+fn is_probe_attribute(attr: &syn::Attribute) -> bool {
+    attr.path.is_ident("probe")
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct ProbeArgSpecification {
     name: String,
@@ -28,7 +148,7 @@ pub(crate) struct ProbeArgSpecification {
     #[serde(with = "serde_helpers::syn")]
     syn_typ_with_lifetimes: syn::Type,
 
-    arg_type_info: ArgTypeInfo,
+    native_args: Vec<NativeArgSpecification>,
 }
 
 impl fmt::Debug for ProbeArgSpecification {
@@ -43,17 +163,28 @@ impl fmt::Debug for ProbeArgSpecification {
 }
 
 impl ProbeArgSpecification {
+    /// Constructs a `ProbeArgSpecification` from a probe method arg.  `defaults` are the
+    /// provider-wide defaults for any options not specified in a `#[probe]` attribute on the arg.
     pub fn from_fnarg(
         probe_method: &syn::TraitItemMethod,
         ordinal: usize,
         arg: &syn::FnArg,
+        defaults: &ProbeArgAttributeArgs,
     ) -> TracersResult<ProbeArgSpecification> {
         //Apologies for the crazy match expression.  Rust's AST is a complicated beast
         //Many things can be function arguments in Rust; we only support the very basic form of:
         //`arg_name: some_type`
-        if let syn::FnArg::Typed(syn::PatType { pat, ty, .. }) = arg {
+        if let syn::FnArg::Typed(syn::PatType { pat, ty, attrs, .. }) = arg {
             if let syn::Pat::Ident(pat_ident) = pat.as_ref() {
-                return Self::from_ident_type_pair(probe_method, ordinal, pat_ident, ty);
+                let mut attr_args: ProbeArgAttributeArgs = parse_probe_attributes(attrs)?;
+                attr_args.inherit_defaults(defaults, ty);
+                return Self::from_ident_type_pair(
+                    probe_method,
+                    ordinal,
+                    pat_ident,
+                    ty,
+                    &attr_args,
+                );
             }
         }
 
@@ -70,22 +201,25 @@ impl ProbeArgSpecification {
         ordinal: usize,
         ident: &syn::PatIdent,
         typ: &syn::Type,
+        attr_args: &ProbeArgAttributeArgs,
     ) -> TracersResult<ProbeArgSpecification> {
         //Note the type is annotated right here with the added lifetime information.  It's easier
         //and faster then to compute the annotations on the fly
-        if let Some(arg_type_info) = argtypes::from_syn_type(typ) {
-            let name = ident.ident.to_string();
-            let probe_name = probe_method.sig.ident.to_string();
-            let syn_typ = typ.clone();
-            let syn_typ_with_lifetimes = Self::add_lifetimes_to_syn_type(&probe_name, &name, typ)?;
+        let name = ident.ident.to_string();
+        let probe_name = probe_method.sig.ident.to_string();
+        let syn_typ_with_lifetimes = Self::add_lifetimes_to_syn_type(&probe_name, &name, typ)?;
+
+        if let Some(native_args) =
+            native_arg::lower_probe_arg(ident, typ, &syn_typ_with_lifetimes, attr_args)?
+        {
             Ok(ProbeArgSpecification {
                 name,
                 probe_name,
                 ordinal,
                 ident: ident.clone(),
-                syn_typ,
+                syn_typ: typ.clone(),
                 syn_typ_with_lifetimes,
-                arg_type_info,
+                native_args,
             })
         } else {
             Err(TracersError::invalid_provider(
@@ -102,8 +236,10 @@ impl ProbeArgSpecification {
         &self.ident
     }
 
-    pub fn arg_type_info(&self) -> &ArgTypeInfo {
-        &self.arg_type_info
+    /// The native arguments which this argument is lowered to when it's passed to the probe.
+    /// Usually that's exactly one native argument, but see the `native_arg` module.
+    pub fn native_args(&self) -> &[NativeArgSpecification] {
+        &self.native_args
     }
 
     pub fn syn_typ(&self) -> &syn::Type {
@@ -117,18 +253,30 @@ impl ProbeArgSpecification {
     }
 
     /// Scans the argument's type information after it has been annotated with explicit lifetimes
-    /// for every reference, and returns a vector of a copy of those lifetimes by themselves,
-    /// separate from any time information
+    /// for every reference (and every `Cow`), and returns a vector of a copy of those lifetimes by
+    /// themselves, separate from any time information
     pub fn lifetimes(&self) -> Vec<syn::Lifetime> {
         let mut lifetimes = Vec::new();
 
         // Traverse the types tree, pulling out the lifetimes and putting them into a list.
         let _ = syn_helpers::transform_types(&self.syn_typ_with_lifetimes, |typ| {
             let typ = typ.clone();
-            if let syn::Type::Reference(ref tr) = typ {
-                if let Some(ref lt) = tr.lifetime {
-                    lifetimes.push(lt.clone());
+            match typ {
+                syn::Type::Reference(ref tr) => {
+                    if let Some(ref lt) = tr.lifetime {
+                        lifetimes.push(lt.clone());
+                    }
+                }
+                syn::Type::Path(ref tp) => {
+                    if let Some(args) = Self::cow_generic_args(tp) {
+                        for arg in args.args.iter() {
+                            if let syn::GenericArgument::Lifetime(ref lt) = arg {
+                                lifetimes.push(lt.clone());
+                            }
+                        }
+                    }
                 }
+                _ => {}
             }
 
             Ok(typ)
@@ -154,6 +302,9 @@ impl ProbeArgSpecification {
     /// This method returns a modified copy of the `syn` library's parse tree types, with the
     /// lifetimes added next to each reference.
     ///
+    /// `Cow` is the one supported type which isn't a reference but still has a lifetime, which is
+    /// usually elided as in `&Cow<str>`.  That elided lifetime is made explicit the same way.
+    ///
     /// Each lifetime name is unique, and is derived from both the name of the probe method and the
     /// name of the argument.  So within a provider, every lifetime parameter will have a unique
     /// name.  That's important because the `struct` we declare as part of the implementation of
@@ -198,13 +349,58 @@ impl ProbeArgSpecification {
         syn_helpers::transform_types(syn_typ, |typ: &syn::Type| {
             let mut new_typ = typ.clone();
 
-            if let syn::Type::Reference(ref mut tr) = new_typ {
-                tr.lifetime = Some(generate_lifetime(probe_name, arg_name, &typ, &mut count));
+            match new_typ {
+                syn::Type::Reference(ref mut tr) => {
+                    tr.lifetime = Some(generate_lifetime(probe_name, arg_name, &typ, &mut count));
+                }
+                syn::Type::Path(ref mut tp) => {
+                    if let Some(args) = Self::cow_generic_args_mut(tp) {
+                        let has_lifetime = args.args.iter().any(|arg| {
+                            if let syn::GenericArgument::Lifetime(_) = arg {
+                                true
+                            } else {
+                                false
+                            }
+                        });
+
+                        if !has_lifetime {
+                            let lifetime =
+                                generate_lifetime(probe_name, arg_name, &typ, &mut count);
+                            args.args
+                                .insert(0, syn::GenericArgument::Lifetime(lifetime));
+                        }
+                    }
+                }
+                _ => {}
             }
 
             Ok(new_typ)
         })
     }
+
+    /// If this type is a `Cow<..>`, gets its generic arguments
+    fn cow_generic_args(tp: &syn::TypePath) -> Option<&syn::AngleBracketedGenericArguments> {
+        match tp.path.segments.iter().last() {
+            Some(syn::PathSegment {
+                ident,
+                arguments: syn::PathArguments::AngleBracketed(args),
+            }) if ident == "Cow" => Some(args),
+            _ => None,
+        }
+    }
+
+    /// Mutable version of `cow_generic_args`
+    fn cow_generic_args_mut(
+        tp: &mut syn::TypePath,
+    ) -> Option<&mut syn::AngleBracketedGenericArguments> {
+        match tp.path.segments.iter_mut().last() {
+            Some(syn::PathSegment {
+                ident,
+                arguments: syn::PathArguments::AngleBracketed(args),
+            }) if ident == "Cow" => Some(args),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -276,6 +472,9 @@ mod test {
             test_case!(None, probe0, arg0, &String, &'probe0_arg0_1 String, 'probe0_arg0_1),
             test_case!(None, probe0, arg0, &Option<usize>, &'probe0_arg0_1 Option<usize>, 'probe0_arg0_1),
             test_case!(None, probe0, arg0, &Option<&str>, &'probe0_arg0_1 Option<&'probe0_arg0_2 str>, 'probe0_arg0_1, 'probe0_arg0_2),
+            test_case!(None, probe0, arg0, &Cow<str>, &'probe0_arg0_1 Cow<'probe0_arg0_2, str>, 'probe0_arg0_1, 'probe0_arg0_2),
+            test_case!(None, probe0, arg0, &Option<&Cow<str>>, &'probe0_arg0_1 Option<&'probe0_arg0_2 Cow<'probe0_arg0_3, str>>, 'probe0_arg0_1, 'probe0_arg0_2, 'probe0_arg0_3),
+            test_case!(None, probe0, arg0, &Arc<str>, &'probe0_arg0_1 Arc<str>, 'probe0_arg0_1),
         ]
     }
 
@@ -289,7 +488,7 @@ mod test {
             .next()
             .expect("expecting exactly one arg");
 
-        ProbeArgSpecification::from_fnarg(&method, 0, &arg)
+        ProbeArgSpecification::from_fnarg(&method, 0, &arg, &Default::default())
     }
 
     #[test]
@@ -331,4 +530,191 @@ mod test {
             assert_eq!(expected, actual, "test# {}", index);
         }
     }
+
+    #[test]
+    fn lowers_probe_args_to_native_args() {
+        use tracers_core::argtypes::CType;
+
+        let cases: Vec<(syn::TraitItemMethod, Vec<(&str, CType)>)> = vec![
+            (
+                parse_quote! { fn probe0(arg0: &str); },
+                vec![("arg0", CType::CharPtr)],
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(encoding = "truncate")] arg0: &str); },
+                vec![("arg0", CType::CharPtr)],
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(encoding = "escape")] arg0: &Option<&String>); },
+                vec![("arg0", CType::CharPtr)],
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(encoding = "ptr_len")] arg0: &CStr); },
+                vec![("arg0", CType::VoidPtr), ("arg0_len", CType::SizeT)],
+            ),
+            (
+                parse_quote! { fn probe0(arg0: u128); },
+                vec![("arg0_hi", CType::ULongLong), ("arg0_lo", CType::ULongLong)],
+            ),
+            (
+                parse_quote! { fn probe0(arg0: &Option<&i128>); },
+                vec![("arg0_hi", CType::ULongLong), ("arg0_lo", CType::ULongLong)],
+            ),
+            (
+                parse_quote! { fn probe0(arg0: &io::Error); },
+                vec![
+                    ("arg0_raw_os_error", CType::Int),
+                    ("arg0_kind", CType::CharPtr),
+                    ("arg0_message", CType::CharPtr),
+                ],
+            ),
+            (
+                parse_quote! { fn probe0(arg0: &Result<&str, u128>); },
+                vec![
+                    ("arg0_is_ok", CType::Int),
+                    ("arg0_ok", CType::CharPtr),
+                    ("arg0_err_hi", CType::ULongLong),
+                    ("arg0_err_lo", CType::ULongLong),
+                ],
+            ),
+            (
+                parse_quote! { fn probe0(arg0: &Result<&Option<&str>, Option<u32>>); },
+                vec![
+                    ("arg0_is_ok", CType::Int),
+                    ("arg0_ok", CType::CharPtr),
+                    ("arg0_err", CType::UInt),
+                ],
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(option_flag)] arg0: &Option<u32>); },
+                vec![("arg0_is_some", CType::Int), ("arg0", CType::UInt)],
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(option_flag, encoding = "ptr_len")] arg0: &Option<&str>); },
+                vec![
+                    ("arg0_is_some", CType::Int),
+                    ("arg0", CType::VoidPtr),
+                    ("arg0_len", CType::SizeT),
+                ],
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(none_value = "-1")] arg0: &Option<&i64>); },
+                vec![("arg0", CType::LongLong)],
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(option_flag, none_value = r#""none""#)] arg0: &Option<&str>); },
+                vec![("arg0_is_some", CType::Int), ("arg0", CType::CharPtr)],
+            ),
+            (
+                parse_quote! { fn probe0(arg0: &io::Result<()>); },
+                vec![
+                    ("arg0_is_ok", CType::Int),
+                    ("arg0_err_raw_os_error", CType::Int),
+                    ("arg0_err_kind", CType::CharPtr),
+                    ("arg0_err_message", CType::CharPtr),
+                ],
+            ),
+        ];
+
+        for (method, expected) in cases.into_iter() {
+            let arg = method.sig.inputs.iter().next().unwrap();
+            let arg = ProbeArgSpecification::from_fnarg(&method, 0, &arg, &Default::default())
+                .expect("unexpected error parsing arg");
+
+            let actual: Vec<_> = arg
+                .native_args()
+                .iter()
+                .map(|native_arg| {
+                    (
+                        native_arg.name(),
+                        native_arg.arg_type_info().get_c_type_enum(),
+                    )
+                })
+                .collect();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_attributes() {
+        let cases: Vec<(syn::TraitItemMethod, &str)> = vec![
+            (
+                parse_quote! { fn probe0(#[probe(encoding = "truncate")] arg0: usize); },
+                "can only be used on string arguments",
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(encoding = "ptr_len")] arg0: u128); },
+                "can only be used on string arguments",
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(encoding = "escape")] arg0: &Result<&str, ()>); },
+                "can only be used on string arguments",
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(encoding = "rot13")] arg0: &str); },
+                "rot13",
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(compression = "gzip")] arg0: &str); },
+                "compression",
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(option_flag)] arg0: u32); },
+                "can only be used on `Option` arguments",
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(none_value = "0")] arg0: &Option<&String>); },
+                "can only be used on `Option`s of primitive types",
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(none_value = "1 +")] arg0: &Option<u32>); },
+                "must be a Rust expression",
+            ),
+            (
+                parse_quote! { fn probe0(#[probe(none_value = "0", encoding = "escape")] arg0: &Option<&str>); },
+                "can't both be used",
+            ),
+        ];
+
+        for (method, expected_error) in cases.into_iter() {
+            let arg = method.sig.inputs.iter().next().unwrap();
+            let message = ProbeArgSpecification::from_fnarg(&method, 0, &arg, &Default::default())
+                .err()
+                .expect("expected an error")
+                .to_string();
+
+            assert!(
+                message.contains(expected_error),
+                "error '{}' doesn't contain '{}'",
+                message,
+                expected_error
+            );
+        }
+    }
+
+    #[test]
+    fn applies_provider_defaults() {
+        let defaults = ProbeArgAttributeArgs::provider_defaults(true);
+        let cases: Vec<(syn::TraitItemMethod, Vec<&str>)> = vec![
+            (
+                parse_quote! { fn probe0(arg0: &Option<u32>); },
+                vec!["arg0_is_some", "arg0"],
+            ),
+            //Args which aren't `Option`s are unaffected, rather than being an error
+            (parse_quote! { fn probe0(arg0: u32); }, vec!["arg0"]),
+            (
+                parse_quote! { fn probe0(#[probe(option_flag = false)] arg0: &Option<u32>); },
+                vec!["arg0"],
+            ),
+        ];
+
+        for (method, expected) in cases.into_iter() {
+            let arg = method.sig.inputs.iter().next().unwrap();
+            let arg = ProbeArgSpecification::from_fnarg(&method, 0, &arg, &defaults)
+                .expect("unexpected error parsing arg");
+
+            let actual: Vec<_> = arg.native_args().iter().map(|a| a.name()).collect();
+            assert_eq!(expected, actual);
+        }
+    }
 }
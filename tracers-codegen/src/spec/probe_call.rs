@@ -4,6 +4,8 @@ use crate::syn_helpers;
 use crate::{TracersError, TracersResult};
 use proc_macro2::TokenStream;
 use std::fmt;
+use syn::parse::{Parse, ParseStream};
+use syn::Token;
 
 /// There are two kinds of probe calls:
 ///
@@ -33,6 +35,13 @@ use std::fmt;
 ///
 /// Either call can be made on a probe.  Probes are not explicitly one kind or the other; the
 /// difference is only in how they are fired.
+///
+/// With dynamic tracing, either call can also be made on an instance of the provider, rather than
+/// the default one, by preceding the call with the instance:
+///
+/// ```no_execute
+/// probe!(instance => MyProvider::my_probe(arg0, arg1, arg2));
+/// ```
 #[derive(Debug, PartialEq)]
 pub enum ProbeCallSpecification {
     FireOnly(ProbeCallDetails),
@@ -46,10 +55,12 @@ pub enum ProbeCallSpecification {
 impl ProbeCallSpecification {
     pub fn from_token_stream(tokens: TokenStream) -> TracersResult<ProbeCallSpecification> {
         //TODO: Also try matching on a Block expression to support the `FireWithCode` variation
-        match syn::parse2::<syn::Expr>(tokens) {
-            Ok(call) => {
-                ProbeCallDetails::from_call_expression(call).map(ProbeCallSpecification::FireOnly)
-            }
+        match syn::parse2::<ProbeCallTokens>(tokens) {
+            Ok(ProbeCallTokens { instance, call }) => ProbeCallDetails::from_call_expression(call)
+                .map(|mut details| {
+                    details.instance = instance;
+                    ProbeCallSpecification::FireOnly(details)
+                }),
             Err(e) => Err(TracersError::syn_error(
                 "Expecting a Rust function call expression",
                 e,
@@ -58,6 +69,35 @@ impl ProbeCallSpecification {
     }
 }
 
+/// The tokens passed to `probe!`: an expression which should be a call to a probe, optionally
+/// preceded by the provider instance and `=>`
+struct ProbeCallTokens {
+    instance: Option<syn::Expr>,
+    call: syn::Expr,
+}
+
+impl Parse for ProbeCallTokens {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        //An expression never contains a `=>` outside of a nested block, so this reads either the
+        //call, or the instance up to the `=>`
+        let expr: syn::Expr = input.parse()?;
+
+        if input.peek(Token![=>]) {
+            input.parse::<Token![=>]>()?;
+
+            Ok(ProbeCallTokens {
+                instance: Some(expr),
+                call: input.parse()?,
+            })
+        } else {
+            Ok(ProbeCallTokens {
+                instance: None,
+                call: expr,
+            })
+        }
+    }
+}
+
 /// Contains all of the details of an invocation of a probe, already decomposed for the generators
 /// to work with
 #[derive(PartialEq)]
@@ -67,6 +107,9 @@ pub struct ProbeCallDetails {
     pub provider: syn::Path,
     pub probe: syn::PathSegment,
     pub args: Vec<syn::Expr>,
+
+    /// The provider instance on which to fire the probe, if not the default one
+    pub instance: Option<syn::Expr>,
 }
 
 impl fmt::Debug for ProbeCallDetails {
@@ -94,7 +137,13 @@ impl fmt::Debug for ProbeCallDetails {
 
         write!(f, "{}", args.join(", "))?;
 
-        write!(f, "])")
+        write!(f, "]")?;
+
+        if let Some(instance) = &self.instance {
+            write!(f, ", instance={}", syn_helpers::convert_to_string(instance))?;
+        }
+
+        write!(f, ")")
     }
 }
 
@@ -141,6 +190,7 @@ impl ProbeCallDetails {
                         provider,
                         probe,
                         args,
+                        instance: None,
                     })
                 } else {
                     Err(TracersError::invalid_call_expression(format!("Unexpected expression for function call: {}", syn_helpers::convert_to_string(&func)), func))
@@ -151,12 +201,65 @@ impl ProbeCallDetails {
             }
         }
     }
+
+    /// `probe_span!` keeps its args until the span ends, so that the exit probe can be fired with
+    /// them.  That doesn't work for an arg which borrows a temporary, like `&format!(...)`,
+    /// because the temporary is dropped at the end of the statement, which would otherwise fail
+    /// to compile with a confusing borrow checker error pointing into the generated code.  This
+    /// catches that case and explains it.
+    ///
+    /// Only args which are themselves a borrow of a temporary are detected; borrows of locals and
+    /// other places, and of constants which are promoted to `'static`, are fine.
+    pub fn check_span_args(&self) -> TracersResult<()> {
+        for arg in self.args.iter() {
+            if let syn::Expr::Reference(reference) = strip_parens(arg) {
+                if !is_place_or_constant(&reference.expr) {
+                    return Err(TracersError::invalid_call_expression(
+                        format!(
+                            "The args of `probe_span!` are kept until the span ends, but '{}' borrows a temporary value which is dropped at the end of this statement.  Assign the value to a local variable first, and pass a reference to that",
+                            syn_helpers::convert_to_string(arg)
+                        ),
+                        arg,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn strip_parens(expr: &syn::Expr) -> &syn::Expr {
+    match expr {
+        syn::Expr::Paren(paren) => strip_parens(&paren.expr),
+        syn::Expr::Group(group) => strip_parens(&group.expr),
+        other => other,
+    }
+}
+
+/// Tests if borrowing `expr` doesn't create a temporary: either it's a place expression, like a
+/// variable or a field of one, or it's a literal which the compiler promotes to a constant
+fn is_place_or_constant(expr: &syn::Expr) -> bool {
+    match strip_parens(expr) {
+        syn::Expr::Path(_) | syn::Expr::Lit(_) => true,
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Deref(_),
+            ..
+        }) => true,
+        syn::Expr::Unary(syn::ExprUnary { expr, .. }) => {
+            matches!(strip_parens(expr), syn::Expr::Lit(_))
+        }
+        syn::Expr::Field(field) => is_place_or_constant(&field.base),
+        syn::Expr::Index(index) => is_place_or_constant(&index.expr),
+        _ => false,
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::testdata::*;
+    use quote::quote;
 
     #[test]
     fn parses_all_test_cases() {
@@ -187,4 +290,37 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn checks_span_args() {
+        let details =
+            |call: TokenStream| match ProbeCallSpecification::from_token_stream(call).unwrap() {
+                ProbeCallSpecification::FireOnly(details) => details,
+                other => panic!("Unexpected call {:?}", other),
+            };
+
+        for ok in [
+            quote! { MyProvider::my_probe(1, "foo", id, path) },
+            quote! { MyProvider::my_probe(&path, &self.path, &paths[0], &*path, &(path)) },
+            quote! { MyProvider::my_probe(&5, &-1, &"foo", path.as_str()) },
+        ] {
+            assert_eq!(Ok(()), details(ok).check_span_args());
+        }
+
+        for (bad, arg) in [
+            (
+                quote! { MyProvider::my_probe(id, &format!("/{}", x)) },
+                "format !",
+            ),
+            (
+                quote! { MyProvider::my_probe(&path.to_string()) },
+                "to_string",
+            ),
+            (quote! { MyProvider::my_probe((&get().name)) }, "get ("),
+        ] {
+            let e = details(bad).check_span_args().unwrap_err();
+            assert!(e.to_string().contains(arg), "{}", e);
+            assert!(e.to_string().contains("borrows a temporary value"), "{}", e);
+        }
+    }
 }
@@ -4,6 +4,8 @@
 //! in this crate can then process them in various ways
 use crate::hashing::HashCode;
 use crate::serde_helpers;
+use crate::spec::probe;
+use crate::spec::probe_arg::ProbeArgAttributeArgs;
 use crate::spec::ProbeSpecification;
 use crate::{TracersError, TracersResult};
 use darling::FromMeta;
@@ -22,6 +24,21 @@ use syn::{ItemTrait, TraitItem};
 pub(crate) struct TracerAttributeArgs {
     #[darling(default)]
     provider_name: Option<String>,
+
+    /// If set, every `Option` probe arg is passed with a flag indicating if it's `Some`, as if it
+    /// had a `#[probe(option_flag)]` attribute.  An arg can still opt out with
+    /// `#[probe(option_flag = false)]`.
+    #[darling(default)]
+    option_flag: bool,
+
+    /// The level of every probe which doesn't have its own `#[probe(level = "...")]` attribute
+    #[darling(default)]
+    level: Option<String>,
+
+    /// What dynamic tracers with a limit on native args do with every probe which has too many
+    /// and doesn't have its own `#[probe(arg_overflow = "...")]` attribute.  Defaults to `"spill"`.
+    #[darling(default)]
+    arg_overflow: Option<String>,
 }
 
 /// Implement parsing the arguments portion of a `#[tracer]` attribute.  This does _not_ parse the
@@ -175,7 +192,21 @@ impl ProviderSpecification {
         args: TracerAttributeArgs,
         item_trait: ItemTrait,
     ) -> TracersResult<ProviderSpecification> {
-        let probes = find_probes(&item_trait)?;
+        let defaults = ProbeArgAttributeArgs::provider_defaults(args.option_flag);
+        let mut probes = find_probes(&item_trait, &defaults)?;
+        if let Some(ref level) = args.level {
+            let level = probe::parse_probe_level(level, &item_trait.ident)?;
+            for probe in probes.iter_mut() {
+                probe.level = probe.level.or(Some(level));
+            }
+        }
+        let arg_overflow = match args.arg_overflow {
+            Some(ref arg_overflow) => probe::parse_arg_overflow(arg_overflow, &item_trait.ident)?,
+            None => probe::ArgOverflow::Spill,
+        };
+        for probe in probes.iter_mut() {
+            probe.arg_overflow = probe.arg_overflow.or(Some(arg_overflow));
+        }
         let token_stream = quote! { #item_trait };
         let hash = crate::hashing::hash(&item_trait);
 
@@ -342,7 +373,10 @@ pub(crate) fn find_providers(crate_name: &str, ast: &syn::File) -> Vec<ProviderS
 ///
 /// If the trait contains anything other than method declarations, or any of the declarations are
 /// not suitable as probes, an error is returned
-fn find_probes(item: &ItemTrait) -> TracersResult<Vec<ProbeSpecification>> {
+fn find_probes(
+    item: &ItemTrait,
+    defaults: &ProbeArgAttributeArgs,
+) -> TracersResult<Vec<ProbeSpecification>> {
     if item.generics.type_params().next() != None || item.generics.lifetimes().next() != None {
         return Err(TracersError::invalid_provider(
             "Probe traits must not take any lifetime or type parameters",
@@ -355,7 +389,7 @@ fn find_probes(item: &ItemTrait) -> TracersResult<Vec<ProbeSpecification>> {
     for f in item.items.iter() {
         match f {
             TraitItem::Method(ref m) => {
-                specs.push(ProbeSpecification::from_method(item, m)?);
+                specs.extend(ProbeSpecification::from_trait_method(item, m, defaults)?);
             }
             _ => {
                 return Err(TracersError::invalid_provider(
@@ -372,9 +406,11 @@ fn find_probes(item: &ItemTrait) -> TracersResult<Vec<ProbeSpecification>> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::spec::ArgOverflow;
     use crate::testdata::*;
     use std::io::{BufReader, BufWriter};
     use syn::parse_quote;
+    use tracers_core::ProbeLevel;
 
     impl PartialEq<ProviderSpecification> for ProviderSpecification {
         fn eq(&self, other: &ProviderSpecification) -> bool {
@@ -447,7 +483,7 @@ mod test {
                 #trait_decl
             };
 
-            let error = find_probes(&item_trait).err();
+            let error = find_probes(&item_trait, &Default::default()).err();
             assert_ne!(
                 None, error,
                 "The invalid trait '{}' was returned by find_probes as valid",
@@ -474,11 +510,98 @@ mod test {
                 #trait_decl
             };
 
-            let probes = find_probes(&item_trait).unwrap();
+            let probes = find_probes(&item_trait, &Default::default()).unwrap();
             assert_eq!(probes, test_trait.probes.unwrap_or_default());
         }
     }
 
+    #[test]
+    fn applies_provider_level() {
+        let provider = ProviderSpecification::from_trait(
+            TEST_CRATE_NAME,
+            parse_quote! { #[tracer(level = "notice")] },
+            parse_quote! {
+                trait ProviderTrait {
+                    fn probe0();
+                    #[probe(level = "critical")]
+                    fn probe1();
+                }
+            },
+        )
+        .unwrap();
+        let levels: Vec<_> = provider.probes().iter().map(|probe| probe.level).collect();
+        assert_eq!(
+            vec![Some(ProbeLevel::Notice), Some(ProbeLevel::Critical)],
+            levels
+        );
+
+        let error = ProviderSpecification::from_trait(
+            TEST_CRATE_NAME,
+            parse_quote! { #[tracer(level = "loud")] },
+            parse_quote! { trait ProviderTrait { fn probe0(); } },
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("The `level` option must be"));
+    }
+
+    #[test]
+    fn applies_provider_arg_overflow() {
+        let probes = r#"
+            trait ProviderTrait {
+                fn probe0();
+                #[probe(arg_overflow = "reject")]
+                fn probe1();
+                #[probe(span, arg_overflow = "spill")]
+                fn probe2();
+            }
+        "#;
+        let arg_overflows = |attr: TracerAttribute| {
+            let provider = ProviderSpecification::from_trait(
+                TEST_CRATE_NAME,
+                attr,
+                syn::parse_str(probes).unwrap(),
+            )
+            .unwrap();
+            provider
+                .probes()
+                .iter()
+                .map(|probe| probe.arg_overflow.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        //Providers spill unless told otherwise
+        assert_eq!(
+            vec![
+                ArgOverflow::Spill,
+                ArgOverflow::Reject,
+                ArgOverflow::Spill,
+                ArgOverflow::Spill
+            ],
+            arg_overflows(parse_quote! { #[tracer] })
+        );
+        assert_eq!(
+            vec![
+                ArgOverflow::Reject,
+                ArgOverflow::Reject,
+                ArgOverflow::Spill,
+                ArgOverflow::Spill
+            ],
+            arg_overflows(parse_quote! { #[tracer(arg_overflow = "reject")] })
+        );
+
+        let error = ProviderSpecification::from_trait(
+            TEST_CRATE_NAME,
+            parse_quote! { #[tracer(arg_overflow = "drop")] },
+            parse_quote! { trait ProviderTrait { fn probe0(); } },
+        )
+        .err()
+        .unwrap();
+        assert!(error
+            .to_string()
+            .contains("The `arg_overflow` option must be"));
+    }
+
     #[test]
     fn found_providers_have_same_hash() {
         //There are two ways for us to get a ProviderSpecification:
@@ -155,6 +155,34 @@ pub(crate) fn convert_to_string<T: quote::ToTokens>(item: &T) -> String {
     tokenstream.to_string()
 }
 
+/// Like `convert_to_string`, but without the spaces `proc_macro2` puts between every token, so
+/// `&Option<&'a str>` comes out as written rather than as `& Option < & 'a str >`.  Spaces are
+/// kept only where they separate two words, like `mut T` or `dyn Trait`, and after commas.
+///
+/// Used where the string is shown to users at runtime, like the runtime registry of providers.
+pub(crate) fn convert_to_compact_string<T: quote::ToTokens>(item: &T) -> String {
+    let spaced = convert_to_string(item);
+    let chars: Vec<char> = spaced.chars().collect();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut compact = String::with_capacity(chars.len());
+    for (index, c) in chars.iter().enumerate() {
+        if *c == ' ' {
+            let prev = compact.chars().last();
+            let next = chars.get(index + 1);
+            match (prev, next) {
+                (Some(','), _) => {}
+                (Some(prev), Some(next)) if is_word_char(prev) && is_word_char(*next) => {}
+                _ => continue,
+            }
+        }
+
+        compact.push(*c);
+    }
+
+    compact
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -223,4 +251,24 @@ mod test {
             assert_eq!(expected_tokens.to_string(), actual_tokens.to_string());
         }
     }
+
+    #[test]
+    fn compact_strings() {
+        let cases: Vec<(syn::Type, &str)> = vec![
+            (parse_quote! { usize }, "usize"),
+            (parse_quote! { &str }, "&str"),
+            (
+                parse_quote! { &'a mut Option<&'b str> },
+                "&'a mut Option<&'b str>",
+            ),
+            (
+                parse_quote! { &Result<std::io::Error, (u32, i64)> },
+                "&Result<std::io::Error, (u32, i64)>",
+            ),
+        ];
+
+        for (typ, expected) in cases.into_iter() {
+            assert_eq!(expected, convert_to_compact_string(&typ));
+        }
+    }
 }
@@ -16,12 +16,15 @@ use fs_extra::{copy_items, dir};
 use lazy_static::lazy_static;
 use proc_macro2::TokenStream;
 use quote::quote;
+use std::borrow::Cow;
 use std::env;
 use std::fmt;
 #[cfg(not(target_os = "windows"))]
 use std::fs::canonicalize; //on non-Windows just use the built-in function
+use std::num::NonZeroU32;
 use std::path::PathBuf;
-use std::sync::{Mutex, MutexGuard};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 use tempfile::tempdir;
 use tracers_core::argtypes::{CType, ProbeArgNativeTypeInfo, ProbeArgType, ProbeArgWrapper};
 
@@ -370,6 +373,279 @@ pub(crate) fn get_test_provider_traits<F: FnMut(&TestProviderTrait) -> bool>(
                 ),
             ],
         ),
+        TestProviderTrait::new_valid(
+            "valid with smart string types",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    fn probe0(arg0: &Cow<str>, arg1: &Box<str>, arg2: &Arc<str>);
+                    fn probe1(arg0: &Option<&Cow<str>>, arg1: &Option<&Arc<str>>);
+                }
+            },
+            vec![
+                TestProbe::new(
+                    "probe0",
+                    vec![
+                        probe_arg!("arg0", &Cow<str>),
+                        probe_arg!("arg1", &Box<str>),
+                        probe_arg!("arg2", &Arc<str>),
+                    ],
+                ),
+                TestProbe::new(
+                    "probe1",
+                    vec![
+                        probe_arg!("arg0", &Option<&Cow<str>>),
+                        probe_arg!("arg1", &Option<&Arc<str>>),
+                    ],
+                ),
+            ],
+        ),
+        TestProviderTrait::new_valid(
+            "valid with wide and special integer types",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    fn probe0(arg0: char, arg1: NonZeroU32, arg2: Duration);
+                    fn probe1(arg0: &Option<NonZeroU32>, arg1: &Duration);
+                    fn probe2(arg0: u128, arg1: &Option<&i128>);
+                }
+            },
+            vec![
+                TestProbe::new(
+                    "probe0",
+                    vec![
+                        probe_arg!("arg0", char),
+                        probe_arg!("arg1", NonZeroU32),
+                        probe_arg!("arg2", Duration),
+                    ],
+                ),
+                TestProbe::new(
+                    "probe1",
+                    vec![
+                        probe_arg!("arg0", &Option<NonZeroU32>),
+                        probe_arg!("arg1", &Duration),
+                    ],
+                ),
+                //128-bit ints aren't `ProbeArgType`s; each is passed as two `u64`s
+                TestProbe::new(
+                    "probe2",
+                    vec![
+                        ("arg0", "u128", CType::ULongLong),
+                        ("arg1", "&Option<&i128>", CType::ULongLong),
+                    ],
+                ),
+            ],
+        ),
+        TestProviderTrait::new_valid(
+            "valid with results and io errors",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    fn probe0(arg0: &Result<u32, &str>);
+                    fn probe1(arg0: &io::Result<()>, arg1: &io::Error);
+                    fn probe2(arg0: &Result<String, std::io::Error>, arg1: usize);
+                }
+            },
+            vec![
+                //`Result` and `io::Error` aren't `ProbeArgType`s; each is lowered into several
+                //native args, the first of which is either the `is_ok` flag or the OS error code
+                TestProbe::new("probe0", vec![("arg0", "&Result<u32, &str>", CType::Int)]),
+                TestProbe::new(
+                    "probe1",
+                    vec![
+                        ("arg0", "&io::Result<()>", CType::Int),
+                        ("arg1", "&io::Error", CType::Int),
+                    ],
+                ),
+                TestProbe::new(
+                    "probe2",
+                    vec![
+                        ("arg0", "&Result<String, std::io::Error>", CType::Int),
+                        probe_arg!("arg1", usize),
+                    ],
+                ),
+            ],
+        ),
+        TestProviderTrait::new_valid(
+            "valid with string encodings",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    fn probe0(#[probe(encoding = "truncate")] arg0: &str);
+                    fn probe1(#[probe(encoding = "escape")] arg0: &String, arg1: usize);
+                    fn probe2(#[probe(encoding = "ptr_len")] arg0: &Option<&str>, arg1: &str);
+                }
+            },
+            vec![
+                TestProbe::new("probe0", vec![probe_arg!("arg0", &str)]),
+                TestProbe::new(
+                    "probe1",
+                    vec![probe_arg!("arg0", &String), probe_arg!("arg1", usize)],
+                ),
+                TestProbe::new(
+                    "probe2",
+                    vec![probe_arg!("arg0", &Option<&str>), probe_arg!("arg1", &str)],
+                ),
+            ],
+        ),
+        TestProviderTrait::new_valid(
+            "valid with option flags and none values",
+            "test_provider_trait",
+            quote! { #[tracer(option_flag)] },
+            quote! {
+                trait ProviderTrait {
+                    fn probe0(arg0: &Option<u32>, arg1: u32);
+                    fn probe1(#[probe(option_flag = false)] arg0: &Option<&str>);
+                    fn probe2(#[probe(option_flag = false, none_value = "-1")] arg0: &Option<i64>);
+                }
+            },
+            vec![
+                //With the provider-level `option_flag`, `probe0`'s `arg0` is lowered into an
+                //`is_some` flag followed by its value; the other args are unaffected
+                TestProbe::new(
+                    "probe0",
+                    vec![probe_arg!("arg0", &Option<u32>), probe_arg!("arg1", u32)],
+                ),
+                TestProbe::new("probe1", vec![probe_arg!("arg0", &Option<&str>)]),
+                TestProbe::new("probe2", vec![probe_arg!("arg0", &Option<i64>)]),
+            ],
+        ),
+        TestProviderTrait::new_valid(
+            "valid with throttled probes",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    #[probe(sample = 100)]
+                    fn probe0(arg0: u32);
+                    #[probe(rate_limit = "1000/s")]
+                    fn probe1();
+                    fn probe2(arg0: &str);
+                }
+            },
+            vec![
+                //Throttled probes are passed an extra `suppressed` native arg after their own
+                TestProbe::new("probe0", vec![probe_arg!("arg0", u32)]),
+                TestProbe::new("probe1", vec![]),
+                TestProbe::new("probe2", vec![probe_arg!("arg0", &str)]),
+            ],
+        ),
+        TestProviderTrait::new_valid(
+            "valid with span probes",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    #[probe(span)]
+                    fn probe0(arg0: u64, arg1: &str);
+                    fn probe1(arg0: u64);
+                }
+            },
+            vec![
+                //A span probe is an enter probe and an exit probe, which is also passed the elapsed
+                //time
+                TestProbe::new(
+                    "probe0_enter",
+                    vec![probe_arg!("arg0", u64), probe_arg!("arg1", &str)],
+                ),
+                TestProbe::new(
+                    "probe0_exit",
+                    vec![
+                        probe_arg!("arg0", u64),
+                        probe_arg!("arg1", &str),
+                        probe_arg!("elapsed_ns", u64),
+                    ],
+                ),
+                TestProbe::new("probe1", vec![probe_arg!("arg0", u64)]),
+            ],
+        ),
+        TestProviderTrait::new_valid(
+            "valid with probe levels",
+            "test_provider_trait",
+            quote! { #[tracer(level = "warning")] },
+            quote! {
+                trait ProviderTrait {
+                    #[probe(level = "error", emf_uri = "platform:/resource/model.emf#//ProviderTrait")]
+                    fn probe0(arg0: u32);
+                    fn probe1();
+                    #[probe(span, level = "debug")]
+                    fn probe2(arg0: &str);
+                }
+            },
+            vec![
+                //Probes without a level of their own get the provider's level, which only affects
+                //the native code and the registry, not the probes' args
+                TestProbe::new("probe0", vec![probe_arg!("arg0", u32)]),
+                TestProbe::new("probe1", vec![]),
+                TestProbe::new("probe2_enter", vec![probe_arg!("arg0", &str)]),
+                TestProbe::new(
+                    "probe2_exit",
+                    vec![probe_arg!("arg0", &str), probe_arg!("elapsed_ns", u64)],
+                ),
+            ],
+        ),
+        TestProviderTrait::new_invalid(
+            "has an invalid probe level",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    #[probe(level = "warn")]
+                    fn probe0(arg0: u32);
+                }
+            },
+            "The `level` option must be one of",
+        ),
+        TestProviderTrait::new_invalid(
+            "has a throttled span probe",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    #[probe(span, sample = 10)]
+                    fn probe0(arg0: u64);
+                }
+            },
+            "Span probes can't be throttled",
+        ),
+        TestProviderTrait::new_invalid(
+            "has an invalid rate limit",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    #[probe(rate_limit = "1000 per second")]
+                    fn probe0(arg0: u32);
+                }
+            },
+            "The `rate_limit` option must be",
+        ),
+        TestProviderTrait::new_invalid(
+            "has an option flag on a non-Option parameter",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    fn probe0(#[probe(option_flag)] arg0: &str);
+                }
+            },
+            "can only be used on `Option` arguments",
+        ),
+        TestProviderTrait::new_invalid(
+            "has an encoding on a non-string parameter",
+            "test_provider_trait",
+            default_attr_tokenstream.clone(),
+            quote! {
+                trait ProviderTrait {
+                    fn probe0(#[probe(encoding = "escape")] arg0: i32);
+                }
+            },
+            "can only be used on string arguments",
+        ),
         TestProviderTrait::new_invalid(
             "has trait type param",
             "test_provider_trait",
@@ -550,12 +826,12 @@ pub(crate) fn get_test_provider_traits<F: FnMut(&TestProviderTrait) -> bool>(
             "is not supported for probing",
         ),
         TestProviderTrait::new_invalid(
-            "has a Result parameter which is not supported",
+            "has a Result parameter with a payload which is not supported",
             "test_provider_trait",
             default_attr_tokenstream.clone(),
             quote! {
                 trait ProviderTrait {
-                    fn probe0(arg0: &Result<&str, &str>);
+                    fn probe0(arg0: &Result<&str, Vec<u8>>);
                 }
             },
             "is not supported for probing",
@@ -573,32 +849,44 @@ pub(crate) fn get_test_provider_traits<F: FnMut(&TestProviderTrait) -> bool>(
 /// Helper macro to help declare test probe calls
 /// TODO: Add support for FireWithCode variations
 macro_rules! test_probe_call {
+    (@expected $instance:expr, $provider:path, $probe:path, $($arg:expr),*) => {
+        Ok(
+            ProbeCallSpecification::FireOnly(
+                ProbeCallDetails {
+                    call: {
+                        match ::syn::parse2::<syn::Expr>(quote! { $provider::$probe($($arg),*) }).unwrap(){
+                            syn::Expr::Call(call) => call,
+                            _ => {
+                                assert!(false, "The impossible happened!");
+                                unimplemented!()
+                            }
+                        }
+                    },
+                    probe_fq_path: ::syn::parse2::<syn::Path>(quote! { $provider::$probe }).unwrap(),
+                    provider: ::syn::parse2::<syn::Path>(quote! { $provider }).unwrap(),
+                    probe: ::syn::parse2::<syn::PathSegment>(quote! { $probe }).unwrap(),
+                    args: vec![
+                        $(
+                            syn::parse2::<syn::Expr>(quote! { $arg }).unwrap()
+                            ),*
+                    ],
+                    instance: $instance,
+                }
+                )
+            )
+    };
+
+    ($instance:expr => $call:expr, @result $provider:path, $probe:path, $($arg:expr),*) => {
+        TestProbeCall {
+            call: quote! { $instance => $call },
+            expected: test_probe_call!(@expected Some(syn::parse2::<syn::Expr>(quote! { $instance }).unwrap()), $provider, $probe, $($arg),*)
+        }
+    };
+
     ($call:expr, @result $provider:path, $probe:path, $($arg:expr),*) => {
         TestProbeCall {
             call: quote! { $call },
-            expected: Ok(
-                ProbeCallSpecification::FireOnly(
-                    ProbeCallDetails {
-                        call: {
-                            match ::syn::parse2::<syn::Expr>(quote! { $provider::$probe($($arg),*) }).unwrap(){
-                                syn::Expr::Call(call) => call,
-                                _ => {
-                                    assert!(false, "The impossible happened!");
-                                    unimplemented!()
-                                }
-                            }
-                        },
-                        probe_fq_path: ::syn::parse2::<syn::Path>(quote! { $provider::$probe }).unwrap(),
-                        provider: ::syn::parse2::<syn::Path>(quote! { $provider }).unwrap(),
-                        probe: ::syn::parse2::<syn::PathSegment>(quote! { $probe }).unwrap(),
-                        args: vec![
-                            $(
-                                syn::parse2::<syn::Expr>(quote! { $arg }).unwrap()
-                                ),*
-                        ]
-                    }
-                    )
-                )
+            expected: test_probe_call!(@expected None, $provider, $probe, $($arg),*)
         }
     };
 
@@ -612,6 +900,12 @@ macro_rules! test_probe_call {
             expected: Err($error_msg)
         }
     };
+    ($instance:expr => $call:expr, @error $error_msg:expr) => {
+        TestProbeCall {
+            call: quote! { $instance => $call },
+            expected: Err($error_msg)
+        }
+    };
 }
 
 pub(crate) fn get_test_probe_calls() -> Vec<TestProbeCall> {
@@ -624,11 +918,14 @@ pub(crate) fn get_test_probe_calls() -> Vec<TestProbeCall> {
         test_probe_call!(MyProvider::my_probe(somefunc(arg1, arg2, arg3)), @result MyProvider, my_probe, somefunc(arg1, arg2, arg3)),
         test_probe_call!(MyProvider::my_probe(arg0, arg1, arg3), @result MyProvider, my_probe, arg0, arg1, arg3),
         test_probe_call!(my_module::my_othermodule::my_foomodule::MyProvider::my_probe(arg0), @result my_module::my_othermodule::my_foomodule::MyProvider, my_probe, arg0),
+        test_probe_call!(instance => MyProvider::my_probe(arg0), @result MyProvider, my_probe, arg0),
+        test_probe_call!(tenants.get("acme").unwrap() => MyProvider::my_probe(arg0, arg1), @result MyProvider, my_probe, arg0, arg1),
         //various kinds of errors
         test_probe_call!(not_even_a_function_call, @error "requires the name of a provider trait and its probe method"),
         test_probe_call!(missing_provider(), @error "is missing the name of the provider trait"),
         test_probe_call!(MyProvider::not_even_a_function_call, @error "requires the name of a provider trait and its probe method"),
         test_probe_call!({ MyProvider::my_probe() }, @error "requires the name of a provider trait and its probe method"),
+        test_probe_call!(instance => not_even_a_function_call, @error "requires the name of a provider trait and its probe method"),
     ]
 }
 
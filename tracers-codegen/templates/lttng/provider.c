@@ -0,0 +1,8 @@
+
+#define TRACEPOINT_CREATE_PROBES
+/*
+ * The header containing our TRACEPOINT_EVENTs.
+ */
+#define TRACEPOINT_DEFINE
+#include "{{header_file_name}}"
+{# This comment keeps the trailing newline, which askama would otherwise drop #}
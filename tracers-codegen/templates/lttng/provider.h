@@ -0,0 +1,17 @@
+
+#undef TRACEPOINT_PROVIDER
+#define TRACEPOINT_PROVIDER {{provider_name}}
+
+#undef TRACEPOINT_INCLUDE
+#define TRACEPOINT_INCLUDE "./{{header_file_name}}"
+
+#if !defined({{include_guard}}) || defined(TRACEPOINT_HEADER_MULTI_READ)
+#define {{include_guard}}
+
+#include <lttng/tracepoint.h>
+
+{{tracepoint_events}}
+#endif /* {{include_guard}} */
+
+#include <lttng/tracepoint-event.h>
+{# This comment keeps the trailing newline, which askama would otherwise drop #}
@@ -9,30 +9,45 @@
  * {{spec.token_stream().to_string()}}
  * ```
  */
+{% if dtrace_header.is_some() %}
+/* The probes and their semaphores are declared in the header which SystemTap's `dtrace -h`
+ * generated from the provider's `.d` file.  The semaphores are defined in the object file which
+ * `dtrace -G` generated from it. */
+#include "{{ self.get_dtrace_header() }}"
+{% else %}
 #define _SDT_HAS_SEMAPHORES 1
 
 
 #define STAP_HAS_SEMAPHORES 1 /* deprecated */
 
 {% include "sys_sdt.h" %}
+{% endif %}
 
 /* The C-callable wrapper functions which the Rust bindings will invoke in order to fire the probes */
 extern "C" {
 {% for probe_spec in spec.probes() %}
+    {% let args = probe_spec.native_args() %}
 
     /* A C function which fires the {{spec.name()}} probe {{probe_spec.name}} */
     void {{spec.name_with_hash()}}_{{probe_spec.name}}(
-	{%for arg in probe_spec.args %}{{ arg.arg_type_info().get_c_type_str() }} {{ arg.name() }}{% if !loop.last %}, {% endif %}{%endfor%}
+	{%for arg in args %}{{ arg.arg_type_info().get_c_type_str() }} {{ arg.name() }}{% if !loop.last %}, {% endif %}{%endfor%}
     ) {
-	STAP_PROBE{% if probe_spec.args.len() > 0 %}{{ probe_spec.args.len() }}{% endif %}(
+{% if dtrace_header.is_some() %}
+	{{ self.get_dtrace_probe_macro(probe_spec) }}(
+	    {% for arg in args %}{{ arg.name() }}{% if !loop.last %}, {% endif %}{%endfor%}
+	);
+    }
+{% else %}
+	STAP_PROBE{% if args.len() > 0 %}{{ args.len() }}{% endif %}(
 	    {{ spec.name() }},
 	    {{ probe_spec.name }}
-	    {% for arg in probe_spec.args %}, {{ arg.name() }}{%endfor%}
+	    {% for arg in args %}, {{ arg.name() }}{%endfor%}
 	);
     }
 
     /* The semaphore which will be incremented if the probe is enabled */
     __extension__ unsigned short {{spec.name()}}_{{probe_spec.name}}_semaphore __attribute__ ((unused)) __attribute__ ((section (".probes"))) __attribute__ ((visibility ("hidden")));
+{% endif %}
 
 {% endfor %}
 }
@@ -18,6 +18,8 @@ fn main() -> Fallible<()> {
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("probe_args.rs");
     let dest_tests_path = Path::new(&out_dir).join("probe_args_tests.rs");
+    let dest_tee_path = Path::new(&out_dir).join("tee_probe.rs");
+    let dest_callback_path = Path::new(&out_dir).join("callback_probe.rs");
     let mut f = File::create(&dest_path)?;
     let mut f_tests = File::create(&dest_tests_path)?;
 
@@ -29,6 +31,8 @@ fn main() -> Fallible<()> {
     f.write_all(generate_unsafe_provider_probe_impl_trait().as_bytes())?;
     f.write_all(generate_unsafe_provider_probe_native_impl_trait().as_bytes())?;
     f_tests.write_all(generate_tests().as_bytes())?;
+    File::create(&dest_tee_path)?.write_all(generate_tee_probe_impl().as_bytes())?;
+    File::create(&dest_callback_path)?.write_all(generate_callback_probe_impl().as_bytes())?;
 
     Ok(())
 }
@@ -82,7 +86,8 @@ fn generate_probe_args_impl(type_params: &Vec<String>) -> String {
     "#,
         type_list = type_params.join(","),
         tuple_type = make_tuple_type(&type_params),
-        args_where_clause = xform_types(&type_params, |x| format!("{t}: ProbeArgType<{t}>", t=x)).join(","),
+        args_where_clause =
+            xform_types(&type_params, |x| format!("{t}: ProbeArgType<{t}>", t = x)).join(","),
         arg_count = type_params.len(),
         ctypes = xform_types(&type_params, |x| format!("get_ctype::<{}>()", x)).join(","),
         probe_args = probe_args.join(",")
@@ -241,6 +246,116 @@ fn generate_unsafe_provider_probe_native_impl_trait() -> String {
     decl
 }
 
+/// Generates the implementation of `UnsafeProviderProbeNativeImpl` for `TeeProbe`.  The args are
+/// converted to their C representation once, by the blanket `UnsafeProviderProbeImpl` impl, and
+/// then passed on to the `c_fireN` method of whichever of the two wrapped probes are enabled.
+fn generate_tee_probe_impl() -> String {
+    let mut decl = r#"
+        #[allow(clippy::too_many_arguments)]
+        impl<A: UnsafeProviderProbeNativeImpl, B: UnsafeProviderProbeNativeImpl> UnsafeProviderProbeNativeImpl for TeeProbe<A, B>
+        {
+            fn is_enabled(&self) -> bool {
+                UnsafeProviderProbeNativeImpl::is_enabled(self.first())
+                    || UnsafeProviderProbeNativeImpl::is_enabled(self.second())
+            }
+
+            unsafe fn c_fire0(&self) {
+                if UnsafeProviderProbeNativeImpl::is_enabled(self.first()) {
+                    self.first().c_fire0();
+                }
+                if UnsafeProviderProbeNativeImpl::is_enabled(self.second()) {
+                    self.second().c_fire0();
+                }
+            }
+    "#
+    .to_string();
+
+    for arity in 1..=MAX_ARITY {
+        //For every possible arity level `N`, implement `c_fireN` by passing the native args to the
+        //`c_fireN` method of each enabled probe; the first gets copies of them
+        let type_params = get_type_param_names(arity);
+        let copied_args = xform_types_i(&type_params, |i, _| format!("copy_native(&arg{})", i));
+        let probe_args = xform_types_i(&type_params, |i, _| format!("arg{}", i));
+
+        decl += &format!(
+            r##"
+            unsafe fn c_fire{arg_count}<{type_list}>(&self, {args})
+                where {where_clause} {{
+                if UnsafeProviderProbeNativeImpl::is_enabled(self.first()) {{
+                    self.first().c_fire{arg_count}({copied_args});
+                }}
+                if UnsafeProviderProbeNativeImpl::is_enabled(self.second()) {{
+                    self.second().c_fire{arg_count}({probe_args});
+                }}
+            }}
+            "##,
+            arg_count = type_params.len(),
+            type_list = type_params.join(","),
+            args = xform_types_i(&type_params, |i, x| format!("arg{}: {}", i, x)).join(","),
+            where_clause = xform_types(&type_params, |x| format!(
+                "{t}: ProbeArgNativeType<{t}>",
+                t = x
+            ))
+            .join(","),
+            copied_args = copied_args.join(","),
+            probe_args = probe_args.join(","),
+        );
+    }
+
+    decl += "}\n";
+
+    decl
+}
+
+/// Generates the implementation of `UnsafeProviderProbeNativeImpl` for `CallbackProbe`.  Each of
+/// the native args is widened to 64 bits, so that all of the `c_fireN` methods can pass their args
+/// to the callback the same way.
+fn generate_callback_probe_impl() -> String {
+    let mut decl = r#"
+        #[allow(clippy::too_many_arguments)]
+        impl UnsafeProviderProbeNativeImpl for CallbackProbe
+        {
+            fn is_enabled(&self) -> bool {
+                ENABLED.load(Ordering::Acquire)
+            }
+
+            unsafe fn c_fire0(&self) {
+                self.fire_registers(&[]);
+            }
+    "#
+    .to_string();
+
+    for arity in 1..=MAX_ARITY {
+        let type_params = get_type_param_names(arity);
+
+        decl += &format!(
+            r##"
+            unsafe fn c_fire{arg_count}<{type_list}>(&self, {args})
+                where {where_clause} {{
+                self.fire_registers(&[{registers}]);
+            }}
+            "##,
+            arg_count = type_params.len(),
+            type_list = type_params.join(","),
+            args = xform_types_i(&type_params, |i, x| format!("arg{}: {}", i, x)).join(","),
+            where_clause = xform_types(&type_params, |x| format!(
+                "{t}: ProbeArgNativeType<{t}>",
+                t = x
+            ))
+            .join(","),
+            registers = xform_types_i(&type_params, |i, _| format!(
+                "register_from_native(arg{})",
+                i
+            ))
+            .join(","),
+        );
+    }
+
+    decl += "}\n";
+
+    decl
+}
+
 /// This doesn't generate ALL test code, but it generates some test helpers that the `probes` module will use.
 fn generate_tests() -> String {
     [
@@ -8,35 +8,54 @@
 //!
 //! This library provides implementations for all of the following:
 //!
-//! * All integer types from `u8/i8` to `u64/i64`
+//! * All integer types from `u8/i8` to `u64/i64`, and the corresponding `NonZero*` types
 //! * `bool` (passed as an `i32` `1` means `true` and `0` means `false`)
+//! * `char` (passed as its Unicode scalar value in a `u32`)
+//! * `Duration` (passed as a `u64` number of nanoseconds)
 //! * String references `&str`
 //! * C-style string references `&CStr`
+//! * String references wrapped in one of the encodings in `string_encoding`, for strings which may
+//! contain NUL bytes
 //! * `Option<T>` for any `T` which is itself a supported probe argument type and implements `Copy`
 //! * Any pointer type, which is passed as either a 32- or 64-bit unsigned int depending upon
 //! architecture
 //!
+//! `std::io::Error` is passed as its raw OS error code, kind and message using the helpers in
+//! `io_error`, and `Result<T, E>` is passed as an `is_ok` flag followed by the `T` and `E` payloads.
+//! The generated probing code does this lowering; neither type implements `ProbeArgType`.
+//!
+//! `u128` and `i128` don't have a C type at all, and so don't implement `ProbeArgType`.  Instead the
+//! generated probing code splits them into two `u64` probe arguments using `ProbeArgWideInteger`.
+//!
 //!
 use std::fmt::Debug;
 use strum_macros::{Display, EnumString, IntoStaticStr};
 
 pub mod bool;
+pub mod char;
 pub mod cstring;
 pub mod int;
+pub mod io_error;
 pub mod native;
 pub mod option;
 pub mod pointer;
 pub mod refs;
 pub mod string;
+pub mod string_encoding;
+pub mod time;
 
 pub use self::bool::*;
+pub use self::char::*;
 pub use cstring::*;
 pub use int::*;
+pub use io_error::*;
 pub use native::*;
 pub use option::*;
 pub use pointer::*;
 pub use refs::*;
 pub use string::*;
+pub use string_encoding::*;
+pub use time::*;
 
 #[derive(Display, Debug, Clone, PartialEq, Hash, Eq, IntoStaticStr, EnumString)]
 pub enum CType {
@@ -124,6 +143,10 @@ where
 
     /// This is ugly but unavoidable.  The underlying C type for an Opt<T> is the same C type as T.
     /// We will use the default value for T to indicate a value of None.  That will have to be good enough.
+    ///
+    /// When it isn't, a probe arg can opt in to `#[probe(option_flag)]`, which passes an extra
+    /// `is_some` flag ahead of the value, or `#[probe(none_value = "...")]`, which passes a
+    /// sentinel of the caller's choosing instead of this default.
     fn default_c_value() -> Self::CType {
         <Self::CType as ProbeArgNativeType<Self::CType>>::get_default_value()
     }
@@ -0,0 +1,29 @@
+//! Implements `ProbeArgType` for `char`, which is passed to probes as its Unicode scalar value.  Note
+//! that this is not the same as a C `char`; every Rust `char` fits in a C `unsigned int`.
+use super::ProbeArgType;
+
+impl ProbeArgType<char> for char {
+    type WrapperType = u32;
+    fn wrap(arg: char) -> Self::WrapperType {
+        u32::from(arg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{wrap, ProbeArgWrapper};
+    use std::convert::TryFrom;
+
+    #[quickcheck]
+    fn as_c_type(x: char) -> bool {
+        let c_value = wrap(x).as_c_type();
+
+        char::try_from(c_value) == Ok(x)
+    }
+
+    #[test]
+    fn option_char() {
+        assert_eq!(0x1f600, wrap(&Some('😀')).as_c_type());
+        assert_eq!(0, wrap(&None::<char>).as_c_type());
+    }
+}
@@ -1,4 +1,8 @@
 use super::{ProbeArgType, ProbeArgWrapper};
+use std::num::{
+    NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU16, NonZeroU32,
+    NonZeroU64, NonZeroU8, NonZeroUsize,
+};
 
 #[cfg(test)]
 extern crate quickcheck;
@@ -50,3 +54,106 @@ impl_integer_arg_type!(u16, std::os::raw::c_ushort, u16_test);
 impl_integer_arg_type!(i16, std::os::raw::c_short, i16_test);
 impl_integer_arg_type!(u8, std::os::raw::c_uchar, u8_test);
 impl_integer_arg_type!(i8, std::os::raw::c_char, i8_test);
+
+// The `NonZero*` types are passed as the primitive integer they wrap.  If they're wrapped in an
+// `Option`, `None` is passed as `0`, which is conveniently never a valid value otherwise.
+macro_rules! impl_nonzero_arg_type {
+    ( $rust_type:ident, $primitive_type:ty, $tests:ident ) => {
+        impl ProbeArgType<$rust_type> for $rust_type {
+            type WrapperType = $primitive_type;
+
+            fn wrap(arg: $rust_type) -> Self::WrapperType {
+                arg.get()
+            }
+        }
+
+        #[cfg(test)]
+        mod $tests {
+            use crate::{wrap, ProbeArgWrapper};
+            use std::num::$rust_type;
+
+            #[quickcheck]
+            fn converts_to_c_type(x: $primitive_type) {
+                let nonzero = $rust_type::new(x);
+
+                if let Some(nonzero) = nonzero {
+                    assert_eq!(wrap(x).as_c_type(), wrap(nonzero).as_c_type());
+                }
+                assert_eq!(wrap(x).as_c_type(), wrap(&nonzero).as_c_type());
+            }
+        }
+    };
+}
+
+impl_nonzero_arg_type!(NonZeroUsize, usize, nonzero_usize_test);
+impl_nonzero_arg_type!(NonZeroIsize, isize, nonzero_isize_test);
+impl_nonzero_arg_type!(NonZeroU64, u64, nonzero_u64_test);
+impl_nonzero_arg_type!(NonZeroI64, i64, nonzero_i64_test);
+impl_nonzero_arg_type!(NonZeroU32, u32, nonzero_u32_test);
+impl_nonzero_arg_type!(NonZeroI32, i32, nonzero_i32_test);
+impl_nonzero_arg_type!(NonZeroU16, u16, nonzero_u16_test);
+impl_nonzero_arg_type!(NonZeroI16, i16, nonzero_i16_test);
+impl_nonzero_arg_type!(NonZeroU8, u8, nonzero_u8_test);
+impl_nonzero_arg_type!(NonZeroI8, i8, nonzero_i8_test);
+
+/// 128-bit integers don't have a C type, so the generated probing code passes them as two `u64`
+/// args, the high 64 bits and the low 64 bits.  Signed integers are split according to their
+/// two's complement representation.
+///
+/// This is implemented for references too, since the generated code doesn't know if a 128-bit
+/// probe arg is passed by value, by reference, or inside an `Option`.
+pub trait ProbeArgWideInteger {
+    fn probe_arg_high_bits(&self) -> u64;
+    fn probe_arg_low_bits(&self) -> u64;
+}
+
+impl ProbeArgWideInteger for u128 {
+    fn probe_arg_high_bits(&self) -> u64 {
+        (*self >> 64) as u64
+    }
+
+    fn probe_arg_low_bits(&self) -> u64 {
+        *self as u64
+    }
+}
+
+impl ProbeArgWideInteger for i128 {
+    fn probe_arg_high_bits(&self) -> u64 {
+        (*self as u128).probe_arg_high_bits()
+    }
+
+    fn probe_arg_low_bits(&self) -> u64 {
+        (*self as u128).probe_arg_low_bits()
+    }
+}
+
+impl<T: ProbeArgWideInteger + ?Sized> ProbeArgWideInteger for &T {
+    fn probe_arg_high_bits(&self) -> u64 {
+        (**self).probe_arg_high_bits()
+    }
+
+    fn probe_arg_low_bits(&self) -> u64 {
+        (**self).probe_arg_low_bits()
+    }
+}
+
+#[cfg(test)]
+mod wide_integer_test {
+    use super::ProbeArgWideInteger;
+
+    #[quickcheck]
+    fn u128_halves(hi: u64, lo: u64) -> bool {
+        let x = (u128::from(hi) << 64) | u128::from(lo);
+
+        x.probe_arg_high_bits() == hi && (&&x).probe_arg_low_bits() == lo
+    }
+
+    #[quickcheck]
+    fn i128_halves(x: i64) -> bool {
+        //Sign extension means the high bits of a negative number are all ones
+        let wide = i128::from(x);
+        let expected_hi = if x < 0 { u64::MAX } else { 0 };
+
+        wide.probe_arg_high_bits() == expected_hi && wide.probe_arg_low_bits() == x as u64
+    }
+}
@@ -0,0 +1,141 @@
+//! `std::io::Error` can't be passed to a probe as a single C type, so the generated probing code
+//! passes it as three probe arguments: the raw OS error code (or `0` if there isn't one), the
+//! error kind as a string, and the error message.  That way tracing scripts can aggregate on the
+//! error code without having to parse strings.  This module contains the runtime helpers that code
+//! uses to get the latter two.
+use super::ProbeArgType;
+use std::ffi::CString;
+use std::io;
+use std::sync::Mutex;
+
+/// Names of `io::ErrorKind`s added to std after `io_error_kind_str` was written, formatted with
+/// their `Debug` impl the first time each is seen.  There are only ever a handful of these, so
+/// leaking them is fine.
+static UNLISTED_KINDS: Mutex<Vec<(io::ErrorKind, &'static str)>> = Mutex::new(Vec::new());
+
+/// Gets the name of an `io::ErrorKind` as a static string, so it can be passed to a probe without
+/// allocating.  `ErrorKind` is non-exhaustive, so any kind which isn't known to this function is
+/// named by its `Debug` formatting, which is what the names of the known kinds match.
+pub fn io_error_kind_str(kind: io::ErrorKind) -> &'static str {
+    use std::io::ErrorKind::*;
+
+    match kind {
+        NotFound => "NotFound",
+        PermissionDenied => "PermissionDenied",
+        ConnectionRefused => "ConnectionRefused",
+        ConnectionReset => "ConnectionReset",
+        HostUnreachable => "HostUnreachable",
+        NetworkUnreachable => "NetworkUnreachable",
+        ConnectionAborted => "ConnectionAborted",
+        NotConnected => "NotConnected",
+        AddrInUse => "AddrInUse",
+        AddrNotAvailable => "AddrNotAvailable",
+        NetworkDown => "NetworkDown",
+        BrokenPipe => "BrokenPipe",
+        AlreadyExists => "AlreadyExists",
+        WouldBlock => "WouldBlock",
+        NotADirectory => "NotADirectory",
+        IsADirectory => "IsADirectory",
+        DirectoryNotEmpty => "DirectoryNotEmpty",
+        ReadOnlyFilesystem => "ReadOnlyFilesystem",
+        StaleNetworkFileHandle => "StaleNetworkFileHandle",
+        InvalidInput => "InvalidInput",
+        InvalidData => "InvalidData",
+        TimedOut => "TimedOut",
+        WriteZero => "WriteZero",
+        StorageFull => "StorageFull",
+        NotSeekable => "NotSeekable",
+        QuotaExceeded => "QuotaExceeded",
+        FileTooLarge => "FileTooLarge",
+        ResourceBusy => "ResourceBusy",
+        ExecutableFileBusy => "ExecutableFileBusy",
+        Deadlock => "Deadlock",
+        CrossesDevices => "CrossesDevices",
+        TooManyLinks => "TooManyLinks",
+        InvalidFilename => "InvalidFilename",
+        ArgumentListTooLong => "ArgumentListTooLong",
+        Interrupted => "Interrupted",
+        Unsupported => "Unsupported",
+        UnexpectedEof => "UnexpectedEof",
+        OutOfMemory => "OutOfMemory",
+        Other => "Other",
+        kind => unlisted_kind_str(kind),
+    }
+}
+
+fn unlisted_kind_str(kind: io::ErrorKind) -> &'static str {
+    let mut kinds = UNLISTED_KINDS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    match kinds.iter().find(|(k, _)| *k == kind) {
+        Some((_, name)) => name,
+        None => {
+            let name: &'static str = Box::leak(format!("{:?}", kind).into_boxed_str());
+            kinds.push((kind, name));
+            name
+        }
+    }
+}
+
+/// The message of an `io::Error`, as it would be formatted by its `Display` impl
+#[derive(Debug, Clone, Copy)]
+pub struct IoErrorMessage<'a>(&'a io::Error);
+
+impl<'a> IoErrorMessage<'a> {
+    pub fn new(err: &'a io::Error) -> IoErrorMessage<'a> {
+        IoErrorMessage(err)
+    }
+}
+
+impl<'a> ProbeArgType<IoErrorMessage<'a>> for IoErrorMessage<'a> {
+    type WrapperType = Option<CString>;
+    fn wrap(arg: IoErrorMessage<'a>) -> Self::WrapperType {
+        super::wrap(&arg.0.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{wrap, ProbeArgWrapper};
+    use std::ffi::CStr;
+
+    #[test]
+    fn error_kinds() {
+        assert_eq!("NotFound", io_error_kind_str(io::ErrorKind::NotFound));
+        assert_eq!("Other", io_error_kind_str(io::ErrorKind::Other));
+
+        let err = io::Error::from_raw_os_error(2);
+        assert_eq!("NotFound", io_error_kind_str(err.kind()));
+
+        //Kinds which aren't in the original short list aren't lumped in with `Other`
+        let err = io::Error::from_raw_os_error(libc::ENOTDIR);
+        assert_eq!("NotADirectory", io_error_kind_str(err.kind()));
+        assert_eq!("Unsupported", io_error_kind_str(io::ErrorKind::Unsupported));
+        assert_eq!("OutOfMemory", io_error_kind_str(io::ErrorKind::OutOfMemory));
+    }
+
+    #[test]
+    fn unlisted_error_kinds() {
+        //Every kind std knows about can't be enumerated here, but the fallback has to agree with
+        //the explicit names, and only intern each kind once
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::Other] {
+            assert_eq!(format!("{:?}", kind), unlisted_kind_str(kind));
+            assert!(std::ptr::eq(
+                unlisted_kind_str(kind),
+                unlisted_kind_str(kind)
+            ));
+        }
+    }
+
+    #[test]
+    fn error_messages() {
+        let err = io::Error::new(io::ErrorKind::InvalidData, "something broke");
+        let wrapper = wrap(IoErrorMessage::new(&err));
+
+        //This is only safe because the wrapper is still alive
+        let message = unsafe { CStr::from_ptr(wrapper.as_c_type()) };
+        assert_eq!("something broke", message.to_str().unwrap());
+    }
+}
@@ -1,32 +1,85 @@
 //! This module implements ProbeArgType and ProbeArgWrapper for Rust's string types.  This
-//! implementation will be available for `&String` and `&str` on all supported platforms.  On the
-//! `unix` family of platforms, `&OsString` and `&OsStr` are also supported.
+//! implementation will be available for `&String`, `&str`, `&Cow<str>`, `&Box<str>` and
+//! `&Arc<str>` on all supported platforms.  On the `unix` family of platforms, `&OsString`,
+//! `&OsStr`, `&PathBuf` and `&Path` are also supported.
 //!
-//! In all four of these cases the idea is the same.  The wrapper for all of the string types is
+//! In all of these cases the idea is the same.  The wrapper for all of the string types is
 //! `Option<CString>`, which will contain either nothing or a `CString` containing the C
 //! representation (meaning null terminated).  Since Rust strings can contain embedded NULL
 //! bytes,this means that some Rust strings cannot be represented as CStrings.  Hence the use of
 //! `Option`.  If the string can't be represented as a `CString`, it will be passed to the C
 //! probeAPI as a NULL.
-use super::{ProbeArgType, ProbeArgWrapper};
+//!
+//! Passing a NULL in place of a string is a lossy conversion, and so is truncating a string at its
+//! first NUL (see the `string_encoding` module for the opt-in alternatives).  Every lossy conversion
+//! increments a process-wide counter which can be queried with `lossy_string_conversions`, so it's
+//! possible to tell if the strings seen by a tracing tool are not the strings which were actually
+//! passed to the probes.
+use super::ProbeArgType;
+use std::borrow::Cow;
+use std::cell::Cell;
 use std::ffi::CString;
 #[cfg(unix)]
 use std::ffi::{OsStr, OsString};
-use std::os::raw::c_char;
 #[cfg(unix)]
 use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+static LOSSY_STRING_CONVERSIONS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The same count as `LOSSY_STRING_CONVERSIONS` but only for the current thread, so the lossy
+    /// conversions can be attributed to the probe whose args were being wrapped
+    static THREAD_LOSSY_STRING_CONVERSIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Returns the number of times since the process started that a string probe argument could not be
+/// passed to a probe exactly as it was, either because it was replaced by a NULL pointer or
+/// because it was truncated at an embedded NUL byte.
+pub fn lossy_string_conversions() -> usize {
+    LOSSY_STRING_CONVERSIONS.load(Ordering::Relaxed)
+}
+
+/// Records that a string probe argument was not passed to the probe faithfully.  This is only
+/// called on the (presumably rare) lossy path so it doesn't cost anything in the common case
+pub(crate) fn record_lossy_string_conversion() {
+    LOSSY_STRING_CONVERSIONS.fetch_add(1, Ordering::Relaxed);
+    THREAD_LOSSY_STRING_CONVERSIONS.with(|count| count.set(count.get() + 1));
+}
+
+/// Like `lossy_string_conversions`, but only counts the conversions on the current thread.  The
+/// generated probing code uses this to count the lossy conversions of each probe's args when
+/// probe statistics are enabled.
+pub fn thread_lossy_string_conversions() -> usize {
+    THREAD_LOSSY_STRING_CONVERSIONS.with(Cell::get)
+}
+
+/// Converts the bytes of a string into a `CString`, or `None` if the string has embedded NUL bytes
+/// and thus can't be represented as a C string.  The latter case is counted as a lossy conversion.
+fn to_cstring(bytes: &[u8]) -> Option<CString> {
+    let cstring = CString::new(bytes).ok();
+
+    if cstring.is_none() {
+        record_lossy_string_conversion();
+    }
+
+    cstring
+}
 
 impl ProbeArgType<&str> for &str {
     type WrapperType = Option<CString>;
     fn wrap(arg: &str) -> Self::WrapperType {
-        CString::new(arg).ok()
+        to_cstring(arg.as_bytes())
     }
 }
 
 impl ProbeArgType<&String> for &String {
     type WrapperType = Option<CString>;
     fn wrap(arg: &String) -> Self::WrapperType {
-        CString::new(arg.as_str()).ok()
+        to_cstring(arg.as_bytes())
     }
 }
 
@@ -34,7 +87,7 @@ impl ProbeArgType<&String> for &String {
 impl ProbeArgType<&OsStr> for &OsStr {
     type WrapperType = Option<CString>;
     fn wrap(arg: &OsStr) -> Self::WrapperType {
-        CString::new(arg.as_bytes()).ok()
+        to_cstring(arg.as_bytes())
     }
 }
 
@@ -42,34 +95,75 @@ impl ProbeArgType<&OsStr> for &OsStr {
 impl ProbeArgType<&OsString> for &OsString {
     type WrapperType = Option<CString>;
     fn wrap(arg: &OsString) -> Self::WrapperType {
-        CString::new(arg.as_bytes()).ok()
+        to_cstring(arg.as_bytes())
+    }
+}
+
+impl<'a, 'b> ProbeArgType<&'a Cow<'b, str>> for &'a Cow<'b, str> {
+    type WrapperType = Option<CString>;
+    fn wrap(arg: &Cow<str>) -> Self::WrapperType {
+        to_cstring(arg.as_bytes())
+    }
+}
+
+impl ProbeArgType<&Box<str>> for &Box<str> {
+    type WrapperType = Option<CString>;
+    fn wrap(arg: &Box<str>) -> Self::WrapperType {
+        to_cstring(arg.as_bytes())
+    }
+}
+
+impl ProbeArgType<&Arc<str>> for &Arc<str> {
+    type WrapperType = Option<CString>;
+    fn wrap(arg: &Arc<str>) -> Self::WrapperType {
+        to_cstring(arg.as_bytes())
     }
 }
 
-impl<'a> ProbeArgWrapper for Option<CString> {
-    type CType = *const c_char;
+#[cfg(unix)] // Paths are `OsStr`s underneath, so the same restriction applies
+impl ProbeArgType<&Path> for &Path {
+    type WrapperType = Option<CString>;
+    fn wrap(arg: &Path) -> Self::WrapperType {
+        to_cstring(arg.as_os_str().as_bytes())
+    }
+}
 
-    fn as_c_type(&self) -> Self::CType {
-        self.as_ref()
-            .map(|x| x.as_ptr())
-            .unwrap_or_else(Self::default_c_value)
+#[cfg(unix)] // Paths are `OsStr`s underneath, so the same restriction applies
+impl ProbeArgType<&PathBuf> for &PathBuf {
+    type WrapperType = Option<CString>;
+    fn wrap(arg: &PathBuf) -> Self::WrapperType {
+        to_cstring(arg.as_os_str().as_bytes())
     }
 }
 
-/// The tests module is a bit messy because it is exercising four slightly different implementations:
+// `Option<CString>` is a `ProbeArgWrapper` by virtue of the generic `Option<T>` implementation in
+// the `option` module, since `CString` itself is a wrapper (see `string_encoding`).  `None` is
+// passed as a NULL pointer.
+
+/// The tests module is a bit messy because it is exercising several slightly different implementations:
 /// * `&String`
 /// * `&str`
+/// * `&Cow<str>`
+/// * `&Box<str>`
+/// * `&Arc<str>`
 /// * `&OsString`
 /// * `&OsStr`
+/// * `&PathBuf`
+/// * `&Path`
 #[cfg(test)]
 mod tests {
+    use super::{lossy_string_conversions, thread_lossy_string_conversions};
     use crate::{wrap, ProbeArgType, ProbeArgWrapper};
+    use std::borrow::Cow;
     use std::ffi::{CStr, CString};
     #[cfg(unix)]
     use std::ffi::{OsStr, OsString};
     use std::fmt::Debug;
     #[cfg(unix)]
     use std::os::unix::ffi::OsStrExt;
+    #[cfg(unix)]
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
 
     #[quickcheck]
     fn string_as_c_type(x: String) -> bool {
@@ -87,6 +181,38 @@ mod tests {
         true
     }
 
+    #[quickcheck]
+    fn cow_str_as_c_type(x: String) -> bool {
+        let borrowed: Cow<str> = Cow::Borrowed(x.as_str());
+        let wrapper = wrap(&borrowed);
+        let pointer: *const i8 = wrapper.as_c_type();
+        test_with_string(&borrowed, pointer);
+
+        let owned: Cow<str> = Cow::Owned(x.clone());
+        let wrapper = wrap(&owned);
+        let pointer: *const i8 = wrapper.as_c_type();
+        test_with_string(&owned, pointer);
+        true
+    }
+
+    #[quickcheck]
+    fn box_str_as_c_type(x: String) -> bool {
+        let x = x.into_boxed_str();
+        let wrapper = wrap(&x);
+        let pointer: *const i8 = wrapper.as_c_type();
+        test_with_string(&x, pointer);
+        true
+    }
+
+    #[quickcheck]
+    fn arc_str_as_c_type(x: String) -> bool {
+        let x: Arc<str> = Arc::from(x);
+        let wrapper = wrap(&x);
+        let pointer: *const i8 = wrapper.as_c_type();
+        test_with_string(&x, pointer);
+        true
+    }
+
     #[quickcheck]
     #[cfg(unix)]
     fn osstring_as_c_type(x: OsString) -> bool {
@@ -105,6 +231,26 @@ mod tests {
         true
     }
 
+    #[quickcheck]
+    #[cfg(unix)]
+    fn pathbuf_as_c_type(x: OsString) -> bool {
+        let x = PathBuf::from(x);
+        let wrapper = wrap(&x);
+        let pointer: *const i8 = wrapper.as_c_type();
+        test_with_string(&x, pointer);
+        true
+    }
+
+    #[quickcheck]
+    #[cfg(unix)]
+    fn path_as_c_type(x: OsString) -> bool {
+        let x = PathBuf::from(x);
+        let wrapper = wrap(x.as_path());
+        let pointer: *const i8 = wrapper.as_c_type();
+        test_with_string(x.as_path(), pointer);
+        true
+    }
+
     /// Implementing this trait for each of the supported string types helps take out some of the
     /// repetition in the test code
     trait StringHelpers {
@@ -130,6 +276,33 @@ mod tests {
         }
     }
 
+    impl StringHelpers for &Cow<'_, str> {
+        fn to_cstring(&self) -> Option<CString> {
+            CString::new(self.as_bytes()).ok()
+        }
+        fn assert_equals(&self, x: &str) {
+            assert_eq!(self.as_ref(), x)
+        }
+    }
+
+    impl StringHelpers for &Box<str> {
+        fn to_cstring(&self) -> Option<CString> {
+            CString::new(self.as_bytes()).ok()
+        }
+        fn assert_equals(&self, x: &str) {
+            assert_eq!(self.as_ref(), x)
+        }
+    }
+
+    impl StringHelpers for &Arc<str> {
+        fn to_cstring(&self) -> Option<CString> {
+            CString::new(self.as_bytes()).ok()
+        }
+        fn assert_equals(&self, x: &str) {
+            assert_eq!(self.as_ref(), x)
+        }
+    }
+
     #[cfg(unix)]
     impl StringHelpers for &OsStr {
         fn to_cstring(&self) -> Option<CString> {
@@ -151,6 +324,26 @@ mod tests {
         }
     }
 
+    #[cfg(unix)]
+    impl StringHelpers for &Path {
+        fn to_cstring(&self) -> Option<CString> {
+            CString::new(Vec::from(self.as_os_str().as_bytes())).ok()
+        }
+        fn assert_equals(&self, x: &str) {
+            assert_eq!(self.to_str().expect("should always be valid UTF-8"), x)
+        }
+    }
+
+    #[cfg(unix)]
+    impl StringHelpers for &PathBuf {
+        fn to_cstring(&self) -> Option<CString> {
+            CString::new(Vec::from(self.as_os_str().as_bytes())).ok()
+        }
+        fn assert_equals(&self, x: &str) {
+            assert_eq!(self.to_str().expect("should always be valid UTF-8"), x)
+        }
+    }
+
     fn test_with_string<T: StringHelpers + ProbeArgType<T> + Debug>(x: T, pointer: *const i8) {
         if pointer.is_null() {
             //This may happen if the string x has embedded NUL bytes.  In that case the string
@@ -171,4 +364,21 @@ mod tests {
             x.assert_equals(as_string);
         }
     }
+
+    #[test]
+    fn nul_strings_are_counted_as_lossy() {
+        //Other tests running concurrently may also bump the counter, so only assert that it went
+        //up by at least as many conversions as were lossy here
+        let before = lossy_string_conversions();
+        let thread_before = thread_lossy_string_conversions();
+
+        assert!(wrap("foo\0bar").as_c_type().is_null());
+        assert!(wrap(&String::from("\0")).as_c_type().is_null());
+        assert!(!wrap("foobar").as_c_type().is_null());
+
+        assert!(lossy_string_conversions() >= before + 2);
+
+        //...but the per-thread count is exact
+        assert_eq!(thread_before + 2, thread_lossy_string_conversions());
+    }
 }
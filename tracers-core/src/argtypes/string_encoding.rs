@@ -0,0 +1,263 @@
+//! This module implements the opt-in encodings for string probe arguments.  By default a string
+//! with an embedded NUL byte can't be represented as a C string, so it's passed to the probe as
+//! NULL (see the `string` module).  When that's not acceptable, a probe argument can be
+//! annotated with `#[probe(encoding = "...")]` and the generated probing code will use one of the
+//! types in this module instead:
+//!
+//! * `NulTruncated` passes the string up to (but not including) the first NUL byte.  This is lossy,
+//!   and is counted as such by `lossy_string_conversions`.
+//! * `NulEscaped` passes the whole string, with each NUL byte replaced by the two characters `\0`
+//!   and each `\` replaced by `\\` so the escaping is unambiguous.
+//!
+//! The third encoding, `ptr_len`, doesn't need a wrapper; the generated code passes the pointer to
+//! the string's bytes and its length as two separate probe arguments, obtained via
+//! `ProbeArgBytes`.
+//!
+//! None of these encodings care if the bytes are valid UTF-8, so non-UTF-8 `OsStr` contents are
+//! passed through as-is.
+use super::string::record_lossy_string_conversion;
+use super::{ProbeArgType, ProbeArgWrapper};
+use std::borrow::Cow;
+use std::ffi::{CStr, CString};
+#[cfg(unix)]
+use std::ffi::{OsStr, OsString};
+use std::os::raw::c_char;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Implemented for all of the string types which can be passed to a probe using one of the
+/// encodings in this module.  Exposes the raw bytes of the string, not including any NUL
+/// terminator.
+pub trait ProbeArgBytes {
+    fn probe_arg_bytes(&self) -> &[u8];
+}
+
+impl ProbeArgBytes for str {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl ProbeArgBytes for String {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl ProbeArgBytes for Cow<'_, str> {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl ProbeArgBytes for Box<str> {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl ProbeArgBytes for Arc<str> {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+#[cfg(unix)] // Only the unix impl of OsStr/OsString exposes the string as bytes
+impl ProbeArgBytes for OsStr {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+#[cfg(unix)] // Only the unix impl of OsStr/OsString exposes the string as bytes
+impl ProbeArgBytes for OsString {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+#[cfg(unix)] // Paths are `OsStr`s underneath, so the same restriction applies
+impl ProbeArgBytes for Path {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_os_str().as_bytes()
+    }
+}
+
+#[cfg(unix)] // Paths are `OsStr`s underneath, so the same restriction applies
+impl ProbeArgBytes for PathBuf {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_os_str().as_bytes()
+    }
+}
+
+impl ProbeArgBytes for CStr {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.to_bytes()
+    }
+}
+
+impl ProbeArgBytes for CString {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+/// References to string references, like `&&str`, show up when the string is inside an `Option`
+impl<T: ProbeArgBytes + ?Sized> ProbeArgBytes for &T {
+    fn probe_arg_bytes(&self) -> &[u8] {
+        (**self).probe_arg_bytes()
+    }
+}
+
+/// A string probe argument which will be truncated at its first NUL byte, if any
+#[derive(Debug, Clone, Copy)]
+pub struct NulTruncated<'a>(&'a [u8]);
+
+impl<'a> NulTruncated<'a> {
+    pub fn new<T: ProbeArgBytes + ?Sized>(arg: &'a T) -> NulTruncated<'a> {
+        NulTruncated(arg.probe_arg_bytes())
+    }
+}
+
+/// A string probe argument in which NUL bytes will be escaped as `\0`
+#[derive(Debug, Clone, Copy)]
+pub struct NulEscaped<'a>(&'a [u8]);
+
+impl<'a> NulEscaped<'a> {
+    pub fn new<T: ProbeArgBytes + ?Sized>(arg: &'a T) -> NulEscaped<'a> {
+        NulEscaped(arg.probe_arg_bytes())
+    }
+}
+
+impl<'a> ProbeArgType<NulTruncated<'a>> for NulTruncated<'a> {
+    type WrapperType = CString;
+    fn wrap(arg: NulTruncated<'a>) -> Self::WrapperType {
+        let bytes = match arg.0.iter().position(|b| *b == 0) {
+            Some(nul_position) => {
+                record_lossy_string_conversion();
+                &arg.0[..nul_position]
+            }
+            None => arg.0,
+        };
+
+        CString::new(bytes).expect("bytes before the first NUL can't contain a NUL")
+    }
+}
+
+impl<'a> ProbeArgType<NulEscaped<'a>> for NulEscaped<'a> {
+    type WrapperType = CString;
+    fn wrap(arg: NulEscaped<'a>) -> Self::WrapperType {
+        //Most strings have nothing to escape, in which case it's a simple copy
+        if !arg.0.iter().any(|b| *b == 0 || *b == b'\\') {
+            return CString::new(arg.0).expect("string without NUL bytes is always valid");
+        }
+
+        let mut escaped = Vec::with_capacity(arg.0.len() + 8);
+        for b in arg.0.iter() {
+            match *b {
+                0 => escaped.extend_from_slice(b"\\0"),
+                b'\\' => escaped.extend_from_slice(b"\\\\"),
+                b => escaped.push(b),
+            }
+        }
+
+        CString::new(escaped).expect("escaped string can't contain a NUL")
+    }
+}
+
+impl ProbeArgWrapper for CString {
+    type CType = *const c_char;
+
+    fn as_c_type(&self) -> Self::CType {
+        self.as_ptr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::argtypes::lossy_string_conversions;
+    use crate::wrap;
+    #[cfg(unix)]
+    use std::os::unix::ffi::OsStringExt;
+
+    fn wrapper_to_bytes(wrapper: &CString) -> Vec<u8> {
+        //As in the `string` tests, this is only safe because the wrapper is still alive
+        let cstr = unsafe { CStr::from_ptr(wrapper.as_c_type()) };
+        cstr.to_bytes().to_vec()
+    }
+
+    #[quickcheck]
+    fn truncated_strings(x: String) -> bool {
+        let wrapper = wrap(NulTruncated::new(&x));
+        let expected = x.split('\0').next().unwrap();
+
+        wrapper_to_bytes(&wrapper) == expected.as_bytes()
+    }
+
+    #[quickcheck]
+    fn escaped_strings_round_trip(x: String) -> bool {
+        let wrapper = wrap(NulEscaped::new(x.as_str()));
+        let escaped = String::from_utf8(wrapper_to_bytes(&wrapper)).unwrap();
+
+        //Undo the escaping, and the result should be the original string
+        let mut unescaped = String::new();
+        let mut chars = escaped.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('0') => unescaped.push('\0'),
+                    Some('\\') => unescaped.push('\\'),
+                    other => panic!("unexpected escape sequence {:?}", other),
+                }
+            } else {
+                unescaped.push(c);
+            }
+        }
+
+        unescaped == x
+    }
+
+    #[quickcheck]
+    #[cfg(unix)]
+    fn non_utf8_bytes_are_preserved(x: Vec<u8>) -> bool {
+        let x: Vec<u8> = x.into_iter().filter(|b| *b != 0 && *b != b'\\').collect();
+        let os_string = OsString::from_vec(x.clone());
+
+        wrapper_to_bytes(&wrap(NulEscaped::new(&os_string))) == x
+            && wrapper_to_bytes(&wrap(NulTruncated::new(os_string.as_os_str()))) == x
+    }
+
+    #[test]
+    fn truncation_is_counted_as_lossy() {
+        let before = lossy_string_conversions();
+
+        assert_eq!(
+            b"foo",
+            wrapper_to_bytes(&wrap(NulTruncated::new("foo\0bar"))).as_slice()
+        );
+        assert_eq!(
+            b"foo\\0bar\\\\",
+            wrapper_to_bytes(&wrap(NulEscaped::new("foo\0bar\\"))).as_slice()
+        );
+
+        //Other tests running concurrently may also bump the counter
+        assert!(lossy_string_conversions() > before);
+    }
+
+    #[test]
+    fn probe_arg_bytes_through_references() {
+        let string = String::from("foo\0bar");
+        let str_ref: &str = &string;
+
+        assert_eq!(b"foo\0bar", (&str_ref).probe_arg_bytes());
+        assert_eq!(b"foo\0bar", (&&string).probe_arg_bytes());
+
+        let arc: Arc<str> = Arc::from(str_ref);
+        assert_eq!(b"foo\0bar", (&&arc).probe_arg_bytes());
+        assert_eq!(b"foo\0bar", Cow::from(str_ref).probe_arg_bytes());
+    }
+}
@@ -0,0 +1,37 @@
+//! Implements `ProbeArgType` for `Duration`, which is passed to probes as a number of nanoseconds.
+//! A `Duration` can represent much longer spans of time than will fit in a `u64` worth of
+//! nanoseconds (about 584 years), so longer durations are passed as `u64::MAX`.
+use super::ProbeArgType;
+use std::time::Duration;
+
+impl ProbeArgType<Duration> for Duration {
+    type WrapperType = u64;
+    fn wrap(arg: Duration) -> Self::WrapperType {
+        let nanos = arg.as_nanos();
+
+        if nanos > u128::from(u64::MAX) {
+            u64::MAX
+        } else {
+            nanos as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{wrap, ProbeArgWrapper};
+    use std::time::Duration;
+
+    #[quickcheck]
+    fn as_c_type(secs: u32, nanos: u32) -> bool {
+        let duration = Duration::new(u64::from(secs), nanos % 1_000_000_000);
+
+        u128::from(wrap(duration).as_c_type()) == duration.as_nanos()
+    }
+
+    #[test]
+    fn saturates_long_durations() {
+        assert_eq!(u64::MAX, wrap(Duration::from_secs(u64::MAX)).as_c_type());
+        assert_eq!(1_500, wrap(&Duration::from_nanos(1_500)).as_c_type());
+    }
+}
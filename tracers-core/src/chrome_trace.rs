@@ -0,0 +1,261 @@
+//! Writes probe firings to a file in the Chrome Trace Event Format, which can be viewed with
+//! `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).  This is the runtime of the static
+//! `chrome_trace` tracing implementation, which is for profiling on developer machines where
+//! SystemTap or LTTng aren't available, or root privileges aren't.
+//!
+//! The trace is only written if the `TRACERS_CHROME_TRACE_FILE` environment variable is set to
+//! the path of the file to write when the first probe is tested; otherwise every probe is
+//! disabled.  Each probe firing is an event whose `name` is the probe and whose `cat` is the
+//! provider, with the probe's args in `args`.  Both probes of a span probe are written as the begin
+//! (`B`) and end (`E`) events of a single span, and all other probes as instant (`i`) events.
+//!
+//! The file is in the "JSON Array Format", without the closing `]`.  That's explicitly allowed by
+//! the format, so that a trace is still valid if the process ends without cleaning up, which it
+//! usually does since there's nothing to tell us when it's about to exit.  Each event is flushed
+//! to the file as soon as it's written, for the same reason.
+use crate::trace_file::{self, TraceFile};
+use crate::trace_value::ArgValue;
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Write};
+use std::slice;
+use std::time::Instant;
+
+/// The environment variable which holds the path of the file to write the trace to
+pub const TRACE_FILE_ENV_VAR: &str = "TRACERS_CHROME_TRACE_FILE";
+
+/// The phase of a trace event, which says how the trace viewer displays it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    /// A probe firing at a single point in time
+    Instant,
+
+    /// The start of a span, fired by the enter probe of a span probe
+    Begin,
+
+    /// The end of a span, fired by the exit probe of a span probe
+    End,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Phase::Instant => "i",
+            Phase::Begin => "B",
+            Phase::End => "E",
+        }
+    }
+}
+
+/// Appends `value` to `json`.
+///
+/// # Safety
+///
+/// If `value` is a `Str` or `Bytes` value, its pointer must be null or point to a string which is
+/// still valid
+unsafe fn write_json_value(json: &mut String, value: ArgValue) {
+    match value {
+        ArgValue::Int(value) => write!(json, "{}", value).unwrap(),
+        ArgValue::UInt(value) => write!(json, "{}", value).unwrap(),
+        ArgValue::Hex(value) => write!(json, "\"{:#x}\"", value).unwrap(),
+        ArgValue::Pointer(address) => write!(json, "\"{:#x}\"", address).unwrap(),
+        ArgValue::Str(ptr) if ptr.is_null() => json.push_str("null"),
+        ArgValue::Bytes(ptr, _) if ptr.is_null() => json.push_str("null"),
+        ArgValue::Str(ptr) => {
+            write_json_string(json, CStr::from_ptr(ptr as *const _).to_string_lossy())
+        }
+        ArgValue::Bytes(ptr, len) => write_json_string(
+            json,
+            String::from_utf8_lossy(slice::from_raw_parts(ptr, len)),
+        ),
+    }
+}
+
+/// Appends `value` to `json` as a quoted JSON string
+fn write_json_string(json: &mut String, value: Cow<str>) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Writes trace events to `W`
+pub struct TraceWriter<W: Write> {
+    out: W,
+    start: Instant,
+    pid: u32,
+    first_event: bool,
+}
+
+impl<W: Write> TraceWriter<W> {
+    /// Starts a trace written to `out`.  Event timestamps are relative to when this is called.
+    pub fn new(mut out: W) -> io::Result<TraceWriter<W>> {
+        out.write_all(b"[\n")?;
+        out.flush()?;
+
+        Ok(TraceWriter {
+            out,
+            start: Instant::now(),
+            pid: std::process::id(),
+            first_event: true,
+        })
+    }
+
+    /// Writes one event, for a probe `name` on the provider `category`, and flushes it.
+    ///
+    /// # Safety
+    ///
+    /// The pointers in any `Str` or `Bytes` args must be null or point to strings which are still
+    /// valid
+    pub unsafe fn write_event(
+        &mut self,
+        category: &str,
+        name: &str,
+        phase: Phase,
+        args: &[(&str, ArgValue)],
+    ) -> io::Result<()> {
+        let elapsed_ns = self.start.elapsed().as_nanos();
+        let tid = trace_file::thread_id();
+
+        let mut json = String::new();
+        if !self.first_event {
+            json.push_str(",\n");
+        }
+        json.push_str("{\"name\":");
+        write_json_string(&mut json, Cow::Borrowed(name));
+        json.push_str(",\"cat\":");
+        write_json_string(&mut json, Cow::Borrowed(category));
+        write!(json, ",\"ph\":\"{}\"", phase.as_str()).unwrap();
+        if phase == Phase::Instant {
+            //Instant events are drawn across the whole process by default; this keeps them on
+            //their thread's track
+            json.push_str(",\"s\":\"t\"");
+        }
+        write!(
+            json,
+            ",\"ts\":{}.{:03},\"pid\":{},\"tid\":{},\"args\":{{",
+            elapsed_ns / 1000,
+            elapsed_ns % 1000,
+            self.pid,
+            tid
+        )
+        .unwrap();
+        for (index, (arg_name, value)) in args.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            write_json_string(&mut json, Cow::Borrowed(arg_name));
+            json.push(':');
+            write_json_value(&mut json, *value);
+        }
+        json.push_str("}}");
+
+        self.out.write_all(json.as_bytes())?;
+        self.out.flush()?;
+        self.first_event = false;
+
+        Ok(())
+    }
+}
+
+static TRACE: TraceFile<TraceWriter<File>> = TraceFile::new("Chrome", TRACE_FILE_ENV_VAR);
+
+/// Tests if the trace is being written to the file named by `TRACERS_CHROME_TRACE_FILE`, which
+/// the first call opens
+#[inline]
+pub fn is_enabled() -> bool {
+    TRACE.is_enabled(|path| File::create(path).and_then(TraceWriter::new))
+}
+
+/// Writes one event to the trace file, if it's being written.
+///
+/// # Safety
+///
+/// The pointers in any `Str` or `Bytes` args must be null or point to strings which are still
+/// valid
+pub unsafe fn write_event(category: &str, name: &str, phase: Phase, args: &[(&str, ArgValue)]) {
+    TRACE.write(|| TRACE.with_trace(|writer| writer.write_event(category, name, phase, args)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::raw::c_void;
+    use std::ptr;
+
+    #[test]
+    fn writes_events_as_json() {
+        let name = b"world\0";
+        let path = "tmp/\"quoted\"\n";
+        let mut out = Vec::new();
+
+        {
+            let mut writer = TraceWriter::new(&mut out).unwrap();
+            unsafe {
+                writer
+                    .write_event(
+                        "my_provider",
+                        "hello",
+                        Phase::Instant,
+                        &[
+                            ("count", ArgValue::from(-3i32)),
+                            ("name", ArgValue::from(name.as_ptr())),
+                            (
+                                "path",
+                                ArgValue::bytes(path.as_ptr() as *const c_void, path.len()),
+                            ),
+                            ("missing", ArgValue::from(ptr::null::<i8>())),
+                            ("bits", ArgValue::hex(255)),
+                        ],
+                    )
+                    .unwrap();
+                writer
+                    .write_event("my_provider", "request", Phase::Begin, &[])
+                    .unwrap();
+                writer
+                    .write_event(
+                        "my_provider",
+                        "request",
+                        Phase::End,
+                        &[("elapsed_ns", ArgValue::from(42u64))],
+                    )
+                    .unwrap();
+            }
+        }
+
+        //Timestamps vary, so they're replaced before comparing
+        let trace = String::from_utf8(out).unwrap();
+        let events: Vec<String> = trace
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let ts = line.find("\"ts\":").unwrap();
+                let pid = line.find(",\"pid\"").unwrap();
+                format!("{}\"ts\":0{}", &line[..ts], &line[pid..])
+            })
+            .collect();
+        let pid = std::process::id();
+        let tid = trace_file::thread_id();
+
+        assert!(trace.starts_with("[\n"));
+        assert_eq!(
+            vec![
+                format!("{{\"name\":\"hello\",\"cat\":\"my_provider\",\"ph\":\"i\",\"s\":\"t\",\"ts\":0,\"pid\":{},\"tid\":{},\"args\":{{\"count\":-3,\"name\":\"world\",\"path\":\"tmp/\\\"quoted\\\"\\n\",\"missing\":null,\"bits\":\"0xff\"}}}},", pid, tid),
+                format!("{{\"name\":\"request\",\"cat\":\"my_provider\",\"ph\":\"B\",\"ts\":0,\"pid\":{},\"tid\":{},\"args\":{{}}}},", pid, tid),
+                format!("{{\"name\":\"request\",\"cat\":\"my_provider\",\"ph\":\"E\",\"ts\":0,\"pid\":{},\"tid\":{},\"args\":{{\"elapsed_ns\":42}}}}", pid, tid),
+            ],
+            events
+        );
+    }
+}
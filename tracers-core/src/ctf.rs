@@ -0,0 +1,688 @@
+//! Writes probe firings as a [CTF 1.8](https://diamon.org/ctf/v1.8.3/) trace, the format LTTng
+//! records, so they can be analyzed with babeltrace or Trace Compass.  This is the runtime of the
+//! static `ctf` tracing implementation, which unlike LTTng needs no session daemon or kernel
+//! modules; the trace is written by the traced process itself.
+//!
+//! The trace is only written if the `TRACERS_CTF_TRACE_DIR` environment variable is set to the
+//! directory to write it to when the first probe is tested; otherwise every probe is disabled.
+//! The directory holds the TSDL `metadata` file, and one stream file per thread which fires a
+//! probe, named `stream_N` where `N` numbers the threads in the order they first fire a probe.
+//!
+//! Each probe is declared as an event named `provider:probe` in the metadata when it first fires,
+//! with a field for each of its native args, whose TSDL types are derived from the args' `CType`s.
+//! A string passed with its length is a sequence of bytes, so it's written whole even if it
+//! contains a NUL.
+//! The trace is always valid, even if the process ends without cleaning up: declarations are
+//! written to the metadata before any event which uses them, and every event is followed by
+//! updating the size and end time of the packet it's in, so each stream always ends in a complete
+//! packet.
+pub use crate::argtypes::CType;
+use crate::probe_level::ProbeLevel;
+use crate::trace_file::{self, TraceFile};
+use crate::trace_value::ArgValue;
+use std::cell::RefCell;
+use std::env;
+use std::ffi::CStr;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::mem;
+use std::os::raw::{c_char, c_int, c_long, c_longlong, c_short, c_void};
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// The environment variable which holds the path of the directory to write the trace to
+pub const TRACE_DIR_ENV_VAR: &str = "TRACERS_CTF_TRACE_DIR";
+
+/// The magic number at the start of every CTF packet
+const PACKET_MAGIC: u32 = 0xC1FC_1FC1;
+
+/// The size of the packet header and packet context declared in the metadata, in bytes
+const PACKET_HEADER_SIZE: u64 = 4 + 4;
+const PACKET_CONTEXT_SIZE: u64 = 8 + 8 + 8 + 8 + 8;
+
+/// Once a packet is at least this big, the next event starts a new one.  Readers load a packet at
+/// a time, so they shouldn't be too big.
+const MAX_PACKET_SIZE: u64 = 1024 * 1024;
+
+/// Marks an `Event` whose ID hasn't been assigned yet
+const UNASSIGNED_EVENT_ID: u32 = u32::MAX;
+
+/// One of the fields of an event, which is one of the native args of a probe
+#[derive(Debug)]
+pub struct Field {
+    name: &'static str,
+    c_type: CType,
+    length: Option<&'static str>,
+}
+
+impl Field {
+    pub const fn new(name: &'static str, c_type: CType) -> Field {
+        Field {
+            name,
+            c_type,
+            length: None,
+        }
+    }
+
+    /// A field holding the bytes of a string passed with its length, which is the field named
+    /// `length`.  TSDL requires that field to come before this one.
+    pub const fn sequence(name: &'static str, length: &'static str) -> Field {
+        Field {
+            name,
+            c_type: CType::UCharPtr,
+            length: Some(length),
+        }
+    }
+}
+
+/// The declaration of a probe as a CTF event.  The code generated by the `tracer` macro declares
+/// a static `Event` for each probe.  Its ID is assigned, and it's declared in the metadata, the
+/// first time the probe fires.
+#[derive(Debug)]
+pub struct Event {
+    provider: &'static str,
+    name: &'static str,
+    level: Option<ProbeLevel>,
+    fields: &'static [Field],
+    id: AtomicU32,
+}
+
+impl Event {
+    pub const fn new(
+        provider: &'static str,
+        name: &'static str,
+        level: Option<ProbeLevel>,
+        fields: &'static [Field],
+    ) -> Event {
+        Event {
+            provider,
+            name,
+            level,
+            fields,
+            id: AtomicU32::new(UNASSIGNED_EVENT_ID),
+        }
+    }
+}
+
+/// The TSDL declaration of an integer type of `size` bytes
+fn tsdl_integer(size: usize, signed: bool, base: u32) -> String {
+    format!(
+        "integer {{ size = {}; align = 8; signed = {}; base = {}; }}",
+        size * 8,
+        signed as u8,
+        base
+    )
+}
+
+/// The TSDL type which values of a C type are written as.  Integers are the size of the C type on
+/// this platform.  Strings are written inline, NUL-terminated.
+fn tsdl_type(c_type: &CType) -> String {
+    match c_type {
+        CType::CharPtr | CType::UCharPtr => "string".to_owned(),
+        CType::VoidPtr => tsdl_integer(mem::size_of::<*const c_void>(), false, 16),
+        CType::NoArg => unreachable!("probes have no `void` args"),
+        c_type => {
+            let (size, signed) = integer_layout(c_type);
+            tsdl_integer(size, signed, 10)
+        }
+    }
+}
+
+/// The size in bytes and signedness of an integer C type on this platform
+fn integer_layout(c_type: &CType) -> (usize, bool) {
+    match c_type {
+        CType::Char => (mem::size_of::<c_char>(), c_char::MIN != 0),
+        CType::UChar => (1, false),
+        CType::Short => (mem::size_of::<c_short>(), true),
+        CType::UShort => (mem::size_of::<c_short>(), false),
+        CType::Int => (mem::size_of::<c_int>(), true),
+        CType::UInt => (mem::size_of::<c_int>(), false),
+        CType::Long => (mem::size_of::<c_long>(), true),
+        CType::ULong => (mem::size_of::<c_long>(), false),
+        CType::LongLong => (mem::size_of::<c_longlong>(), true),
+        CType::ULongLong => (mem::size_of::<c_longlong>(), false),
+        CType::SizeT => (mem::size_of::<usize>(), false),
+        CType::SSizeT => (mem::size_of::<isize>(), true),
+        CType::VoidPtr => (mem::size_of::<*const c_void>(), false),
+        CType::CharPtr | CType::UCharPtr | CType::NoArg => {
+            unreachable!("{} isn't an integer", c_type)
+        }
+    }
+}
+
+/// The TSDL declarations which are the same for every trace: the integer types, the packet and
+/// event headers, and the clock, whose timestamps are offset by `clock_offset_ns` since the epoch
+fn metadata_preamble(clock_offset_ns: u128) -> String {
+    let procname = env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default();
+
+    format!(
+        r#"/* CTF 1.8 */
+
+typealias integer {{ size = 8; align = 8; signed = false; }} := uint8_t;
+typealias integer {{ size = 32; align = 8; signed = false; }} := uint32_t;
+typealias integer {{ size = 64; align = 8; signed = false; }} := uint64_t;
+
+trace {{
+    major = 1;
+    minor = 8;
+    byte_order = le;
+    packet.header := struct {{
+        uint32_t magic;
+        uint32_t stream_id;
+    }};
+}};
+
+env {{
+    tracer_name = "tracers";
+    tracer_version = "{version}";
+    procname = "{procname}";
+    vpid = {pid};
+}};
+
+clock {{
+    name = monotonic;
+    description = "Monotonic clock, offset to the time the trace started";
+    freq = 1000000000;
+    offset_s = {offset_s};
+    offset = {offset_ns};
+}};
+
+typealias integer {{ size = 64; align = 8; signed = false; map = clock.monotonic.value; }} := uint64_clock_monotonic_t;
+
+stream {{
+    id = 0;
+    packet.context := struct {{
+        uint64_clock_monotonic_t timestamp_begin;
+        uint64_clock_monotonic_t timestamp_end;
+        uint64_t content_size;
+        uint64_t packet_size;
+        uint64_t thread_id;
+    }};
+    event.header := struct {{
+        uint32_t id;
+        uint64_clock_monotonic_t timestamp;
+    }};
+}};
+"#,
+        version = env!("CARGO_PKG_VERSION"),
+        procname = procname.replace('\\', "\\\\").replace('"', "\\\""),
+        pid = std::process::id(),
+        offset_s = clock_offset_ns / 1_000_000_000,
+        offset_ns = clock_offset_ns % 1_000_000_000,
+    )
+}
+
+/// The TSDL declaration of an event.  Field names get a `_` prefix, as LTTng does, so they can't
+/// collide with TSDL keywords; readers strip it again.
+fn event_declaration(event: &Event, id: u32) -> String {
+    let mut declaration = format!(
+        "\nevent {{\n    name = \"{}:{}\";\n    id = {};\n    stream_id = 0;\n",
+        event.provider, event.name, id
+    );
+    if let Some(level) = event.level {
+        //The levels are declared in the order of the LTTng log levels, so this is the LTTng number
+        writeln!(declaration, "    loglevel = {};", level as u32).unwrap();
+    }
+    declaration.push_str("    fields := struct {\n");
+    for field in event.fields.iter() {
+        match field.length {
+            Some(length) => writeln!(declaration, "        uint8_t _{}[_{}];", field.name, length),
+            None => writeln!(
+                declaration,
+                "        {} _{};",
+                tsdl_type(&field.c_type),
+                field.name
+            ),
+        }
+        .unwrap();
+    }
+    declaration.push_str("    };\n};\n");
+
+    declaration
+}
+
+/// Appends the bytes of a string field to `buffer`, including the terminating NUL.  A string which
+/// contains a NUL is truncated there, and a null pointer is written as an empty string.
+///
+/// # Safety
+///
+/// If the value is a `Str` or `Bytes`, its pointer must be null or point to a valid string
+unsafe fn encode_string(buffer: &mut Vec<u8>, value: ArgValue) {
+    let bytes: &[u8] = match value {
+        ArgValue::Str(ptr) if !ptr.is_null() => CStr::from_ptr(ptr as *const c_char).to_bytes(),
+        ArgValue::Bytes(ptr, len) if !ptr.is_null() => slice::from_raw_parts(ptr, len),
+        _ => &[],
+    };
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+
+    buffer.extend_from_slice(&bytes[..len]);
+    buffer.push(0);
+}
+
+/// Appends the bytes of a sequence field to `buffer`, all of them, NULs included.  Since the
+/// length was already written as its own field, a null pointer is written as that many zeros.
+/// Only `Bytes` values have a length, so anything else is written as an empty sequence.
+///
+/// # Safety
+///
+/// If the value is `Bytes`, its pointer must be null or point to at least its length in bytes
+unsafe fn encode_bytes(buffer: &mut Vec<u8>, value: ArgValue) {
+    match value {
+        ArgValue::Bytes(ptr, len) if !ptr.is_null() => {
+            buffer.extend_from_slice(slice::from_raw_parts(ptr, len))
+        }
+        ArgValue::Bytes(_, len) => buffer.resize(buffer.len() + len, 0),
+        _ => {}
+    }
+}
+
+/// Appends a field's value to `buffer` as the TSDL type of the field
+///
+/// # Safety
+///
+/// If the value is a `Str` or `Bytes`, its pointer must be null or point to a valid string
+unsafe fn encode_field(buffer: &mut Vec<u8>, field: &Field, value: ArgValue) {
+    let size = match &field.c_type {
+        _ if field.length.is_some() => return encode_bytes(buffer, value),
+        CType::CharPtr | CType::UCharPtr => return encode_string(buffer, value),
+        c_type => integer_layout(c_type).0,
+    };
+    let bits = match value {
+        ArgValue::Int(value) => value as u64,
+        ArgValue::UInt(value) | ArgValue::Hex(value) => value,
+        ArgValue::Pointer(address) => address as u64,
+        ArgValue::Str(ptr) | ArgValue::Bytes(ptr, _) => ptr as u64,
+    };
+
+    buffer.extend_from_slice(&bits.to_le_bytes()[..size]);
+}
+
+/// A CTF trace being written to a directory.  The events are written by `Stream`s.
+struct Trace {
+    dir: PathBuf,
+    start: Instant,
+    metadata: File,
+    next_event_id: u32,
+}
+
+impl Trace {
+    /// Creates the trace directory, if needed, and writes the start of the metadata to it
+    fn create(dir: &Path) -> io::Result<Trace> {
+        fs::create_dir_all(dir)?;
+
+        let start = Instant::now();
+        let clock_offset_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_nanos())
+            .unwrap_or_default();
+        let mut metadata = File::create(dir.join("metadata"))?;
+        metadata.write_all(metadata_preamble(clock_offset_ns).as_bytes())?;
+
+        Ok(Trace {
+            dir: dir.to_owned(),
+            start,
+            metadata,
+            next_event_id: 0,
+        })
+    }
+
+    /// Assigns the next ID to `event`, and declares it in the metadata
+    fn declare_event(&mut self, event: &Event) -> io::Result<u32> {
+        let id = self.next_event_id;
+        self.metadata
+            .write_all(event_declaration(event, id).as_bytes())?;
+        self.next_event_id += 1;
+
+        Ok(id)
+    }
+
+    /// Creates the stream file for the thread numbered `thread_id`
+    fn create_stream(&self, thread_id: u64) -> io::Result<Stream> {
+        let file = File::create(self.dir.join(format!("stream_{}", thread_id)))?;
+
+        Ok(Stream {
+            file,
+            start: self.start,
+            thread_id,
+            packet_start: 0,
+            packet_end: 0,
+            timestamp_begin: 0,
+        })
+    }
+}
+
+/// One thread's stream of events
+struct Stream {
+    file: File,
+    start: Instant,
+    thread_id: u64,
+
+    /// The offsets in the file of the start and end of the last packet, which are the same before
+    /// the first packet is written
+    packet_start: u64,
+    packet_end: u64,
+
+    timestamp_begin: u64,
+}
+
+impl Stream {
+    fn packet_context(&self, timestamp_end: u64) -> Vec<u8> {
+        let packet_bits = (self.packet_end - self.packet_start) * 8;
+        let mut context = Vec::with_capacity(PACKET_CONTEXT_SIZE as usize);
+        context.extend_from_slice(&self.timestamp_begin.to_le_bytes());
+        context.extend_from_slice(&timestamp_end.to_le_bytes());
+        context.extend_from_slice(&packet_bits.to_le_bytes()); //content_size
+        context.extend_from_slice(&packet_bits.to_le_bytes()); //packet_size
+        context.extend_from_slice(&self.thread_id.to_le_bytes());
+
+        context
+    }
+
+    /// Appends an event to the last packet, first starting a new packet if there isn't one or the
+    /// last one is full, and then updates the packet's context to include it.
+    ///
+    /// # Safety
+    ///
+    /// The pointers in any `Str` or `Bytes` values must be null or point to valid strings
+    unsafe fn write_event(
+        &mut self,
+        id: u32,
+        fields: &[Field],
+        values: &[ArgValue],
+    ) -> io::Result<()> {
+        let timestamp = self.start.elapsed().as_nanos() as u64;
+        let mut buffer = Vec::new();
+
+        if self.packet_start == self.packet_end
+            || self.packet_end - self.packet_start >= MAX_PACKET_SIZE
+        {
+            //The context is filled in below, once the packet's size is known
+            self.packet_start = self.packet_end;
+            self.timestamp_begin = timestamp;
+            buffer.extend_from_slice(&PACKET_MAGIC.to_le_bytes());
+            buffer.extend_from_slice(&0u32.to_le_bytes()); //stream_id
+            buffer.resize((PACKET_HEADER_SIZE + PACKET_CONTEXT_SIZE) as usize, 0);
+        }
+
+        buffer.extend_from_slice(&id.to_le_bytes());
+        buffer.extend_from_slice(&timestamp.to_le_bytes());
+        for (field, value) in fields.iter().zip(values.iter()) {
+            encode_field(&mut buffer, field, *value);
+        }
+
+        self.file.seek(SeekFrom::Start(self.packet_end))?;
+        self.file.write_all(&buffer)?;
+        self.packet_end += buffer.len() as u64;
+
+        self.file
+            .seek(SeekFrom::Start(self.packet_start + PACKET_HEADER_SIZE))?;
+        self.file.write_all(&self.packet_context(timestamp))?;
+
+        Ok(())
+    }
+}
+
+static TRACE: TraceFile<Trace> = TraceFile::new("CTF", TRACE_DIR_ENV_VAR);
+
+thread_local! {
+    /// Each thread writes its events to its own stream, created when it first fires a probe
+    static STREAM: RefCell<Option<Stream>> = const { RefCell::new(None) };
+}
+
+/// Tests if the trace is being written to the directory named by `TRACERS_CTF_TRACE_DIR`, which
+/// the first call creates
+#[inline]
+pub fn is_enabled() -> bool {
+    TRACE.is_enabled(Trace::create)
+}
+
+/// Gets the ID of `event`, declaring it in the trace if this is the first time it's fired
+fn event_id(event: &Event) -> io::Result<u32> {
+    let id = event.id.load(Ordering::Acquire);
+    if id != UNASSIGNED_EVENT_ID {
+        return Ok(id);
+    }
+
+    TRACE.with_trace(|trace| {
+        //Another thread may have declared it while this one waited for the lock
+        let id = event.id.load(Ordering::Acquire);
+        if id != UNASSIGNED_EVENT_ID {
+            return Ok(id);
+        }
+
+        let id = trace.declare_event(event)?;
+        event.id.store(id, Ordering::Release);
+
+        Ok(id)
+    })
+}
+
+/// Writes one event to this thread's stream
+///
+/// # Safety
+///
+/// The pointers in any `Str` or `Bytes` values must be null or point to valid strings
+unsafe fn write_event_to_stream(event: &Event, values: &[ArgValue]) -> io::Result<()> {
+    let id = event_id(event)?;
+
+    STREAM.with(|stream| {
+        let mut stream = stream.borrow_mut();
+        if stream.is_none() {
+            *stream = Some(TRACE.with_trace(|trace| trace.create_stream(trace_file::thread_id()))?);
+        }
+
+        stream
+            .as_mut()
+            .unwrap()
+            .write_event(id, event.fields, values)
+    })
+}
+
+/// Writes one firing of the probe declared by `event`, with a value for each of its fields, if
+/// the trace is being written.
+///
+/// # Safety
+///
+/// The pointers in any `Str` or `Bytes` values must be null or point to valid strings
+pub unsafe fn write_event(event: &Event, values: &[ArgValue]) {
+    TRACE.write(|| write_event_to_stream(event, values));
+}
+
+/// Reads a little-endian integer of `size` bytes from a stream file, advancing `offset` past it.
+///
+/// This and `read_string` are for the tests which read a stream back; there should be no reason
+/// to call them otherwise.
+#[doc(hidden)]
+pub fn read_int(bytes: &[u8], offset: &mut usize, size: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf[..size].copy_from_slice(&bytes[*offset..*offset + size]);
+    *offset += size;
+    u64::from_le_bytes(buf)
+}
+
+/// Reads a NUL-terminated string from a stream file, advancing `offset` past the NUL.  Panics if
+/// it isn't terminated or isn't UTF-8.
+#[doc(hidden)]
+pub fn read_string<'a>(bytes: &'a [u8], offset: &mut usize) -> &'a str {
+    let len = bytes[*offset..].iter().position(|b| *b == 0).unwrap();
+    let s = std::str::from_utf8(&bytes[*offset..*offset + len]).unwrap();
+    *offset += len + 1;
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_trace_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("tracers-ctf-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    static FIELDS: [Field; 4] = [
+        Field::new("count", CType::Int),
+        Field::new("name", CType::CharPtr),
+        Field::new("path_len", CType::SizeT),
+        Field::sequence("path", "path_len"),
+    ];
+    static EVENT: Event = Event::new("my_provider", "hello", Some(ProbeLevel::Warning), &FIELDS);
+    static NO_FIELDS: Event = Event::new("my_provider", "goodbye", None, &[]);
+
+    #[test]
+    fn declares_events_in_metadata() {
+        let dir = temp_trace_dir("metadata");
+        let mut trace = Trace::create(&dir).unwrap();
+        assert_eq!(0, trace.declare_event(&EVENT).unwrap());
+        assert_eq!(1, trace.declare_event(&NO_FIELDS).unwrap());
+        drop(trace);
+
+        let metadata = fs::read_to_string(dir.join("metadata")).unwrap();
+        assert!(metadata.starts_with("/* CTF 1.8 */\n"));
+        assert_eq!(
+            metadata.matches('{').count(),
+            metadata.matches('}').count(),
+            "{}",
+            metadata
+        );
+        assert!(metadata.contains(
+            "
+event {
+    name = \"my_provider:hello\";
+    id = 0;
+    stream_id = 0;
+    loglevel = 4;
+    fields := struct {
+        integer { size = 32; align = 8; signed = 1; base = 10; } _count;
+        string _name;
+        integer { size = 64; align = 8; signed = 0; base = 10; } _path_len;
+        uint8_t _path[_path_len];
+    };
+};
+"
+        ));
+        assert!(metadata.ends_with(
+            "
+event {
+    name = \"my_provider:goodbye\";
+    id = 1;
+    stream_id = 0;
+    fields := struct {
+    };
+};
+"
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stream_parses_back() {
+        let dir = temp_trace_dir("stream");
+        let trace = Trace::create(&dir).unwrap();
+        let mut stream = trace.create_stream(7).unwrap();
+        let name = b"world\0";
+        let path = b"/foo\0bar";
+
+        unsafe {
+            for count in 0..3i32 {
+                stream
+                    .write_event(
+                        0,
+                        &FIELDS,
+                        &[
+                            ArgValue::from(-count),
+                            ArgValue::from(name.as_ptr()),
+                            ArgValue::from(path.len()),
+                            ArgValue::bytes(path.as_ptr() as *const c_void, path.len()),
+                        ],
+                    )
+                    .unwrap();
+            }
+            stream.write_event(1, &[], &[]).unwrap();
+        }
+
+        let bytes = fs::read(dir.join("stream_7")).unwrap();
+        let mut offset = 0;
+        assert_eq!(PACKET_MAGIC as u64, read_int(&bytes, &mut offset, 4));
+        assert_eq!(0, read_int(&bytes, &mut offset, 4)); //stream_id
+        let timestamp_begin = read_int(&bytes, &mut offset, 8);
+        let timestamp_end = read_int(&bytes, &mut offset, 8);
+        assert!(timestamp_begin <= timestamp_end);
+        assert_eq!(bytes.len() as u64 * 8, read_int(&bytes, &mut offset, 8)); //content_size
+        assert_eq!(bytes.len() as u64 * 8, read_int(&bytes, &mut offset, 8)); //packet_size
+        assert_eq!(7, read_int(&bytes, &mut offset, 8)); //thread_id
+
+        let mut last_timestamp = timestamp_begin;
+        for count in 0..3i32 {
+            assert_eq!(0, read_int(&bytes, &mut offset, 4)); //id
+            let timestamp = read_int(&bytes, &mut offset, 8);
+            assert!(timestamp >= last_timestamp);
+            last_timestamp = timestamp;
+
+            let value = read_int(&bytes, &mut offset, 4) as u32;
+            assert_eq!(-count, value as i32);
+            assert_eq!("world", read_string(&bytes, &mut offset));
+            assert_eq!(8, read_int(&bytes, &mut offset, 8));
+            assert_eq!(b"/foo\0bar", &bytes[offset..offset + 8]);
+            offset += 8;
+        }
+
+        assert_eq!(1, read_int(&bytes, &mut offset, 4));
+        assert_eq!(timestamp_end, read_int(&bytes, &mut offset, 8));
+        assert_eq!(bytes.len(), offset);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn encodes_fields_by_c_type() {
+        let mut buffer = Vec::new();
+        unsafe {
+            let string = Field::new("string", CType::CharPtr);
+            let sequence = Field::sequence("sequence", "sequence_len");
+
+            encode_field(
+                &mut buffer,
+                &Field::new("short", CType::Short),
+                ArgValue::from(-2i16),
+            );
+            encode_field(
+                &mut buffer,
+                &Field::new("uchar", CType::UChar),
+                ArgValue::from(200u8),
+            );
+            encode_field(&mut buffer, &string, ArgValue::from(std::ptr::null::<u8>()));
+            encode_field(
+                &mut buffer,
+                &string,
+                ArgValue::bytes(b"a\0b".as_ptr() as *const c_void, 3),
+            );
+            encode_field(
+                &mut buffer,
+                &sequence,
+                ArgValue::bytes(b"c\0d".as_ptr() as *const c_void, 3),
+            );
+            encode_field(&mut buffer, &sequence, ArgValue::bytes(std::ptr::null(), 2));
+        }
+
+        assert_eq!(
+            vec![0xfe, 0xff, 200, 0, b'a', 0, b'c', 0, b'd', 0, 0],
+            buffer
+        );
+        assert_eq!(
+            (2, true),
+            integer_layout(&CType::Short),
+            "short is 16 bits on every supported platform"
+        );
+    }
+}
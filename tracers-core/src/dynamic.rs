@@ -8,8 +8,112 @@
 use crate::argtypes::{
     wrap, CType, ProbeArgNativeType, ProbeArgNativeTypeInfo, ProbeArgType, ProbeArgWrapper,
 };
-use failure::Fallible;
+use failure::{Fail, Fallible};
+use std::ffi::CStr;
+use std::fmt;
 use std::marker::PhantomData;
+use std::os::raw::{
+    c_char, c_int, c_long, c_longlong, c_short, c_uchar, c_uint, c_ulong, c_ulonglong, c_ushort,
+    c_void,
+};
+use std::ptr;
+
+mod callback;
+mod tee;
+
+pub use self::callback::*;
+pub use self::tee::*;
+
+/// The most arguments a probe can take, which is the most the `fireN` methods support
+pub const MAX_PROBE_ARGS: usize = 12;
+
+#[derive(Debug)]
+pub enum DynProbeError {
+    TooManyArgs {
+        name: &'static str,
+        count: usize,
+        max: usize,
+    },
+
+    VoidArg {
+        name: &'static str,
+        index: usize,
+    },
+
+    WrongArgCount {
+        name: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+
+    WrongArgType {
+        name: &'static str,
+        index: usize,
+        expected: CType,
+        actual: CType,
+    },
+
+    Unsupported {
+        name: &'static str,
+        tracer: &'static str,
+    },
+}
+
+impl fmt::Display for DynProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DynProbeError::TooManyArgs { name, count, max } => write!(
+                f,
+                "probe '{}' has {} arguments but probes can have at most {}",
+                name, count, max
+            ),
+            DynProbeError::VoidArg { name, index } => write!(
+                f,
+                "argument {} of probe '{}' can't be of type void",
+                index, name
+            ),
+            DynProbeError::WrongArgCount {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "probe '{}' takes {} arguments but was fired with {}",
+                name, expected, actual
+            ),
+            DynProbeError::WrongArgType {
+                name,
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "argument {} of probe '{}' is of type {} but was fired with a value of type {}",
+                index, name, expected, actual
+            ),
+            DynProbeError::Unsupported { name, tracer } => write!(
+                f,
+                "probe '{}' can't be added because the {} tracer doesn't support dynamically typed probes",
+                name, tracer
+            ),
+        }
+    }
+}
+
+impl Fail for DynProbeError {}
+
+/// What a `ProviderBuilder` does with a probe which has more args than its tracer can pass
+/// natively, like SystemTap's six
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ArgOverflow {
+    /// Fail to add the probe.  This is the default.
+    #[default]
+    Reject,
+
+    /// Pass the args which don't fit some other way, such as packed into a struct passed by
+    /// pointer.  How is up to the tracer.
+    Spill,
+}
 
 /// Each implementation of the tracing API provides a `Tracer` implementation, which provides
 /// tracing functionality for an entire process.
@@ -35,6 +139,33 @@ pub trait Tracer: Sized {
 
 pub trait ProviderBuilder<TracerT: Tracer> {
     fn add_probe<ArgsT: ProbeArgs<ArgsT>>(&mut self, name: &'static str) -> Fallible<()>;
+
+    /// Adds a probe whose argument types are only known at runtime, like a probe defined by a
+    /// script or by configuration.  Such probes are fired with the `DynProbe` returned by
+    /// `Provider::get_probe_dyn`.
+    ///
+    /// Like all probe names, `name` must be `'static`; since providers generally live as long as
+    /// the process, a name only known at runtime can be leaked with `Box::leak`.
+    ///
+    /// Tracers which don't support such probes needn't implement this; by default it fails with
+    /// `DynProbeError::Unsupported`.
+    fn add_probe_dyn(&mut self, name: &'static str, arg_types: &[CType]) -> Fallible<()> {
+        let _ = arg_types;
+        Err(DynProbeError::Unsupported {
+            name,
+            tracer: TracerT::TRACING_IMPLEMENTATION,
+        }
+        .into())
+    }
+
+    /// Sets what happens to probes added after this call which have more args than the tracer can
+    /// pass natively.  Tracers without such a limit needn't implement this; by default it's
+    /// ignored.
+    fn arg_overflow(&mut self, arg_overflow: ArgOverflow) -> &mut Self {
+        let _ = arg_overflow;
+        self
+    }
+
     fn build(self, name: &str) -> Fallible<<TracerT as Tracer>::ProviderType>;
 }
 
@@ -48,6 +179,18 @@ pub trait Provider<TracerT: Tracer> {
         Ok(ProviderProbe::new(unsafe_impl))
     }
 
+    /// Gets a probe added with `ProviderBuilder::add_probe_dyn`.  `arg_types` must be the same as
+    /// when the probe was added.
+    fn get_probe_dyn(
+        &self,
+        name: &'static str,
+        arg_types: &[CType],
+    ) -> Fallible<DynProbe<'_, <TracerT as Tracer>::ProbeType>> {
+        let definition = ProbeDefinition::with_arg_types(name, arg_types)?;
+        let unsafe_impl = self.get_probe_unsafe(&definition)?;
+        Ok(DynProbe::new(definition, unsafe_impl))
+    }
+
     fn get_probe_unsafe(
         &self,
         definition: &ProbeDefinition,
@@ -97,6 +240,171 @@ unsafe impl<'probe, ImplT: UnsafeProviderProbeImpl + Send, ArgsT: ProbeArgs<Args
 {
 }
 
+/// The value of a probe argument whose type is only known at runtime, for firing a `DynProbe`.
+/// There is one variant for each `CType` a probe argument can have.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeValue<'a> {
+    VoidPtr(*const c_void),
+    /// A string, or `None` to pass a NULL pointer
+    CharPtr(Option<&'a CStr>),
+    UCharPtr(*const c_uchar),
+    Char(c_char),
+    UChar(c_uchar),
+    Short(c_short),
+    UShort(c_ushort),
+    Int(c_int),
+    UInt(c_uint),
+    Long(c_long),
+    ULong(c_ulong),
+    LongLong(c_longlong),
+    ULongLong(c_ulonglong),
+    SizeT(libc::size_t),
+    SSizeT(libc::ssize_t),
+}
+
+impl<'a> ProbeValue<'a> {
+    /// The C type of this value, which must match the type of the probe argument it's passed as
+    pub fn c_type(&self) -> CType {
+        match self {
+            ProbeValue::VoidPtr(_) => CType::VoidPtr,
+            ProbeValue::CharPtr(_) => CType::CharPtr,
+            ProbeValue::UCharPtr(_) => CType::UCharPtr,
+            ProbeValue::Char(_) => CType::Char,
+            ProbeValue::UChar(_) => CType::UChar,
+            ProbeValue::Short(_) => CType::Short,
+            ProbeValue::UShort(_) => CType::UShort,
+            ProbeValue::Int(_) => CType::Int,
+            ProbeValue::UInt(_) => CType::UInt,
+            ProbeValue::Long(_) => CType::Long,
+            ProbeValue::ULong(_) => CType::ULong,
+            ProbeValue::LongLong(_) => CType::LongLong,
+            ProbeValue::ULongLong(_) => CType::ULongLong,
+            ProbeValue::SizeT(_) => CType::SizeT,
+            ProbeValue::SSizeT(_) => CType::SSizeT,
+        }
+    }
+
+    /// The value widened to 64 bits, as it's passed in a register.  Signed values are sign
+    /// extended, so a tracer which reads only the low bits of the argument sees the right value.
+    #[allow(clippy::useless_conversion)] //`c_long` and `c_ulong` are only 32 bits on some platforms
+    fn as_register(&self) -> u64 {
+        match *self {
+            ProbeValue::VoidPtr(p) => p as usize as u64,
+            ProbeValue::CharPtr(s) => s.map_or(ptr::null(), CStr::as_ptr) as usize as u64,
+            ProbeValue::UCharPtr(p) => p as usize as u64,
+            ProbeValue::Char(v) => i64::from(v) as u64,
+            ProbeValue::UChar(v) => u64::from(v),
+            ProbeValue::Short(v) => i64::from(v) as u64,
+            ProbeValue::UShort(v) => u64::from(v),
+            ProbeValue::Int(v) => i64::from(v) as u64,
+            ProbeValue::UInt(v) => u64::from(v),
+            ProbeValue::Long(v) => i64::from(v) as u64,
+            ProbeValue::ULong(v) => u64::from(v),
+            ProbeValue::LongLong(v) => v as u64,
+            ProbeValue::ULongLong(v) => v,
+            ProbeValue::SizeT(v) => v as u64,
+            ProbeValue::SSizeT(v) => v as i64 as u64,
+        }
+    }
+}
+
+impl<'a> From<&'a CStr> for ProbeValue<'a> {
+    fn from(s: &'a CStr) -> Self {
+        ProbeValue::CharPtr(Some(s))
+    }
+}
+
+/// A probe whose argument types are only known at runtime.  Unlike `ProviderProbe` the types of
+/// the args can't be checked at compile time, so `fire` checks them against the probe's definition
+/// every time.
+pub struct DynProbe<'probe, ImplT: UnsafeProviderProbeImpl> {
+    definition: ProbeDefinition,
+    unsafe_probe_impl: &'probe ImplT,
+}
+
+impl<'probe, ImplT: UnsafeProviderProbeImpl> DynProbe<'probe, ImplT> {
+    fn new(definition: ProbeDefinition, probe: &'probe ImplT) -> Self {
+        DynProbe {
+            definition,
+            unsafe_probe_impl: probe,
+        }
+    }
+
+    pub fn definition(&self) -> &ProbeDefinition {
+        &self.definition
+    }
+
+    /// Indicates if this probe is currently enabled.  See `ProviderProbe::is_enabled`.
+    pub fn is_enabled(&self) -> bool {
+        self.unsafe_probe_impl.is_enabled()
+    }
+
+    /// Fires the probe, after verifying that `args` are the same number and types as the probe's
+    /// arguments.  As with `ProviderProbe::fire`, it's assumed higher level code has tested
+    /// `is_enabled()` already.
+    ///
+    /// Each value is passed to the tracing implementation as a 64-bit integer, which is how
+    /// `libstapsdt` passes every probe argument on the 64-bit platforms it supports.
+    pub fn fire(&self, args: &[ProbeValue]) -> Fallible<()> {
+        let name = self.definition.name;
+        let arg_types = &self.definition.arg_types;
+
+        if args.len() != arg_types.len() {
+            return Err(DynProbeError::WrongArgCount {
+                name,
+                expected: arg_types.len(),
+                actual: args.len(),
+            }
+            .into());
+        }
+
+        let mut a = [0u64; MAX_PROBE_ARGS];
+        for (index, (arg, arg_type)) in args.iter().zip(arg_types.iter()).enumerate() {
+            if arg.c_type() != *arg_type {
+                return Err(DynProbeError::WrongArgType {
+                    name,
+                    index,
+                    expected: arg_type.clone(),
+                    actual: arg.c_type(),
+                }
+                .into());
+            }
+
+            a[index] = arg.as_register();
+        }
+
+        let p = self.unsafe_probe_impl;
+        unsafe {
+            match args.len() {
+                0 => p.fire0(),
+                1 => p.fire1(a[0]),
+                2 => p.fire2(a[0], a[1]),
+                3 => p.fire3(a[0], a[1], a[2]),
+                4 => p.fire4(a[0], a[1], a[2], a[3]),
+                5 => p.fire5(a[0], a[1], a[2], a[3], a[4]),
+                6 => p.fire6(a[0], a[1], a[2], a[3], a[4], a[5]),
+                7 => p.fire7(a[0], a[1], a[2], a[3], a[4], a[5], a[6]),
+                8 => p.fire8(a[0], a[1], a[2], a[3], a[4], a[5], a[6], a[7]),
+                9 => p.fire9(a[0], a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8]),
+                10 => p.fire10(a[0], a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9]),
+                11 => p.fire11(
+                    a[0], a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10],
+                ),
+                _ => p.fire12(
+                    a[0], a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10], a[11],
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// If the underlying impl is Sync/Send, then so is this wrapper around it
+
+unsafe impl<'probe, ImplT: UnsafeProviderProbeImpl + Sync> Sync for DynProbe<'probe, ImplT> {}
+unsafe impl<'probe, ImplT: UnsafeProviderProbeImpl + Send> Send for DynProbe<'probe, ImplT> {}
+
 /// All tuple types which consist entirely of elements which have a `ProbeArgType<T>` implementation
 /// also implement `ProbeArgs<T>` where `T` is the tuple type.  This provides auto-generated methods
 /// to obtain runtime information about the argument types, and also to wrap the elements and pass them
@@ -131,6 +439,28 @@ impl ProbeDefinition {
             arg_types: <ArgsT as ProbeArgs<ArgsT>>::arg_types(),
         }
     }
+
+    /// Defines a probe whose argument types are only known at runtime.  Fails if there are more
+    /// args than a probe can take, or if any of them is `void`.
+    pub fn with_arg_types(name: &'static str, arg_types: &[CType]) -> Fallible<ProbeDefinition> {
+        if arg_types.len() > MAX_PROBE_ARGS {
+            return Err(DynProbeError::TooManyArgs {
+                name,
+                count: arg_types.len(),
+                max: MAX_PROBE_ARGS,
+            }
+            .into());
+        }
+
+        if let Some(index) = arg_types.iter().position(|typ| *typ == CType::NoArg) {
+            return Err(DynProbeError::VoidArg { name, index }.into());
+        }
+
+        Ok(ProbeDefinition {
+            name,
+            arg_types: arg_types.to_vec(),
+        })
+    }
 }
 
 /// Internal helper func used by the generated implementation to evaluate a probe arg type to its corresponding
@@ -218,4 +548,141 @@ mod test {
             vec!["hey the probe fired"]
         );
     }
+
+    #[test]
+    fn test_dyn_probe_fire() {
+        let definition = ProbeDefinition::with_arg_types(
+            "dyn_probe",
+            &[CType::Int, CType::ULong, CType::CharPtr],
+        )
+        .unwrap();
+        let unsafe_impl = TestingProviderProbeImpl::new("%d %lu %s".to_string());
+        let probe = DynProbe::new(definition, &unsafe_impl);
+        let foo = CString::new("foo").unwrap();
+
+        probe
+            .fire(&[
+                ProbeValue::Int(-42),
+                ProbeValue::ULong(42),
+                ProbeValue::from(foo.as_c_str()),
+            ])
+            .unwrap();
+        probe
+            .fire(&[
+                ProbeValue::Int(0),
+                ProbeValue::ULong(0),
+                ProbeValue::CharPtr(None),
+            ])
+            .unwrap();
+
+        assert_eq!(unsafe_impl.get_calls(), vec!["-42 42 foo", "0 0 (null)"]);
+    }
+
+    #[test]
+    fn test_tee_probe_fire() {
+        let mut first = TestingProviderProbeImpl::new("first %d %s".to_string());
+        let mut second = TestingProviderProbeImpl::new("second %d %s".to_string());
+        let foo = "foo".to_string();
+
+        //Neither probe is enabled, so neither fires
+        {
+            let tee = TeeProbe::new(&first, &second);
+            let probe: ProviderProbe<_, (i32, &String)> = ProviderProbe::new(&tee);
+            assert!(!probe.is_enabled());
+        }
+
+        first.is_enabled = true;
+        {
+            let tee = TeeProbe::new(&first, &second);
+            let probe = ProviderProbe::new(&tee);
+            assert!(probe.is_enabled());
+            probe.fire((1i32, &foo));
+        }
+
+        second.is_enabled = true;
+        {
+            let tee = TeeProbe::new(&first, &second);
+            let probe = ProviderProbe::new(&tee);
+            probe.fire((2i32, &foo));
+        }
+
+        assert_eq!(first.get_calls(), vec!["first 1 foo", "first 2 foo"]);
+        assert_eq!(second.get_calls(), vec!["second 2 foo"]);
+    }
+
+    #[test]
+    fn test_dyn_probe_validation() {
+        let unsafe_impl = TestingProviderProbeImpl::new("%d".to_string());
+        let probe = DynProbe::new(
+            ProbeDefinition::with_arg_types("dyn_probe", &[CType::Int]).unwrap(),
+            &unsafe_impl,
+        );
+
+        assert!(probe.fire(&[]).is_err());
+        assert!(probe
+            .fire(&[ProbeValue::Int(1), ProbeValue::Int(2)])
+            .is_err());
+        assert!(probe.fire(&[ProbeValue::UInt(1)]).is_err());
+        assert!(unsafe_impl.get_calls().is_empty());
+
+        assert!(ProbeDefinition::with_arg_types("void", &[CType::NoArg]).is_err());
+        assert!(ProbeDefinition::with_arg_types("too_many", &vec![CType::Int; 13]).is_err());
+    }
+
+    /// A tracer whose builder only implements the required methods, like one written before
+    /// `add_probe_dyn` existed
+    struct StaticOnlyTracer;
+    struct StaticOnlyBuilder;
+    struct StaticOnlyProvider;
+
+    impl Tracer for StaticOnlyTracer {
+        const TRACING_IMPLEMENTATION: &'static str = "static_only";
+
+        type ProviderBuilderType = StaticOnlyBuilder;
+        type ProviderType = StaticOnlyProvider;
+        type ProbeType = TestingProviderProbeImpl;
+
+        fn define_provider(
+            name: &str,
+            f: impl FnOnce(Self::ProviderBuilderType) -> Fallible<Self::ProviderBuilderType>,
+        ) -> Fallible<Self::ProviderType> {
+            f(StaticOnlyBuilder)?.build(name)
+        }
+    }
+
+    impl ProviderBuilder<StaticOnlyTracer> for StaticOnlyBuilder {
+        fn add_probe<ArgsT: ProbeArgs<ArgsT>>(&mut self, _name: &'static str) -> Fallible<()> {
+            Ok(())
+        }
+
+        fn build(self, _name: &str) -> Fallible<StaticOnlyProvider> {
+            Ok(StaticOnlyProvider)
+        }
+    }
+
+    impl Provider<StaticOnlyTracer> for StaticOnlyProvider {
+        fn get_probe_unsafe(
+            &self,
+            definition: &ProbeDefinition,
+        ) -> Fallible<&TestingProviderProbeImpl> {
+            Err(failure::format_err!("no probe {}", definition.name))
+        }
+    }
+
+    #[test]
+    fn test_add_probe_dyn_unsupported_by_default() {
+        let result = StaticOnlyTracer::define_provider("provider", |mut builder| {
+            builder.add_probe::<(i32,)>("static_probe")?;
+            builder.add_probe_dyn("dyn_probe", &[CType::Int])?;
+            Ok(builder)
+        });
+
+        match result {
+            Ok(_) => panic!("adding a dynamically typed probe should have failed"),
+            Err(e) => assert_eq!(
+                "probe 'dyn_probe' can't be added because the static_only tracer doesn't support dynamically typed probes",
+                e.to_string()
+            ),
+        }
+    }
 }
@@ -0,0 +1,231 @@
+//! A `Tracer` which passes every probe firing to a callback in the same process.
+//!
+//! On its own this isn't much of a tracer, but teed with a platform tracer it lets a process
+//! observe its own probes, for example to log them or to check them in tests, while they remain
+//! visible to external tools.  The callback is process-wide; until one is set every probe of this
+//! tracer is disabled.
+use super::{
+    CType, ProbeArgNativeType, ProbeArgs, ProbeDefinition, ProbeValue, Provider, ProviderBuilder,
+    Tracer, UnsafeProviderProbeNativeImpl,
+};
+use failure::{Fail, Fallible};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fmt;
+use std::mem;
+use std::os::raw::{
+    c_char, c_int, c_long, c_longlong, c_short, c_uchar, c_uint, c_ulong, c_ulonglong, c_ushort,
+    c_void,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug)]
+pub enum CallbackError {
+    ProbeDefinitionNotFound { probe: ProbeDefinition },
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CallbackError::ProbeDefinitionNotFound { probe } => {
+                write!(f, "probe definition not found: {:?}", probe)
+            }
+        }
+    }
+}
+
+impl Fail for CallbackError {}
+
+/// A single firing of a probe, as passed to the callback.  The args have the types in the probe's
+/// definition; the probes generated by `#[tracer]` define string args as pointers, so those are
+/// `ProbeValue::VoidPtr`s which point to NUL-terminated strings, or are NULL.
+#[derive(Debug)]
+pub struct ProbeFiring<'a> {
+    pub provider: &'a str,
+    pub probe: &'a str,
+    pub args: &'a [ProbeValue<'a>],
+}
+
+type Callback = dyn Fn(&ProbeFiring) + Send + Sync;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static CALLBACK: RwLock<Option<Arc<Callback>>> = RwLock::new(None);
+
+/// Sets the callback which every probe of `CallbackTracer` passes its firings to, replacing any
+/// previous one.  The callback is called on the thread which fires the probe, so it should be
+/// quick; the string args it's passed are only valid for the duration of the call.
+pub fn set_probe_callback(callback: impl Fn(&ProbeFiring) + Send + Sync + 'static) {
+    *CALLBACK.write().unwrap() = Some(Arc::new(callback));
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Removes the callback, disabling every probe of `CallbackTracer` again
+pub fn clear_probe_callback() {
+    ENABLED.store(false, Ordering::Release);
+    *CALLBACK.write().unwrap() = None;
+}
+
+/// Implements `Tracer` by passing each firing to the callback set with `set_probe_callback`
+pub struct CallbackTracer {}
+
+impl Tracer for CallbackTracer {
+    const TRACING_IMPLEMENTATION: &'static str = "callback";
+
+    type ProviderBuilderType = CallbackProviderBuilder;
+    type ProviderType = CallbackProvider;
+    type ProbeType = CallbackProbe;
+
+    fn define_provider(
+        name: &str,
+        f: impl FnOnce(Self::ProviderBuilderType) -> Fallible<Self::ProviderBuilderType>,
+    ) -> Fallible<Self::ProviderType> {
+        let builder = f(CallbackProviderBuilder { probes: Vec::new() })?;
+        builder.build(name)
+    }
+}
+
+pub struct CallbackProviderBuilder {
+    probes: Vec<ProbeDefinition>,
+}
+
+impl ProviderBuilder<CallbackTracer> for CallbackProviderBuilder {
+    fn add_probe<ArgsT: ProbeArgs<ArgsT>>(&mut self, name: &'static str) -> Fallible<()> {
+        self.probes.push(ProbeDefinition::new::<ArgsT>(name));
+
+        Ok(())
+    }
+
+    fn add_probe_dyn(&mut self, name: &'static str, arg_types: &[CType]) -> Fallible<()> {
+        self.probes
+            .push(ProbeDefinition::with_arg_types(name, arg_types)?);
+
+        Ok(())
+    }
+
+    fn build(self, name: &str) -> Fallible<CallbackProvider> {
+        let probes = self
+            .probes
+            .into_iter()
+            .map(|definition| {
+                let probe = CallbackProbe {
+                    provider: name.to_owned(),
+                    definition: definition.clone(),
+                };
+                (definition, probe)
+            })
+            .collect();
+
+        Ok(CallbackProvider { probes })
+    }
+}
+
+pub struct CallbackProvider {
+    probes: HashMap<ProbeDefinition, CallbackProbe>,
+}
+
+impl Provider<CallbackTracer> for CallbackProvider {
+    /// Look up the probe by its definition (that is, name and arg types)
+    fn get_probe_unsafe(&self, definition: &ProbeDefinition) -> Fallible<&CallbackProbe> {
+        self.probes.get(definition).ok_or_else(|| {
+            CallbackError::ProbeDefinitionNotFound {
+                probe: definition.clone(),
+            }
+            .into()
+        })
+    }
+}
+
+/// A probe which passes its firings to the callback.  Its args arrive either as their native C
+/// types or, from a `DynProbe`, already widened to 64 bits; both are widened to 64 bits and then
+/// read back as the types in the probe's definition.
+pub struct CallbackProbe {
+    provider: String,
+    definition: ProbeDefinition,
+}
+
+impl CallbackProbe {
+    /// Passes a firing with these args, each widened to 64 bits, to the callback if there is one.
+    ///
+    /// The caller must ensure the args are of the types in the probe's definition, and that any
+    /// string pointers among them are either NULL or point to NUL-terminated strings.
+    unsafe fn fire_registers(&self, registers: &[u64]) {
+        //Don't hold the lock while the callback runs, in case it fires a probe or sets a callback
+        let callback = match CALLBACK.read().unwrap().as_ref() {
+            Some(callback) => callback.clone(),
+            None => return,
+        };
+
+        let args: Vec<ProbeValue> = self
+            .definition
+            .arg_types
+            .iter()
+            .zip(registers.iter())
+            .map(|(c_type, register)| value_from_register(c_type, *register))
+            .collect();
+
+        callback(&ProbeFiring {
+            provider: &self.provider,
+            probe: self.definition.name,
+            args: &args,
+        });
+    }
+}
+
+/// Widens a native probe arg to 64 bits, the same way `ProbeValue` does
+#[allow(clippy::useless_conversion)] //`c_long` and `c_ulong` are only 32 bits on some platforms
+unsafe fn register_from_native<T: ProbeArgNativeType<T>>(arg: T) -> u64 {
+    //`T` is the Rust type of its C type, so it can be read as that type
+    match T::get_c_type() {
+        CType::NoArg => 0,
+        CType::VoidPtr | CType::CharPtr | CType::UCharPtr => {
+            mem::transmute_copy::<T, usize>(&arg) as u64
+        }
+        CType::Char => i64::from(mem::transmute_copy::<T, c_char>(&arg)) as u64,
+        CType::UChar => u64::from(mem::transmute_copy::<T, c_uchar>(&arg)),
+        CType::Short => i64::from(mem::transmute_copy::<T, c_short>(&arg)) as u64,
+        CType::UShort => u64::from(mem::transmute_copy::<T, c_ushort>(&arg)),
+        CType::Int => i64::from(mem::transmute_copy::<T, c_int>(&arg)) as u64,
+        CType::UInt => u64::from(mem::transmute_copy::<T, c_uint>(&arg)),
+        CType::Long => i64::from(mem::transmute_copy::<T, c_long>(&arg)) as u64,
+        CType::ULong => u64::from(mem::transmute_copy::<T, c_ulong>(&arg)),
+        CType::LongLong => mem::transmute_copy::<T, c_longlong>(&arg) as u64,
+        CType::ULongLong => mem::transmute_copy::<T, c_ulonglong>(&arg),
+        CType::SizeT => mem::transmute_copy::<T, libc::size_t>(&arg) as u64,
+        CType::SSizeT => mem::transmute_copy::<T, libc::ssize_t>(&arg) as u64,
+    }
+}
+
+/// Reads an arg widened to 64 bits back as a value of its C type.  A string is borrowed from the
+/// pointer, so it must be either NULL or valid for the lifetime `'a`.
+unsafe fn value_from_register<'a>(c_type: &CType, register: u64) -> ProbeValue<'a> {
+    match c_type {
+        CType::NoArg => unreachable!("probes can't have void args"),
+        CType::VoidPtr => ProbeValue::VoidPtr(register as usize as *const c_void),
+        CType::CharPtr => {
+            let ptr = register as usize as *const c_char;
+            ProbeValue::CharPtr(if ptr.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(ptr))
+            })
+        }
+        CType::UCharPtr => ProbeValue::UCharPtr(register as usize as *const c_uchar),
+        CType::Char => ProbeValue::Char(register as c_char),
+        CType::UChar => ProbeValue::UChar(register as c_uchar),
+        CType::Short => ProbeValue::Short(register as c_short),
+        CType::UShort => ProbeValue::UShort(register as c_ushort),
+        CType::Int => ProbeValue::Int(register as c_int),
+        CType::UInt => ProbeValue::UInt(register as c_uint),
+        CType::Long => ProbeValue::Long(register as c_long),
+        CType::ULong => ProbeValue::ULong(register as c_ulong),
+        CType::LongLong => ProbeValue::LongLong(register as c_longlong),
+        CType::ULongLong => ProbeValue::ULongLong(register),
+        CType::SizeT => ProbeValue::SizeT(register as libc::size_t),
+        CType::SSizeT => ProbeValue::SSizeT(register as libc::ssize_t),
+    }
+}
+
+// The implementation of `UnsafeProviderProbeNativeImpl` has a `c_fireN` method for each of 0 to 12
+// args, so it's generated by the code in `build.rs`.
+include!(concat!(env!("OUT_DIR"), "/callback_probe.rs"));
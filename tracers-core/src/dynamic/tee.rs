@@ -0,0 +1,231 @@
+//! A `Tracer` which fans out to two other tracers at once.
+//!
+//! This is useful during a migration from one tracing implementation to another, or to make
+//! probes visible to an external tool like SystemTap while also capturing them with an in-process
+//! consumer.  `TeeTracer`s can be nested to fan out to more than two tracers.
+use super::{
+    ArgOverflow, CType, ProbeArgNativeType, ProbeArgs, ProbeDefinition, Provider, ProviderBuilder,
+    Tracer, UnsafeProviderProbeImpl, UnsafeProviderProbeNativeImpl,
+};
+use failure::{Fail, Fallible};
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr;
+
+#[derive(Debug)]
+pub enum TeeError {
+    ProbeDefinitionNotFound { probe: ProbeDefinition },
+}
+
+impl fmt::Display for TeeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TeeError::ProbeDefinitionNotFound { probe } => {
+                write!(f, "probe definition not found: {:?}", probe)
+            }
+        }
+    }
+}
+
+impl Fail for TeeError {}
+
+/// Implements `Tracer` by defining every provider, and firing every probe, with both tracer `A`
+/// and tracer `B`.  A probe is enabled if it's enabled in either tracer, and when it fires it fires
+/// only in the tracers in which it's enabled.
+///
+/// The args are converted to their C types once and those are passed to both tracers, so both
+/// tracers' probes must implement `UnsafeProviderProbeNativeImpl`, as every tracer in this crate's
+/// implementations does.
+pub struct TeeTracer<A: Tracer, B: Tracer> {
+    _tracers: PhantomData<(A, B)>,
+}
+
+impl<A: Tracer, B: Tracer> Tracer for TeeTracer<A, B>
+where
+    A::ProbeType: UnsafeProviderProbeNativeImpl,
+    B::ProbeType: UnsafeProviderProbeNativeImpl,
+{
+    const TRACING_IMPLEMENTATION: &'static str = "tee";
+
+    type ProviderBuilderType = TeeProviderBuilder<A, B>;
+    type ProviderType = TeeProvider<A, B>;
+    type ProbeType = TeeProbe<A::ProbeType, B::ProbeType>;
+
+    fn define_provider(
+        name: &str,
+        f: impl FnOnce(Self::ProviderBuilderType) -> Fallible<Self::ProviderBuilderType>,
+    ) -> Fallible<Self::ProviderType> {
+        let builder = f(TeeProviderBuilder::new())?;
+        builder.build(name)
+    }
+}
+
+/// Records the definition of each probe, and what to do if it has too many args, so that the same
+/// probes can be added to the providers of both tracers once the provider is built.
+pub struct TeeProviderBuilder<A: Tracer, B: Tracer> {
+    probes: Vec<(ProbeDefinition, ArgOverflow)>,
+    arg_overflow: ArgOverflow,
+    _tracers: PhantomData<(A, B)>,
+}
+
+impl<A: Tracer, B: Tracer> TeeProviderBuilder<A, B> {
+    fn new() -> TeeProviderBuilder<A, B> {
+        TeeProviderBuilder {
+            probes: Vec::new(),
+            arg_overflow: ArgOverflow::default(),
+            _tracers: PhantomData,
+        }
+    }
+
+    /// Defines a provider with the probes added to this builder using tracer `T`.  Every probe is
+    /// added with `add_probe_dyn`, since only the definition of each probe is known here.  The
+    /// resulting probes are the same as those added by `add_probe`.
+    fn define_provider<T: Tracer>(&self, name: &str) -> Fallible<T::ProviderType> {
+        T::define_provider(name, |mut builder| {
+            for (probe, arg_overflow) in self.probes.iter() {
+                builder.arg_overflow(*arg_overflow);
+                builder.add_probe_dyn(probe.name, &probe.arg_types)?;
+            }
+
+            Ok(builder)
+        })
+    }
+}
+
+impl<A: Tracer, B: Tracer> ProviderBuilder<TeeTracer<A, B>> for TeeProviderBuilder<A, B>
+where
+    A::ProbeType: UnsafeProviderProbeNativeImpl,
+    B::ProbeType: UnsafeProviderProbeNativeImpl,
+{
+    fn add_probe<ArgsT: ProbeArgs<ArgsT>>(&mut self, name: &'static str) -> Fallible<()> {
+        self.probes
+            .push((ProbeDefinition::new::<ArgsT>(name), self.arg_overflow));
+
+        Ok(())
+    }
+
+    fn add_probe_dyn(&mut self, name: &'static str, arg_types: &[CType]) -> Fallible<()> {
+        self.probes.push((
+            ProbeDefinition::with_arg_types(name, arg_types)?,
+            self.arg_overflow,
+        ));
+
+        Ok(())
+    }
+
+    fn arg_overflow(&mut self, arg_overflow: ArgOverflow) -> &mut Self {
+        self.arg_overflow = arg_overflow;
+        self
+    }
+
+    fn build(self, name: &str) -> Fallible<TeeProvider<A, B>> {
+        let first = Box::new(self.define_provider::<A>(name)?);
+        let second = Box::new(self.define_provider::<B>(name)?);
+
+        let mut probes = HashMap::new();
+        for (definition, _) in self.probes.into_iter() {
+            let probe = TeeProbe::new(
+                first.get_probe_unsafe(&definition)?,
+                second.get_probe_unsafe(&definition)?,
+            );
+            probes.insert(definition, probe);
+        }
+
+        Ok(TeeProvider {
+            probes,
+            first,
+            second,
+        })
+    }
+}
+
+/// A provider of both tracers.  The probes of each are owned by its own provider, so those
+/// providers are boxed to keep them at the same address for as long as the `TeeProbe`s which
+/// point to them.
+pub struct TeeProvider<A: Tracer, B: Tracer> {
+    //NB: `probes` must be declared first so it's dropped before the providers it points into
+    probes: HashMap<ProbeDefinition, TeeProbe<A::ProbeType, B::ProbeType>>,
+    first: Box<A::ProviderType>,
+    second: Box<B::ProviderType>,
+}
+
+impl<A: Tracer, B: Tracer> TeeProvider<A, B> {
+    /// The provider of tracer `A`
+    pub fn first(&self) -> &A::ProviderType {
+        &self.first
+    }
+
+    /// The provider of tracer `B`
+    pub fn second(&self) -> &B::ProviderType {
+        &self.second
+    }
+}
+
+impl<A: Tracer, B: Tracer> Provider<TeeTracer<A, B>> for TeeProvider<A, B>
+where
+    A::ProbeType: UnsafeProviderProbeNativeImpl,
+    B::ProbeType: UnsafeProviderProbeNativeImpl,
+{
+    /// Look up the probe by its definition (that is, name and arg types)
+    fn get_probe_unsafe(
+        &self,
+        definition: &ProbeDefinition,
+    ) -> Fallible<&TeeProbe<A::ProbeType, B::ProbeType>> {
+        self.probes.get(definition).ok_or_else(|| {
+            TeeError::ProbeDefinitionNotFound {
+                probe: definition.clone(),
+            }
+            .into()
+        })
+    }
+}
+
+/// A probe of both tracers.  The args of each fire are converted to their C representation once,
+/// and then passed to the probe of each tracer in which it's enabled.
+pub struct TeeProbe<A: UnsafeProviderProbeImpl, B: UnsafeProviderProbeImpl> {
+    first: *const A,
+    second: *const B,
+}
+
+impl<A: UnsafeProviderProbeImpl, B: UnsafeProviderProbeImpl> TeeProbe<A, B> {
+    /// The caller must ensure both probes outlive the `TeeProbe`
+    pub(super) fn new(first: &A, second: &B) -> TeeProbe<A, B> {
+        TeeProbe { first, second }
+    }
+
+    /// The probe of tracer `A`
+    pub fn first(&self) -> &A {
+        //The probe is owned by the provider of tracer `A`, which outlives this probe
+        unsafe { &*self.first }
+    }
+
+    /// The probe of tracer `B`
+    pub fn second(&self) -> &B {
+        unsafe { &*self.second }
+    }
+}
+
+// `TeeProbe` points to its two probes, rather than borrowing them, only because they're owned by a
+// sibling of the map which owns it.  It's as `Sync` and `Send` as a reference to them would be.
+
+unsafe impl<A: UnsafeProviderProbeImpl + Sync, B: UnsafeProviderProbeImpl + Sync> Sync
+    for TeeProbe<A, B>
+{
+}
+unsafe impl<A: UnsafeProviderProbeImpl + Sync, B: UnsafeProviderProbeImpl + Sync> Send
+    for TeeProbe<A, B>
+{
+}
+
+/// Copies a native arg, so it can be passed to both probes.  `ProbeArgNativeType` is only
+/// implemented for the Rust types of C types, which are all `Copy`, though the trait doesn't say
+/// so; `register_from_native` in `callback` relies on the same thing.
+#[inline]
+unsafe fn copy_native<T: ProbeArgNativeType<T>>(arg: &T) -> T {
+    ptr::read(arg)
+}
+
+// The implementation of `UnsafeProviderProbeNativeImpl` has a `c_fireN` method for each of 0 to 12
+// args, so it's generated by the code in `build.rs`.
+include!(concat!(env!("OUT_DIR"), "/tee_probe.rs"));
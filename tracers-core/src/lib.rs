@@ -13,21 +13,18 @@ pub extern crate libc;
 pub mod argtypes;
 pub use argtypes::{wrap, ProbeArgNativeType, ProbeArgType, ProbeArgWrapper};
 
-/// The result of a provider init is either a string with some free-form details about the
-/// provider, or a string indicating the error which prevented the provider from initializing
-///
-/// On success, the string takes the form:
-///
-/// ```not_rust
-/// $PROVIDER_NAME::$IMPLEMENTATION::$VERSION
-/// ```
-///
-/// for example:
-///
-/// ```not_rust
-/// my_provider::native/native_noop::0.1.0
-/// ```
-pub type ProviderInitResult = std::result::Result<&'static str, &'static str>;
+pub mod chrome_trace;
+pub mod ctf;
+pub mod probe_level;
+pub use probe_level::ProbeLevel;
+pub mod probe_stats;
+pub mod provider_init;
+pub use provider_init::{ProviderInfo, ProviderInitError, ProviderInitResult};
+pub mod registry;
+pub mod span;
+pub mod throttle;
+mod trace_file;
+pub mod trace_value;
 
 #[cfg(feature = "dynamic")]
 pub mod dynamic;
@@ -0,0 +1,124 @@
+//! The severity level of a probe, set with `#[probe(level = "...")]` on a probe method or with
+//! `#[tracer(level = "...")]` as the default for a whole provider.
+//!
+//! The levels are those of LTTng, which lets a tracing session enable only the probes at or above
+//! some level, like only warnings and above in production.  Other tracing implementations ignore
+//! the level for now, but it's recorded in the runtime registry so it can be used for filtering.
+use strum_macros::{Display, EnumString, IntoStaticStr};
+
+/// The level of a probe, from the most severe to the least.  Levels compare in that order, so a
+/// more severe level is _less than_ a less severe one, just like the numeric LTTng log levels.
+/// Thus "warnings and above" are the probes whose level is `<= ProbeLevel::Warning`.
+///
+/// Each level is written in the `level` attribute as the string it's displayed as, for example
+/// `"warning"` or `"debug_system"`.
+#[derive(
+    Display, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, IntoStaticStr, EnumString,
+)]
+pub enum ProbeLevel {
+    #[strum(serialize = "emergency")]
+    Emergency,
+    #[strum(serialize = "alert")]
+    Alert,
+    #[strum(serialize = "critical")]
+    Critical,
+    #[strum(serialize = "error")]
+    Error,
+    #[strum(serialize = "warning")]
+    Warning,
+    #[strum(serialize = "notice")]
+    Notice,
+    #[strum(serialize = "info")]
+    Info,
+    #[strum(serialize = "debug_system")]
+    DebugSystem,
+    #[strum(serialize = "debug_program")]
+    DebugProgram,
+    #[strum(serialize = "debug_process")]
+    DebugProcess,
+    #[strum(serialize = "debug_module")]
+    DebugModule,
+    #[strum(serialize = "debug_unit")]
+    DebugUnit,
+    #[strum(serialize = "debug_function")]
+    DebugFunction,
+    #[strum(serialize = "debug_line")]
+    DebugLine,
+    #[strum(serialize = "debug")]
+    Debug,
+}
+
+impl ProbeLevel {
+    /// All of the levels, from the most severe to the least
+    pub const ALL: [ProbeLevel; 15] = [
+        ProbeLevel::Emergency,
+        ProbeLevel::Alert,
+        ProbeLevel::Critical,
+        ProbeLevel::Error,
+        ProbeLevel::Warning,
+        ProbeLevel::Notice,
+        ProbeLevel::Info,
+        ProbeLevel::DebugSystem,
+        ProbeLevel::DebugProgram,
+        ProbeLevel::DebugProcess,
+        ProbeLevel::DebugModule,
+        ProbeLevel::DebugUnit,
+        ProbeLevel::DebugFunction,
+        ProbeLevel::DebugLine,
+        ProbeLevel::Debug,
+    ];
+
+    /// The name of the level as it's written in the `level` attribute, like `warning`
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+
+    /// The name of the LTTng log level this level corresponds to, like `TRACE_WARNING`.  Note that
+    /// a tracepoint without a log level is at LTTng's default level, `TRACE_DEBUG_LINE`.
+    pub fn lttng_loglevel(self) -> &'static str {
+        match self {
+            ProbeLevel::Emergency => "TRACE_EMERG",
+            ProbeLevel::Alert => "TRACE_ALERT",
+            ProbeLevel::Critical => "TRACE_CRIT",
+            ProbeLevel::Error => "TRACE_ERR",
+            ProbeLevel::Warning => "TRACE_WARNING",
+            ProbeLevel::Notice => "TRACE_NOTICE",
+            ProbeLevel::Info => "TRACE_INFO",
+            ProbeLevel::DebugSystem => "TRACE_DEBUG_SYSTEM",
+            ProbeLevel::DebugProgram => "TRACE_DEBUG_PROGRAM",
+            ProbeLevel::DebugProcess => "TRACE_DEBUG_PROCESS",
+            ProbeLevel::DebugModule => "TRACE_DEBUG_MODULE",
+            ProbeLevel::DebugUnit => "TRACE_DEBUG_UNIT",
+            ProbeLevel::DebugFunction => "TRACE_DEBUG_FUNCTION",
+            ProbeLevel::DebugLine => "TRACE_DEBUG_LINE",
+            ProbeLevel::Debug => "TRACE_DEBUG",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn names_round_trip() {
+        for level in ProbeLevel::ALL.iter() {
+            assert_eq!(Ok(*level), ProbeLevel::from_str(level.name()));
+            assert_eq!(level.name(), level.to_string());
+        }
+
+        assert!(ProbeLevel::from_str("warn").is_err());
+    }
+
+    #[test]
+    fn ordered_by_severity() {
+        let mut sorted = ProbeLevel::ALL;
+        sorted.sort();
+        assert_eq!(ProbeLevel::ALL, sorted);
+
+        assert!(ProbeLevel::Error <= ProbeLevel::Warning);
+        assert!(ProbeLevel::Info > ProbeLevel::Warning);
+        assert_eq!("TRACE_WARNING", ProbeLevel::Warning.lttng_loglevel());
+    }
+}
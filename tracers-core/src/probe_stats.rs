@@ -0,0 +1,96 @@
+//! Per-probe counters, which are only generated when `tracers` is built with the `probe-stats`
+//! feature.  They make it possible to see how often each probe is hit, and how often it was
+//! actually enabled, without attaching a tracer.
+//!
+//! The counters are updated with relaxed atomic operations, so they're cheap but they're not a
+//! consistent snapshot of the counters at a single instant.
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The counters of a single probe.  The generated code declares one of these as a static for each
+/// probe, and updates it from the `probe!` macro.
+#[derive(Debug)]
+pub struct ProbeStats {
+    hits: AtomicU64,
+    enabled_fires: AtomicU64,
+    arg_failures: AtomicU64,
+}
+
+impl ProbeStats {
+    pub const fn new() -> ProbeStats {
+        ProbeStats {
+            hits: AtomicU64::new(0),
+            enabled_fires: AtomicU64::new(0),
+            arg_failures: AtomicU64::new(0),
+        }
+    }
+
+    /// Records that a `probe!` call for this probe was reached, whether or not it was enabled
+    #[inline(always)]
+    pub fn record_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that the probe was enabled and so it was fired
+    #[inline(always)]
+    pub fn record_enabled_fire(&self) {
+        self.enabled_fires.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that some of the probe's args couldn't be passed to the probe faithfully, like a
+    /// string with an embedded NUL byte
+    pub fn record_arg_failures(&self, failures: u64) {
+        self.arg_failures.fetch_add(failures, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ProbeStatsSnapshot {
+        ProbeStatsSnapshot {
+            hits: self.hits.load(Ordering::Relaxed),
+            enabled_fires: self.enabled_fires.load(Ordering::Relaxed),
+            arg_failures: self.arg_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for ProbeStats {
+    fn default() -> Self {
+        ProbeStats::new()
+    }
+}
+
+/// The values of a probe's counters at the time `ProbeStats::snapshot` was called
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProbeStatsSnapshot {
+    /// The number of times a `probe!` call for the probe was reached
+    pub hits: u64,
+
+    /// The number of those hits for which the probe was enabled, and thus fired
+    pub enabled_fires: u64,
+
+    /// The number of probe args which couldn't be passed to the probe faithfully when it fired
+    pub arg_failures: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_are_independent() {
+        let stats = ProbeStats::new();
+        assert_eq!(ProbeStatsSnapshot::default(), stats.snapshot());
+
+        stats.record_hit();
+        stats.record_hit();
+        stats.record_enabled_fire();
+        stats.record_arg_failures(3);
+
+        assert_eq!(
+            ProbeStatsSnapshot {
+                hits: 2,
+                enabled_fires: 1,
+                arg_failures: 3
+            },
+            stats.snapshot()
+        );
+    }
+}
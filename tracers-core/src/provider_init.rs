@@ -0,0 +1,137 @@
+//! The result of initializing a provider with the `init_provider!` macro.
+//!
+//! Initialization either succeeds with a `ProviderInfo` describing the provider and the tracing
+//! implementation it was built with, or fails with a `ProviderInitError` saying why.  Each provider
+//! is only initialized once, and the result is kept for the life of the process, so the error is
+//! borrowed with a `'static` lifetime rather than owned.
+//!
+//! Before these types existed, the result was a pair of strings.  Both types implement `Display`,
+//! producing exactly those strings, so code which only logs the result works with either.
+use failure::Fail;
+use std::fmt;
+
+/// Describes a provider which was initialized successfully
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProviderInfo {
+    /// The name of the provider, as the platform's tracing tools will see it
+    pub name: &'static str,
+
+    /// The tracing implementation which the provider was built with, for example
+    /// `native/native_noop` or `dynamic/dyn_stap`, or `disabled` if tracing is disabled
+    pub implementation: &'static str,
+
+    /// The version of `tracers` which generated the provider
+    pub version: &'static str,
+
+    /// The names of the provider's probes, in the order they're declared in the provider trait
+    pub probes: &'static [&'static str],
+}
+
+/// Formats the provider info in the string form which `init_provider!` used to return:
+///
+/// ```not_rust
+/// $PROVIDER_NAME::$IMPLEMENTATION::$VERSION
+/// ```
+///
+/// for example:
+///
+/// ```not_rust
+/// my_provider::native/native_noop::0.1.0
+/// ```
+impl fmt::Display for ProviderInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}::{}::{}",
+            self.name, self.implementation, self.version
+        )
+    }
+}
+
+/// The reason a provider failed to initialize.  Only dynamic tracing implementations can fail; the
+/// underlying error from the tracing implementation is available in `error`, and can be
+/// downcast to the implementation's own error type.
+#[derive(Debug)]
+pub enum ProviderInitError {
+    /// The tracing implementation failed to create the provider
+    ProviderFailed {
+        provider: &'static str,
+        error: failure::Error,
+    },
+
+    /// The provider was created, but one of its probes couldn't be retrieved from it
+    ProbeFailed {
+        provider: &'static str,
+        probe: &'static str,
+        error: failure::Error,
+    },
+}
+
+impl ProviderInitError {
+    /// The name of the provider which failed to initialize
+    pub fn provider(&self) -> &'static str {
+        match self {
+            ProviderInitError::ProviderFailed { provider, .. }
+            | ProviderInitError::ProbeFailed { provider, .. } => provider,
+        }
+    }
+
+    /// The error reported by the tracing implementation
+    pub fn error(&self) -> &failure::Error {
+        match self {
+            ProviderInitError::ProviderFailed { error, .. }
+            | ProviderInitError::ProbeFailed { error, .. } => error,
+        }
+    }
+}
+
+impl fmt::Display for ProviderInitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProviderInitError::ProviderFailed { error, .. } => {
+                write!(f, "Provider initialization failed: {}", error)
+            }
+            ProviderInitError::ProbeFailed { probe, error, .. } => {
+                write!(f, "Error getting probe '{}': {}", probe, error)
+            }
+        }
+    }
+}
+
+impl Fail for ProviderInitError {}
+
+/// The result of a provider init is either information about the provider, or the error which
+/// prevented the provider from initializing.
+///
+/// Callers which still expect the old string form can get it with `to_string()` on either the
+/// `Ok` or the `Err` value.
+pub type ProviderInitResult = std::result::Result<ProviderInfo, &'static ProviderInitError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use failure::format_err;
+
+    #[test]
+    fn formats_as_legacy_strings() {
+        let info = ProviderInfo {
+            name: "my_provider",
+            implementation: "native/native_noop",
+            version: "0.1.0",
+            probes: &["probe0"],
+        };
+        assert_eq!("my_provider::native/native_noop::0.1.0", info.to_string());
+
+        let error = ProviderInitError::ProbeFailed {
+            provider: "my_provider",
+            probe: "probe0",
+            error: format_err!("no such probe"),
+        };
+        assert_eq!(
+            "Error getting probe 'probe0': no such probe",
+            error.to_string()
+        );
+        assert_eq!("my_provider", error.provider());
+        assert_eq!("no such probe", error.error().to_string());
+    }
+}
@@ -0,0 +1,385 @@
+//! A process-wide registry of every provider generated by the `tracer` attribute macro.
+//!
+//! Each provider trait expands into a static `RegisteredProvider` describing the provider and its
+//! probes, plus a small constructor function placed in the platform's initializer section
+//! (`.init_array` on ELF platforms, `__mod_init_func` on macOS, `.CRT$XCU` on Windows).  The
+//! loader runs those constructors before `main`, and each one pushes its provider onto a
+//! lock-free intrusive list.  Thus the registry costs nothing when probes fire, and there is no
+//! need to name each provider trait to find it at runtime.
+//!
+//! There are two caveats:
+//!
+//! * On platforms without an initializer section, nothing is registered and the registry is
+//!   always empty.
+//! * Like any static constructor, a provider is only registered if the linker includes the
+//!   object file which contains it.  A provider declared in a library crate but never used by the
+//!   final binary may be dropped by the linker, in which case it won't be in the registry.
+use crate::probe_level::ProbeLevel;
+use crate::probe_stats::{ProbeStats, ProbeStatsSnapshot};
+use crate::ProviderInitResult;
+use std::fmt;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+/// The head of the list of registered providers, most recently registered first
+static REGISTERED_PROVIDERS: AtomicPtr<RegisteredProvider> = AtomicPtr::new(ptr::null_mut());
+
+/// Describes an argument of a probe, as it was declared in the provider trait
+#[derive(Debug)]
+pub struct RegisteredProbeArg {
+    name: &'static str,
+    rust_type: &'static str,
+}
+
+impl RegisteredProbeArg {
+    pub const fn new(name: &'static str, rust_type: &'static str) -> RegisteredProbeArg {
+        RegisteredProbeArg { name, rust_type }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The Rust type of the argument, as it was written in the provider trait
+    pub fn rust_type(&self) -> &'static str {
+        self.rust_type
+    }
+}
+
+/// Describes one of the probes of a registered provider
+pub struct RegisteredProbe {
+    name: &'static str,
+    args: &'static [RegisteredProbeArg],
+    is_enabled: fn() -> bool,
+    level: Option<ProbeLevel>,
+    stats: Option<&'static ProbeStats>,
+}
+
+impl RegisteredProbe {
+    pub const fn new(
+        name: &'static str,
+        args: &'static [RegisteredProbeArg],
+        is_enabled: fn() -> bool,
+    ) -> RegisteredProbe {
+        RegisteredProbe {
+            name,
+            args,
+            is_enabled,
+            level: None,
+            stats: None,
+        }
+    }
+
+    /// Sets the level of the probe, from its `#[probe(level = "...")]` attribute or the default
+    /// level of its provider
+    pub const fn with_level(mut self, level: ProbeLevel) -> RegisteredProbe {
+        self.level = Some(level);
+        self
+    }
+
+    /// Attaches the probe's counters, which only exist when `tracers` is built with the
+    /// `probe-stats` feature
+    pub const fn with_stats(mut self, stats: &'static ProbeStats) -> RegisteredProbe {
+        self.stats = Some(stats);
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn args(&self) -> &'static [RegisteredProbeArg] {
+        self.args
+    }
+
+    /// Tests if the probe is enabled right now.
+    ///
+    /// This never initializes the provider; a dynamic provider which hasn't been initialized yet
+    /// can't have any enabled probes.
+    pub fn is_enabled(&self) -> bool {
+        (self.is_enabled)()
+    }
+
+    /// The level of the probe, or `None` if neither the probe nor its provider has a level
+    pub fn level(&self) -> Option<ProbeLevel> {
+        self.level
+    }
+
+    /// The current values of the probe's counters, or `None` if `tracers` wasn't built with the
+    /// `probe-stats` feature
+    pub fn stats(&self) -> Option<ProbeStatsSnapshot> {
+        self.stats.map(ProbeStats::snapshot)
+    }
+}
+
+impl fmt::Debug for RegisteredProbe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RegisteredProbe")
+            .field("name", &self.name)
+            .field("args", &self.args)
+            .field("is_enabled", &self.is_enabled())
+            .field("level", &self.level)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// Describes a provider generated by the `tracer` attribute macro
+pub struct RegisteredProvider {
+    name: &'static str,
+    probes: &'static [RegisteredProbe],
+    init: fn() -> ProviderInitResult,
+    registered: AtomicBool,
+    next: AtomicPtr<RegisteredProvider>,
+}
+
+impl RegisteredProvider {
+    pub const fn new(
+        name: &'static str,
+        probes: &'static [RegisteredProbe],
+        init: fn() -> ProviderInitResult,
+    ) -> RegisteredProvider {
+        RegisteredProvider {
+            name,
+            probes,
+            init,
+            registered: AtomicBool::new(false),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// The name of the provider, as the platform's tracing tools will see it
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn probes(&self) -> &'static [RegisteredProbe] {
+        self.probes
+    }
+
+    pub fn find_probe(&self, name: &str) -> Option<&'static RegisteredProbe> {
+        self.probes.iter().find(|probe| probe.name == name)
+    }
+
+    /// Initializes the provider, exactly as `init_provider!` would
+    pub fn init(&self) -> ProviderInitResult {
+        (self.init)()
+    }
+}
+
+impl fmt::Debug for RegisteredProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RegisteredProvider")
+            .field("name", &self.name)
+            .field("probes", &self.probes)
+            .finish()
+    }
+}
+
+/// Adds a provider to the registry.  Registering the same provider more than once has no effect.
+///
+/// The code generated by the `tracer` macro calls this from a static constructor; there should be
+/// no reason to call it directly.
+#[doc(hidden)]
+pub fn register_provider(provider: &'static RegisteredProvider) {
+    if provider.registered.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    let provider_ptr = provider as *const RegisteredProvider as *mut RegisteredProvider;
+    let mut head = REGISTERED_PROVIDERS.load(Ordering::Acquire);
+    loop {
+        provider.next.store(head, Ordering::Relaxed);
+        match REGISTERED_PROVIDERS.compare_exchange_weak(
+            head,
+            provider_ptr,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => break,
+            Err(current) => head = current,
+        }
+    }
+}
+
+/// Gets the registry of all of the providers in the process
+pub fn registry() -> Registry {
+    Registry { _private: () }
+}
+
+/// Handle to the process-wide registry of providers.  Obtained with `registry()`.
+#[derive(Debug, Clone, Copy)]
+pub struct Registry {
+    _private: (),
+}
+
+impl Registry {
+    /// Iterates over all of the registered providers, in no particular order
+    pub fn providers(&self) -> Providers {
+        Providers {
+            next: REGISTERED_PROVIDERS.load(Ordering::Acquire),
+        }
+    }
+
+    pub fn find_provider(&self, name: &str) -> Option<&'static RegisteredProvider> {
+        self.providers().find(|provider| provider.name == name)
+    }
+
+    /// Initializes every registered provider, returning the provider and the result of its
+    /// initialization.  As with `init_provider!`, initializing a provider more than once is
+    /// harmless, and a provider which failed to initialize won't fire any probes.
+    pub fn init_all(&self) -> Vec<(&'static RegisteredProvider, ProviderInitResult)> {
+        self.providers()
+            .map(|provider| (provider, provider.init()))
+            .collect()
+    }
+
+    /// Takes a snapshot of the counters of every probe of every registered provider.  Probes are
+    /// only counted when `tracers` is built with the `probe-stats` feature, so without it this is
+    /// always empty.
+    pub fn probe_stats(
+        &self,
+    ) -> Vec<(
+        &'static RegisteredProvider,
+        &'static RegisteredProbe,
+        ProbeStatsSnapshot,
+    )> {
+        self.providers()
+            .flat_map(|provider| {
+                provider
+                    .probes()
+                    .iter()
+                    .filter_map(move |probe| probe.stats().map(|stats| (provider, probe, stats)))
+            })
+            .collect()
+    }
+}
+
+/// Iterator over the registered providers.  Obtained with `Registry::providers`.
+pub struct Providers {
+    next: *const RegisteredProvider,
+}
+
+impl Iterator for Providers {
+    type Item = &'static RegisteredProvider;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        //The list only ever contains `&'static RegisteredProvider`s, and it's only ever added to
+        //at the head, so every pointer in it is valid forever
+        let provider = unsafe { self.next.as_ref() }?;
+        self.next = provider.next.load(Ordering::Acquire);
+
+        Some(provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ProviderInfo, ProviderInitError};
+
+    fn never_enabled() -> bool {
+        false
+    }
+
+    fn always_enabled() -> bool {
+        true
+    }
+
+    fn init_ok() -> ProviderInitResult {
+        Ok(ProviderInfo {
+            name: "registry_test_provider",
+            implementation: "native/native_noop",
+            version: "0.0.0",
+            probes: &["probe0", "probe1"],
+        })
+    }
+
+    fn init_err() -> ProviderInitResult {
+        //A real provider keeps its init error in a static; leaking one is the closest equivalent
+        Err(Box::leak(Box::new(ProviderInitError::ProviderFailed {
+            provider: "registry_failing_provider",
+            error: failure::format_err!("this provider never initializes"),
+        })))
+    }
+
+    static PROBE1_STATS: ProbeStats = ProbeStats::new();
+
+    static TEST_PROBES: [RegisteredProbe; 2] = [
+        RegisteredProbe::new(
+            "probe0",
+            &[RegisteredProbeArg::new("arg0", "&str")],
+            never_enabled,
+        )
+        .with_level(ProbeLevel::Warning),
+        RegisteredProbe::new("probe1", &[], always_enabled).with_stats(&PROBE1_STATS),
+    ];
+
+    static TEST_PROVIDER: RegisteredProvider =
+        RegisteredProvider::new("registry_test_provider", &TEST_PROBES, init_ok);
+
+    static FAILING_PROVIDER: RegisteredProvider =
+        RegisteredProvider::new("registry_failing_provider", &[], init_err);
+
+    #[test]
+    fn registered_providers_are_listed() {
+        register_provider(&TEST_PROVIDER);
+        register_provider(&FAILING_PROVIDER);
+
+        //Registering again must not create a cycle or a duplicate
+        register_provider(&TEST_PROVIDER);
+
+        let names: Vec<_> = registry().providers().map(|p| p.name()).collect();
+        assert_eq!(
+            1,
+            names
+                .iter()
+                .filter(|n| **n == "registry_test_provider")
+                .count()
+        );
+        assert!(names.contains(&"registry_failing_provider"));
+
+        let provider = registry()
+            .find_provider("registry_test_provider")
+            .expect("provider wasn't registered");
+        let probe = provider.find_probe("probe0").unwrap();
+        assert_eq!("&str", probe.args()[0].rust_type());
+        assert!(!probe.is_enabled());
+        assert_eq!(Some(ProbeLevel::Warning), probe.level());
+        assert!(provider.find_probe("probe1").unwrap().is_enabled());
+        assert_eq!(None, provider.find_probe("probe1").unwrap().level());
+        assert!(provider.find_probe("probe2").is_none());
+    }
+
+    #[test]
+    fn init_all_reports_each_result() {
+        register_provider(&TEST_PROVIDER);
+        register_provider(&FAILING_PROVIDER);
+
+        let results = registry().init_all();
+        for (provider, result) in results.into_iter() {
+            match provider.name() {
+                "registry_test_provider" => assert!(result.is_ok()),
+                "registry_failing_provider" => assert!(result.is_err()),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn probe_stats_only_include_counted_probes() {
+        register_provider(&TEST_PROVIDER);
+        PROBE1_STATS.record_hit();
+
+        let stats = registry().probe_stats();
+        let test_stats: Vec<_> = stats
+            .iter()
+            .filter(|(provider, _, _)| provider.name() == "registry_test_provider")
+            .collect();
+
+        assert_eq!(1, test_stats.len());
+        let (_, probe, snapshot) = test_stats[0];
+        assert_eq!("probe1", probe.name());
+        assert!(snapshot.hits >= 1);
+    }
+}
@@ -0,0 +1,110 @@
+//! Support for span probes, declared with `#[probe(span)]` and fired with `probe_span!`.
+//!
+//! A span probe is a pair of probes, one fired when the span is entered and one when it's exited.
+//! `probe_span!` fires the enter probe and returns a `SpanGuard`, which fires the exit probe with
+//! the elapsed time when it's dropped.  Because that happens in `Drop`, the exit probe fires on
+//! every path out of the scope which holds the guard, including early returns and unwinding.
+//!
+//! The guard holds on to the args until the exit probe fires, so they can't borrow a temporary,
+//! like `&format!(...)`.  `probe_span!` rejects that with a compile error; assign the value to a
+//! local variable first.
+use std::cmp;
+use std::fmt;
+use std::time::Instant;
+
+/// Fires the exit probe of a span probe when dropped.  Obtained from the `probe_span!` macro.
+///
+/// The guard must be bound to a variable which lives as long as the span, like `let _span =
+/// probe_span!(...)`.  Note that `let _ = probe_span!(...)` drops the guard immediately, so the
+/// span ends as soon as it starts.
+#[must_use = "the span ends when the guard is dropped"]
+pub struct SpanGuard<F: FnOnce(u64)> {
+    span: Option<(Instant, F)>,
+}
+
+impl<F: FnOnce(u64)> SpanGuard<F> {
+    /// Starts a span.  When the guard is dropped, `exit` is called with the number of nanoseconds
+    /// since the span started.
+    #[inline(always)]
+    pub fn new(exit: F) -> SpanGuard<F> {
+        SpanGuard {
+            span: Some((Instant::now(), exit)),
+        }
+    }
+
+    /// A guard for a span probe which wasn't enabled when the span started, which does nothing
+    /// when dropped
+    #[inline(always)]
+    pub fn disabled() -> SpanGuard<F> {
+        SpanGuard { span: None }
+    }
+
+    /// Tests if the span probe was enabled when the span started, in which case the exit probe
+    /// may fire when the guard is dropped
+    pub fn is_active(&self) -> bool {
+        self.span.is_some()
+    }
+}
+
+impl<F: FnOnce(u64)> Drop for SpanGuard<F> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        if let Some((start, exit)) = self.span.take() {
+            let elapsed_ns = cmp::min(start.elapsed().as_nanos(), u128::from(u64::MAX)) as u64;
+            exit(elapsed_ns);
+        }
+    }
+}
+
+impl<F: FnOnce(u64)> fmt::Debug for SpanGuard<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SpanGuard")
+            .field("start", &self.span.as_ref().map(|(start, _)| start))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::panic;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn exit_fires_on_drop() {
+        let elapsed = Cell::new(None);
+
+        {
+            let guard = SpanGuard::new(|elapsed_ns| elapsed.set(Some(elapsed_ns)));
+            assert!(guard.is_active());
+            thread::sleep(Duration::from_millis(1));
+            assert_eq!(None, elapsed.get());
+        }
+
+        assert!(elapsed.get().unwrap() >= 1_000_000);
+    }
+
+    #[test]
+    fn exit_fires_during_unwinding() {
+        let fired = Cell::new(false);
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _span = SpanGuard::new(|_| fired.set(true));
+            panic!("the span ends here");
+        }));
+
+        assert!(result.is_err());
+        assert!(fired.get());
+    }
+
+    #[test]
+    fn disabled_guard_does_nothing() {
+        let guard = SpanGuard::disabled();
+        assert!(!guard.is_active());
+
+        //The type of the exit closure still has to be inferred from somewhere
+        let _: &SpanGuard<fn(u64)> = &guard;
+    }
+}
@@ -0,0 +1,193 @@
+//! Throttles which limit how often a probe fires, for probes declared with
+//! `#[probe(sample = N)]` or `#[probe(rate_limit = "N/s")]`.
+//!
+//! The generated code declares a thread-local throttle for each throttled probe, and consults it
+//! in the `probe!` macro once it has determined the probe is enabled.  Because the throttles are
+//! per-thread they need neither atomics nor locks; the flip side is that a rate limit applies to
+//! each thread separately, not to the process as a whole.
+//!
+//! Every time a throttled probe does fire, it's passed the number of fires which the throttle
+//! suppressed on the current thread since the previous one, so tracing tools can scale their
+//! counts accordingly.
+use std::cell::Cell;
+use std::cmp;
+use std::time::Instant;
+
+/// Counts the fires suppressed by a throttle since the last fire it allowed
+#[derive(Debug)]
+struct SuppressedFires {
+    pending: Cell<u64>,
+    reported: Cell<u64>,
+}
+
+impl SuppressedFires {
+    const fn new() -> SuppressedFires {
+        SuppressedFires {
+            pending: Cell::new(0),
+            reported: Cell::new(0),
+        }
+    }
+
+    #[inline(always)]
+    fn allow(&self) -> bool {
+        self.reported.set(self.pending.replace(0));
+        true
+    }
+
+    #[inline(always)]
+    fn suppress(&self) -> bool {
+        self.pending.set(self.pending.get().saturating_add(1));
+        false
+    }
+}
+
+/// Fires a probe once in every `every` attempts, starting with the first
+#[derive(Debug)]
+pub struct SampleThrottle {
+    every: u64,
+    countdown: Cell<u64>,
+    suppressed: SuppressedFires,
+}
+
+impl SampleThrottle {
+    pub const fn new(every: u64) -> SampleThrottle {
+        SampleThrottle {
+            every,
+            countdown: Cell::new(0),
+            suppressed: SuppressedFires::new(),
+        }
+    }
+
+    /// Tests if the probe should fire this time
+    #[inline(always)]
+    pub fn try_fire(&self) -> bool {
+        match self.countdown.get() {
+            0 => {
+                self.countdown.set(self.every.saturating_sub(1));
+                self.suppressed.allow()
+            }
+            countdown => {
+                self.countdown.set(countdown - 1);
+                self.suppressed.suppress()
+            }
+        }
+    }
+
+    /// The number of fires suppressed between the most recent fire `try_fire` allowed and the one
+    /// before it
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.reported.get()
+    }
+}
+
+/// Fires a probe at most `count` times per `period_nanos` nanoseconds, using a token bucket.  The
+/// bucket starts out full, so a burst of up to `count` fires is allowed after a quiet period.
+#[derive(Debug)]
+pub struct RateLimitThrottle {
+    count: u64,
+    period_nanos: u64,
+
+    /// The bucket's level, in units of a nanosecond's worth of one token per period.  A fire costs
+    /// `period_nanos` credits, and every nanosecond adds `count` credits, up to `count *
+    /// period_nanos`.  Keeping the level in these units avoids any rounding.
+    credits: Cell<u128>,
+    last_refill: Cell<Option<Instant>>,
+    suppressed: SuppressedFires,
+}
+
+impl RateLimitThrottle {
+    pub const fn new(count: u64, period_nanos: u64) -> RateLimitThrottle {
+        RateLimitThrottle {
+            count,
+            period_nanos,
+            credits: Cell::new(0),
+            last_refill: Cell::new(None),
+            suppressed: SuppressedFires::new(),
+        }
+    }
+
+    /// Tests if the probe should fire this time
+    #[inline(always)]
+    pub fn try_fire(&self) -> bool {
+        self.try_fire_at(Instant::now())
+    }
+
+    /// The number of fires suppressed between the most recent fire `try_fire` allowed and the one
+    /// before it
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.reported.get()
+    }
+
+    fn try_fire_at(&self, now: Instant) -> bool {
+        let capacity = u128::from(self.count) * u128::from(self.period_nanos);
+        let credits = match self.last_refill.get() {
+            None => capacity,
+            Some(last_refill) => {
+                let elapsed = now.saturating_duration_since(last_refill).as_nanos();
+                cmp::min(
+                    capacity,
+                    self.credits
+                        .get()
+                        .saturating_add(elapsed.saturating_mul(u128::from(self.count))),
+                )
+            }
+        };
+        self.last_refill.set(Some(now));
+
+        let cost = u128::from(self.period_nanos);
+        if credits >= cost {
+            self.credits.set(credits - cost);
+            self.suppressed.allow()
+        } else {
+            self.credits.set(credits);
+            self.suppressed.suppress()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn sample_fires_one_in_n() {
+        let throttle = SampleThrottle::new(3);
+        let fires: Vec<_> = (0..7).map(|_| throttle.try_fire()).collect();
+
+        assert_eq!(vec![true, false, false, true, false, false, true], fires);
+        assert_eq!(2, throttle.suppressed());
+    }
+
+    #[test]
+    fn sample_of_one_never_suppresses() {
+        let throttle = SampleThrottle::new(1);
+
+        assert!((0..10).all(|_| throttle.try_fire()));
+        assert_eq!(0, throttle.suppressed());
+    }
+
+    #[test]
+    fn rate_limit_allows_a_burst_then_refills() {
+        //2 fires every 10ms
+        let throttle = RateLimitThrottle::new(2, 10_000_000);
+        let start = Instant::now();
+
+        assert!(throttle.try_fire_at(start));
+        assert!(throttle.try_fire_at(start));
+        assert!(!throttle.try_fire_at(start));
+        assert!(!throttle.try_fire_at(start + Duration::from_millis(1)));
+
+        //After half the period, one token has been refilled
+        assert!(throttle.try_fire_at(start + Duration::from_millis(5)));
+        assert_eq!(2, throttle.suppressed());
+        assert!(!throttle.try_fire_at(start + Duration::from_millis(5)));
+
+        //However long it's quiet, the bucket never holds more than `count` tokens
+        let later = start + Duration::from_secs(10);
+        assert!(throttle.try_fire_at(later));
+        assert_eq!(1, throttle.suppressed());
+        assert!(throttle.try_fire_at(later));
+        assert!(!throttle.try_fire_at(later));
+    }
+}
@@ -0,0 +1,100 @@
+//! The state shared by the tracing implementations which write their own traces, `chrome_trace`
+//! and `ctf`.  Each writes its trace to a path taken from an environment variable when the first
+//! probe is tested, and if it's not set, every probe is disabled.  Failures are reported on stderr
+//! rather than failing the process, since tracing is never essential.
+use std::env;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+
+/// A trace of type `T`, which is created the first time a probe is tested, and abandoned the first
+/// time writing to it fails
+pub(crate) struct TraceFile<T> {
+    /// The name of the trace format, for error messages
+    format: &'static str,
+
+    /// The environment variable which holds the path to write the trace to
+    env_var: &'static str,
+
+    init: Once,
+    enabled: AtomicBool,
+    trace: Mutex<Option<T>>,
+}
+
+impl<T> TraceFile<T> {
+    pub(crate) const fn new(format: &'static str, env_var: &'static str) -> TraceFile<T> {
+        TraceFile {
+            format,
+            env_var,
+            init: Once::new(),
+            enabled: AtomicBool::new(false),
+            trace: Mutex::new(None),
+        }
+    }
+
+    /// Tests if the trace is being written, in which case every probe is enabled.  The first call
+    /// creates the trace with `create`, if the environment variable is set.
+    #[inline]
+    pub(crate) fn is_enabled(&self, create: impl FnOnce(&Path) -> io::Result<T>) -> bool {
+        self.init.call_once(|| {
+            if let Some(path) = env::var_os(self.env_var) {
+                let path = Path::new(&path);
+                match create(path) {
+                    Ok(trace) => {
+                        *self.trace.lock().unwrap() = Some(trace);
+                        self.enabled.store(true, Ordering::Release);
+                    }
+                    Err(e) => eprintln!(
+                        "tracers: unable to write the {} trace to {}: {}",
+                        self.format,
+                        path.display(),
+                        e
+                    ),
+                }
+            }
+        });
+
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Calls `f` with the trace, holding its lock.  Fails if the trace has been abandoned.
+    pub(crate) fn with_trace<R>(&self, f: impl FnOnce(&mut T) -> io::Result<R>) -> io::Result<R> {
+        let mut trace = self.trace.lock().unwrap_or_else(|e| e.into_inner());
+        let trace = trace
+            .as_mut()
+            .ok_or_else(|| io::Error::other("the trace was abandoned"))?;
+
+        f(trace)
+    }
+
+    /// Calls `write` to write to the trace, if it's being written.  If that fails the trace is
+    /// abandoned, and all probes are disabled from then on.
+    pub(crate) fn write(&self, write: impl FnOnce() -> io::Result<()>) {
+        if !self.enabled.load(Ordering::Acquire) {
+            return;
+        }
+
+        if let Err(e) = write() {
+            if self.enabled.swap(false, Ordering::AcqRel) {
+                eprintln!(
+                    "tracers: error writing the {} trace, which is abandoned: {}",
+                    self.format, e
+                );
+                *self.trace.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            }
+        }
+    }
+}
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Rust has no portable numeric thread ID, so each thread which fires a probe is numbered in the
+/// order it first does so, starting from 1
+pub(crate) fn thread_id() -> u64 {
+    THREAD_ID.with(|id| *id)
+}
@@ -0,0 +1,68 @@
+//! The values of probe args as they're written to a trace file by the tracing implementations which
+//! write their own traces, `chrome_trace` and `ctf`.
+use std::os::raw::c_void;
+
+/// The value of one of a probe's args in a trace event.  These are created from the native values
+/// of probe args, after they've been wrapped in their `ProbeArgWrapper`s.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgValue {
+    Int(i64),
+    UInt(u64),
+
+    /// An integer which is only meaningful as a bit pattern, so it's displayed in hex
+    Hex(u64),
+
+    /// A pointer to a NUL-terminated string, or null
+    Str(*const u8),
+
+    /// A pointer to a string which isn't NUL-terminated, and its length in bytes
+    Bytes(*const u8, usize),
+
+    /// Any other pointer, written as its address
+    Pointer(usize),
+}
+
+impl ArgValue {
+    /// A string passed as a pointer and a length, like one with the `ptr_len` encoding
+    pub fn bytes(ptr: *const c_void, len: usize) -> ArgValue {
+        ArgValue::Bytes(ptr as *const u8, len)
+    }
+
+    /// An integer which is best displayed in hex, like one half of a `u128`
+    pub fn hex(value: u64) -> ArgValue {
+        ArgValue::Hex(value)
+    }
+}
+
+macro_rules! impl_from_integer {
+    ($variant:ident, $as_type:ty, $($rust_type:ty),+) => {
+        $(
+            impl From<$rust_type> for ArgValue {
+                fn from(value: $rust_type) -> ArgValue {
+                    ArgValue::$variant(value as $as_type)
+                }
+            }
+        )+
+    };
+}
+
+impl_from_integer!(Int, i64, i8, i16, i32, i64, isize);
+impl_from_integer!(UInt, u64, u8, u16, u32, u64, usize);
+
+impl From<*const i8> for ArgValue {
+    fn from(value: *const i8) -> ArgValue {
+        ArgValue::Str(value as *const u8)
+    }
+}
+
+impl From<*const u8> for ArgValue {
+    fn from(value: *const u8) -> ArgValue {
+        ArgValue::Str(value)
+    }
+}
+
+impl From<*const c_void> for ArgValue {
+    fn from(value: *const c_void) -> ArgValue {
+        ArgValue::Pointer(value as usize)
+    }
+}
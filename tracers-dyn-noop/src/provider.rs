@@ -1,5 +1,6 @@
 //! Implements the `ProviderBuilder` and `Provider` traits for SystemTap
 use failure::Fallible;
+use tracers_core::argtypes::CType;
 use tracers_core::dynamic::{ProbeArgs, ProbeDefinition, Provider, ProviderBuilder};
 
 use super::{NoOpProbe, NoOpTracer};
@@ -17,6 +18,13 @@ impl ProviderBuilder<NoOpTracer> for NoOpProviderBuilder {
         Ok(())
     }
 
+    fn add_probe_dyn(&mut self, name: &'static str, arg_types: &[CType]) -> Fallible<()> {
+        //Even though the probe will never fire, an invalid definition should fail the same way it
+        //would with a real tracer
+        ProbeDefinition::with_arg_types(name, arg_types)?;
+        Ok(())
+    }
+
     fn build(self, _name: &str) -> Fallible<NoOpProvider> {
         Ok(NoOpProvider {})
     }
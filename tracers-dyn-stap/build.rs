@@ -7,7 +7,7 @@ use std::io::Write;
 use std::path::Path;
 
 const MAX_ARITY: usize = 12; //AFAIK Rust itself only allows tuples up to this arity
-const STAP_MAX_ARITY: usize = 6; //any more than this number of probe arguments are allowed but are ignored
+const STAP_MAX_ARITY: usize = 6; //any more than this number of probe arguments are spilled into a struct
 
 fn is_enabled() -> bool {
     env::var("CARGO_FEATURE_ENABLED").is_ok() || is_required()
@@ -57,6 +57,7 @@ fn try_build() -> Fallible<()> {
     let dest_path = Path::new(&out_dir).join("probe_unsafe_impl.rs");
     let mut f = File::create(&dest_path)?;
 
+    f.write_all(generate_spilled_args_structs().as_bytes())?;
     f.write_all(generate_stap_native_impl().as_bytes())?;
 
     Ok(())
@@ -82,12 +83,45 @@ fn xform_types_i<F: FnMut(usize, &String) -> String>(
         .collect::<Vec<String>>()
 }
 
+/// The number of args passed directly to a probe whose args are spilled.  The last native arg is
+/// a pointer to the struct which holds the rest.
+fn direct_arg_count() -> usize {
+    STAP_MAX_ARITY - 1
+}
+
+/// Generates a `#[repr(C)]` struct for every possible number of spilled args, which has the same
+/// layout as the C struct described by `spill::spilled_args_struct`
+fn generate_spilled_args_structs() -> String {
+    let mut decl = String::new();
+
+    for arity in (STAP_MAX_ARITY + 1)..=MAX_ARITY {
+        let type_params = get_type_param_names(arity - direct_arg_count());
+
+        decl += &format!(
+            r##"
+            /// The args of a probe with {arity} args which don't fit in SystemTap's {max} args
+            #[repr(C)]
+            #[allow(dead_code)] //the fields are only ever read by the tracer, through a pointer
+            struct SpilledArgs{count}<{type_list}>({type_list});
+            "##,
+            arity = arity,
+            max = STAP_MAX_ARITY,
+            count = type_params.len(),
+            type_list = type_params.join(","),
+        );
+    }
+
+    decl
+}
+
 fn generate_stap_native_impl() -> String {
     let mut decl= r#"
         /// Implementation of `UnsafeProviderProbeNativeImpl` for SystemTap.
         ///
         /// NB: While the `tracers` API supports probes with from 0 to 12 arguments, the libstapsdt library (or maybe SystemTap itself)
-        /// support up to 6.  This implementation must provide all arities from 0 to 12, but only the first 6 parameters are used.
+        /// support up to 6.  This implementation must provide all arities from 0 to 12, so probes with more than 6 args are
+        /// passed the first 5 directly and the rest in a struct, by pointer.  `StapProviderBuilder` only allows such probes
+        /// to be added with `ArgOverflow::Spill`.
         impl UnsafeProviderProbeNativeImpl for StapProbe
         {
             fn is_enabled(&self) -> bool { StapProbe::is_enabled(self) }
@@ -100,41 +134,49 @@ fn generate_stap_native_impl() -> String {
 
     for arity in 1..=MAX_ARITY {
         //For every possible arity level `N`, declare the probe method `c_fireN` which takes C native argument types
-        //because SystemTap can only accept up to STAP_MAX_ARITY arguments, any ones after that need to have a leading
-        //underscore to mark them as unused
-        let stap_arg_count = if arity > STAP_MAX_ARITY {
-            STAP_MAX_ARITY
+        //because SystemTap can only accept up to STAP_MAX_ARITY arguments, any ones after the first
+        //`direct_arg_count()` are spilled into a struct, and a pointer to that struct is passed as the last arg
+        let type_params = get_type_param_names(arity);
+        let arg_names = xform_types_i(&type_params, |i, _| format!("arg{}", i));
+
+        let (spill_decl, stap_arg_names) = if arity > STAP_MAX_ARITY {
+            let (direct_arg_names, spilled_arg_names) = arg_names.split_at(direct_arg_count());
+            let spilled_type_params = &type_params[direct_arg_count()..];
+
+            let spill_decl = format!(
+                "let spilled = SpilledArgs{count}({spilled_args});",
+                count = spilled_arg_names.len(),
+                spilled_args = spilled_arg_names.join(",")
+            );
+            let mut stap_arg_names = direct_arg_names.to_vec();
+            stap_arg_names.push(format!(
+                "&spilled as *const SpilledArgs{count}<{type_list}>",
+                count = spilled_type_params.len(),
+                type_list = spilled_type_params.join(",")
+            ));
+
+            (spill_decl, stap_arg_names)
         } else {
-            arity
+            (String::new(), arg_names)
         };
 
-        let type_params = get_type_param_names(arity);
-        let mut stap_type_params = type_params.clone();
-        let ignored_type_params = stap_type_params.split_off(stap_arg_count);
-
-        let stap_args = xform_types_i(&stap_type_params, |i, x| format!("arg{}: {}", i, x));
-        let ignored_type_args =
-            xform_types_i(&ignored_type_params, |i, x| format!("_arg{}: {}", i, x));
-        let mut all_args = stap_args.clone();
-        all_args.extend(ignored_type_args.clone());
-        let stap_arg_names = xform_types_i(&stap_type_params, |i, _| format!("arg{}", i));
-
         decl += &format!(
             r##"
-            #[allow(clippy::duplicate_underscore_argument)]
             unsafe fn c_fire{arg_count}<{type_list}>(&self, {args})
                 where {where_clause} {{
+                  {spill_decl}
                   probeFire(self.probe, {stap_arg_names});
                 }}
             "##,
             arg_count = type_params.len(),
             type_list = type_params.join(","),
-            args = all_args.join(","),
+            args = xform_types_i(&type_params, |i, x| format!("arg{}: {}", i, x)).join(","),
             where_clause = xform_types(&type_params, |x| format!(
                 "{t}: ProbeArgNativeType<{t}>",
                 t = x
             ))
             .join(","),
+            spill_decl = spill_decl,
             stap_arg_names = stap_arg_names.join(",")
         );
     }
@@ -0,0 +1,101 @@
+//! Support for reloading stap providers in the child process after a `fork()`.
+//!
+//! A child process inherits the libraries which its parent's providers loaded, so without help its
+//! probes can't be told apart from its parent's, and if the parent's providers are unloaded or
+//! dropped the child's are in an undefined state.  Nothing is reloaded automatically; pre-forking
+//! servers should call `reload_providers_if_forked` before they fork, so that forks are noted, and
+//! then again in each child before it fires any probes, so that the child's providers are
+//! reloaded, and thus registered with SystemTap as the child's own.
+use failure::Fallible;
+use once_cell::sync::OnceCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use tracers_core::libc;
+
+use super::provider::NativeProvider;
+use super::StapError;
+
+/// All of the providers which currently exist in this process
+static PROVIDERS: Mutex<Vec<Arc<NativeProvider>>> = Mutex::new(Vec::new());
+
+/// Set in the child after a fork, until the providers are reloaded
+static FORKED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn register_provider(provider: &Arc<NativeProvider>) {
+    lock_providers().push(provider.clone());
+}
+
+pub(crate) fn unregister_provider(provider: &Arc<NativeProvider>) {
+    lock_providers().retain(|p| !Arc::ptr_eq(p, provider));
+}
+
+fn lock_providers() -> MutexGuard<'static, Vec<Arc<NativeProvider>>> {
+    //The list is never left half-changed, so it's still usable if a thread panicked holding it
+    PROVIDERS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+extern "C" fn after_fork_in_child() {
+    //Only async-signal-safe calls are allowed between a fork and an `exec` in a multithreaded
+    //process, which rules out reloading the providers here.  That's left to the child's own call
+    //to `reload_providers_if_forked`.
+    FORKED.store(true, Ordering::Relaxed);
+}
+
+/// Reloads every loaded stap provider, including providers built after the first call, if this
+/// process is a child which was forked since the last call.  Returns the number of providers which
+/// were reloaded.
+///
+/// The first call installs a `pthread_atfork` handler, which only notes that the process forked;
+/// the providers aren't reloaded until the child calls this again, which it must do before firing
+/// any probes.  Reloading allocates memory and opens files, so a child which only calls
+/// async-signal-safe functions before an `exec` shouldn't call this.  Calls in a process which
+/// hasn't forked since the last call do nothing.
+///
+/// If another thread of the parent was building or dropping a stap provider at the moment of the
+/// fork, the providers can't be reloaded, and this fails with `StapError::ForkedWhileBusy`.
+///
+/// # Safety
+///
+/// In a child process which has forked, no other thread may be firing any of the providers' probes
+/// while they're reloaded.
+pub unsafe fn reload_providers_if_forked() -> Fallible<usize> {
+    static ATFORK_RESULT: OnceCell<libc::c_int> = OnceCell::new();
+
+    let result =
+        *ATFORK_RESULT.get_or_init(|| libc::pthread_atfork(None, None, Some(after_fork_in_child)));
+
+    if result != 0 {
+        return Err(StapError::NativeCallFailed {
+            func: "pthread_atfork",
+        }
+        .into());
+    }
+
+    if FORKED.swap(false, Ordering::Relaxed) {
+        reload_providers()
+    } else {
+        Ok(0)
+    }
+}
+
+unsafe fn reload_providers() -> Fallible<usize> {
+    //A lock held by some other thread of the parent when it forked is never released in the
+    //child, since that thread doesn't exist here
+    let providers = match PROVIDERS.try_lock() {
+        Ok(providers) => providers,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(TryLockError::WouldBlock) => return Err(StapError::ForkedWhileBusy.into()),
+    };
+
+    //Providers which were unloaded in the parent stay unloaded.  A provider which fails to load
+    //has its probes disabled, but the others are still reloaded.
+    let mut result = Ok(0);
+    for provider in providers.iter().filter(|provider| provider.is_loaded()) {
+        result = match provider.reload() {
+            Ok(()) => result.map(|count| count + 1),
+            Err(e) => result.and(Err(e)),
+        };
+    }
+
+    result
+}
@@ -1,12 +1,15 @@
 //! Implements the `ProviderBuilder` and `Provider` traits for SystemTap
 use failure::{Fail, Fallible};
-use tracers_libstapsdt_sys::*;
 use std::collections::HashMap;
 use std::ffi::CString;
-use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tracers_core::argtypes::CType;
 use tracers_core::dynamic::{ProbeArgs, ProbeDefinition, Provider, ProviderBuilder};
+use tracers_libstapsdt_sys::*;
 
+use super::fork;
+use super::spill::{self, ArgOverflow};
 use super::{StapProbe, StapTracer};
 
 #[derive(Debug, Fail)]
@@ -17,25 +20,43 @@ pub enum StapError {
     #[fail(display = "duplicate probe name '{}'", name)]
     DuplicateProbeName { name: &'static str },
 
+    #[fail(
+        display = "probe '{}' has {} args but SystemTap supports at most {}; use `ArgOverflow::Spill` to pass the rest by pointer",
+        name, count, max
+    )]
+    TooManyArgs {
+        name: &'static str,
+        count: usize,
+        max: usize,
+    },
+
     #[fail(display = "libstapsdt call failed: {}", func)]
     NativeCallFailed { func: &'static str },
 
     #[fail(display = "probe definition not found: {:?}", probe)]
     ProbeDefinitionNotFound { probe: ProbeDefinition },
+
+    #[fail(
+        display = "a provider was being built or dropped when the process forked, so the providers can't be reloaded in the child"
+    )]
+    ForkedWhileBusy,
 }
 
 pub struct StapProviderBuilder {
     probes: Vec<ProbeDefinition>,
+    arg_overflow: ArgOverflow,
 }
 
 impl StapProviderBuilder {
     pub(crate) fn new() -> StapProviderBuilder {
-        StapProviderBuilder { probes: Vec::new() }
+        StapProviderBuilder {
+            probes: Vec::new(),
+            arg_overflow: ArgOverflow::default(),
+        }
     }
-}
 
-impl ProviderBuilder<StapTracer> for StapProviderBuilder {
-    fn add_probe<ArgsT: ProbeArgs<ArgsT>>(&mut self, name: &'static str) -> Fallible<()> {
+    fn add_definition(&mut self, definition: ProbeDefinition) -> Fallible<()> {
+        let name = definition.name;
         if name.is_empty() {
             return Err(StapError::ProbeNameRequired.into());
         }
@@ -45,10 +66,36 @@ impl ProviderBuilder<StapTracer> for StapProviderBuilder {
             return Err(StapError::DuplicateProbeName { name }.into());
         }
 
-        self.probes.push(ProbeDefinition::new::<ArgsT>(name));
+        if spill::has_spilled_args(&definition) && self.arg_overflow == ArgOverflow::Reject {
+            return Err(StapError::TooManyArgs {
+                name,
+                count: definition.arg_types.len(),
+                max: MAX_ARGUMENTS as usize,
+            }
+            .into());
+        }
+
+        self.probes.push(definition);
 
         Ok(())
     }
+}
+
+impl ProviderBuilder<StapTracer> for StapProviderBuilder {
+    fn add_probe<ArgsT: ProbeArgs<ArgsT>>(&mut self, name: &'static str) -> Fallible<()> {
+        self.add_definition(ProbeDefinition::new::<ArgsT>(name))
+    }
+
+    fn add_probe_dyn(&mut self, name: &'static str, arg_types: &[CType]) -> Fallible<()> {
+        self.add_definition(ProbeDefinition::with_arg_types(name, arg_types)?)
+    }
+
+    /// Sets what happens to probes added after this call which have more args than SystemTap
+    /// supports.  See the `spill` module for details.
+    fn arg_overflow(&mut self, arg_overflow: ArgOverflow) -> &mut Self {
+        self.arg_overflow = arg_overflow;
+        self
+    }
 
     fn build(self, name: &str) -> Fallible<StapProvider> {
         let mut provider = StapProvider::new(name)?;
@@ -57,14 +104,15 @@ impl ProviderBuilder<StapTracer> for StapProviderBuilder {
             provider.add_probe(probe)?;
         }
 
-        provider.load()?;
+        unsafe { provider.native.load()? };
+        fork::register_provider(&provider.native);
 
         Ok(provider)
     }
 }
 
 pub struct StapProvider {
-    provider: *mut SDTProvider_t,
+    native: Arc<NativeProvider>,
     probes: HashMap<ProbeDefinition, StapProbe>,
 }
 
@@ -97,7 +145,10 @@ impl StapProvider {
         }
 
         Ok(StapProvider {
-            provider: provider_ptr,
+            native: Arc::new(NativeProvider {
+                provider: provider_ptr,
+                loaded: AtomicBool::new(false),
+            }),
             probes: HashMap::new(),
         })
     }
@@ -112,27 +163,34 @@ impl StapProvider {
         // as variadic arguments.  This isn't a very ergonomic API design for either C or Rust wrappers.
         // In this case there is a max number of supported arguments, exposed in the `MAX_ARGUMENTS` constant.  At
         // the time of this writing this is '6', so we must handle from 0 to 6 possible arg counts.
+        // Probes with more args than that spill the rest into a struct, passed by pointer as the
+        // last arg; see the `spill` module.
         assert_eq!(6, MAX_ARGUMENTS);
 
-        let arg_types: Vec<ArgType_t> = definition
+        let mut arg_types: Vec<ArgType_t> = definition
             .arg_types
             .iter()
             .map(|x| Self::get_arg_type(x.clone()))
             .collect();
 
+        if spill::has_spilled_args(&definition) {
+            arg_types.truncate(spill::DIRECT_ARG_COUNT);
+            arg_types.push(Self::get_arg_type(CType::VoidPtr));
+        }
+
         let probe_ptr = unsafe {
             match arg_types.len() {
-                0 => providerAddProbe(self.provider, c_name.as_ptr(), 0),
-                1 => providerAddProbe(self.provider, c_name.as_ptr(), 1, arg_types[0]),
+                0 => providerAddProbe(self.native.provider, c_name.as_ptr(), 0),
+                1 => providerAddProbe(self.native.provider, c_name.as_ptr(), 1, arg_types[0]),
                 2 => providerAddProbe(
-                    self.provider,
+                    self.native.provider,
                     c_name.as_ptr(),
                     2,
                     arg_types[0],
                     arg_types[1],
                 ),
                 3 => providerAddProbe(
-                    self.provider,
+                    self.native.provider,
                     c_name.as_ptr(),
                     3,
                     arg_types[0],
@@ -140,7 +198,7 @@ impl StapProvider {
                     arg_types[2],
                 ),
                 4 => providerAddProbe(
-                    self.provider,
+                    self.native.provider,
                     c_name.as_ptr(),
                     4,
                     arg_types[0],
@@ -149,7 +207,7 @@ impl StapProvider {
                     arg_types[3],
                 ),
                 5 => providerAddProbe(
-                    self.provider,
+                    self.native.provider,
                     c_name.as_ptr(),
                     5,
                     arg_types[0],
@@ -159,7 +217,7 @@ impl StapProvider {
                     arg_types[4],
                 ),
                 _ => providerAddProbe(
-                    self.provider,
+                    self.native.provider,
                     c_name.as_ptr(),
                     6,
                     arg_types[0],
@@ -185,18 +243,32 @@ impl StapProvider {
         Ok(())
     }
 
-    /// Loads the provider into the process, making the probes available for firing
-    fn load(&mut self) -> Fallible<()> {
-        unsafe {
-            if providerLoad(self.provider) != 0 {
-                Err(StapError::NativeCallFailed {
-                    func: "providerLoad",
-                }
-                .into())
-            } else {
-                Ok(())
-            }
-        }
+    /// Tests if the provider is currently loaded.  A provider is loaded when it's built, and
+    /// remains loaded until it's unloaded with `unload` or dropped.
+    pub fn is_loaded(&self) -> bool {
+        self.native.is_loaded()
+    }
+
+    /// Unloads the provider from the process.  Until it's loaded again with `reload`, all of its
+    /// probes are disabled, and SystemTap can't see them.  Unloading a provider which isn't loaded
+    /// does nothing.
+    ///
+    /// # Safety
+    ///
+    /// Firing a probe calls into code which this unloads, so no other thread may be firing any of
+    /// this provider's probes.
+    pub unsafe fn unload(&self) -> Fallible<()> {
+        self.native.unload()
+    }
+
+    /// Unloads the provider if it's loaded, and then loads it again.  The probes are registered
+    /// anew, as if the provider had just been built.  If loading fails the probes remain disabled.
+    ///
+    /// # Safety
+    ///
+    /// See `unload`
+    pub unsafe fn reload(&self) -> Fallible<()> {
+        self.native.reload()
     }
 
     /// Translates from the `tracers-core` `CType` enum to the constants used by libstapsdt
@@ -234,15 +306,123 @@ impl Provider<StapTracer> for StapProvider {
     }
 }
 
+/// The provider is no longer reloaded after a fork once it's dropped.  The native provider itself
+/// is destroyed along with the last reference to it.
+impl Drop for StapProvider {
+    fn drop(&mut self) {
+        fork::unregister_provider(&self.native);
+    }
+}
+
+/// The libstapsdt provider object, along with whether it's loaded.  libstapsdt tracks that too,
+/// but not in its API, so it's tracked here as well.  This is shared with the `fork` module, which
+/// keeps a reference to every provider in the process so it can reload them after a fork.
+pub(crate) struct NativeProvider {
+    provider: *mut SDTProvider_t,
+    loaded: AtomicBool,
+}
+
+// See the `Sync` and `Send` impls of `StapProvider`
+unsafe impl Sync for NativeProvider {}
+unsafe impl Send for NativeProvider {}
+
+impl NativeProvider {
+    pub(crate) fn is_loaded(&self) -> bool {
+        self.loaded.load(Ordering::Acquire)
+    }
+
+    unsafe fn load(&self) -> Fallible<()> {
+        if providerLoad(self.provider) != 0 {
+            Err(StapError::NativeCallFailed {
+                func: "providerLoad",
+            }
+            .into())
+        } else {
+            self.loaded.store(true, Ordering::Release);
+            Ok(())
+        }
+    }
+
+    unsafe fn unload(&self) -> Fallible<()> {
+        if providerUnload(self.provider) != 0 {
+            Err(StapError::NativeCallFailed {
+                func: "providerUnload",
+            }
+            .into())
+        } else {
+            self.loaded.store(false, Ordering::Release);
+            Ok(())
+        }
+    }
+
+    pub(crate) unsafe fn reload(&self) -> Fallible<()> {
+        self.unload()?;
+        self.load()
+    }
+}
+
 /// Implementation of `Drop` which destroys the stap provider object and frees and memory
 /// associated with it.  Note that `providerDestroy` also frees any probes that have been
 /// allocatedon a provider.
-impl Drop for StapProvider {
+impl Drop for NativeProvider {
     fn drop(&mut self) {
-        if !self.provider.is_null() {
-            unsafe { providerUnload(self.provider) };
-            unsafe { providerDestroy(self.provider) };
-            self.provider = ptr::null_mut();
-        }
+        unsafe { providerUnload(self.provider) };
+        unsafe { providerDestroy(self.provider) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracers_core::dynamic::Tracer;
+
+    #[test]
+    fn providers_can_be_unloaded_and_reloaded() {
+        let provider = StapTracer::define_provider("reload_test", |mut builder| {
+            builder.add_probe::<(u64,)>("reloaded")?;
+            Ok(builder)
+        })
+        .unwrap();
+        let probe = provider.get_probe::<(u64,)>("reloaded").unwrap();
+        assert!(provider.is_loaded());
+
+        unsafe { provider.unload().unwrap() };
+        assert!(!provider.is_loaded());
+        assert!(!probe.is_enabled());
+
+        //Unloading a provider which isn't loaded does nothing
+        unsafe { provider.unload().unwrap() };
+        assert!(!provider.is_loaded());
+
+        unsafe { provider.reload().unwrap() };
+        assert!(provider.is_loaded());
+        probe.fire((1,));
+    }
+
+    #[test]
+    fn probes_with_too_many_args_are_rejected() {
+        let mut builder = StapProviderBuilder::new();
+
+        assert!(builder
+            .add_probe::<(u8, u8, u8, u8, u8, u8)>("six_args")
+            .is_ok());
+        assert!(builder
+            .add_probe::<(u8, u8, u8, u8, u8, u8, u8)>("seven_args")
+            .is_err());
+    }
+
+    #[test]
+    fn probes_with_too_many_args_can_spill() {
+        type SpilledArgs<'a> = (u8, u16, u32, u64, i8, i16, i32, i64, &'a str);
+
+        let provider = StapTracer::define_provider("spill_test", |mut builder| {
+            builder.arg_overflow(ArgOverflow::Spill);
+            builder.add_probe::<SpilledArgs>("spilled")?;
+            Ok(builder)
+        })
+        .unwrap();
+        let probe = provider.get_probe::<SpilledArgs>("spilled").unwrap();
+
+        probe.fire((1, 2, 3, 4, -5, -6, -7, -8, "nine"));
     }
 }
@@ -0,0 +1,80 @@
+//! Support for probes with more args than SystemTap supports.
+//!
+//! `libstapsdt` supports at most `MAX_ARGUMENTS` (6) args per probe, while `tracers` probes can
+//! have up to 12.  By default `StapProviderBuilder` refuses to add a probe with more than 6 args,
+//! rather than silently dropping the extra ones.  With `ArgOverflow::Spill`, such a probe is
+//! instead passed its first five args as usual, and the rest are packed into a C struct passed by
+//! pointer as the sixth arg.  `spilled_args_struct` describes the layout of that struct, so
+//! tracing tools like `bpftrace` can read the spilled args.
+//!
+//! Providers declared with `#[tracer]` spill by default, since their probes' native arg counts grow
+//! with features like `ptr_len` strings; `#[tracer(arg_overflow = "reject")]` or
+//! `#[probe(arg_overflow = "reject")]` opts out.
+pub use tracers_core::dynamic::ArgOverflow;
+use tracers_core::dynamic::ProbeDefinition;
+use tracers_libstapsdt_sys::MAX_ARGUMENTS;
+
+/// The number of args passed directly to a probe whose args are spilled; the last native arg is
+/// the pointer to the rest
+pub(crate) const DIRECT_ARG_COUNT: usize = MAX_ARGUMENTS as usize - 1;
+
+/// Tests if a probe's args don't all fit in the native args SystemTap supports
+pub(crate) fn has_spilled_args(definition: &ProbeDefinition) -> bool {
+    definition.arg_types.len() > MAX_ARGUMENTS as usize
+}
+
+/// Describes the layout of the struct which holds the spilled args of a probe added with
+/// `ArgOverflow::Spill`, as a C struct declaration.  Each member is named for the index of the arg
+/// it holds, so the struct for a probe `bar` with 7 args on the provider `foo` is:
+///
+/// ```c
+/// struct foo_bar_args {
+///     int arg5;
+///     char* arg6;
+/// };
+/// ```
+///
+/// A `bpftrace` script which includes that declaration can read the spilled args with, for
+/// example, `((struct foo_bar_args *)arg5)->arg6`.
+///
+/// Returns `None` if the probe's args all fit without spilling.
+pub fn spilled_args_struct(provider_name: &str, definition: &ProbeDefinition) -> Option<String> {
+    if !has_spilled_args(definition) {
+        return None;
+    }
+
+    let mut decl = format!("struct {}_{}_args {{\n", provider_name, definition.name);
+    for (index, arg_type) in definition
+        .arg_types
+        .iter()
+        .enumerate()
+        .skip(DIRECT_ARG_COUNT)
+    {
+        decl += &format!("    {} arg{};\n", arg_type, index);
+    }
+    decl += "};\n";
+
+    Some(decl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_spilled_args() {
+        let definition =
+            ProbeDefinition::new::<(u8, u8, u8, u8, u8, i32, &str, usize)>("eight_args");
+
+        assert_eq!(
+            Some(
+                "struct foo_eight_args_args {\n    int arg5;\n    char* arg6;\n    size_t arg7;\n};\n"
+                    .to_string()
+            ),
+            spilled_args_struct("foo", &definition)
+        );
+
+        let definition = ProbeDefinition::new::<(u8, u8, u8, u8, u8, u8)>("six_args");
+        assert_eq!(None, spilled_args_struct("foo", &definition));
+    }
+}
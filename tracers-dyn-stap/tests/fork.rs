@@ -0,0 +1,68 @@
+//! Tests that providers are reloaded in the child after a `fork()`.
+//!
+//! This doesn't use the test harness, which runs each test on its own thread; a process has to be
+//! single-threaded for its forked child to do anything more than call async-signal-safe functions.
+//! It reports its result in the harness's format, so that it's plain when it's been skipped.
+#![deny(warnings)]
+
+#[cfg(enabled)]
+fn main() {
+    use tracers_core::dynamic::{Provider, ProviderBuilder, Tracer};
+    use tracers_dyn_stap::{reload_providers_if_forked, StapTracer};
+
+    println!("\nrunning 1 test");
+
+    //Nothing has forked yet, so nothing is reloaded
+    assert_eq!(0, unsafe { reload_providers_if_forked().unwrap() });
+
+    let provider = StapTracer::define_provider("fork_test", |mut builder| {
+        builder.add_probe::<(u64,)>("forked")?;
+        Ok(builder)
+    })
+    .unwrap();
+    let probe = provider.get_probe::<(u64,)>("forked").unwrap();
+
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork failed"),
+        0 => {
+            //In the child; report the result with the exit code, rather than panicking, so the
+            //parent's assertions are the ones reported.  The provider must have been reloaded, and
+            //its probe must work with the reloaded library; nothing is tracing this process, so
+            //the probe is disabled.
+            let exit_code = match unsafe { reload_providers_if_forked() } {
+                Ok(1) if provider.is_loaded() && !probe.is_enabled() => 0,
+                Ok(_) => 1,
+                Err(_) => 2,
+            };
+            probe.fire((1,));
+            unsafe { libc::_exit(exit_code) };
+        }
+        child => {
+            let mut status = 0;
+            assert_eq!(child, unsafe { libc::waitpid(child, &mut status, 0) });
+            assert!(libc::WIFEXITED(status));
+            assert_eq!(
+                0,
+                libc::WEXITSTATUS(status),
+                "the child's provider wasn't reloaded (1), or reloading it failed (2)"
+            );
+
+            //The parent's provider is untouched, and it didn't fork itself, so calling this again
+            //does nothing
+            assert!(provider.is_loaded());
+            assert_eq!(0, unsafe { reload_providers_if_forked().unwrap() });
+            assert!(!probe.is_enabled());
+            probe.fire((2,));
+        }
+    }
+
+    println!("test providers_reloaded_after_fork ... ok");
+    println!("\ntest result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out\n");
+}
+
+#[cfg(not(enabled))]
+fn main() {
+    println!("\nrunning 1 test");
+    println!("test providers_reloaded_after_fork ... ignored, tracers-dyn-stap is not enabled");
+    println!("\ntest result: ok. 0 passed; 0 failed; 1 ignored; 0 measured; 0 filtered out\n");
+}
@@ -5,7 +5,7 @@ extern crate proc_macro;
 use proc_macro::TokenStream as CompilerTokenStream;
 use proc_macro2::TokenStream;
 use proc_macro_hack::proc_macro_hack;
-use tracers_codegen::proc_macros::{init_provider_impl, probe_impl, tracer_impl};
+use tracers_codegen::proc_macros::{init_provider_impl, probe_impl, probe_span_impl, tracer_impl};
 
 #[proc_macro_hack]
 pub fn probe(input: CompilerTokenStream) -> CompilerTokenStream {
@@ -16,6 +16,15 @@ pub fn probe(input: CompilerTokenStream) -> CompilerTokenStream {
     .into()
 }
 
+#[proc_macro_hack]
+pub fn probe_span(input: CompilerTokenStream) -> CompilerTokenStream {
+    match probe_span_impl(TokenStream::from(input)) {
+        Ok(stream) => stream,
+        Err(err) => err.into_compiler_error(),
+    }
+    .into()
+}
+
 #[proc_macro_hack]
 pub fn init_provider(input: CompilerTokenStream) -> CompilerTokenStream {
     match init_provider_impl(TokenStream::from(input)) {
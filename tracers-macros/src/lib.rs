@@ -4,6 +4,9 @@ use proc_macro_hack::proc_macro_hack;
 #[proc_macro_hack]
 pub use tracers_macros_hack::probe;
 
+#[proc_macro_hack]
+pub use tracers_macros_hack::probe_span;
+
 #[proc_macro_hack]
 pub use tracers_macros_hack::init_provider;
 
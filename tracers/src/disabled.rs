@@ -0,0 +1,51 @@
+//! Stand-ins for the types in `tracers-core` which the generated code names even when tracing is
+//! disabled.  With tracing disabled `tracers-core` isn't a dependency at all, so these have no
+//! dependencies of their own.
+use std::fmt;
+
+/// Describes a provider which was initialized successfully.  This is the same as the
+/// `ProviderInfo` when tracing is enabled, and is formatted the same way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProviderInfo {
+    /// The name of the provider, as the platform's tracing tools would see it
+    pub name: &'static str,
+
+    /// The tracing implementation which the provider was built with, which is always `disabled`
+    pub implementation: &'static str,
+
+    /// The version of `tracers` which generated the provider
+    pub version: &'static str,
+
+    /// The names of the provider's probes, in the order they're declared in the provider trait
+    pub probes: &'static [&'static str],
+}
+
+impl fmt::Display for ProviderInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}::{}::{}",
+            self.name, self.implementation, self.version
+        )
+    }
+}
+
+/// Providers can't fail to initialize when tracing is disabled, so there are no values of this type
+#[derive(Debug)]
+pub enum ProviderInitError {}
+
+impl ProviderInitError {
+    /// The name of the provider which failed to initialize
+    pub fn provider(&self) -> &'static str {
+        match *self {}
+    }
+}
+
+impl fmt::Display for ProviderInitError {
+    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+/// The result of a provider init, which when tracing is disabled is always `Ok`
+pub type ProviderInitResult = std::result::Result<ProviderInfo, &'static ProviderInitError>;
@@ -1,5 +1,15 @@
 #![deny(warnings)]
 
+// The result of `init_provider!` is available whether or not tracing is enabled.  When it's
+// disabled there's no `tracers-core`, so the generated code uses dependency-free stand-ins
+#[cfg(enabled)]
+pub use tracers_core::{ProviderInfo, ProviderInitError, ProviderInitResult};
+
+#[cfg(not(enabled))]
+mod disabled;
+#[cfg(not(enabled))]
+pub use disabled::{ProviderInfo, ProviderInitError, ProviderInitResult};
+
 /// The code generated by `tracers-macros` will at runtime require some functionality, both from
 /// within this crate but also from third-party crates like `failure`.  It's important that the
 /// generated code use _our_ version of these crates, and not be required to add some explicit
@@ -14,8 +24,23 @@
 /// there's no reason to even include this runtime
 #[cfg(enabled)]
 pub mod runtime {
+    pub use tracers_core::argtypes::{
+        io_error_kind_str, lossy_string_conversions, thread_lossy_string_conversions,
+        IoErrorMessage, NulEscaped, NulTruncated, ProbeArgBytes, ProbeArgWideInteger,
+    };
+    pub use tracers_core::chrome_trace;
+    pub use tracers_core::ctf;
     pub use tracers_core::failure;
     pub use tracers_core::libc;
+    pub use tracers_core::probe_level::ProbeLevel;
+    pub use tracers_core::probe_stats::{ProbeStats, ProbeStatsSnapshot};
+    pub use tracers_core::registry::{
+        register_provider, registry, RegisteredProbe, RegisteredProbeArg, RegisteredProvider,
+        Registry,
+    };
+    pub use tracers_core::span::SpanGuard;
+    pub use tracers_core::throttle::{RateLimitThrottle, SampleThrottle};
+    pub use tracers_core::trace_value::ArgValue;
     pub use tracers_core::{wrap, ProbeArgNativeType, ProbeArgType, ProbeArgWrapper};
 
     #[cfg(dynamic_enabled)]
@@ -27,19 +52,32 @@ pub mod runtime {
         // Re-export some types from child crates which callers will need to be able to use.  Ergonomically
         // it makes more sense to a caller to deal with, for example, `tracers::Provider`
 
-        //Alias `SystemTracer` to the appropriate implementation based on the determination made in
-        //`build.rs`
+        //Alias `PlatformTracer` to the appropriate implementation based on the determination made
+        //in `build.rs`
         #[cfg(dyn_stap_enabled)]
-        pub type SystemTracer = tracers_dyn_stap::StapTracer;
+        pub type PlatformTracer = tracers_dyn_stap::StapTracer;
 
         #[cfg(dyn_noop_enabled)]
-        pub type SystemTracer = tracers_noop::NoOpTracer;
+        pub type PlatformTracer = tracers_dyn_noop::NoOpTracer;
+
+        //`SystemTracer` is the tracer the generated code uses.  With the `tee-callback` feature
+        //it also passes every probe firing to the in-process callback
+        #[cfg(not(dyn_tee_callback_enabled))]
+        pub type SystemTracer = PlatformTracer;
+
+        #[cfg(dyn_tee_callback_enabled)]
+        pub type SystemTracer = TeeTracer<PlatformTracer, CallbackTracer>;
 
         #[cfg(dynamic_enabled)]
         pub type SystemProvider = <SystemTracer as Tracer>::ProviderType;
 
         #[cfg(dynamic_enabled)]
         pub type SystemProbe = <SystemTracer as Tracer>::ProbeType;
+
+        /// Fires every probe with both `SystemTracer` and some other tracer `T`, like an in-process
+        /// consumer of probe firings
+        #[cfg(dynamic_enabled)]
+        pub type SystemTeeTracer<T> = TeeTracer<SystemTracer, T>;
     }
 }
 
@@ -62,6 +100,35 @@ mod test {
         }
     }
 
+    #[test]
+    #[cfg(dynamic_enabled)]
+    fn tee_tracer_fires_both_tracers() {
+        use tracers_core::argtypes::CType;
+        use tracers_core::dynamic::{Provider, ProviderBuilder};
+
+        let provider = dynamic::SystemTeeTracer::<dynamic::SystemTracer>::define_provider(
+            "tee_test",
+            |mut builder| {
+                builder.add_probe::<(u64, &str)>("typed")?;
+                builder.add_probe_dyn("dynamic", &[CType::Int])?;
+                Ok(builder)
+            },
+        )
+        .unwrap();
+
+        let typed = provider.get_probe::<(u64, &str)>("typed").unwrap();
+        if typed.is_enabled() {
+            typed.fire((42, "foo"));
+        }
+
+        let dyn_probe = provider.get_probe_dyn("dynamic", &[CType::Int]).unwrap();
+        if dyn_probe.is_enabled() {
+            dyn_probe.fire(&[dynamic::ProbeValue::Int(42)]).unwrap();
+        }
+
+        assert!(provider.get_probe::<(u64,)>("typed").is_err());
+    }
+
     #[test]
     #[cfg(not(dynamic_enabled))]
     fn verify_expected_dynamic_tracing_impl() {
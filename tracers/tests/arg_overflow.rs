@@ -0,0 +1,64 @@
+//! SystemTap can only pass six args to a probe, so dynamic `#[tracer]` providers spill the rest
+//! by default rather than failing to initialize.  A probe can still opt out of that.
+#![cfg(feature = "dynamic-tracing")]
+#![deny(warnings)]
+use tracers::runtime::dynamic::{PlatformTracer, Tracer};
+use tracers_macros::{init_provider, probe, tracer};
+
+#[tracer]
+trait ManyArgsProbes {
+    #[allow(clippy::too_many_arguments)]
+    fn int_arg12(
+        arg0: u64,
+        arg1: u64,
+        arg2: u64,
+        arg3: u64,
+        arg4: u64,
+        arg5: u64,
+        arg6: u64,
+        arg7: u64,
+        arg8: u64,
+        arg9: u64,
+        arg10: u64,
+        arg11: u64,
+    );
+
+    //`ptr_len` strings are two native args each
+    fn string_arg6(
+        #[probe(encoding = "ptr_len")] arg0: &str,
+        #[probe(encoding = "ptr_len")] arg1: &str,
+        #[probe(encoding = "ptr_len")] arg2: &str,
+        #[probe(encoding = "ptr_len")] arg3: &str,
+        #[probe(encoding = "ptr_len")] arg4: &str,
+        #[probe(encoding = "ptr_len")] arg5: &str,
+    );
+}
+
+#[tracer]
+trait RejectedArgsProbes {
+    #[probe(arg_overflow = "reject")]
+    fn int_arg7(arg0: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64, arg6: u64);
+}
+
+#[test]
+fn spills_args_by_default() {
+    init_provider!(ManyArgsProbes).expect("Provider init failed");
+
+    probe!(ManyArgsProbes::int_arg12(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11));
+    probe!(ManyArgsProbes::string_arg6("a", "b", "c", "d", "e", "f"));
+}
+
+#[test]
+fn rejects_args_if_asked() {
+    let result = init_provider!(RejectedArgsProbes);
+
+    //Only SystemTap has a limit to enforce
+    if PlatformTracer::TRACING_IMPLEMENTATION == "dyn_stap" {
+        assert!(result.is_err());
+    } else {
+        result.expect("Provider init failed");
+    }
+
+    //Either way, firing the probe is harmless
+    probe!(RejectedArgsProbes::int_arg7(0, 1, 2, 3, 4, 5, 6));
+}
@@ -0,0 +1,53 @@
+//! With the `force-static-chrome-trace` feature, probe firings are written to the file named by
+//! `TRACERS_CHROME_TRACE_FILE` in the Chrome Trace Event Format.
+//!
+//! Run with `cargo test --features force-static-chrome-trace`.
+#![cfg(feature = "force-static-chrome-trace")]
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use std::env;
+use std::fs;
+use tracers::runtime::chrome_trace;
+use tracers_macros::{probe, probe_span, tracer};
+
+#[tracer]
+trait ChromeTraceProbes {
+    fn greeting(greeting: &str, count: u32);
+
+    #[probe(span)]
+    fn handle_request(#[probe(encoding = "ptr_len")] path: &str);
+}
+
+#[test]
+fn writes_trace_events() {
+    //The trace file is opened when a probe is first tested, so this has to be set before then.
+    //This is the only test in this binary, so nothing else fires a probe first.
+    let trace_path =
+        env::temp_dir().join(format!("tracers-chrome-trace-{}.json", std::process::id()));
+    env::set_var(chrome_trace::TRACE_FILE_ENV_VAR, &trace_path);
+
+    probe!(ChromeTraceProbes::greeting("hello \"world\"", 2));
+    {
+        let _span = probe_span!(ChromeTraceProbes::handle_request("/foo"));
+    }
+
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    fs::remove_file(&trace_path).unwrap();
+    let events: Vec<_> = trace.lines().skip(1).collect();
+    assert_eq!(3, events.len(), "{}", trace);
+
+    assert!(events[0].starts_with(
+        "{\"name\":\"greeting\",\"cat\":\"tracers_chrome_trace_probes\",\"ph\":\"i\","
+    ));
+    assert!(events[0].ends_with(",\"args\":{\"greeting\":\"hello \\\"world\\\"\",\"count\":2}},"));
+
+    assert!(events[1].starts_with(
+        "{\"name\":\"handle_request\",\"cat\":\"tracers_chrome_trace_probes\",\"ph\":\"B\","
+    ));
+    assert!(events[1].ends_with(",\"args\":{\"path\":\"/foo\"}},"));
+
+    assert!(events[2].starts_with(
+        "{\"name\":\"handle_request\",\"cat\":\"tracers_chrome_trace_probes\",\"ph\":\"E\","
+    ));
+    assert!(events[2].contains(",\"args\":{\"path\":\"/foo\",\"elapsed_ns\":"));
+}
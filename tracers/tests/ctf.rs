@@ -0,0 +1,92 @@
+//! With the `force-static-ctf` feature, probe firings are written as a CTF trace to the directory
+//! named by `TRACERS_CTF_TRACE_DIR`.
+//!
+//! Run with `cargo test --features force-static-ctf`.
+#![cfg(feature = "force-static-ctf")]
+#![deny(warnings)]
+use std::env;
+use std::fs;
+use tracers::runtime::ctf::{self, read_int, read_string};
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait CtfProbes {
+    #[probe(level = "warning")]
+    fn greeting(greeting: &str, count: u32);
+
+    fn request(#[probe(encoding = "ptr_len")] path: &str);
+}
+
+#[test]
+fn writes_ctf_trace() {
+    //The trace is created when a probe is first tested, so this has to be set before then.  This
+    //is the only test in this binary, so nothing else fires a probe first.
+    let trace_dir = env::temp_dir().join(format!("tracers-ctf-{}", std::process::id()));
+    env::set_var(ctf::TRACE_DIR_ENV_VAR, &trace_dir);
+
+    probe!(CtfProbes::greeting("hello", 2));
+    probe!(CtfProbes::request("/foo\0bar"));
+    probe!(CtfProbes::greeting("goodbye", 3));
+
+    let metadata = fs::read_to_string(trace_dir.join("metadata")).unwrap();
+    let stream = fs::read(trace_dir.join("stream_1")).unwrap();
+    fs::remove_dir_all(&trace_dir).unwrap();
+
+    assert!(metadata.starts_with("/* CTF 1.8 */"));
+    assert!(
+        metadata.contains(
+            "name = \"tracers_ctf_probes:greeting\";
+    id = 0;
+    stream_id = 0;
+    loglevel = 4;
+    fields := struct {
+        string _greeting;
+        integer { size = 32; align = 8; signed = 0; base = 10; } _count;
+    };"
+        ),
+        "{}",
+        metadata
+    );
+    assert!(
+        metadata.contains(
+            "name = \"tracers_ctf_probes:request\";
+    id = 1;
+    stream_id = 0;
+    fields := struct {
+        integer { size = 64; align = 8; signed = 0; base = 10; } _path_len;
+        uint8_t _path[_path_len];"
+        ),
+        "{}",
+        metadata
+    );
+
+    //The packet header and context
+    let mut offset = 0;
+    assert_eq!(0xC1FC_1FC1, read_int(&stream, &mut offset, 4));
+    assert_eq!(0, read_int(&stream, &mut offset, 4));
+    offset += 16; //timestamps
+    assert_eq!(stream.len() as u64 * 8, read_int(&stream, &mut offset, 8));
+    assert_eq!(stream.len() as u64 * 8, read_int(&stream, &mut offset, 8));
+    offset += 8; //thread ID
+
+    assert_eq!(0, read_int(&stream, &mut offset, 4));
+    offset += 8; //timestamp
+    assert_eq!("hello", read_string(&stream, &mut offset));
+    assert_eq!(2, read_int(&stream, &mut offset, 4));
+
+    assert_eq!(1, read_int(&stream, &mut offset, 4));
+    offset += 8;
+    assert_eq!(
+        8,
+        read_int(&stream, &mut offset, std::mem::size_of::<usize>())
+    );
+    assert_eq!(b"/foo\0bar", &stream[offset..offset + 8]);
+    offset += 8;
+
+    assert_eq!(0, read_int(&stream, &mut offset, 4));
+    offset += 8;
+    assert_eq!("goodbye", read_string(&stream, &mut offset));
+    assert_eq!(3, read_int(&stream, &mut offset, 4));
+
+    assert_eq!(stream.len(), offset);
+}
@@ -0,0 +1,33 @@
+//! `char`, the `NonZero*` integers, `Duration` and the 128-bit integers are all passed to probes
+//! as one or more C integers.  If this compiles and runs it means the generated code handles each
+//! of them, including splitting 128-bit integers into two native args.
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use std::num::{NonZeroI64, NonZeroU32};
+use std::time::Duration;
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait NumericTypeProbes {
+    fn character(foo: char, bar: &Option<char>);
+    fn non_zero(foo: NonZeroU32, bar: &Option<NonZeroI64>);
+    fn duration(foo: Duration, bar: &Duration);
+    fn wide(foo: u128, bar: &i128, baz: &Option<&u128>);
+}
+
+#[test]
+fn probe_firing() {
+    let id = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210u128;
+
+    probe!(NumericTypeProbes::character('x', &Some('😀')));
+    probe!(NumericTypeProbes::non_zero(
+        NonZeroU32::new(42).unwrap(),
+        &NonZeroI64::new(-1)
+    ));
+    probe!(NumericTypeProbes::duration(
+        Duration::from_millis(1500),
+        &Duration::from_secs(u64::max_value())
+    ));
+    probe!(NumericTypeProbes::wide(id, &-1, &Some(&id)));
+    probe!(NumericTypeProbes::wide(0, &0, &None));
+}
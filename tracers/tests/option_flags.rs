@@ -0,0 +1,35 @@
+//! By default `None` is passed to a probe as the default value of the C type, which can't be told
+//! apart from `Some` of that value.  Probe args can opt in to an `is_some` flag or a sentinel
+//! value, either individually or for the whole provider.  If this compiles and runs it means the
+//! generated code for each option works.
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait OptionProbes {
+    fn flagged(#[probe(option_flag)] foo: &Option<u32>, bar: &Option<&str>);
+    fn sentinel(#[probe(none_value = "-1")] foo: &Option<&i64>);
+    fn string_sentinel(#[probe(option_flag, none_value = r#""<none>""#)] foo: &Option<&str>);
+}
+
+#[tracer(option_flag)]
+trait FlaggedOptionProbes {
+    fn all_flagged(foo: &Option<u64>, bar: &Option<&str>, baz: usize);
+    fn opted_out(#[probe(option_flag = false)] foo: &Option<u64>);
+}
+
+#[test]
+fn probe_firing() {
+    let foo = 5i64;
+
+    probe!(OptionProbes::flagged(&Some(0), &Some("bar")));
+    probe!(OptionProbes::flagged(&None, &None));
+    probe!(OptionProbes::sentinel(&Some(&foo)));
+    probe!(OptionProbes::sentinel(&None));
+    probe!(OptionProbes::string_sentinel(&Some("foo")));
+    probe!(OptionProbes::string_sentinel(&None));
+
+    probe!(FlaggedOptionProbes::all_flagged(&Some(0), &None, 1));
+    probe!(FlaggedOptionProbes::opted_out(&None));
+}
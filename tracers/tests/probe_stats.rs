@@ -0,0 +1,51 @@
+//! With the `probe-stats` feature, every `probe!` call counts how often the probe was hit and
+//! fired, and how many of its args couldn't be passed faithfully.
+//!
+//! Run with `cargo test --features probe-stats`.
+#![cfg(feature = "probe-stats")]
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use tracers::runtime::registry;
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait CountedProbes {
+    fn counted(foo: &str);
+    fn never_called(foo: u32);
+}
+
+#[test]
+fn probe_calls_are_counted() {
+    for _ in 0..3 {
+        probe!(CountedProbes::counted("foo"));
+    }
+
+    //No tracer is attached so the probe is never enabled, and its args are never evaluated
+    let stats = CountedProbes::counted_stats();
+    assert_eq!(3, stats.hits);
+    assert_eq!(0, stats.enabled_fires);
+    assert_eq!(0, stats.arg_failures);
+
+    assert_eq!(0, CountedProbes::never_called_stats().hits);
+
+    let registered: Vec<_> = registry()
+        .probe_stats()
+        .into_iter()
+        .filter(|(provider, _, _)| provider.name() == "tracers_counted_probes")
+        .map(|(_, probe, stats)| (probe.name(), stats.hits))
+        .collect();
+    assert_eq!(vec![("counted", 3), ("never_called", 0)], registered);
+}
+
+#[cfg(feature = "dynamic-tracing")]
+#[test]
+fn instances_count_their_own_probe_calls() {
+    let instance = CountedProbes::define_instance("tracers_counted_instance").unwrap();
+    for _ in 0..2 {
+        probe!(instance => CountedProbes::counted("foo"));
+    }
+
+    assert_eq!(2, instance.counted_stats().hits);
+    assert_eq!(0, instance.counted_stats().enabled_fires);
+    assert_eq!(0, instance.never_called_stats().hits);
+}
@@ -0,0 +1,64 @@
+//! Provider instances, with names chosen at runtime, are only supported with dynamic tracing.
+#![cfg(feature = "dynamic-tracing")]
+#![deny(warnings)]
+use std::cell::Cell;
+use tracers_macros::{probe, probe_span, tracer};
+
+#[tracer]
+trait TenantProbes {
+    fn request(id: u64, path: &str);
+
+    #[probe(span)]
+    fn handle(id: u64);
+
+    //Throttled probes can only be fired on the default instance, but they don't stop a provider
+    //from having instances
+    #[probe(sample = 10)]
+    fn sampled(id: u64);
+}
+
+#[test]
+fn instances_fire_probes() {
+    let acme = TenantProbes::define_instance("tenant_acme").unwrap();
+    let globex = TenantProbes::define_instance("tenant_globex").unwrap();
+
+    assert_eq!("tenant_acme", acme.name());
+    assert_eq!("tenant_globex", globex.name());
+
+    //Nothing is tracing this test, so the args must not be evaluated
+    let evaluated = Cell::new(false);
+    probe!(acme => TenantProbes::request(1, {
+        evaluated.set(true);
+        "/"
+    }));
+    assert!(!evaluated.get());
+    probe!(&globex => TenantProbes::request(2, "/index.html"));
+
+    //A clone shares the instance's provider, which outlives the original
+    let globex_clone = globex.clone();
+    drop(globex);
+    assert_eq!("tenant_globex", globex_clone.name());
+    probe!(globex_clone => TenantProbes::request(4, "/about.html"));
+
+    //The default instance of the provider is unaffected
+    probe!(TenantProbes::request(3, "/"));
+    probe!(TenantProbes::sampled(3));
+    {
+        let _span = probe_span!(TenantProbes::handle(3));
+    }
+}
+
+#[test]
+fn instances_fire_span_probes() {
+    let acme = TenantProbes::define_instance("tenant_acme_spans").unwrap();
+
+    //The instance expression is only evaluated once, even though it supplies both probes
+    let evaluations = Cell::new(0);
+    {
+        let _span = probe_span!({
+            evaluations.set(evaluations.get() + 1);
+            &acme
+        } => TenantProbes::handle(1));
+    }
+    assert_eq!(1, evaluations.get());
+}
@@ -0,0 +1,77 @@
+//! Every provider generated by `#[tracer]` registers itself in a process-wide registry before
+//! `main` runs, so the providers and their probes can be enumerated and initialized without
+//! naming each provider trait.
+//!
+//! The registry is part of `tracers::runtime`, which doesn't exist when tracing is disabled.
+#![cfg(any(feature = "static-tracing", feature = "dynamic-tracing"))]
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use tracers::runtime::{registry, ProbeLevel};
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait RegisteredProbes {
+    fn probe0();
+    fn probe1(foo: &str, bar: &Option<u32>);
+}
+
+#[tracer(provider_name = "other_registered_probes", level = "warning")]
+trait OtherRegisteredProbes {
+    fn probe0(foo: usize);
+    #[probe(level = "error")]
+    fn probe1();
+}
+
+#[test]
+fn providers_are_registered() {
+    let provider = registry()
+        .find_provider("tracers_registered_probes")
+        .expect("provider wasn't registered");
+
+    let probe_names: Vec<_> = provider.probes().iter().map(|p| p.name()).collect();
+    assert_eq!(vec!["probe0", "probe1"], probe_names);
+
+    let args: Vec<_> = provider
+        .find_probe("probe1")
+        .unwrap()
+        .args()
+        .iter()
+        .map(|arg| (arg.name(), arg.rust_type()))
+        .collect();
+    assert_eq!(vec![("foo", "&str"), ("bar", "&Option<u32>")], args);
+
+    assert_eq!(None, provider.find_probe("probe0").unwrap().level());
+
+    //Probes get the level of their provider unless they have their own
+    let provider = registry()
+        .find_provider("other_registered_probes")
+        .expect("provider wasn't registered");
+    let levels: Vec<_> = provider.probes().iter().map(|p| p.level()).collect();
+    assert_eq!(
+        vec![Some(ProbeLevel::Warning), Some(ProbeLevel::Error)],
+        levels
+    );
+}
+
+#[test]
+fn init_all_initializes_every_provider() {
+    let results = registry().init_all();
+    for name in ["tracers_registered_probes", "other_registered_probes"].iter() {
+        let (_, result) = results
+            .iter()
+            .find(|(provider, _)| provider.name() == *name)
+            .unwrap_or_else(|| panic!("provider '{}' wasn't initialized", name));
+
+        assert!(result.is_ok(), "provider '{}' failed: {:?}", name, result);
+    }
+
+    //Nothing is tracing this test so none of the probes can be enabled
+    for provider in registry().providers() {
+        assert!(provider.probes().iter().all(|probe| !probe.is_enabled()));
+    }
+
+    probe!(RegisteredProbes::probe0());
+    probe!(RegisteredProbes::probe1("foo", &Some(1)));
+    probe!(OtherRegisteredProbes::probe0(1));
+    probe!(OtherRegisteredProbes::probe1());
+}
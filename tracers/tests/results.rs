@@ -0,0 +1,29 @@
+//! `&Result<T, E>` is passed to probes as an `is_ok` flag followed by both payloads, and
+//! `&io::Error` as its raw OS error code, kind and message.  If this compiles and runs it means the
+//! generated code handles each of them, including errors nested inside results.
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use std::io;
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait ResultProbes {
+    fn result(foo: &Result<u32, &str>, bar: &Result<&str, Option<i64>>);
+    fn io_result(foo: &io::Result<()>, bar: &Result<String, std::io::Error>);
+    fn io_error(foo: &io::Error);
+}
+
+#[test]
+fn probe_firing() {
+    let not_found = io::Error::from_raw_os_error(2);
+    let custom = io::Error::new(io::ErrorKind::InvalidData, "something broke");
+
+    probe!(ResultProbes::result(&Ok(5), &Ok("foo")));
+    probe!(ResultProbes::result(&Err("foo"), &Err(Some(-1))));
+    probe!(ResultProbes::io_result(&Ok(()), &Ok("foo".to_owned())));
+    probe!(ResultProbes::io_result(
+        &Err(io::Error::from_raw_os_error(13)),
+        &Err(custom)
+    ));
+    probe!(ResultProbes::io_error(&not_found));
+}
@@ -25,7 +25,18 @@ fn expected_impl() {
     if let Ok(expected_impl) = std::env::var("TRACERS_EXPECTED_PROVIDER") {
         match init_provider!(TestProbes) {
             Err(e) => panic!("Provider initialization error: {}", e),
-            Ok(details) => assert_eq!(expected_impl, details),
+            Ok(details) => assert_eq!(expected_impl, details.to_string()),
         }
     }
 }
+
+#[test]
+fn init_provider_describes_provider() {
+    let info = init_provider!(TestProbes).expect("Provider initialization error");
+
+    assert_eq!(&["probe0", "probe1", "probe2"], info.probes);
+    assert_eq!(
+        format!("{}::{}::{}", info.name, info.implementation, info.version),
+        info.to_string()
+    );
+}
@@ -0,0 +1,82 @@
+//! A span probe is a pair of enter and exit probes, fired by `probe_span!`.  The exit probe fires
+//! when the guard the macro returns is dropped, on every path out of the span.  If this compiles
+//! and runs it means the generated code for span probes works.
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use std::cell::Cell;
+use std::panic;
+use tracers_macros::{probe, probe_span, tracer};
+
+#[tracer]
+trait SpanProbes {
+    #[probe(span)]
+    fn handle_request(id: u64, path: &str);
+
+    #[probe(span)]
+    fn no_args();
+
+    fn regular(foo: u64);
+}
+
+fn handle_request(id: u64, path: &str) -> Result<(), ()> {
+    let _span = probe_span!(SpanProbes::handle_request(id, path));
+
+    if id > 4 {
+        return Err(());
+    }
+
+    probe!(SpanProbes::regular(id));
+    Ok(())
+}
+
+#[test]
+fn probe_firing() {
+    for id in 0..10 {
+        let _ = handle_request(id, "/foo");
+    }
+
+    {
+        let _span = probe_span!(SpanProbes::no_args());
+    }
+
+    //The enter and exit probes are ordinary probes, so they can also be fired directly
+    probe!(SpanProbes::handle_request_enter(1, "/foo"));
+    probe!(SpanProbes::handle_request_exit(1, "/foo", 42));
+}
+
+#[test]
+fn probe_firing_with_borrowed_args() {
+    //The guard keeps the args until the span ends, so computed args are borrowed from locals
+    //rather than from temporaries like `&format!(...)`, which `probe_span!` rejects
+    for id in 0..3 {
+        let path = format!("/items/{}", id);
+        let _span = probe_span!(SpanProbes::handle_request(id, &path));
+        let _nested = probe_span!(SpanProbes::handle_request(id + 1, path.as_str()));
+    }
+}
+
+#[test]
+fn probe_firing_while_unwinding() {
+    let result = panic::catch_unwind(|| {
+        let _span = probe_span!(SpanProbes::handle_request(1, "/panics"));
+        panic!("the span ends here");
+    });
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn args_are_not_evaluated_when_disabled() {
+    let evaluated = Cell::new(false);
+    let next_id = || {
+        evaluated.set(true);
+        1
+    };
+
+    //No tracer is attached, so neither probe of the span is enabled
+    {
+        let _span = probe_span!(SpanProbes::handle_request(next_id(), "/foo"));
+    }
+
+    assert!(!evaluated.get());
+}
@@ -0,0 +1,35 @@
+//! Strings which contain NUL bytes can't be passed to a probe as C strings.  By default they're
+//! passed as NULL, but a probe arg can opt in to another encoding with `#[probe(encoding =
+//! "...")]`.  If this compiles and runs it means the generated code for each encoding works for
+//! each kind of string type.
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use std::ffi::{CString, OsString};
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait StringEncodingProbes {
+    fn truncated(#[probe(encoding = "truncate")] foo: &str, bar: usize);
+    fn escaped(#[probe(encoding = "escape")] foo: &String);
+    fn ptr_len(#[probe(encoding = "ptr_len")] foo: &OsString, bar: &str);
+    fn optional(
+        #[probe(encoding = "truncate")] foo: &Option<&str>,
+        #[probe(encoding = "ptr_len")] bar: &Option<&CString>,
+    );
+}
+
+#[test]
+fn probe_firing() {
+    let foo = String::from("foo\0bar");
+    let os_foo = OsString::from(foo.clone());
+    let c_bar = CString::new("baz").unwrap();
+
+    probe!(StringEncodingProbes::truncated(&foo, 5));
+    probe!(StringEncodingProbes::escaped(&foo));
+    probe!(StringEncodingProbes::ptr_len(&os_foo, "baz"));
+    probe!(StringEncodingProbes::optional(
+        &Some("foo\0bar"),
+        &Some(&c_bar)
+    ));
+    probe!(StringEncodingProbes::optional(&None, &None));
+}
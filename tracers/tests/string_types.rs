@@ -0,0 +1,47 @@
+//! Besides `&str` and `&String`, the smart pointer string types and (on unix) paths can be passed
+//! to probes as strings.  If this compiles and runs it means the generated code handles each of
+//! them, including the `Cow` lifetime which is usually elided.
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use std::borrow::Cow;
+use std::sync::Arc;
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait StringTypeProbes {
+    fn cow(foo: &Cow<str>, bar: &Option<&Cow<str>>);
+    fn boxed(foo: &Box<str>);
+    fn arc(foo: &Arc<str>, bar: &Option<&Arc<str>>);
+}
+
+#[test]
+fn probe_firing() {
+    let cow: Cow<str> = Cow::Borrowed("foo");
+    let boxed: Box<str> = "foo".into();
+    let arc: Arc<str> = Arc::from("foo");
+
+    probe!(StringTypeProbes::cow(&cow, &Some(&cow)));
+    probe!(StringTypeProbes::cow(&Cow::Owned("bar".to_owned()), &None));
+    probe!(StringTypeProbes::boxed(&boxed));
+    probe!(StringTypeProbes::arc(&arc, &Some(&arc)));
+}
+
+#[cfg(unix)]
+mod paths {
+    use std::path::{Path, PathBuf};
+    use tracers_macros::{probe, tracer};
+
+    #[tracer]
+    trait PathProbes {
+        fn path(foo: &Path, bar: &PathBuf);
+        fn optional_path(foo: &Option<&Path>, bar: &Option<&PathBuf>);
+    }
+
+    #[test]
+    fn probe_firing() {
+        let path_buf = PathBuf::from("/tmp/foo");
+
+        probe!(PathProbes::path(Path::new("/tmp"), &path_buf));
+        probe!(PathProbes::optional_path(&Some(path_buf.as_path()), &None));
+    }
+}
@@ -0,0 +1,76 @@
+//! With the `tee-callback` feature, the `SystemTracer` used by the generated code fires every probe
+//! with both the platform's tracer and the in-process callback.
+#![cfg(feature = "tee-callback")]
+#![deny(warnings)]
+use std::any::TypeId;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::{Arc, Mutex};
+use tracers::runtime::dynamic::{
+    clear_probe_callback, set_probe_callback, CallbackTracer, PlatformTracer, ProbeValue,
+    SystemTracer, TeeTracer,
+};
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait TeeCallbackProbes {
+    fn greeting(count: u32, name: &str, title: &Option<&str>);
+}
+
+fn arg_to_string(arg: &ProbeValue) -> String {
+    match arg {
+        ProbeValue::UInt(value) => value.to_string(),
+        //The generated code passes strings as pointers, which are only valid during the callback
+        ProbeValue::VoidPtr(p) if p.is_null() => "NULL".to_owned(),
+        ProbeValue::VoidPtr(p) => unsafe { CStr::from_ptr(*p as *const c_char) }
+            .to_str()
+            .unwrap()
+            .to_owned(),
+        other => panic!("unexpected arg {:?}", other),
+    }
+}
+
+#[test]
+fn probes_fire_with_platform_tracer_and_callback() {
+    //The generated code names `SystemTracer`, which must be the tee of both
+    assert_eq!(
+        TypeId::of::<TeeTracer<PlatformTracer, CallbackTracer>>(),
+        TypeId::of::<SystemTracer>()
+    );
+
+    let firings = Arc::new(Mutex::new(Vec::new()));
+
+    //Until there's a callback, the callback's side of the tee is disabled
+    probe!(TeeCallbackProbes::greeting(1, "nobody", &None));
+
+    let callback_firings = firings.clone();
+    set_probe_callback(move |firing| {
+        if firing.provider == "tracers_tee_callback_probes" {
+            let args: Vec<String> = firing.args.iter().map(arg_to_string).collect();
+            callback_firings
+                .lock()
+                .unwrap()
+                .push((firing.probe.to_owned(), args));
+        }
+    });
+
+    probe!(TeeCallbackProbes::greeting(42, "world", &Some("Dr.")));
+    probe!(TeeCallbackProbes::greeting(7, "anonymous", &None));
+
+    clear_probe_callback();
+    probe!(TeeCallbackProbes::greeting(2, "nobody", &None));
+
+    assert_eq!(
+        vec![
+            (
+                "greeting".to_owned(),
+                vec!["42".to_owned(), "world".to_owned(), "Dr.".to_owned()]
+            ),
+            (
+                "greeting".to_owned(),
+                vec!["7".to_owned(), "anonymous".to_owned(), "NULL".to_owned()]
+            ),
+        ],
+        *firings.lock().unwrap()
+    );
+}
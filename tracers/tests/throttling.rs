@@ -0,0 +1,50 @@
+//! Probes can be throttled to fire only once in every N times they're enabled, or at most N times
+//! per period, in which case they're passed the number of suppressed fires as an extra arg.  If
+//! this compiles and runs it means the generated code for each throttle works.
+#![deny(warnings)]
+#![allow(clippy::blacklisted_name)] //"foo"
+use std::thread;
+use tracers_macros::{probe, tracer};
+
+#[tracer]
+trait ThrottledProbes {
+    #[probe(sample = 100)]
+    fn sampled(foo: &str, bar: u64);
+
+    #[probe(rate_limit = "1000/s")]
+    fn rate_limited(foo: u32);
+
+    #[probe(rate_limit = "10/ms")]
+    fn no_args();
+
+    fn unthrottled(foo: u32);
+}
+
+#[test]
+fn probe_firing() {
+    for i in 0..1000 {
+        probe!(ThrottledProbes::sampled("foo", i));
+        probe!(ThrottledProbes::rate_limited(i as u32));
+        probe!(ThrottledProbes::no_args());
+        probe!(ThrottledProbes::unthrottled(i as u32));
+    }
+}
+
+#[test]
+fn probe_firing_on_other_threads() {
+    //Each thread has its own throttles
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            thread::spawn(|| {
+                for i in 0..100 {
+                    probe!(ThrottledProbes::sampled("foo", i));
+                    probe!(ThrottledProbes::rate_limited(i as u32));
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}